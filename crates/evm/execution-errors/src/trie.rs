@@ -15,6 +15,14 @@ pub enum StateRootError {
     /// Storage root error.
     #[error(transparent)]
     StorageRootError(#[from] StorageRootError),
+    /// Computed state root doesn't match the expected one.
+    #[error("state root mismatch: got {got}, expected {expected}")]
+    RootMismatch {
+        /// The computed state root.
+        got: B256,
+        /// The expected state root.
+        expected: B256,
+    },
 }
 
 impl From<StateRootError> for DatabaseError {
@@ -22,6 +30,7 @@ impl From<StateRootError> for DatabaseError {
         match err {
             StateRootError::Database(err) |
             StateRootError::StorageRootError(StorageRootError::Database(err)) => err,
+            err @ StateRootError::RootMismatch { .. } => Self::Other(err.to_string()),
         }
     }
 }
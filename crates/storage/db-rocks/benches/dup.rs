@@ -0,0 +1,58 @@
+#![allow(missing_docs)]
+
+use alloy_primitives::{B256, U256};
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use reth_db::HashedStorages;
+use reth_db_api::{
+    cursor::{DbCursorRO, DbDupCursorRO},
+    database::Database,
+    transaction::{DbTx, DbTxMut},
+};
+use reth_db_rocks::{utils::create_test_rocksdb, RocksDBConfig};
+use reth_primitives::StorageEntry;
+
+criterion_group!(benches, next_dup);
+criterion_main!(benches);
+
+/// Walking the duplicates of one key: `next_dup`, which decodes every key, versus
+/// `next_dup_val`, which only decompresses the values.
+pub fn next_dup(c: &mut Criterion) {
+    let (db, _temp_dir) = create_test_rocksdb(RocksDBConfig::default());
+
+    let address = B256::from([1; 32]);
+    let write_tx = db.tx_mut().unwrap();
+    let mut cursor = write_tx.cursor_dup_write::<HashedStorages>().unwrap();
+    for i in 0..1_000u64 {
+        let slot = B256::from(U256::from(i));
+        cursor
+            .upsert_dup(address, slot, &StorageEntry { key: slot, value: U256::from(i) })
+            .unwrap();
+    }
+    drop(cursor);
+    write_tx.commit().unwrap();
+
+    let mut group = c.benchmark_group("next_dup");
+    let tx = db.tx().unwrap();
+
+    group.bench_function("next_dup", |b| {
+        b.iter(|| {
+            let mut cursor = tx.cursor_dup_read::<HashedStorages>().unwrap();
+            cursor.seek_exact(address).unwrap();
+            while let Some(entry) = cursor.next_dup().unwrap() {
+                black_box(entry);
+            }
+        })
+    });
+
+    group.bench_function("next_dup_val", |b| {
+        b.iter(|| {
+            let mut cursor = tx.cursor_dup_read::<HashedStorages>().unwrap();
+            cursor.seek_exact(address).unwrap();
+            while let Some(value) = cursor.next_dup_val().unwrap() {
+                black_box(value);
+            }
+        })
+    });
+
+    group.finish();
+}
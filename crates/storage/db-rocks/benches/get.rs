@@ -1 +1,50 @@
-fn main() {}
+#![allow(missing_docs)]
+
+use alloy_primitives::{B256, U256};
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use reth_db::HashedAccounts;
+use reth_db_api::{
+    database::Database,
+    table::{Encode, Table},
+    transaction::{DbTx, DbTxMut},
+};
+use reth_db_rocks::{utils::create_test_rocksdb, Account, RocksDBConfig};
+
+criterion_group!(benches, get);
+criterion_main!(benches);
+
+/// Tight `get` loop on a single table, resolving the column family by name on every read as
+/// transactions do, versus resolving it once. The difference is the overhead a per-transaction
+/// handle cache could save.
+pub fn get(c: &mut Criterion) {
+    let (db, _temp_dir) = create_test_rocksdb(RocksDBConfig::default());
+
+    let key = B256::from([1; 32]);
+    let account = Account { nonce: 1, balance: U256::from(1000), bytecode_hash: None };
+    let write_tx = db.tx_mut().unwrap();
+    write_tx.put::<HashedAccounts>(key, account).unwrap();
+    write_tx.commit().unwrap();
+
+    let mut group = c.benchmark_group("get");
+
+    let tx = db.tx().unwrap();
+    group.bench_function("tx_get", |b| {
+        b.iter(|| black_box(tx.get::<HashedAccounts>(black_box(key)).unwrap()))
+    });
+
+    let raw = db.inner();
+    let encoded = key.encode();
+    group.bench_function("cf_handle_per_get", |b| {
+        b.iter(|| {
+            let cf = raw.cf_handle(black_box(HashedAccounts::NAME)).unwrap();
+            black_box(raw.get_pinned_cf(cf, &encoded).unwrap())
+        })
+    });
+
+    let cf = raw.cf_handle(HashedAccounts::NAME).unwrap();
+    group.bench_function("cf_handle_resolved_once", |b| {
+        b.iter(|| black_box(raw.get_pinned_cf(cf, &encoded).unwrap()))
+    });
+
+    group.finish();
+}
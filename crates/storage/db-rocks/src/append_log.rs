@@ -0,0 +1,68 @@
+use crate::implementation::rocks::tx::RocksTransaction;
+use reth_db_api::{
+    cursor::DbCursorRO,
+    table::Table,
+    transaction::{DbTx, DbTxMut},
+    DatabaseError,
+};
+use std::{marker::PhantomData, sync::Mutex};
+
+/// Append-only log over the table `T`, assigning each appended value the next sequence number
+/// as its key.
+///
+/// Keys are `u64`s, encoded big-endian, so walking the table returns the entries in the order
+/// they were appended. Numbering continues after the last entry the transaction sees when the
+/// log is created. Appends are staged in the transaction's batch like any other write, so they
+/// become visible together on commit and are dropped together if the transaction is.
+///
+/// Keys are unique as long as a single write transaction appends to `T` at a time, through a
+/// single log: two logs started from the same last entry hand out the same keys, and the later
+/// commit overwrites the earlier one's entries. Appends through one log may come from several
+/// threads.
+pub struct AppendLog<'tx, T: Table<Key = u64>> {
+    tx: &'tx RocksTransaction<true>,
+    /// Key of the next appended value
+    next: Mutex<u64>,
+    _table: PhantomData<T>,
+}
+
+impl<T: Table<Key = u64>> std::fmt::Debug for AppendLog<'_, T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AppendLog")
+            .field("table", &T::NAME)
+            .field("next", &*self.next.lock().unwrap_or_else(|poisoned| poisoned.into_inner()))
+            .finish()
+    }
+}
+
+impl<'tx, T: Table<Key = u64>> AppendLog<'tx, T> {
+    /// Start appending to `T` through `tx`, after the last entry of the table
+    pub fn new(tx: &'tx RocksTransaction<true>) -> Result<Self, DatabaseError> {
+        let next = match tx.cursor_read::<T>()?.last()? {
+            Some((last, _)) => last.checked_add(1).ok_or_else(|| {
+                DatabaseError::Other(format!("Append log {} has no keys left", T::NAME))
+            })?,
+            None => 0,
+        };
+        Ok(Self { tx, next: Mutex::new(next), _table: PhantomData })
+    }
+
+    /// Append `value`, returning the key it was stored under. Keys only increase, and a failed
+    /// append doesn't use one up.
+    pub fn append(&self, value: T::Value) -> Result<u64, DatabaseError> {
+        let mut next = self.next.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let key = *next;
+        let after = key.checked_add(1).ok_or_else(|| {
+            DatabaseError::Other(format!("Append log {} has no keys left", T::NAME))
+        })?;
+
+        self.tx.put::<T>(key, value)?;
+        *next = after;
+        Ok(key)
+    }
+
+    /// Key the next appended value will be stored under
+    pub fn next_key(&self) -> u64 {
+        *self.next.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
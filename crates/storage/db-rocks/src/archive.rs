@@ -0,0 +1,376 @@
+use crate::version::{read_version, write_version, VersionManager, CURRENT_VERSION};
+use alloy_primitives::{Keccak256, B256};
+use reth_db_api::DatabaseError;
+use rocksdb::{ColumnFamily, Direction, IteratorMode, ReadOptions, Snapshot, WriteBatch, DB};
+use std::io::{Read, Seek, SeekFrom, Write};
+
+/// Identifies an archive and its format revision
+const MAGIC: &[u8; 8] = b"RRDBARC1";
+/// Marks another entry of the current table
+const ENTRY_TAG: u8 = 1;
+/// Marks the end of the current table
+const END_TAG: u8 = 0;
+/// Size at which buffered imported entries are written out
+const IMPORT_BATCH_BYTES: usize = 16 * 1024 * 1024;
+
+/// Write every table in `tables` to `out`, read through a single snapshot.
+///
+/// Layout, with lengths and counts as big-endian `u32`:
+/// - header: [`MAGIC`], the schema version, the table count and each length-prefixed table name
+/// - for each table, in header order: entries as [`ENTRY_TAG`] followed by the length-prefixed
+///   raw key and value, then [`END_TAG`]
+/// - trailer: the keccak256 of everything before it
+pub(crate) fn export_archive(
+    db: &DB,
+    tables: &[String],
+    out: impl Write,
+) -> Result<(), DatabaseError> {
+    let mut out = HashingWriter { inner: out, hasher: Keccak256::new() };
+    write_header(db, tables, &mut out)?;
+
+    let snapshot = db.snapshot();
+    for table in tables {
+        let cf = column_family(db, table)?;
+        for item in snapshot.iterator_cf_opt(cf, export_read_options(), IteratorMode::Start) {
+            let (key, value) =
+                item.map_err(|e| DatabaseError::Other(format!("Failed to read {}: {}", table, e)))?;
+            out.write_entry(&key, &value)?;
+        }
+        out.write(&[END_TAG])?;
+    }
+
+    let checksum = out.hasher.finalize();
+    out.inner
+        .write_all(checksum.as_slice())
+        .and_then(|_| out.inner.flush())
+        .map_err(|e| DatabaseError::Other(format!("Failed to write archive: {}", e)))
+}
+
+/// Progress of an export by [`RocksDB::export_resumable`](crate::RocksDB::export_resumable).
+///
+/// The checkpoint holds the snapshot the export reads through, so every call resuming from it
+/// sees the database as it was when the export started. It borrows the database that created
+/// it, and is only valid for that database.
+pub struct ExportCheckpoint<'a> {
+    /// Snapshot the export reads through
+    snapshot: Snapshot<'a>,
+    /// Database being exported
+    db: &'a DB,
+    /// Tables being exported, in archive order
+    tables: Vec<String>,
+    /// Index in `tables` of the table being exported, `tables.len()` once all are done
+    table: usize,
+    /// Last key exported from that table, `None` if none yet
+    last_key: Option<Vec<u8>>,
+    /// Offset in the output the next call writes at
+    offset: u64,
+    /// Hash of the archive written so far
+    hasher: Keccak256,
+    /// Whether the trailer has been written
+    complete: bool,
+}
+
+impl std::fmt::Debug for ExportCheckpoint<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ExportCheckpoint")
+            .field("table", &self.tables.get(self.table))
+            .field("last_key", &self.last_key)
+            .field("offset", &self.offset)
+            .field("complete", &self.complete)
+            .finish()
+    }
+}
+
+impl ExportCheckpoint<'_> {
+    /// Table the next call resumes in, `None` once all tables are exported
+    pub fn table(&self) -> Option<&str> {
+        self.tables.get(self.table).map(String::as_str)
+    }
+
+    /// Last raw key exported from [`ExportCheckpoint::table`], `None` if none yet
+    pub fn last_key(&self) -> Option<&[u8]> {
+        self.last_key.as_deref()
+    }
+
+    /// Bytes of the archive written so far
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Whether the whole archive, checksum included, has been written
+    pub fn is_complete(&self) -> bool {
+        self.complete
+    }
+}
+
+/// Continue the export of `checkpoint`, or start a new one of `tables` if there is none,
+/// writing at most about `budget` bytes of entries to `out` before returning the next
+/// checkpoint. The archive is the same as [`export_archive`] writes.
+///
+/// Every call seeks `out` to the checkpoint's offset first, so output written by a call that
+/// failed is overwritten on retry. At least one entry is written per call.
+pub(crate) fn export_resumable<'a, W: Write + Seek>(
+    db: &'a DB,
+    tables: &[String],
+    mut out: W,
+    checkpoint: Option<ExportCheckpoint<'a>>,
+    budget: Option<usize>,
+) -> Result<ExportCheckpoint<'a>, DatabaseError> {
+    let mut checkpoint = match checkpoint {
+        Some(checkpoint) if !std::ptr::eq(checkpoint.db, db) => {
+            return Err(DatabaseError::Other(
+                "Export checkpoint belongs to another database".to_string(),
+            ))
+        }
+        Some(checkpoint) if checkpoint.complete => return Ok(checkpoint),
+        Some(checkpoint) => checkpoint,
+        None => ExportCheckpoint {
+            snapshot: db.snapshot(),
+            db,
+            tables: tables.to_vec(),
+            table: 0,
+            last_key: None,
+            offset: 0,
+            hasher: Keccak256::new(),
+            complete: false,
+        },
+    };
+
+    out.seek(SeekFrom::Start(checkpoint.offset))
+        .map_err(|e| DatabaseError::Other(format!("Failed to write archive: {}", e)))?;
+    let mut writer = HashingWriter { inner: &mut out, hasher: checkpoint.hasher.clone() };
+    if checkpoint.offset == 0 {
+        write_header(db, &checkpoint.tables, &mut writer)?;
+    }
+
+    let budget = budget.unwrap_or(usize::MAX);
+    let mut written = 0;
+    let mut table = checkpoint.table;
+    let mut last_key = checkpoint.last_key.clone();
+    'tables: while table < checkpoint.tables.len() {
+        let name = &checkpoint.tables[table];
+        let cf = column_family(db, name)?;
+        let mode = match &last_key {
+            Some(key) => IteratorMode::From(key, Direction::Forward),
+            None => IteratorMode::Start,
+        };
+
+        for item in checkpoint.snapshot.iterator_cf_opt(cf, export_read_options(), mode) {
+            let (key, value) =
+                item.map_err(|e| DatabaseError::Other(format!("Failed to read {}: {}", name, e)))?;
+            if last_key.as_deref() == Some(&*key) {
+                continue;
+            }
+            if written > 0 && written >= budget {
+                break 'tables;
+            }
+            writer.write_entry(&key, &value)?;
+            written += 1 + 4 + key.len() + 4 + value.len();
+            last_key = Some(key.into_vec());
+        }
+
+        writer.write(&[END_TAG])?;
+        table += 1;
+        last_key = None;
+    }
+
+    let complete = table == checkpoint.tables.len();
+    if complete {
+        let checksum = writer.hasher.clone().finalize();
+        writer
+            .inner
+            .write_all(checksum.as_slice())
+            .map_err(|e| DatabaseError::Other(format!("Failed to write archive: {}", e)))?;
+    }
+    writer
+        .inner
+        .flush()
+        .map_err(|e| DatabaseError::Other(format!("Failed to write archive: {}", e)))?;
+
+    checkpoint.hasher = writer.hasher;
+    checkpoint.offset = out
+        .stream_position()
+        .map_err(|e| DatabaseError::Other(format!("Failed to write archive: {}", e)))?;
+    checkpoint.table = table;
+    checkpoint.last_key = last_key;
+    checkpoint.complete = complete;
+    Ok(checkpoint)
+}
+
+/// Write the archive header: [`MAGIC`], the schema version and the table names
+fn write_header<W: Write>(
+    db: &DB,
+    tables: &[String],
+    out: &mut HashingWriter<W>,
+) -> Result<(), DatabaseError> {
+    let version = read_version(db)?.unwrap_or(CURRENT_VERSION);
+    out.write(MAGIC)?;
+    out.write(&version.to_be_bytes())?;
+    out.write(&(tables.len() as u32).to_be_bytes())?;
+    for table in tables {
+        out.write_field(table.as_bytes())?;
+    }
+    Ok(())
+}
+
+/// Column family of the exported table `name`
+fn column_family<'a>(db: &'a DB, name: &str) -> Result<&'a ColumnFamily, DatabaseError> {
+    db.cf_handle(name)
+        .ok_or_else(|| DatabaseError::Other(format!("Column family not found: {}", name)))
+}
+
+/// Read options of exports
+fn export_read_options() -> ReadOptions {
+    // A one-off full read shouldn't evict the working set from the block cache
+    let mut read_opts = ReadOptions::default();
+    read_opts.fill_cache(false);
+    // Resumed exports seek to the last exported key, which must not be confined to its prefix
+    // in tables with a prefix extractor
+    read_opts.set_total_order_seek(true);
+    read_opts
+}
+
+/// Load an archive written by [`export_archive`] into `db` and restore its schema version.
+///
+/// Every table in the archive must already have a column family. Entries are written as they
+/// are read, so if the archive turns out to be truncated or its checksum doesn't match, `db`
+/// is left partially populated and should be discarded.
+pub(crate) fn import_archive(db: &DB, reader: impl Read) -> Result<(), DatabaseError> {
+    let mut reader = HashingReader { inner: reader, hasher: Keccak256::new() };
+
+    if &reader.read_array::<8>()? != MAGIC {
+        return Err(invalid("unrecognized header"));
+    }
+    let version = u32::from_be_bytes(reader.read_array()?);
+    if version > CURRENT_VERSION {
+        return Err(invalid(&format!(
+            "schema version {} is newer than the supported version {}",
+            version, CURRENT_VERSION
+        )));
+    }
+
+    let table_count = u32::from_be_bytes(reader.read_array()?);
+    let mut tables = Vec::new();
+    for _ in 0..table_count {
+        let name = String::from_utf8(reader.read_field()?)
+            .map_err(|_| invalid("table name is not valid UTF-8"))?;
+        tables.push(name);
+    }
+
+    for table in &tables {
+        let cf = db.cf_handle(table).ok_or_else(|| {
+            DatabaseError::Other(format!("Archive table {} has no column family", table))
+        })?;
+
+        let mut batch = WriteBatch::default();
+        loop {
+            match reader.read_array::<1>()? {
+                [ENTRY_TAG] => {
+                    let key = reader.read_field()?;
+                    let value = reader.read_field()?;
+                    batch.put_cf(cf, key, value);
+                }
+                [END_TAG] => break,
+                [tag] => return Err(invalid(&format!("unexpected tag {}", tag))),
+            }
+
+            if batch.size_in_bytes() >= IMPORT_BATCH_BYTES {
+                write_batch(db, std::mem::take(&mut batch))?;
+            }
+        }
+        write_batch(db, batch)?;
+    }
+
+    let expected = reader.hasher.finalize();
+    let mut checksum = [0u8; 32];
+    reader
+        .inner
+        .read_exact(&mut checksum)
+        .map_err(|e| DatabaseError::Other(format!("Failed to read archive: {}", e)))?;
+    if B256::from(checksum) != expected {
+        return Err(invalid("checksum mismatch"));
+    }
+
+    write_version(db, version)?;
+    VersionManager::new(db)?.migrate(db)
+}
+
+/// Write a batch of imported entries
+fn write_batch(db: &DB, batch: WriteBatch) -> Result<(), DatabaseError> {
+    db.write(batch).map_err(|e| DatabaseError::Other(format!("Failed to import archive: {}", e)))
+}
+
+/// Error for an archive that can't be imported
+fn invalid(reason: &str) -> DatabaseError {
+    DatabaseError::Other(format!("Invalid archive: {}", reason))
+}
+
+/// Writer that hashes everything written through it
+struct HashingWriter<W> {
+    inner: W,
+    hasher: Keccak256,
+}
+
+impl<W: Write> HashingWriter<W> {
+    /// Write raw bytes
+    fn write(&mut self, bytes: &[u8]) -> Result<(), DatabaseError> {
+        self.hasher.update(bytes);
+        self.inner
+            .write_all(bytes)
+            .map_err(|e| DatabaseError::Other(format!("Failed to write archive: {}", e)))
+    }
+
+    /// Write a length-prefixed field
+    fn write_field(&mut self, bytes: &[u8]) -> Result<(), DatabaseError> {
+        self.write(&(bytes.len() as u32).to_be_bytes())?;
+        self.write(bytes)
+    }
+
+    /// Write a table entry
+    fn write_entry(&mut self, key: &[u8], value: &[u8]) -> Result<(), DatabaseError> {
+        self.write(&[ENTRY_TAG])?;
+        self.write_field(key)?;
+        self.write_field(value)
+    }
+}
+
+/// Reader that hashes everything read through it
+struct HashingReader<R> {
+    inner: R,
+    hasher: Keccak256,
+}
+
+impl<R: Read> HashingReader<R> {
+    /// Read exactly `N` bytes
+    fn read_array<const N: usize>(&mut self) -> Result<[u8; N], DatabaseError> {
+        let mut bytes = [0u8; N];
+        self.read_exact(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    /// Read a length-prefixed field
+    fn read_field(&mut self) -> Result<Vec<u8>, DatabaseError> {
+        let len = u32::from_be_bytes(self.read_array()?) as u64;
+
+        // Don't trust the length with an allocation up front, a corrupt one could be huge
+        let mut bytes = Vec::new();
+        (&mut self.inner)
+            .take(len)
+            .read_to_end(&mut bytes)
+            .map_err(|e| DatabaseError::Other(format!("Failed to read archive: {}", e)))?;
+        if bytes.len() as u64 != len {
+            return Err(invalid("unexpected end of archive"));
+        }
+        self.hasher.update(&bytes);
+        Ok(bytes)
+    }
+
+    /// Read exactly enough bytes to fill `bytes`
+    fn read_exact(&mut self, bytes: &mut [u8]) -> Result<(), DatabaseError> {
+        self.inner
+            .read_exact(bytes)
+            .map_err(|e| DatabaseError::Other(format!("Failed to read archive: {}", e)))?;
+        self.hasher.update(&*bytes);
+        Ok(())
+    }
+}
@@ -0,0 +1,211 @@
+use crate::version::RESERVED_CFS;
+use reth_db_api::DatabaseError;
+use rocksdb::{Options, DB};
+use std::{
+    sync::{Arc, Condvar, Mutex},
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+use tracing::{debug, warn};
+
+/// When an [`AutoCompactor`] compacts tables
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AutoCompactConfig {
+    /// Time between two checks of the tables
+    pub interval: Duration,
+    /// Compact a table once its `rocksdb.estimate-pending-compaction-bytes` exceeds this
+    pub pending_compaction_bytes_threshold: u64,
+    /// Longest time between two checks while writes are being throttled. The wait doubles from
+    /// `interval` on every check that finds the database busy.
+    pub max_backoff: Duration,
+}
+
+impl Default for AutoCompactConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(60),
+            pending_compaction_bytes_threshold: 256 * 1024 * 1024,
+            max_backoff: Duration::from_secs(600),
+        }
+    }
+}
+
+/// Background thread compacting tables that fell behind on compaction.
+///
+/// Every [`AutoCompactConfig::interval`], each table whose pending compaction bytes exceed the
+/// threshold is fully compacted. While RocksDB is delaying or stopping writes the check is
+/// skipped and the wait backs off, so compaction doesn't compete with a write burst; a failed
+/// compaction backs off the same way. The thread stops when the compactor is dropped.
+pub struct AutoCompactor {
+    db: Arc<DB>,
+    config: AutoCompactConfig,
+    /// Wait before the next check, shared by the thread and [`AutoCompactor::tick`]
+    backoff: Arc<Mutex<Backoff>>,
+    /// Whether RocksDB is currently throttling writes, [`writes_throttled`] outside of tests
+    throttled: fn(&DB) -> bool,
+    /// Set to stop the thread, which waits on the condvar between checks
+    stop: Arc<(Mutex<bool>, Condvar)>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl std::fmt::Debug for AutoCompactor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AutoCompactor").field("config", &self.config).finish()
+    }
+}
+
+impl AutoCompactor {
+    /// Start compacting the tables of `db` in the background
+    pub(crate) fn spawn(db: Arc<DB>, config: AutoCompactConfig) -> Self {
+        Self::spawn_with(db, config, writes_throttled)
+    }
+
+    /// [`Self::spawn`], telling whether writes are throttled with `throttled`
+    pub(crate) fn spawn_with(
+        db: Arc<DB>,
+        config: AutoCompactConfig,
+        throttled: fn(&DB) -> bool,
+    ) -> Self {
+        let backoff = Arc::new(Mutex::new(Backoff { delay: config.interval }));
+        let stop = Arc::new((Mutex::new(false), Condvar::new()));
+
+        let handle = {
+            let db = db.clone();
+            let backoff = backoff.clone();
+            let stop = stop.clone();
+            thread::spawn(move || run(&db, config, throttled, &backoff, &stop))
+        };
+
+        Self { db, config, backoff, throttled, stop, handle: Some(handle) }
+    }
+
+    /// The compaction settings
+    pub fn config(&self) -> AutoCompactConfig {
+        self.config
+    }
+
+    /// Wait before the thread's next check: the interval, or longer while backing off
+    pub fn delay(&self) -> Duration {
+        self.backoff.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).delay
+    }
+
+    /// Run a single check now, as the thread does: compact every table over the threshold,
+    /// unless writes are throttled. Returns the names of the compacted tables, none if the
+    /// check was skipped.
+    ///
+    /// A skipped check or a failed compaction backs off the wait before the thread's next
+    /// check, a successful one resets it to the interval.
+    pub fn tick(&self) -> Result<Vec<String>, DatabaseError> {
+        check(&self.db, self.config, self.throttled, &self.backoff)
+    }
+}
+
+/// Wait between two checks, doubled on every check that is skipped or fails
+#[derive(Debug)]
+struct Backoff {
+    delay: Duration,
+}
+
+impl Backoff {
+    /// Record the outcome of a check
+    fn record(&mut self, ok: bool, config: AutoCompactConfig) {
+        self.delay = if ok {
+            config.interval
+        } else {
+            (self.delay * 2).min(config.max_backoff.max(config.interval))
+        };
+    }
+}
+
+impl Drop for AutoCompactor {
+    fn drop(&mut self) {
+        let (stopped, wakeup) = &*self.stop;
+        *stopped.lock().unwrap_or_else(|poisoned| poisoned.into_inner()) = true;
+        wakeup.notify_all();
+
+        if let Some(handle) = self.handle.take() {
+            // A compaction in progress is finished first
+            let _ = handle.join();
+        }
+    }
+}
+
+/// Body of the compactor thread
+fn run(
+    db: &DB,
+    config: AutoCompactConfig,
+    throttled: fn(&DB) -> bool,
+    backoff: &Mutex<Backoff>,
+    stop: &(Mutex<bool>, Condvar),
+) {
+    let (stopped, wakeup) = stop;
+
+    loop {
+        let delay = backoff.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).delay;
+        let guard = stopped.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let (guard, _) = wakeup
+            .wait_timeout_while(guard, delay, |stopped| !*stopped)
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if *guard {
+            return;
+        }
+        drop(guard);
+
+        if let Err(err) = check(db, config, throttled, backoff) {
+            warn!(target: "storage::db::rocks", %err, "Background compaction failed");
+        }
+    }
+}
+
+/// Compact the tables over the threshold unless writes are throttled, backing off if they are
+/// or if the compaction fails
+fn check(
+    db: &DB,
+    config: AutoCompactConfig,
+    throttled: fn(&DB) -> bool,
+    backoff: &Mutex<Backoff>,
+) -> Result<Vec<String>, DatabaseError> {
+    let mut backoff = backoff.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    if throttled(db) {
+        backoff.record(false, config);
+        debug!(target: "storage::db::rocks", delay = ?backoff.delay, "Writes throttled, postponing compaction");
+        return Ok(Vec::new());
+    }
+
+    let result = compact_tables(db, config.pending_compaction_bytes_threshold);
+    backoff.record(result.is_ok(), config);
+    result
+}
+
+/// Whether RocksDB is currently delaying or stopping writes
+fn writes_throttled(db: &DB) -> bool {
+    let property = |name| db.property_int_value(name).ok().flatten().unwrap_or(0);
+    property("rocksdb.actual-delayed-write-rate") > 0 || property("rocksdb.is-write-stopped") > 0
+}
+
+/// Fully compact every table with more than `threshold` pending compaction bytes, returning
+/// their names
+fn compact_tables(db: &DB, threshold: u64) -> Result<Vec<String>, DatabaseError> {
+    let names = DB::list_cf(&Options::default(), db.path())
+        .map_err(|e| DatabaseError::Other(format!("Failed to list column families: {}", e)))?;
+
+    let mut compacted = Vec::new();
+    for name in names {
+        if RESERVED_CFS.contains(&name.as_str()) {
+            continue;
+        }
+        let Some(cf) = db.cf_handle(&name) else { continue };
+
+        let pending = db
+            .property_int_value_cf(cf, "rocksdb.estimate-pending-compaction-bytes")
+            .map_err(|e| DatabaseError::Other(format!("RocksDB error: {}", e)))?
+            .unwrap_or(0);
+        if pending > threshold {
+            debug!(target: "storage::db::rocks", table = %name, pending, "Compacting table");
+            db.compact_range_cf(cf, None::<&[u8]>, None::<&[u8]>);
+            compacted.push(name);
+        }
+    }
+
+    Ok(compacted)
+}
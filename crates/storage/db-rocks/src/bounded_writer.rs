@@ -0,0 +1,110 @@
+use crate::{
+    errors::RocksDBError,
+    implementation::rocks::tx::{RocksTransaction, TransactionBuilder},
+};
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+/// Hands out write transactions that share a limit on the bytes they may stage.
+///
+/// Every key and value staged by a transaction of the writer counts against
+/// `max_pending_bytes` until the transaction is committed or dropped. A write that would take
+/// the total over the limit fails with a write error carrying
+/// [`RocksDBError::WOULD_EXCEED_LIMIT_CODE`], leaving the transaction as it was, so importers
+/// building large batches get backpressure instead of running out of memory: commit what was
+/// staged and retry. A single write larger than the whole limit can never succeed.
+///
+/// Transactions are created like the database's own write transactions, with the
+/// post-commit hook set when the writer was created.
+pub struct BoundedWriter {
+    /// Builder of the database's write transactions
+    builder: TransactionBuilder,
+    budget: Arc<WriteBudget>,
+}
+
+impl std::fmt::Debug for BoundedWriter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BoundedWriter")
+            .field("max_pending_bytes", &self.budget.limit)
+            .field("pending_bytes", &self.pending_bytes())
+            .finish()
+    }
+}
+
+impl BoundedWriter {
+    /// Create a writer of the transactions of `builder` allowing `max_pending_bytes` staged
+    /// across them. The builder must not be read-only.
+    pub(crate) fn new(builder: TransactionBuilder, max_pending_bytes: usize) -> Self {
+        Self {
+            builder,
+            budget: Arc::new(WriteBudget { limit: max_pending_bytes, ..Default::default() }),
+        }
+    }
+
+    /// Create a write transaction whose staged writes count against the limit. Waits while a
+    /// consistent snapshot is being taken.
+    pub fn tx_mut(&self) -> RocksTransaction<true> {
+        self.builder
+            .clone()
+            .write()
+            .expect("bounded writers are only created for writable databases")
+            .with_write_budget(self.budget.clone())
+    }
+
+    /// Bytes staged by the outstanding transactions
+    pub fn pending_bytes(&self) -> usize {
+        self.budget.pending.load(Ordering::Acquire)
+    }
+
+    /// The limit on [`BoundedWriter::pending_bytes`]
+    pub fn max_pending_bytes(&self) -> usize {
+        self.budget.limit
+    }
+}
+
+/// Bytes staged by the transactions of a [`BoundedWriter`], and their limit
+#[derive(Debug, Default)]
+pub(crate) struct WriteBudget {
+    limit: usize,
+    pending: AtomicUsize,
+}
+
+/// Share of a [`WriteBudget`] taken by one transaction, given back when it is dropped
+#[derive(Debug)]
+pub(crate) struct WriteReservation {
+    budget: Arc<WriteBudget>,
+    bytes: AtomicUsize,
+}
+
+impl WriteReservation {
+    /// Start a reservation of nothing in `budget`
+    pub(crate) fn new(budget: Arc<WriteBudget>) -> Self {
+        Self { budget, bytes: AtomicUsize::new(0) }
+    }
+
+    /// Take `bytes` more from the budget, failing without taking anything if that would exceed
+    /// its limit
+    pub(crate) fn reserve(&self, bytes: usize) -> Result<(), RocksDBError> {
+        let limit = self.budget.limit;
+        self.budget
+            .pending
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |pending| {
+                pending.checked_add(bytes).filter(|total| *total <= limit)
+            })
+            .map_err(|pending| RocksDBError::WouldExceedLimit {
+                pending,
+                requested: bytes,
+                limit,
+            })?;
+        self.bytes.fetch_add(bytes, Ordering::AcqRel);
+        Ok(())
+    }
+}
+
+impl Drop for WriteReservation {
+    fn drop(&mut self) {
+        self.budget.pending.fetch_sub(*self.bytes.get_mut(), Ordering::AcqRel);
+    }
+}
@@ -0,0 +1,380 @@
+use rocksdb::{BlockBasedOptions, DBCompactionStyle, DBCompressionType, DBRecoveryMode, Options};
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// Configuration for opening a [`RocksDB`](crate::RocksDB) instance
+#[derive(Debug, Clone)]
+pub struct RocksDBConfig {
+    /// Maximum time a read transaction may hold its snapshot before
+    /// `long_read_transaction_action` is applied. `None` disables the check.
+    pub max_read_transaction_duration: Option<Duration>,
+    /// What to do once a read transaction outlives `max_read_transaction_duration`
+    pub long_read_transaction_action: LongReadTransactionAction,
+    /// Size of SST data blocks in bytes. `None` keeps RocksDB's default (4KB).
+    pub block_size: Option<usize>,
+    /// Store index and filter blocks in the block cache, so their memory is bounded by the
+    /// cache capacity. `None` keeps RocksDB's default (disabled).
+    pub cache_index_and_filter_blocks: Option<bool>,
+    /// Number of keys between restart points of SST data blocks. Keys in between are stored as
+    /// deltas to the previous key, so a larger interval shrinks blocks of keys sharing long
+    /// prefixes, like trie nibble paths, at the cost of slower seeks within a block. `None`
+    /// keeps RocksDB's default (16).
+    pub block_restart_interval: Option<i32>,
+    /// Number of keys between restart points of SST index blocks, trading index size for seek
+    /// speed like `block_restart_interval`. `None` keeps RocksDB's default (1, no delta
+    /// encoding).
+    pub index_block_restart_interval: Option<i32>,
+    /// Size of a single memtable in bytes. `None` keeps RocksDB's default (64MB).
+    pub write_buffer_size: Option<usize>,
+    /// Maximum number of memtables kept in memory. `None` keeps RocksDB's default (2).
+    pub max_write_buffer_number: Option<i32>,
+    /// Minimum number of immutable memtables merged into a single flush. Higher values use more
+    /// memory but write less to L0. `None` keeps RocksDB's default (1).
+    pub min_write_buffer_number_to_merge: Option<i32>,
+    /// Compaction style of every table. `None` keeps RocksDB's default (level).
+    pub compaction_style: Option<DBCompactionStyle>,
+    /// Maximum bytes of input a single compaction rewrites, keeping compactions of very large
+    /// tables short. `None` keeps RocksDB's default (25 times the target file size).
+    pub max_compaction_bytes: Option<u64>,
+    /// Number of level-0 files that triggers a compaction into level 1. Higher values batch
+    /// more flushes per compaction for write throughput, lower ones keep reads from checking
+    /// many overlapping files. `None` keeps RocksDB's default (4).
+    pub level0_file_num_compaction_trigger: Option<i32>,
+    /// Compression of every table. `None` keeps RocksDB's default (snappy).
+    pub compression: Option<DBCompressionType>,
+    /// Buffer WAL writes in memory until they are explicitly flushed, trading durability of the
+    /// latest writes for write throughput. Nothing flushes the buffer periodically: it is only
+    /// written out once full, when memtables are flushed and on close.
+    pub manual_wal_flush: bool,
+    /// Aggressively check consistency of the data. `None` keeps RocksDB's default (enabled).
+    pub paranoid_checks: Option<bool>,
+    /// Read back every SST file after it is written by a flush or compaction and check its
+    /// entries, catching corruption before the inputs are deleted at the cost of extra reads.
+    /// Disabled by default.
+    pub paranoid_file_checks: bool,
+    /// Create the database if it doesn't exist yet
+    pub create_if_missing: bool,
+    /// Let full-table scans and exports fill the block cache. Disabled by default so a scan
+    /// doesn't evict the blocks serving point reads.
+    pub scan_fill_cache: bool,
+    /// Maximum number of files RocksDB keeps open, shared by all tables. `None` keeps RocksDB's
+    /// default (unlimited). [`RocksDB::open_file_count`](crate::RocksDB::open_file_count) warns
+    /// once the estimated count gets close.
+    pub max_open_files: Option<i32>,
+    /// Directory for the write-ahead log, e.g. on a faster disk than the data. `None` keeps the
+    /// WAL in the data directory. A database must always be reopened with the same WAL directory.
+    pub wal_dir: Option<String>,
+    /// Check on open that every SST file listed in the MANIFEST exists with the size recorded
+    /// there. A missing or truncated file then fails the open with a `Corruption: Sst file size
+    /// mismatch` error, instead of only surfacing once a read reaches the broken file. Enabled
+    /// by default; disabling it speeds up opening databases with many files.
+    pub verify_sst_on_open: bool,
+    /// How the WAL is replayed on open. `None` keeps RocksDB's default
+    /// ([`DBRecoveryMode::PointInTime`]).
+    ///
+    /// After an unclean shutdown left a corrupt WAL, [`DBRecoveryMode::AbsoluteConsistency`]
+    /// refuses to open. [`DBRecoveryMode::PointInTime`] stops replaying at the first corrupt
+    /// record and opens with the writes before it, and
+    /// [`DBRecoveryMode::SkipAnyCorruptedRecord`] replays every record it can read. Both open
+    /// the data already in SST files, losing the writes recorded past the corruption.
+    pub wal_recovery_mode: Option<DBRecoveryMode>,
+    /// Log a warning with the table, operation and duration of every `get`, `put`, `delete`,
+    /// cursor operation and `commit` taking longer than this. `None` disables the check.
+    pub slow_op_threshold: Option<Duration>,
+    /// Per-table settings taking precedence over the ones above, keyed by table name
+    pub cf_overrides: HashMap<String, CfOverride>,
+}
+
+impl Default for RocksDBConfig {
+    fn default() -> Self {
+        Self {
+            max_read_transaction_duration: None,
+            long_read_transaction_action: LongReadTransactionAction::Warn,
+            block_size: None,
+            cache_index_and_filter_blocks: None,
+            block_restart_interval: None,
+            index_block_restart_interval: None,
+            write_buffer_size: None,
+            max_write_buffer_number: None,
+            min_write_buffer_number_to_merge: None,
+            compaction_style: None,
+            max_compaction_bytes: None,
+            level0_file_num_compaction_trigger: None,
+            compression: None,
+            manual_wal_flush: false,
+            paranoid_checks: None,
+            paranoid_file_checks: false,
+            create_if_missing: true,
+            scan_fill_cache: false,
+            max_open_files: None,
+            wal_dir: None,
+            verify_sst_on_open: true,
+            wal_recovery_mode: None,
+            slow_op_threshold: None,
+            cf_overrides: HashMap::new(),
+        }
+    }
+}
+
+impl RocksDBConfig {
+    /// Configuration tuned for the given [`Profile`]. Individual fields can still be adjusted
+    /// afterwards.
+    pub fn profile(profile: Profile) -> Self {
+        match profile {
+            Profile::FastSync => Self {
+                write_buffer_size: Some(256 * 1024 * 1024),
+                max_write_buffer_number: Some(6),
+                compaction_style: Some(DBCompactionStyle::Universal),
+                compression: Some(DBCompressionType::Lz4),
+                ..Self::default()
+            },
+            Profile::Archive => Self {
+                block_size: Some(16 * 1024),
+                cache_index_and_filter_blocks: Some(true),
+                compaction_style: Some(DBCompactionStyle::Level),
+                compression: Some(DBCompressionType::Zstd),
+                paranoid_file_checks: true,
+                ..Self::default()
+            },
+            Profile::Dev => Self {
+                write_buffer_size: Some(4 * 1024 * 1024),
+                max_write_buffer_number: Some(2),
+                ..Self::default()
+            },
+        }
+    }
+
+    /// Set the maximum duration a read transaction may be held
+    pub fn with_max_read_transaction_duration(mut self, duration: Duration) -> Self {
+        self.max_read_transaction_duration = Some(duration);
+        self
+    }
+
+    /// Set the action taken when a read transaction is held for too long
+    pub fn with_long_read_transaction_action(mut self, action: LongReadTransactionAction) -> Self {
+        self.long_read_transaction_action = action;
+        self
+    }
+
+    /// Set the SST data block size
+    pub fn with_block_size(mut self, block_size: usize) -> Self {
+        self.block_size = Some(block_size);
+        self
+    }
+
+    /// Set whether index and filter blocks are stored in the block cache
+    pub fn with_cache_index_and_filter_blocks(mut self, enabled: bool) -> Self {
+        self.cache_index_and_filter_blocks = Some(enabled);
+        self
+    }
+
+    /// Set the number of keys between restart points of SST data blocks
+    pub fn with_block_restart_interval(mut self, interval: i32) -> Self {
+        self.block_restart_interval = Some(interval);
+        self
+    }
+
+    /// Set the number of keys between restart points of SST index blocks
+    pub fn with_index_block_restart_interval(mut self, interval: i32) -> Self {
+        self.index_block_restart_interval = Some(interval);
+        self
+    }
+
+    /// Set the maximum bytes of input a single compaction rewrites
+    pub fn with_max_compaction_bytes(mut self, bytes: u64) -> Self {
+        self.max_compaction_bytes = Some(bytes);
+        self
+    }
+
+    /// Set the number of level-0 files that triggers a compaction
+    pub fn with_level0_file_num_compaction_trigger(mut self, count: i32) -> Self {
+        self.level0_file_num_compaction_trigger = Some(count);
+        self
+    }
+
+    /// Set whether full-table scans and exports fill the block cache
+    pub fn with_scan_fill_cache(mut self, enabled: bool) -> Self {
+        self.scan_fill_cache = enabled;
+        self
+    }
+
+    /// Set the maximum number of files kept open
+    pub fn with_max_open_files(mut self, max_open_files: i32) -> Self {
+        self.max_open_files = Some(max_open_files);
+        self
+    }
+
+    /// Set the directory the write-ahead log is kept in
+    pub fn with_wal_dir(mut self, wal_dir: impl Into<String>) -> Self {
+        self.wal_dir = Some(wal_dir.into());
+        self
+    }
+
+    /// Set whether SST file sizes are checked against the MANIFEST on open
+    pub fn with_verify_sst_on_open(mut self, enabled: bool) -> Self {
+        self.verify_sst_on_open = enabled;
+        self
+    }
+
+    /// Set how the WAL is replayed on open
+    pub fn with_wal_recovery_mode(mut self, mode: DBRecoveryMode) -> Self {
+        self.wal_recovery_mode = Some(mode);
+        self
+    }
+
+    /// Set the duration above which operations are logged as slow
+    pub fn with_slow_op_threshold(mut self, threshold: Duration) -> Self {
+        self.slow_op_threshold = Some(threshold);
+        self
+    }
+
+    /// Override settings of the table `name`
+    pub fn with_cf_override(mut self, name: impl Into<String>, cf_override: CfOverride) -> Self {
+        self.cf_overrides.insert(name.into(), cf_override);
+        self
+    }
+
+    /// Database-wide options
+    pub(crate) fn db_options(&self) -> Options {
+        let mut opts = Options::default();
+        opts.create_if_missing(self.create_if_missing);
+        opts.create_missing_column_families(true);
+        // Flushes of several tables, as by `RocksDB::consistent_snapshot`, persist them together
+        opts.set_atomic_flush(true);
+        opts.set_manual_wal_flush(self.manual_wal_flush);
+        if let Some(enabled) = self.paranoid_checks {
+            opts.set_paranoid_checks(enabled);
+        }
+        if let Some(max_open_files) = self.max_open_files {
+            opts.set_max_open_files(max_open_files);
+        }
+        if let Some(wal_dir) = &self.wal_dir {
+            opts.set_wal_dir(wal_dir);
+        }
+        opts.set_skip_checking_sst_file_sizes_on_db_open(!self.verify_sst_on_open);
+        if let Some(mode) = self.wal_recovery_mode {
+            opts.set_wal_recovery_mode(mode);
+        }
+        opts
+    }
+
+    /// Column family options shared by all tables
+    pub(crate) fn table_options(&self) -> Options {
+        let mut opts = Options::default();
+
+        if let Some(size) = self.write_buffer_size {
+            opts.set_write_buffer_size(size);
+        }
+        if let Some(count) = self.max_write_buffer_number {
+            opts.set_max_write_buffer_number(count);
+        }
+        if let Some(count) = self.min_write_buffer_number_to_merge {
+            opts.set_min_write_buffer_number_to_merge(count);
+        }
+        if let Some(style) = self.compaction_style {
+            opts.set_compaction_style(style);
+        }
+        if let Some(bytes) = self.max_compaction_bytes {
+            opts.set_max_compaction_bytes(bytes);
+        }
+        if let Some(count) = self.level0_file_num_compaction_trigger {
+            opts.set_level_zero_file_num_compaction_trigger(count);
+        }
+        if let Some(compression) = self.compression {
+            opts.set_compression_type(compression);
+        }
+
+        if let Some(block_opts) = self.block_options() {
+            opts.set_block_based_table_factory(&block_opts);
+        }
+
+        opts
+    }
+
+    /// SST block options, `None` if all of them keep RocksDB's defaults
+    fn block_options(&self) -> Option<BlockBasedOptions> {
+        if self.block_size.is_none() &&
+            self.cache_index_and_filter_blocks.is_none() &&
+            self.block_restart_interval.is_none() &&
+            self.index_block_restart_interval.is_none()
+        {
+            return None;
+        }
+
+        let mut block_opts = BlockBasedOptions::default();
+        if let Some(block_size) = self.block_size {
+            block_opts.set_block_size(block_size);
+        }
+        if let Some(enabled) = self.cache_index_and_filter_blocks {
+            block_opts.set_cache_index_and_filter_blocks(enabled);
+        }
+        if let Some(interval) = self.block_restart_interval {
+            block_opts.set_block_restart_interval(interval);
+        }
+        if let Some(interval) = self.index_block_restart_interval {
+            block_opts.set_index_block_restart_interval(interval);
+        }
+        Some(block_opts)
+    }
+
+    /// Column family options of the table `name`: [`RocksDBConfig::table_options`] with the
+    /// table's bloom filter and its entry in `cf_overrides` applied
+    pub(crate) fn table_options_for(&self, name: &str) -> Options {
+        let mut opts = self.table_options();
+
+        // Tables with a prefix bloom need a filter in their SST files
+        if crate::tables::KeyLayout::named(name).is_some_and(|layout| layout.prefix_bloom.is_some())
+        {
+            let block_opts = self.block_options().unwrap_or_default();
+            opts.set_block_based_table_factory(&crate::tables::bloom_filter_options(block_opts));
+        }
+
+        if let Some(cf_override) = self.cf_overrides.get(name) {
+            if let Some(compression) = cf_override.compression {
+                opts.set_compression_type(compression);
+            }
+            if let Some(size) = cf_override.min_blob_size {
+                opts.set_enable_blob_files(true);
+                opts.set_min_blob_size(size);
+            }
+        }
+
+        opts
+    }
+}
+
+/// Settings of a single table overriding the database-wide ones in [`RocksDBConfig`].
+///
+/// Small values that are read constantly, like those of `HashedAccounts`, compress poorly and
+/// pay the decompression cost on every block cache miss. Setting
+/// [`DBCompressionType::None`] for such hot tables usually saves more CPU than the disk space
+/// it costs, while large cold tables keep the database-wide compression.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CfOverride {
+    /// Compression of the table's SST files. `None` keeps the database-wide `compression`.
+    pub compression: Option<DBCompressionType>,
+    /// Store values of at least this many bytes in blob files, keeping only references to them
+    /// in the SST files, so compactions rewrite the small references instead of the large
+    /// values. `None` keeps all values inline.
+    pub min_blob_size: Option<u64>,
+}
+
+/// Action taken when a read transaction is held beyond the configured maximum
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LongReadTransactionAction {
+    /// Log a warning and bump the `rocksdb_long_read_transactions_total` metric
+    Warn,
+    /// Fail every subsequent read on the transaction
+    Error,
+}
+
+/// Preset combinations of [`RocksDBConfig`] fields for common deployments
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Profile {
+    /// Initial sync: large write buffers and universal compaction for write throughput
+    FastSync,
+    /// Long-lived archive node: strong compression and verification of every SST file written
+    Archive,
+    /// Local development: small write buffers
+    Dev,
+}
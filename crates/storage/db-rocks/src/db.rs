@@ -1,39 +1,925 @@
 use crate::{
-    implementation::rocks::tx::RocksTransaction,
-    tables::trie::{AccountTrieTable, StorageTrieTable, TrieTable},
+    archive::{export_archive, export_resumable, import_archive, ExportCheckpoint},
+    auto_compact::{AutoCompactConfig, AutoCompactor},
+    bounded_writer::BoundedWriter,
+    config::RocksDBConfig,
+    diff::{diff_tables, TableDiffSummary},
+    errors::RocksDBError,
+    group_commit::{GroupCommit, GroupCommitPolicy},
+    implementation::rocks::tx::{PostCommitHook, RocksTransaction, TransactionBuilder},
+    scan::{scan_tables, ScanReport},
+    tables::{
+        hashed::HashedAccountHistory,
+        table_descriptor_for, table_schemas,
+        trie::{AccountTrieTable, BlockStateRoots, StorageTrieTable, TrieNodePaths, TrieTable},
+        TableConfig, TableManagement, TableSchema,
+    },
+    version::{read_version, VersionManager, CURRENT_VERSION, META_CF, RESERVED_CFS},
+    write_gate::WriteGate,
 };
-use reth_db_api::{database::Database, DatabaseError};
-use rocksdb::{ColumnFamilyDescriptor, Options, DB};
+use reth_db_api::{
+    database::Database,
+    table::{Encode, Table},
+    DatabaseError,
+};
+use rocksdb::{
+    checkpoint::Checkpoint, ColumnFamily, ColumnFamilyDescriptor, FlushOptions, IteratorMode,
+    LiveFile, Options, ReadOptions, WriteBatch, DB,
+};
+use std::io::{Read, Seek, Write};
 use std::path::Path;
-use std::sync::Arc;
+use std::sync::{atomic::AtomicBool, Arc, RwLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::warn;
+
+/// Number of LSM levels, RocksDB's default
+const NUM_LEVELS: usize = 7;
+/// File in the data directory recording the WAL directory, if it isn't the data directory
+const WAL_DIR_FILE: &str = "WAL_DIR";
+/// Entries read by [`RocksDB::swap_cf_contents`] between two writes
+const SWAP_CHUNK_ENTRIES: usize = 10_000;
+
+/// Write stalls since the database was opened, see [`RocksDB::write_stall_stats`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct WriteStallStats {
+    /// Time writes spent stalled, delayed or stopped, at millisecond precision
+    pub total_stall_micros: u64,
+    /// Times writes were stopped until flushes or compactions caught up, across column families
+    pub stopped_count: u64,
+    /// Times writes were slowed down to the delayed write rate, across column families
+    pub slowdown_count: u64,
+}
 
 /// RocksDB database implementation
-#[derive(Debug)]
 pub struct RocksDB {
     /// Inner database instance
     db: Arc<DB>,
+    /// Configuration the database was opened with
+    config: RocksDBConfig,
+    /// Whether the database was opened without write access
+    read_only: bool,
+    /// Hook handed to write transactions created from now on
+    post_commit_hook: RwLock<Option<Arc<PostCommitHook>>>,
+    /// Gate of the write transactions, closed while a consistent snapshot is taken
+    write_gate: Arc<WriteGate>,
+}
+
+impl std::fmt::Debug for RocksDB {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let has_hook =
+            self.post_commit_hook.read().unwrap_or_else(|poisoned| poisoned.into_inner()).is_some();
+        f.debug_struct("RocksDB")
+            .field("db", &self.db)
+            .field("config", &self.config)
+            .field("read_only", &self.read_only)
+            .field("post_commit_hook", &has_hook)
+            .finish()
+    }
 }
 
 impl RocksDB {
-    // Open database at the given path
-    // pub fn open(path: &Path) -> Result<Self, DatabaseError> {
-    //     let mut opts = Options::default();
-    //     opts.create_if_missing(true);
-    //     opts.create_missing_column_families(true);
+    /// Open database at the given path, creating any missing column families
+    pub fn open(path: &Path, config: RocksDBConfig) -> Result<Self, DatabaseError> {
+        Self::open_with_tables(path, config, &[])
+    }
+
+    /// [`RocksDB::open`], with column families for `extra_tables` on top of the registered
+    /// tables.
+    ///
+    /// Fails if any table would be stored in a column family reserved for metadata: the default
+    /// column family and the meta column family only ever hold metadata.
+    pub(crate) fn open_with_tables(
+        path: &Path,
+        config: RocksDBConfig,
+        extra_tables: &[&str],
+    ) -> Result<Self, DatabaseError> {
+        check_table_names(
+            table_schemas().iter().map(|table| table.name).chain(extra_tables.iter().copied()),
+        )?;
+        let opts = config.db_options();
+
+        // Column families for all reth tables plus the trie and account history tables
+        let mut cf_descriptors = TableManagement::get_all_column_family_descriptors(&config);
+        cf_descriptors.extend([
+            TrieTable::descriptor_with(&config.table_options_for(TrieTable::NAME)),
+            AccountTrieTable::descriptor_with(&config.table_options_for(AccountTrieTable::NAME)),
+            StorageTrieTable::descriptor_with(&config.table_options_for(StorageTrieTable::NAME)),
+            BlockStateRoots::descriptor_with(&config.table_options_for(BlockStateRoots::NAME)),
+            TrieNodePaths::descriptor_with(&config.table_options_for(TrieNodePaths::NAME)),
+            HashedAccountHistory::descriptor_with(
+                &config.table_options_for(HashedAccountHistory::NAME),
+            ),
+        ]);
+        cf_descriptors.extend(
+            extra_tables
+                .iter()
+                .map(|name| ColumnFamilyDescriptor::new(*name, config.table_options_for(name))),
+        );
+        // Reserved column family for metadata such as the schema version
+        cf_descriptors.push(ColumnFamilyDescriptor::new(META_CF, Options::default()));
+
+        check_wal_dir(path, &config)?;
+        let db = DB::open_cf_descriptors(&opts, path, cf_descriptors)
+            .map_err(|e| DatabaseError::Other(format!("Failed to open database: {}", e)))?;
+        record_wal_dir(path, &config)?;
+
+        // Only settable as a mutable option of the open column families
+        if config.paranoid_file_checks {
+            for name in DB::list_cf(&opts, path).map_err(|e| {
+                DatabaseError::Other(format!("Failed to list column families: {}", e))
+            })? {
+                let Some(cf) = db.cf_handle(&name) else { continue };
+                db.set_options_cf(cf, &[("paranoid_file_checks", "true")]).map_err(|e| {
+                    DatabaseError::Other(format!("Failed to enable paranoid file checks: {}", e))
+                })?;
+            }
+        }
+
+        let version = VersionManager::new(&db)?;
+        version.migrate(&db)?;
+
+        Ok(Self::with_db(Arc::new(db), config, false))
+    }
+
+    /// [`RocksDB::open`], retried while another handle still holds the database's `LOCK`, e.g.
+    /// that of a process that is shutting down.
+    ///
+    /// Makes at most `max_attempts` attempts, sleeping `backoff` after the first failed one and
+    /// doubling the sleep after each further one. Any other error, and the lock error of the last
+    /// attempt, is returned right away.
+    pub fn open_with_retry(
+        path: &Path,
+        config: RocksDBConfig,
+        max_attempts: usize,
+        backoff: Duration,
+    ) -> Result<Self, DatabaseError> {
+        let mut delay = backoff;
+        let mut attempt = 1;
+        loop {
+            match Self::open(path, config.clone()) {
+                Err(DatabaseError::Other(message))
+                    if attempt < max_attempts && is_lock_held(&message) =>
+                {
+                    warn!(
+                        target: "storage::db::rocks",
+                        attempt,
+                        ?delay,
+                        "Database lock held, retrying open"
+                    );
+                    std::thread::sleep(delay);
+                    delay = delay.saturating_mul(2);
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Open the database at `path` read-only.
+    ///
+    /// This doesn't take the exclusive `LOCK`, so it works on a database that is open in another
+    /// process or was left locked by one that died, and it never writes to the WAL. Data that
+    /// only lives in the WAL is still read, unless `error_if_log_file_exists` is set, in which
+    /// case opening fails instead. Write transactions can't be created.
+    pub fn open_read_only(
+        path: &Path,
+        config: RocksDBConfig,
+        error_if_log_file_exists: bool,
+    ) -> Result<Self, DatabaseError> {
+        check_wal_dir(path, &config)?;
+        let mut opts = Options::default();
+        if let Some(wal_dir) = &config.wal_dir {
+            opts.set_wal_dir(wal_dir);
+        }
+        opts.set_skip_checking_sst_file_sizes_on_db_open(!config.verify_sst_on_open);
+        if let Some(mode) = config.wal_recovery_mode {
+            opts.set_wal_recovery_mode(mode);
+        }
+
+        let cf_descriptors = existing_cf_descriptors(path, &config)?;
+
+        let db = DB::open_cf_descriptors_read_only(
+            &opts,
+            path,
+            cf_descriptors,
+            error_if_log_file_exists,
+        )
+        .map_err(|e| DatabaseError::Other(format!("Failed to open database read-only: {}", e)))?;
+
+        Ok(Self::with_db(Arc::new(db), config, true))
+    }
+
+    /// Open a secondary instance that follows the primary database at `primary_path`.
+    ///
+    /// A secondary is read-only and only observes data the primary has made durable, i.e. what
+    /// is in SST files or the WAL. Writes that were made without the WAL and are still sitting
+    /// in the primary's memtables stay invisible until the primary flushes them (see
+    /// [`RocksDB::flush_for_secondary`]) and the secondary calls
+    /// [`RocksDB::try_catch_up_with_primary`].
+    pub fn open_secondary(
+        primary_path: &Path,
+        secondary_path: &Path,
+        config: RocksDBConfig,
+    ) -> Result<Self, DatabaseError> {
+        let mut opts = config.db_options();
+        // Secondaries must keep all files open to follow the primary
+        opts.set_max_open_files(-1);
+
+        let cf_descriptors = existing_cf_descriptors(primary_path, &config)?;
+
+        let db = DB::open_cf_descriptors_as_secondary(
+            &opts,
+            primary_path,
+            secondary_path,
+            cf_descriptors,
+        )
+        .map_err(|e| DatabaseError::Other(format!("Failed to open secondary database: {}", e)))?;
+
+        Ok(Self::with_db(Arc::new(db), config, true))
+    }
+
+    /// Replay the primary's MANIFEST and WAL so a secondary sees its latest durable state
+    pub fn try_catch_up_with_primary(&self) -> Result<(), DatabaseError> {
+        self.db
+            .try_catch_up_with_primary()
+            .map_err(|e| DatabaseError::Other(format!("Failed to catch up with primary: {}", e)))
+    }
+
+    /// Flush the memtables of every column family so that a following
+    /// [`RocksDB::try_catch_up_with_primary`] on a secondary sees all writes made so far
+    pub fn flush_for_secondary(&self) -> Result<(), DatabaseError> {
+        for name in self.cf_names()? {
+            let cf = self.db.cf_handle(&name).ok_or_else(|| {
+                DatabaseError::Other(format!("Column family not found: {}", name))
+            })?;
+            self.db
+                .flush_cf(cf)
+                .map_err(|e| DatabaseError::Other(format!("Failed to flush {}: {}", name, e)))?;
+        }
+        Ok(())
+    }
+
+    /// Close the database in a durable state: flush the memtables of every column family, sync
+    /// the WAL and stop background work.
+    ///
+    /// Unlike simply dropping the handle, nothing is left to be replayed from the WAL on the next
+    /// open. This is what the node should call on a controlled stop.
+    pub fn shutdown(self) -> Result<(), DatabaseError> {
+        let mut flush_opts = FlushOptions::default();
+        flush_opts.set_wait(true);
+
+        for name in self.cf_names()? {
+            let cf = self.db.cf_handle(&name).ok_or_else(|| {
+                DatabaseError::Other(format!("Column family not found: {}", name))
+            })?;
+            self.db
+                .flush_cf_opt(cf, &flush_opts)
+                .map_err(|e| DatabaseError::Other(format!("Failed to flush {}: {}", name, e)))?;
+        }
+
+        self.db
+            .flush_wal(true)
+            .map_err(|e| DatabaseError::Other(format!("Failed to sync WAL: {}", e)))?;
+        self.db.cancel_all_background_work(true);
+
+        Ok(())
+    }
+
+    /// Write a checkpoint of every table to `dir`, which must not exist yet, holding the same
+    /// state for all of them.
+    ///
+    /// New writes wait while the snapshot is taken, and the open write transactions are waited
+    /// for, so it must not be called from a thread holding a write transaction. This covers
+    /// transactions of [`Database::tx_mut`], [`RocksDB::transaction_builder`] and
+    /// [`BoundedWriter`]s, groups of a [`GroupCommit`], and the commits and cursor writes of
+    /// write transactions created with [`RocksTransaction::from_db`] on the inner database.
+    /// All memtables are then flushed in one atomic flush and the checkpoint is created from
+    /// the SST files, hard-linked where the filesystem allows. The checkpoint opens as a
+    /// regular database, e.g. for a backup.
+    pub fn consistent_snapshot(&self, dir: &Path) -> Result<(), DatabaseError> {
+        let _closed = self.write_gate.close();
+
+        let names = self.cf_names()?;
+        let cfs = names
+            .iter()
+            .map(|name| {
+                self.db.cf_handle(name).ok_or_else(|| {
+                    DatabaseError::Other(format!("Column family not found: {}", name))
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut flush_opts = FlushOptions::default();
+        flush_opts.set_wait(true);
+        self.db
+            .flush_cfs_opt(&cfs, &flush_opts)
+            .map_err(|e| DatabaseError::Other(format!("Failed to flush tables: {}", e)))?;
+
+        Checkpoint::new(self.db.as_ref())
+            .and_then(|checkpoint| checkpoint.create_checkpoint(dir))
+            .map_err(|e| DatabaseError::Other(format!("Failed to create checkpoint: {}", e)))
+    }
+
+    /// Flush the memtable of the column family backing `T` to an SST file
+    pub fn flush_table<T: Table>(&self) -> Result<(), DatabaseError> {
+        self.db
+            .flush_cf(self.cf::<T>()?)
+            .map_err(|e| DatabaseError::Other(format!("Failed to flush {}: {}", T::NAME, e)))
+    }
+
+    /// Change options of the column family backing `T` on the open database, as option name and
+    /// value pairs, e.g. `[("write_buffer_size", "134217728")]`.
+    ///
+    /// Only dynamically mutable options can be changed, such as write buffer sizes, compaction
+    /// triggers and target file sizes. Setting a static option, like the table format or the
+    /// prefix extractor, or an unknown one fails without changing any of `opts`. Changes last
+    /// until the database is closed: reopening applies its [`RocksDBConfig`] again.
+    pub fn set_cf_options<T: Table>(&self, opts: &[(&str, &str)]) -> Result<(), DatabaseError> {
+        self.db.set_options_cf(self.cf::<T>()?, opts).map_err(|e| {
+            RocksDBError::Config(format!(
+                "Failed to set options {:?} of {}, only dynamically mutable options can be set \
+                 on an open database: {}",
+                opts,
+                T::NAME,
+                e
+            ))
+            .into()
+        })
+    }
+
+    /// Metadata of all live SST files across column families
+    pub fn sst_files(&self) -> Result<Vec<LiveFile>, DatabaseError> {
+        self.db
+            .live_files()
+            .map_err(|e| DatabaseError::Other(format!("Failed to list SST files: {}", e)))
+    }
+
+    /// Compression ratio achieved by the column family `cf`: the size of its SST files on disk
+    /// divided by the uncompressed size of the keys and values in them. Lower is better.
+    ///
+    /// This is an estimate:
+    /// - only flushed data counts, entries still in memtables are ignored
+    /// - the on-disk size includes index and filter blocks and file metadata, which weigh a lot
+    ///   in small tables and push their ratio up
+    /// - overwritten and deleted entries that weren't compacted away yet count on both sides
+    ///
+    /// Returns `1.0` if the column family has no SST files yet.
+    pub fn compression_ratio(&self, cf: &str) -> Result<f64, DatabaseError> {
+        let handle = self
+            .db
+            .cf_handle(cf)
+            .ok_or_else(|| DatabaseError::Other(format!("Column family not found: {}", cf)))?;
+
+        let on_disk: usize = self
+            .sst_files()?
+            .iter()
+            .filter(|file| file.column_family_name == cf)
+            .map(|file| file.size)
+            .sum();
+
+        let properties = self
+            .db
+            .property_value_cf(handle, "rocksdb.aggregated-table-properties")
+            .map_err(|e| DatabaseError::Other(format!("RocksDB error: {}", e)))?
+            .unwrap_or_default();
+        let uncompressed = table_property(&properties, "raw key size")
+            + table_property(&properties, "raw value size");
+
+        if on_disk == 0 || uncompressed == 0 {
+            return Ok(1.0);
+        }
+        Ok(on_disk as f64 / uncompressed as f64)
+    }
+
+    /// Delete all entries of `T` in `[from, to)` and immediately compact that range, dropping the
+    /// range tombstone and reclaiming the space instead of waiting for background compaction
+    pub fn compact_after_delete<T: Table>(
+        &self,
+        from: T::Key,
+        to: T::Key,
+    ) -> Result<(), DatabaseError> {
+        let cf = self.cf::<T>()?;
+        let from = from.encode();
+        let to = to.encode();
+
+        self.db
+            .delete_range_cf(cf, from.as_ref(), to.as_ref())
+            .map_err(|e| DatabaseError::Other(format!("Failed to delete range: {}", e)))?;
+        self.db.compact_range_cf(cf, Some(from.as_ref()), Some(to.as_ref()));
 
-    //     // Initialize column families for trie tables
-    //     let trie_config = TrieTableConfigs::default();
-    //     let cf_descriptors = vec![
-    //         ColumnFamilyDescriptor::new(TrieTable::NAME, trie_config.column_config()),
-    //         ColumnFamilyDescriptor::new(AccountTrieTable::NAME, trie_config.column_config()),
-    //         ColumnFamilyDescriptor::new(StorageTrieTable::NAME, trie_config.column_config()),
-    //     ];
+        Ok(())
+    }
+
+    /// Exchange the contents of the column families `a` and `b`, e.g. to put trie tables rebuilt
+    /// in scratch column families in place.
+    ///
+    /// New write transactions wait until the swap is done, and the open ones are waited for, so
+    /// it must not be called from a thread holding a write transaction. Both column families are
+    /// then read through one snapshot and rewritten in batches of [`SWAP_CHUNK_ENTRIES`], so
+    /// memory stays bounded whatever their size. Readers may see a partly swapped state while it
+    /// runs.
+    pub fn swap_cf_contents(&self, a: &str, b: &str) -> Result<(), DatabaseError> {
+        self.swap_cf_contents_in_chunks(a, b, SWAP_CHUNK_ENTRIES)
+    }
+
+    /// [`Self::swap_cf_contents`], writing a batch every `chunk_entries` entries read
+    pub(crate) fn swap_cf_contents_in_chunks(
+        &self,
+        a: &str,
+        b: &str,
+        chunk_entries: usize,
+    ) -> Result<(), DatabaseError> {
+        if self.read_only {
+            return Err(DatabaseError::Other(
+                "Write transactions are not supported on a read-only database".to_string(),
+            ));
+        }
+        let cf_handle = |name: &str| {
+            self.db
+                .cf_handle(name)
+                .ok_or_else(|| DatabaseError::Other(format!("Column family not found: {}", name)))
+        };
+        let cf_a = cf_handle(a)?;
+        let cf_b = cf_handle(b)?;
+
+        let _closed = self.write_gate.close();
+        let snapshot = self.db.snapshot();
+        let write = |batch: WriteBatch| {
+            self.db
+                .write(batch)
+                .map_err(|e| DatabaseError::Other(format!("Failed to swap {} and {}: {}", a, b, e)))
+        };
+
+        // Every entry of `from` is copied to `to`, and dropped from `from` if `to` didn't have
+        // its key. Reads go through the snapshot, so the copies made in one direction aren't
+        // seen by the other.
+        let copy = |from: &ColumnFamily, from_name: &str, to: &ColumnFamily, to_name: &str| {
+            let mut read_opts = ReadOptions::default();
+            read_opts.set_total_order_seek(true);
+            let mut batch = WriteBatch::default();
+            let mut entries = 0;
+            for item in snapshot.iterator_cf_opt(from, read_opts, IteratorMode::Start) {
+                let (key, value) = item.map_err(|e| {
+                    DatabaseError::Other(format!("Failed to read {}: {}", from_name, e))
+                })?;
+                let in_to = snapshot.get_cf(to, &key).map_err(|e| {
+                    DatabaseError::Other(format!("Failed to read {}: {}", to_name, e))
+                })?;
+                if in_to.is_none() {
+                    batch.delete_cf(from, &key);
+                }
+                batch.put_cf(to, &key, &value);
+
+                entries += 1;
+                if entries % chunk_entries == 0 {
+                    write(std::mem::take(&mut batch))?;
+                }
+            }
+            write(batch)
+        };
+        copy(cf_a, a, cf_b, b)?;
+        copy(cf_b, b, cf_a, a)
+    }
+
+    /// Create a committer that groups write transactions, issuing a single synced write per
+    /// group as set by `policy`. See [`GroupCommit`].
+    pub fn group_committer(&self, policy: GroupCommitPolicy) -> Result<GroupCommit, DatabaseError> {
+        if self.read_only {
+            return Err(DatabaseError::Other(
+                "Write transactions are not supported on a read-only database".to_string(),
+            ));
+        }
+        Ok(GroupCommit::new(self.db.clone(), self.write_gate.clone(), policy))
+    }
+
+    /// Create a writer handing out write transactions that together may stage at most
+    /// `max_pending_bytes` of keys and values. See [`BoundedWriter`].
+    pub fn bounded_writer(&self, max_pending_bytes: usize) -> Result<BoundedWriter, DatabaseError> {
+        if self.read_only {
+            return Err(DatabaseError::Other(
+                "Write transactions are not supported on a read-only database".to_string(),
+            ));
+        }
+        Ok(BoundedWriter::new(self.transaction_builder(), max_pending_bytes))
+    }
+
+    /// Start a background thread compacting tables whose pending compaction bytes exceed the
+    /// threshold in `config`. The thread stops when the returned [`AutoCompactor`] is dropped.
+    pub fn spawn_auto_compactor(
+        &self,
+        config: AutoCompactConfig,
+    ) -> Result<AutoCompactor, DatabaseError> {
+        if self.read_only {
+            return Err(DatabaseError::Other(
+                "Compaction is not supported on a read-only database".to_string(),
+            ));
+        }
+        Ok(AutoCompactor::spawn(self.db.clone(), config))
+    }
+
+    /// Check that every entry of every table decodes, one table at a time
+    pub fn scan_all(&self) -> Result<ScanReport, DatabaseError> {
+        scan_tables(&self.db, 1, &AtomicBool::new(false), self.config.scan_fill_cache)
+    }
+
+    /// Like [`RocksDB::scan_all`], but scans tables concurrently on `threads` workers, each
+    /// reading through its own snapshot.
+    ///
+    /// Setting `cancel` stops the scan early; the returned report then only covers the tables
+    /// that were scanned to completion.
+    pub fn scan_all_parallel(
+        &self,
+        threads: usize,
+        cancel: &AtomicBool,
+    ) -> Result<ScanReport, DatabaseError> {
+        scan_tables(&self.db, threads, cancel, self.config.scan_fill_cache)
+    }
 
-    //     let db = DB::open_cf_descriptors(&opts, path, cf_descriptors)
-    //         .map_err(|e| DatabaseError::Other(format!("Failed to open database: {}", e)))?;
+    /// Export the account and storage trie tables in reth's native table encoding, read through
+    /// a single snapshot. See [`RocksTransaction::export_trie_reth_format`] for the format.
+    pub fn export_trie_reth_format(&self, mut out: impl Write) -> Result<(), DatabaseError> {
+        self.tx()?.export_trie_reth_format(&mut out)
+    }
+
+    /// Export every table to a single portable archive, read through one snapshot.
+    ///
+    /// The archive holds the schema version, the table list, each table's raw entries and a
+    /// trailing keccak256 checksum. Unlike a RocksDB backup it doesn't depend on RocksDB's file
+    /// layout; load it with [`RocksDB::import_archive`].
+    pub fn export_archive(&self, out: impl Write) -> Result<(), DatabaseError> {
+        export_archive(&self.db, &self.table_names()?, out)
+    }
+
+    /// Export every table to an archive like [`RocksDB::export_archive`], over several calls.
+    ///
+    /// Pass `None` to start an export, then the returned checkpoint to each following call
+    /// until [`ExportCheckpoint::is_complete`]. All calls read through the snapshot taken by
+    /// the first one, which the checkpoint holds until it is dropped. Each call writes about
+    /// `budget` bytes of entries, all remaining ones if `None`. `out` must be the same output on
+    /// every call; it is sought to where the previous call stopped.
+    pub fn export_resumable<'a>(
+        &'a self,
+        out: impl Write + Seek,
+        checkpoint: Option<ExportCheckpoint<'a>>,
+        budget: Option<usize>,
+    ) -> Result<ExportCheckpoint<'a>, DatabaseError> {
+        let tables = if checkpoint.is_some() { Vec::new() } else { self.table_names()? };
+        export_resumable(&self.db, &tables, out, checkpoint, budget)
+    }
+
+    /// Create a database at `db_dir` from an archive written by [`RocksDB::export_archive`],
+    /// restoring its entries and schema version.
+    ///
+    /// `db_dir` must not already hold a database. The checksum is only known once the whole
+    /// archive has been read, so on error `db_dir` may be left partially populated and should
+    /// be removed.
+    pub fn import_archive(
+        reader: impl Read,
+        config: RocksDBConfig,
+        db_dir: &Path,
+    ) -> Result<Self, DatabaseError> {
+        if db_dir.join("CURRENT").exists() {
+            return Err(DatabaseError::Other(format!(
+                "Refusing to import archive into existing database at {}",
+                db_dir.display()
+            )));
+        }
+
+        let db = Self::open(db_dir, config)?;
+        import_archive(&db.db, reader)?;
+        Ok(db)
+    }
+
+    /// Read an integer property (e.g. `rocksdb.estimate-live-data-size`) of the column family
+    /// backing `T`
+    pub fn property_int_value_cf<T: Table>(
+        &self,
+        name: &str,
+    ) -> Result<Option<u64>, DatabaseError> {
+        self.db
+            .property_int_value_cf(self.cf::<T>()?, name)
+            .map_err(|e| DatabaseError::Other(format!("RocksDB error: {}", e)))
+    }
+
+    /// Estimate the number of files the database holds open: the SST files at every level of
+    /// every column family, plus one per live version.
+    ///
+    /// Warns once the estimate reaches 90% of
+    /// [`RocksDBConfig::max_open_files`](crate::RocksDBConfig::max_open_files), as files are then
+    /// closed and reopened on demand.
+    pub fn open_file_count(&self) -> Result<u64, DatabaseError> {
+        let mut count = 0;
+        for name in self.cf_names()? {
+            let Some(cf) = self.db.cf_handle(&name) else { continue };
+
+            let levels =
+                (0..NUM_LEVELS).map(|level| format!("rocksdb.num-files-at-level{}", level));
+            for property in levels.chain(["rocksdb.num-live-versions".to_string()]) {
+                let value = self
+                    .db
+                    .property_value_cf(cf, property.as_str())
+                    .map_err(|e| DatabaseError::Other(format!("RocksDB error: {}", e)))?;
+                count += value.and_then(|value| value.trim().parse::<u64>().ok()).unwrap_or(0);
+            }
+        }
+
+        if let Some(max_open_files) = self.config.max_open_files.filter(|max| *max > 0) {
+            if count * 10 >= max_open_files as u64 * 9 {
+                warn!(
+                    target: "storage::db::rocks",
+                    count,
+                    max_open_files,
+                    "Open file count approaching the configured maximum"
+                );
+            }
+        }
+
+        Ok(count)
+    }
+
+    /// Get the column family handle for a table
+    fn cf<T: Table>(&self) -> Result<&ColumnFamily, DatabaseError> {
+        self.db
+            .cf_handle(T::NAME)
+            .ok_or_else(|| DatabaseError::Other(format!("Column family not found: {}", T::NAME)))
+    }
+
+    /// Schema of every table the database opens a column family for: reth's tables followed by
+    /// the trie and account history tables
+    pub fn table_schema(&self) -> Vec<TableSchema> {
+        table_schemas()
+    }
+
+    /// Compare every registered table with its counterpart in `other`, e.g. to check a restored
+    /// backup or a migration, returning the tables that differ. Identical databases give an
+    /// empty list.
+    ///
+    /// Both databases are read in full, each table through snapshots taken when its comparison
+    /// starts, so tables written to during the comparison may be compared at different points
+    /// in time.
+    pub fn diff_against(&self, other: &RocksDB) -> Result<Vec<TableDiffSummary>, DatabaseError> {
+        diff_tables(&self.db, &other.db)
+    }
+
+    /// Names of all column families backing tables, excluding the default and meta column
+    /// families
+    pub fn table_names(&self) -> Result<Vec<String>, DatabaseError> {
+        Ok(self
+            .cf_names()?
+            .into_iter()
+            .filter(|name| !RESERVED_CFS.contains(&name.as_str()))
+            .collect())
+    }
+
+    /// Names of all column families in the database
+    pub fn cf_names(&self) -> Result<Vec<String>, DatabaseError> {
+        DB::list_cf(&Options::default(), self.db.path())
+            .map_err(|e| DatabaseError::Other(format!("Failed to list column families: {}", e)))
+    }
+
+    /// Wrap an already opened database
+    pub fn from_db(db: Arc<DB>, config: RocksDBConfig) -> Self {
+        Self::with_db(db, config, false)
+    }
+
+    /// Wrap an opened database, without a post-commit hook
+    fn with_db(db: Arc<DB>, config: RocksDBConfig, read_only: bool) -> Self {
+        Self {
+            db,
+            config,
+            read_only,
+            post_commit_hook: RwLock::new(None),
+            write_gate: WriteGate::of(&db),
+        }
+    }
+
+    /// Run `hook` after every successful commit of a write transaction created from now on,
+    /// with the operations the transaction applied, in order. It runs on the committing thread
+    /// once the batch is written, and never for failed, aborted, dropped or read-only
+    /// transactions. Replaces any previous hook.
+    ///
+    /// Covers the transactions of [`Database::tx_mut`], [`RocksDB::transaction_builder`] and
+    /// [`BoundedWriter`]s created from now on; transactions committed through a [`GroupCommit`]
+    /// run it once their group is written. Transactions created from the inner database with
+    /// [`RocksTransaction::from_db`] don't know about the database's hook and never run it.
+    pub fn set_post_commit_hook(&self, hook: Box<PostCommitHook>) {
+        *self.post_commit_hook.write().unwrap_or_else(|poisoned| poisoned.into_inner()) =
+            Some(Arc::from(hook));
+    }
+
+    /// Get a handle to the inner database
+    pub fn inner(&self) -> Arc<DB> {
+        self.db.clone()
+    }
+
+    /// Build a transaction with custom read and write options. The builder starts from the
+    /// options [`Database::tx`] and [`Database::tx_mut`] use.
+    pub fn transaction_builder(&self) -> TransactionBuilder {
+        let read_limit = self
+            .config
+            .max_read_transaction_duration
+            .map(|max| (max, self.config.long_read_transaction_action));
+        let post_commit_hook =
+            self.post_commit_hook.read().unwrap_or_else(|poisoned| poisoned.into_inner()).clone();
+        TransactionBuilder::new(self.db.clone(), self.read_only)
+            .read_limit(read_limit)
+            .scan_fill_cache(self.config.scan_fill_cache)
+            .post_commit_hook(post_commit_hook)
+            .slow_op_threshold(self.config.slow_op_threshold)
+            .write_gate(self.write_gate.clone())
+    }
+
+    /// Whether the database was opened without write access
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// Get the configuration the database was opened with
+    pub fn config(&self) -> &RocksDBConfig {
+        &self.config
+    }
+
+    /// Write stalls since the database was opened, read from the `rocksdb.dbstats` and
+    /// per-table `rocksdb.cfstats-no-file-histogram` properties.
+    ///
+    /// With the `metrics` feature, the stats are also published as the
+    /// `rocksdb_write_stall_micros_total`, `rocksdb_write_stall_stops_total` and
+    /// `rocksdb_write_stall_slowdowns_total` counters.
+    pub fn write_stall_stats(&self) -> Result<WriteStallStats, DatabaseError> {
+        let property = |cf: Option<&ColumnFamily>, name: &str| {
+            match cf {
+                Some(cf) => self.db.property_value_cf(cf, name),
+                None => self.db.property_value(name),
+            }
+            .map(Option::unwrap_or_default)
+            .map_err(|e| DatabaseError::Other(format!("RocksDB error: {}", e)))
+        };
+
+        let mut stats = WriteStallStats {
+            total_stall_micros: cumulative_stall_micros(&property(None, "rocksdb.dbstats")?),
+            ..WriteStallStats::default()
+        };
+        for name in self.cf_names()? {
+            let Some(cf) = self.db.cf_handle(&name) else { continue };
+            let cf_stats = property(Some(cf), "rocksdb.cfstats-no-file-histogram")?;
+            stats.stopped_count += write_stall_count(&cf_stats, "total-stops");
+            stats.slowdown_count += write_stall_count(&cf_stats, "total-delays");
+        }
+
+        #[cfg(feature = "metrics")]
+        {
+            metrics::counter!("rocksdb_write_stall_micros_total")
+                .absolute(stats.total_stall_micros);
+            metrics::counter!("rocksdb_write_stall_stops_total").absolute(stats.stopped_count);
+            metrics::counter!("rocksdb_write_stall_slowdowns_total").absolute(stats.slowdown_count);
+        }
+        Ok(stats)
+    }
+
+    /// Schema version stored in the database, read without migrating anything.
+    ///
+    /// [`RocksDB::open`] migrates databases to the current version, so older versions show up
+    /// in databases opened read-only or as a secondary.
+    pub fn schema_version(&self) -> Result<u32, DatabaseError> {
+        read_version(&self.db)?
+            .ok_or_else(|| DatabaseError::Other("Database has no schema version".to_string()))
+    }
+
+    /// Whether the stored schema version needs no migration by this release
+    pub fn is_up_to_date(&self) -> Result<bool, DatabaseError> {
+        Ok(self.schema_version()? >= CURRENT_VERSION)
+    }
+
+    /// Age of the oldest snapshot currently held, read from `rocksdb.oldest-snapshot-time`.
+    ///
+    /// Returns `None` if no snapshot is held.
+    pub fn oldest_snapshot_age(&self) -> Result<Option<Duration>, DatabaseError> {
+        let oldest = self
+            .db
+            .property_int_value("rocksdb.oldest-snapshot-time")
+            .map_err(|e| DatabaseError::Other(format!("RocksDB error: {}", e)))?;
+
+        match oldest {
+            None | Some(0) => Ok(None),
+            Some(secs) => {
+                let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+                Ok(Some(now.saturating_sub(Duration::from_secs(secs))))
+            }
+        }
+    }
+}
+
+/// Value of `name` in table properties formatted as `name=value; name=value`, `0` if absent
+fn table_property(properties: &str, name: &str) -> u64 {
+    properties
+        .split(';')
+        .filter_map(|property| property.split_once('='))
+        .find(|(key, _)| key.trim() == name)
+        .and_then(|(_, value)| value.trim().parse().ok())
+        .unwrap_or(0)
+}
+
+/// Stall time in `rocksdb.dbstats`, reported as `Cumulative stall: HH:MM:SS.mmm H:M:S`, `0` if
+/// absent
+pub(crate) fn cumulative_stall_micros(db_stats: &str) -> u64 {
+    let micros = |time: &str| {
+        let mut parts = time.split(':');
+        let hours: u64 = parts.next()?.parse().ok()?;
+        let minutes: u64 = parts.next()?.parse().ok()?;
+        let seconds: f64 = parts.next()?.parse().ok()?;
+        Some((hours * 3600 + minutes * 60) * 1_000_000 + (seconds * 1_000_000.0).round() as u64)
+    };
+
+    db_stats
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("Cumulative stall:"))
+        .and_then(|rest| rest.split_whitespace().next())
+        .and_then(micros)
+        .unwrap_or(0)
+}
+
+/// Count `name` of the `Write Stall (count): name: count, ...` line of column family stats, `0`
+/// if absent
+pub(crate) fn write_stall_count(cf_stats: &str, name: &str) -> u64 {
+    cf_stats
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("Write Stall (count):"))
+        .flat_map(|counts| counts.split(','))
+        .filter_map(|count| count.split_once(':'))
+        .find(|(key, _)| key.trim() == name)
+        .and_then(|(_, value)| value.trim().parse().ok())
+        .unwrap_or(0)
+}
+
+/// Descriptors of the column families of the database at `path`, with the options
+/// [`RocksDB::open`] gives them, so prefix extractors and comparators match the primary's
+fn existing_cf_descriptors(
+    path: &Path,
+    config: &RocksDBConfig,
+) -> Result<Vec<ColumnFamilyDescriptor>, DatabaseError> {
+    let names = DB::list_cf(&Options::default(), path)
+        .map_err(|e| DatabaseError::Other(format!("Failed to list column families: {}", e)))?;
+
+    Ok(names
+        .into_iter()
+        .map(|name| {
+            if name == META_CF {
+                return ColumnFamilyDescriptor::new(name, Options::default());
+            }
+            table_descriptor_for(&name, config).unwrap_or_else(|| {
+                let opts = config.table_options_for(&name);
+                ColumnFamilyDescriptor::new(name, opts)
+            })
+        })
+        .collect())
+}
+
+/// Whether opening failed with `message` because another handle holds the database's `LOCK`,
+/// in this process or another one
+fn is_lock_held(message: &str) -> bool {
+    message.contains("lock hold by current process") || message.contains("While lock file")
+}
+
+/// WAL directory `config` asks for, `None` if it's the data directory at `path`
+fn configured_wal_dir<'a>(path: &Path, config: &'a RocksDBConfig) -> Option<&'a Path> {
+    config.wal_dir.as_deref().map(Path::new).filter(|wal_dir| *wal_dir != path)
+}
+
+/// Refuse to open an existing database with a different WAL directory than it was created with.
+///
+/// RocksDB would open it anyway, without replaying the WAL, and the next flush would make the
+/// writes that only lived there unrecoverable.
+fn check_wal_dir(path: &Path, config: &RocksDBConfig) -> Result<(), DatabaseError> {
+    if !path.join("CURRENT").exists() {
+        return Ok(());
+    }
+
+    let recorded = match std::fs::read_to_string(path.join(WAL_DIR_FILE)) {
+        Ok(wal_dir) => Some(wal_dir),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+        Err(e) => return Err(DatabaseError::Other(format!("Failed to read WAL directory: {}", e))),
+    };
+
+    let configured = configured_wal_dir(path, config);
+    if recorded.as_deref().map(Path::new) != configured {
+        return Err(DatabaseError::Other(format!(
+            "Database at {} uses WAL directory {}, but {} was configured",
+            path.display(),
+            recorded.as_deref().unwrap_or("<data directory>"),
+            configured.map_or("<data directory>".into(), |wal_dir| wal_dir.display().to_string()),
+        )));
+    }
+    Ok(())
+}
 
-    //     Ok(Self { db: Arc::new(db) })
-    // }
+/// Record the WAL directory of a database opened read-write at `path`
+fn record_wal_dir(path: &Path, config: &RocksDBConfig) -> Result<(), DatabaseError> {
+    let marker = path.join(WAL_DIR_FILE);
+    let result = match configured_wal_dir(path, config) {
+        Some(wal_dir) => std::fs::write(&marker, wal_dir.to_string_lossy().as_bytes()),
+        None if marker.exists() => std::fs::remove_file(&marker),
+        None => Ok(()),
+    };
+    result.map_err(|e| DatabaseError::Other(format!("Failed to record WAL directory: {}", e)))
 }
 
 impl Database for RocksDB {
@@ -41,10 +927,10 @@ impl Database for RocksDB {
     type TXMut = RocksTransaction<true>;
 
     fn tx(&self) -> Result<Self::TX, DatabaseError> {
-        Ok(RocksTransaction::new(self.db.clone(), false))
+        Ok(self.transaction_builder().read())
     }
 
     fn tx_mut(&self) -> Result<Self::TXMut, DatabaseError> {
-        Ok(RocksTransaction::new(self.db.clone(), true))
+        self.transaction_builder().write()
     }
 }
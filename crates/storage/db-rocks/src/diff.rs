@@ -0,0 +1,102 @@
+use crate::tables::table_schemas;
+use reth_db_api::DatabaseError;
+use rocksdb::{ReadOptions, DB};
+use std::cmp::Ordering;
+
+/// Differences between the contents of one table in two databases, see
+/// [`RocksDB::diff_against`](crate::RocksDB::diff_against)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TableDiffSummary {
+    /// Table name
+    pub table: &'static str,
+    /// Keys stored only in the database the diff was taken from
+    pub only_in_self: u64,
+    /// Keys stored only in the database it was compared against
+    pub only_in_other: u64,
+    /// Keys stored in both with different values
+    pub value_mismatches: u64,
+}
+
+impl TableDiffSummary {
+    /// Whether the table is the same in both databases
+    pub fn is_empty(&self) -> bool {
+        self.only_in_self == 0 && self.only_in_other == 0 && self.value_mismatches == 0
+    }
+}
+
+/// Summaries of the registered tables that differ between `ours` and `theirs`, in registry
+/// order.
+///
+/// Each table is read through a snapshot of both databases taken before reading it, and its
+/// raw entries are merge-joined in byte order of their keys.
+pub(crate) fn diff_tables(ours: &DB, theirs: &DB) -> Result<Vec<TableDiffSummary>, DatabaseError> {
+    let mut diffs = Vec::new();
+    for table in table_schemas() {
+        let diff = diff_table(table.name, ours, theirs)?;
+        if !diff.is_empty() {
+            diffs.push(diff);
+        }
+    }
+    Ok(diffs)
+}
+
+/// Merge-join the entries of the column family `name` in `ours` and `theirs`
+fn diff_table(
+    name: &'static str,
+    ours: &DB,
+    theirs: &DB,
+) -> Result<TableDiffSummary, DatabaseError> {
+    let cf = |db| {
+        DB::cf_handle(db, name)
+            .ok_or_else(|| DatabaseError::Other(format!("Column family not found: {}", name)))
+    };
+    let (our_cf, their_cf) = (cf(ours)?, cf(theirs)?);
+
+    let (our_snapshot, their_snapshot) = (ours.snapshot(), theirs.snapshot());
+    let read_options = |snapshot| {
+        let mut read_opts = ReadOptions::default();
+        read_opts.set_snapshot(snapshot);
+        // A one-off full read, of every prefix of tables with a prefix extractor
+        read_opts.fill_cache(false);
+        read_opts.set_total_order_seek(true);
+        read_opts
+    };
+    let mut our_iter = ours.raw_iterator_cf_opt(our_cf, read_options(&our_snapshot));
+    let mut their_iter = theirs.raw_iterator_cf_opt(their_cf, read_options(&their_snapshot));
+    our_iter.seek_to_first();
+    their_iter.seek_to_first();
+
+    let mut diff = TableDiffSummary { table: name, ..Default::default() };
+    loop {
+        let order = match (our_iter.key(), their_iter.key()) {
+            (None, None) => break,
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (Some(our_key), Some(their_key)) => our_key.cmp(their_key),
+        };
+        match order {
+            Ordering::Less => {
+                diff.only_in_self += 1;
+                our_iter.next();
+            }
+            Ordering::Greater => {
+                diff.only_in_other += 1;
+                their_iter.next();
+            }
+            Ordering::Equal => {
+                if our_iter.value() != their_iter.value() {
+                    diff.value_mismatches += 1;
+                }
+                our_iter.next();
+                their_iter.next();
+            }
+        }
+    }
+
+    // Iterators also stop on errors, which would otherwise pass for the end of the table
+    our_iter
+        .status()
+        .and(their_iter.status())
+        .map_err(|e| DatabaseError::Other(format!("RocksDB iterator error: {}", e)))?;
+    Ok(diff)
+}
@@ -1,3 +1,5 @@
+use reth_db_api::{DatabaseError, DatabaseWriteOperation};
+use reth_storage_errors::db::{DatabaseErrorInfo, DatabaseWriteError};
 use thiserror::Error;
 
 /// RocksDB specific errors
@@ -30,11 +32,83 @@ pub enum RocksDBError {
     /// Invalid configuration
     #[error("Configuration error: {0}")]
     Config(String),
+
+    /// Operation didn't complete before its deadline
+    #[error("Operation timed out after {0:?}")]
+    Timeout(std::time::Duration),
+
+    /// A trie node expected to be present was not found
+    #[error("Missing trie node: {0}")]
+    MissingNode(alloy_primitives::B256),
+
+    /// Staging a write would take the pending bytes of a bounded writer over its limit
+    #[error("Write of {requested} bytes would exceed the limit of {limit} pending bytes ({pending} pending)")]
+    WouldExceedLimit { pending: usize, requested: usize, limit: usize },
+
+    /// A proof generated from the database doesn't verify against the expected root
+    #[error("Proof verification failed against root {root}: {reason}")]
+    ProofVerification { root: alloy_primitives::B256, reason: String },
+
+    /// Error of the database interface
+    #[error(transparent)]
+    Database(#[from] DatabaseError),
+}
+
+impl RocksDBError {
+    /// Code of [`RocksDBError::Timeout`] once converted into a [`DatabaseError`]
+    pub const TIMEOUT_CODE: i32 = -31_001;
+    /// Code of [`RocksDBError::MissingNode`] once converted into a [`DatabaseError`]
+    pub const MISSING_NODE_CODE: i32 = -31_002;
+    /// Code of [`RocksDBError::WouldExceedLimit`] once converted into a [`DatabaseError`]
+    pub const WOULD_EXCEED_LIMIT_CODE: i32 = -31_003;
+    /// Code of [`RocksDBError::ProofVerification`] once converted into a [`DatabaseError`]
+    pub const PROOF_VERIFICATION_CODE: i32 = -31_004;
+
+    /// Code of the error info a [`DatabaseError`] converted from `self` carries, if it keeps
+    /// the kind of error distinguishable
+    pub const fn code(&self) -> Option<i32> {
+        match self {
+            Self::Timeout(_) => Some(Self::TIMEOUT_CODE),
+            Self::MissingNode(_) => Some(Self::MISSING_NODE_CODE),
+            Self::WouldExceedLimit { .. } => Some(Self::WOULD_EXCEED_LIMIT_CODE),
+            Self::ProofVerification { .. } => Some(Self::PROOF_VERIFICATION_CODE),
+            _ => None,
+        }
+    }
+
+    /// Code of the error info carried by `error`, to tell which of the typed errors it was
+    /// converted from, if any
+    pub fn code_of(error: &DatabaseError) -> Option<i32> {
+        match error {
+            DatabaseError::Read(info) => Some(info.code),
+            DatabaseError::Write(error) => Some(error.info.code),
+            _ => None,
+        }
+    }
+
+    /// Convert into the [`DatabaseError`] of a refused write of `key` to `table_name`
+    pub(crate) fn into_write_error(self, table_name: &'static str, key: &[u8]) -> DatabaseError {
+        match self.code() {
+            Some(code) => DatabaseWriteError {
+                info: DatabaseErrorInfo { message: self.to_string().into(), code },
+                operation: DatabaseWriteOperation::Put,
+                table_name,
+                key: key.to_vec(),
+            }
+            .into(),
+            None => self.into(),
+        }
+    }
 }
 
-/// Maps RocksDB errors to DatabaseError
-impl From<RocksDBError> for reth_db_api::DatabaseError {
+/// Maps RocksDB errors to DatabaseError. Typed errors with a [`RocksDBError::code`] become
+/// errors carrying it, readable back with [`RocksDBError::code_of`].
+impl From<RocksDBError> for DatabaseError {
     fn from(error: RocksDBError) -> Self {
+        if let Some(code) = error.code() {
+            return Self::Read(DatabaseErrorInfo { message: error.to_string().into(), code });
+        }
+
         match error {
             RocksDBError::RocksDB(e) => Self::Other(format!("RocksDB error: {}", e)),
             RocksDBError::ColumnFamily(msg) => Self::Other(msg),
@@ -45,6 +119,8 @@ impl From<RocksDBError> for reth_db_api::DatabaseError {
             RocksDBError::Migration(msg) => Self::Other(msg),
             RocksDBError::Transaction(msg) => Self::Other(format!("Transaction error: {}", msg)),
             RocksDBError::Config(msg) => Self::Other(msg),
+            RocksDBError::Database(error) => error,
+            error => Self::Other(error.to_string()),
         }
     }
 }
@@ -0,0 +1,231 @@
+use crate::{
+    implementation::rocks::tx::{DeferredCommit, RocksTransaction},
+    write_gate::WriteGate,
+};
+use reth_db_api::DatabaseError;
+use rocksdb::{WriteOptions, DB};
+use std::{
+    sync::{Arc, Condvar, Mutex, MutexGuard},
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+use tracing::error;
+
+/// When a [`GroupCommit`] syncs its pending transactions to disk
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GroupCommitPolicy {
+    /// Sync once this many transactions are pending
+    pub max_commits: usize,
+    /// Sync once the oldest pending transaction has waited this long
+    pub max_delay: Duration,
+}
+
+impl GroupCommitPolicy {
+    /// Sync every `max_commits` transactions, or after `max_delay`, whichever comes first
+    pub const fn new(max_commits: usize, max_delay: Duration) -> Self {
+        Self { max_commits, max_delay }
+    }
+}
+
+impl Default for GroupCommitPolicy {
+    fn default() -> Self {
+        Self { max_commits: 64, max_delay: Duration::from_millis(100) }
+    }
+}
+
+/// Commits write transactions in groups, paying for a single fsync per group.
+///
+/// Committed transactions are held back until the group is full or its oldest transaction has
+/// waited [`GroupCommitPolicy::max_delay`]. The group is then written in commit order, each
+/// transaction as its own atomic batch, and only the last write is synced, which makes the whole
+/// group durable. Until then, committed data is neither visible to readers nor durable.
+///
+/// Each transaction is written like [`DbTx::commit`](reth_db_api::transaction::DbTx::commit)
+/// writes it: timed against the slow operation threshold, and followed by the post-commit hook
+/// the transaction was created with, which must not use the committer. Groups are written through the database's write gate, so
+/// they wait for a [`RocksDB::consistent_snapshot`](crate::RocksDB::consistent_snapshot) in
+/// progress to finish.
+///
+/// A background thread writes a group once its delay runs out, even if no further transaction
+/// is committed; [`GroupCommit::flush`] writes it right away. If writing fails, the transactions
+/// not yet written stay pending and are retried with the next group. Pending transactions are
+/// flushed when the committer is dropped.
+pub struct GroupCommit {
+    shared: Arc<Shared>,
+    policy: GroupCommitPolicy,
+    /// Thread writing groups whose delay ran out
+    flusher: Option<JoinHandle<()>>,
+}
+
+/// State shared between a [`GroupCommit`] and its flusher thread
+struct Shared {
+    db: Arc<DB>,
+    /// Gate of the database, passed for every group written
+    gate: Arc<WriteGate>,
+    state: Mutex<GroupState>,
+    /// Signalled when a new group starts or the committer is dropped
+    wake: Condvar,
+}
+
+/// Transactions waiting for the next group sync
+#[derive(Default)]
+struct GroupState {
+    /// Writes of the committed transactions, in commit order
+    pending: Vec<DeferredCommit>,
+    /// When the oldest pending transaction was committed
+    oldest: Option<Instant>,
+    /// Number of synced writes issued so far
+    synced_writes: u64,
+    /// Whether the committer is being dropped, stopping the flusher thread
+    closed: bool,
+}
+
+impl std::fmt::Debug for GroupCommit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let state = self.shared.lock();
+        f.debug_struct("GroupCommit")
+            .field("policy", &self.policy)
+            .field("pending", &state.pending.len())
+            .field("synced_writes", &state.synced_writes)
+            .finish()
+    }
+}
+
+impl GroupCommit {
+    /// Create a group committer writing to `db`
+    pub(crate) fn new(db: Arc<DB>, gate: Arc<WriteGate>, policy: GroupCommitPolicy) -> Self {
+        let shared = Arc::new(Shared {
+            db,
+            gate,
+            state: Mutex::new(GroupState::default()),
+            wake: Condvar::new(),
+        });
+        let flusher = {
+            let shared = shared.clone();
+            std::thread::spawn(move || shared.run_flusher(policy.max_delay))
+        };
+        Self { shared, policy, flusher: Some(flusher) }
+    }
+
+    /// The grouping policy
+    pub fn policy(&self) -> GroupCommitPolicy {
+        self.policy
+    }
+
+    /// Add the transaction to the current group, syncing the group if the policy says so
+    pub fn commit(&self, tx: RocksTransaction<true>) -> Result<(), DatabaseError> {
+        let mut state = self.shared.lock();
+        state.pending.push(tx.into_deferred_commit());
+        let oldest = match state.oldest {
+            Some(oldest) => oldest,
+            None => {
+                // A new group, for the flusher to time
+                self.shared.wake.notify_one();
+                *state.oldest.insert(Instant::now())
+            }
+        };
+
+        if state.pending.len() >= self.policy.max_commits ||
+            oldest.elapsed() >= self.policy.max_delay
+        {
+            self.shared.write_group(&mut state)?;
+        }
+        Ok(())
+    }
+
+    /// Write and sync all pending transactions
+    pub fn flush(&self) -> Result<(), DatabaseError> {
+        self.shared.write_group(&mut self.shared.lock())
+    }
+
+    /// Number of transactions waiting for the next sync
+    pub fn pending(&self) -> usize {
+        self.shared.lock().pending.len()
+    }
+
+    /// Number of synced writes issued so far, one per group
+    pub fn synced_writes(&self) -> u64 {
+        self.shared.lock().synced_writes
+    }
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, GroupState> {
+        self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Write each group once its oldest transaction has waited `max_delay`, until the committer
+    /// is dropped
+    fn run_flusher(&self, max_delay: Duration) {
+        let mut state = self.lock();
+        while !state.closed {
+            let Some(oldest) = state.oldest else {
+                state = self.wake.wait(state).unwrap_or_else(|poisoned| poisoned.into_inner());
+                continue;
+            };
+
+            let deadline = oldest + max_delay;
+            let now = Instant::now();
+            if now < deadline {
+                state = self
+                    .wake
+                    .wait_timeout(state, deadline - now)
+                    .unwrap_or_else(|poisoned| poisoned.into_inner())
+                    .0;
+            } else if let Err(err) = self.write_group(&mut state) {
+                error!(target: "storage::db::rocks", %err, "Failed to write delayed group commit");
+                // Retry after another delay rather than right away
+                state.oldest = Some(Instant::now());
+            }
+        }
+    }
+
+    /// Write the pending transactions in commit order, syncing only the last write.
+    ///
+    /// Each batch is written from a copy, so if a write fails, the batch and the ones after it
+    /// stay pending.
+    fn write_group(&self, state: &mut GroupState) -> Result<(), DatabaseError> {
+        if state.pending.is_empty() {
+            state.oldest = None;
+            return Ok(());
+        }
+        let _permit = self.gate.enter();
+
+        let mut pending = std::mem::take(&mut state.pending);
+        let last = pending.len().saturating_sub(1);
+
+        // Syncing the last write also persists the WAL records of the writes before it
+        let async_opts = WriteOptions::default();
+        let mut sync_opts = WriteOptions::default();
+        sync_opts.set_sync(true);
+        let mut written = 0;
+        let result = pending.iter().enumerate().try_for_each(|(i, commit)| {
+            let opts = if i == last { &sync_opts } else { &async_opts };
+            commit.write(&self.db, opts)?;
+            written += 1;
+            Ok::<_, DatabaseError>(())
+        });
+
+        pending.drain(..written);
+        state.pending = pending;
+        result.map_err(|e| DatabaseError::Other(format!("Failed to commit group: {}", e)))?;
+
+        state.synced_writes += 1;
+        state.oldest = None;
+        Ok(())
+    }
+}
+
+impl Drop for GroupCommit {
+    fn drop(&mut self) {
+        self.shared.lock().closed = true;
+        self.shared.wake.notify_one();
+        if let Some(flusher) = self.flusher.take() {
+            let _ = flusher.join();
+        }
+
+        if let Err(err) = self.shared.write_group(&mut self.shared.lock()) {
+            error!(target: "storage::db::rocks", %err, "Failed to flush pending group commit");
+        }
+    }
+}
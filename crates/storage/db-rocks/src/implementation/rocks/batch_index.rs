@@ -0,0 +1,390 @@
+use librocksdb_sys as ffi;
+use reth_db_api::DatabaseError;
+use rocksdb::{
+    AsColumnFamilyRef, ColumnFamily, DBRawIteratorWithThreadMode, ReadOptions, WriteBatch, DB,
+};
+use std::{
+    collections::{btree_map, BTreeMap, BTreeSet, HashMap},
+    ffi::{c_char, c_void, CStr},
+    iter::Peekable,
+    ops::Bound,
+    ptr, slice,
+};
+
+/// Size of the header of a serialized [`WriteBatch`]: its sequence number, then the number of
+/// records that follow
+const WRITE_BATCH_HEADER_LEN: usize = 12;
+
+/// Writes staged by a write transaction, applied to the database on commit
+pub(crate) enum TxBatch {
+    /// Batch only read back on commit
+    Plain(WriteBatch),
+    /// Batch indexed by key, so reads of the transaction see its writes before commit
+    Indexed(IndexedBatch),
+}
+
+impl Default for TxBatch {
+    fn default() -> Self {
+        Self::Plain(WriteBatch::default())
+    }
+}
+
+impl TxBatch {
+    /// Stage a put of `key`
+    pub(crate) fn put_cf(
+        &mut self,
+        cf: &ColumnFamily,
+        key: impl AsRef<[u8]>,
+        value: impl AsRef<[u8]>,
+    ) {
+        match self {
+            Self::Plain(batch) => batch.put_cf(cf, key, value),
+            Self::Indexed(batch) => batch.put_cf(cf, key.as_ref(), value.as_ref()),
+        }
+    }
+
+    /// Stage a delete of `key`
+    pub(crate) fn delete_cf(&mut self, cf: &ColumnFamily, key: impl AsRef<[u8]>) {
+        match self {
+            Self::Plain(batch) => batch.delete_cf(cf, key),
+            Self::Indexed(batch) => batch.delete_cf(cf, key.as_ref()),
+        }
+    }
+
+    /// Stage a delete of all keys in `[from, to)`
+    pub(crate) fn delete_range_cf(
+        &mut self,
+        cf: &ColumnFamily,
+        from: impl AsRef<[u8]>,
+        to: impl AsRef<[u8]>,
+    ) {
+        match self {
+            Self::Plain(batch) => batch.delete_range_cf(cf, from, to),
+            Self::Indexed(batch) => batch.delete_range_cf(cf, from.as_ref(), to.as_ref()),
+        }
+    }
+
+    /// Plain batch of the staged writes, as written on commit
+    pub(crate) fn into_write_batch(self) -> WriteBatch {
+        match self {
+            Self::Plain(batch) => batch,
+            Self::Indexed(batch) => batch.into_write_batch(),
+        }
+    }
+
+    /// Value of `key` in `db` merged with the staged writes, reading the database with
+    /// `read_opts`. Returns `None` if the batch isn't indexed, leaving the read to the caller.
+    pub(crate) fn get_merged(
+        &self,
+        db: &DB,
+        cf: &ColumnFamily,
+        key: &[u8],
+        read_opts: &ReadOptions,
+    ) -> Option<Result<Option<Vec<u8>>, DatabaseError>> {
+        let Self::Indexed(batch) = self else { return None };
+
+        if batch.is_written(cf, key) {
+            return Some(batch.get_from_batch(cf, key));
+        }
+        if batch.is_range_deleted(cf, key) {
+            return Some(Ok(None));
+        }
+        Some(
+            db.get_cf_opt(cf, key, read_opts)
+                .map_err(|e| DatabaseError::Other(format!("RocksDB error: {}", e))),
+        )
+    }
+}
+
+/// RocksDB's `WriteBatchWithIndex`, used through the C API since the `rocksdb` bindings don't
+/// expose it.
+///
+/// `WriteBatchWithIndex` doesn't support range deletes, so they are kept aside and written
+/// ahead of the batch on commit. Keys of the batch in a deleted range are deleted in the batch
+/// too, so only writes staged after the range delete survive it.
+pub(crate) struct IndexedBatch {
+    inner: *mut ffi::rocksdb_writebatch_wi_t,
+    /// Options the batch is read with
+    options: *mut ffi::rocksdb_options_t,
+    /// Keys written in the batch, by column family. Reads of the batch don't tell a deleted key
+    /// from one it never saw, so these do.
+    written: HashMap<*const ColumnFamily, BTreeSet<Vec<u8>>>,
+    /// Ranges deleted in the batch, in order
+    deleted_ranges: Vec<(*const ColumnFamily, Vec<u8>, Vec<u8>)>,
+}
+
+// Safety: the batch is owned and only used behind the transaction's lock, and the column
+// families it points to live as long as the database the transaction keeps alive
+unsafe impl Send for IndexedBatch {}
+
+impl Default for IndexedBatch {
+    fn default() -> Self {
+        // Safety: creates an empty batch and options owned by `Self`, which destroys them on drop
+        unsafe {
+            Self {
+                inner: ffi::rocksdb_writebatch_wi_create(0, 1),
+                options: ffi::rocksdb_options_create(),
+                written: HashMap::new(),
+                deleted_ranges: Vec::new(),
+            }
+        }
+    }
+}
+
+impl Drop for IndexedBatch {
+    fn drop(&mut self) {
+        // Safety: `inner` and `options` were created in `default` and aren't used after this
+        unsafe {
+            ffi::rocksdb_writebatch_wi_destroy(self.inner);
+            ffi::rocksdb_options_destroy(self.options);
+        }
+    }
+}
+
+impl IndexedBatch {
+    fn put_cf(&mut self, cf: &ColumnFamily, key: &[u8], value: &[u8]) {
+        // Safety: the batch copies the key and value
+        unsafe {
+            ffi::rocksdb_writebatch_wi_put_cf(
+                self.inner,
+                cf.inner(),
+                key.as_ptr() as *const c_char,
+                key.len(),
+                value.as_ptr() as *const c_char,
+                value.len(),
+            )
+        }
+        self.written.entry(cf as *const _).or_default().insert(key.to_vec());
+    }
+
+    fn delete_cf(&mut self, cf: &ColumnFamily, key: &[u8]) {
+        // Safety: the batch copies the key
+        unsafe {
+            ffi::rocksdb_writebatch_wi_delete_cf(
+                self.inner,
+                cf.inner(),
+                key.as_ptr() as *const c_char,
+                key.len(),
+            )
+        }
+        self.written.entry(cf as *const _).or_default().insert(key.to_vec());
+    }
+
+    fn delete_range_cf(&mut self, cf: &ColumnFamily, from: &[u8], to: &[u8]) {
+        if from >= to {
+            return;
+        }
+
+        // Writes staged before the range delete are written after it on commit, so they are
+        // deleted in the batch instead
+        let staged: Vec<_> = self
+            .written
+            .get(&(cf as *const _))
+            .map(|keys| {
+                keys.range::<[u8], _>((Bound::Included(from), Bound::Excluded(to)))
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default();
+        for key in staged {
+            self.delete_cf(cf, &key);
+        }
+
+        self.deleted_ranges.push((cf as *const _, from.to_vec(), to.to_vec()));
+    }
+
+    fn is_written(&self, cf: &ColumnFamily, key: &[u8]) -> bool {
+        self.written.get(&(cf as *const _)).is_some_and(|keys| keys.contains(key))
+    }
+
+    fn is_range_deleted(&self, cf: &ColumnFamily, key: &[u8]) -> bool {
+        self.deleted_ranges
+            .iter()
+            .any(|(range_cf, from, to)| ptr::eq(*range_cf, cf) && in_range(key, from, to))
+    }
+
+    /// Latest write of `key` staged in the batch, `None` if it was deleted
+    fn get_from_batch(
+        &self,
+        cf: &ColumnFamily,
+        key: &[u8],
+    ) -> Result<Option<Vec<u8>>, DatabaseError> {
+        let mut len = 0;
+        let mut err: *mut c_char = ptr::null_mut();
+        // Safety: the returned value and error are owned by the caller, and freed once copied
+        unsafe {
+            let value = ffi::rocksdb_writebatch_wi_get_from_batch_cf(
+                self.inner,
+                self.options,
+                cf.inner(),
+                key.as_ptr() as *const c_char,
+                key.len(),
+                &mut len,
+                &mut err,
+            );
+            if !err.is_null() {
+                let message = CStr::from_ptr(err).to_string_lossy().into_owned();
+                ffi::rocksdb_free(err as *mut c_void);
+                return Err(DatabaseError::Other(format!("RocksDB error: {}", message)));
+            }
+            if value.is_null() {
+                return Ok(None);
+            }
+            let bytes = slice::from_raw_parts(value as *const u8, len).to_vec();
+            ffi::rocksdb_free(value as *mut c_void);
+            Ok(Some(bytes))
+        }
+    }
+
+    /// Writes of `cf` staged in the batch, `None` for deletes
+    fn staged_writes(&self, cf: &ColumnFamily) -> Result<StagedWrites, DatabaseError> {
+        let Some(keys) = self.written.get(&(cf as *const _)) else {
+            return Ok(StagedWrites::default());
+        };
+        let writes = keys
+            .iter()
+            .map(|key| Ok((key.clone(), self.get_from_batch(cf, key)?)))
+            .collect::<Result<_, DatabaseError>>()?;
+        let deleted_ranges = self
+            .deleted_ranges
+            .iter()
+            .filter(|(range_cf, _, _)| ptr::eq(*range_cf, cf))
+            .map(|(_, from, to)| (from.clone(), to.clone()))
+            .collect();
+        Ok(StagedWrites { writes, deleted_ranges })
+    }
+
+    /// Serialized writes of the batch, in the format of [`WriteBatch::data`]
+    fn data(&self) -> &[u8] {
+        let mut len = 0;
+        // Safety: the returned bytes are owned by the batch and live as long as `&self`
+        unsafe {
+            let data = ffi::rocksdb_writebatch_wi_data(self.inner, &mut len);
+            slice::from_raw_parts(data as *const u8, len)
+        }
+    }
+
+    /// Plain batch of the range deletes followed by the writes of the batch
+    fn into_write_batch(self) -> WriteBatch {
+        let writes = self.data();
+        if self.deleted_ranges.is_empty() {
+            return WriteBatch::from_data(writes);
+        }
+
+        let mut ranges = WriteBatch::default();
+        for (cf, from, to) in &self.deleted_ranges {
+            // Safety: column families live as long as the database
+            ranges.delete_range_cf(unsafe { &**cf }, from, to);
+        }
+
+        // Append the records of the batch to the range deletes, adding up their counts
+        let count = |data: &[u8]| {
+            u32::from_le_bytes(data[8..WRITE_BATCH_HEADER_LEN].try_into().expect("4 bytes"))
+        };
+        let mut data = ranges.data().to_vec();
+        let total = count(&data) + count(writes);
+        data[8..WRITE_BATCH_HEADER_LEN].copy_from_slice(&total.to_le_bytes());
+        data.extend_from_slice(&writes[WRITE_BATCH_HEADER_LEN..]);
+        WriteBatch::from_data(&data)
+    }
+}
+
+/// Whether `key` is in `[from, to)`
+fn in_range(key: &[u8], from: &[u8], to: &[u8]) -> bool {
+    from <= key && key < to
+}
+
+/// Copy of the writes an indexed batch staged in one column family
+#[derive(Default)]
+struct StagedWrites {
+    writes: BTreeMap<Vec<u8>, Option<Vec<u8>>>,
+    deleted_ranges: Vec<(Vec<u8>, Vec<u8>)>,
+}
+
+/// Forward iterator over the raw entries of a column family as seen through an optional
+/// indexed batch: the database merged with the writes staged in the batch when the iterator
+/// was created.
+pub(crate) struct MergedIter<'a> {
+    db: DBRawIteratorWithThreadMode<'a, DB>,
+    /// Staged writes not yet merged, `None` for deletes
+    writes: Peekable<btree_map::IntoIter<Vec<u8>, Option<Vec<u8>>>>,
+    /// Ranges deleted by the batch, hiding the database entries in them
+    deleted_ranges: Vec<(Vec<u8>, Vec<u8>)>,
+}
+
+impl<'a> MergedIter<'a> {
+    /// Iterate `cf` from its first key, merged with the transaction's batch if it is indexed.
+    ///
+    /// The staged writes are copied, so the batch can take more writes while the iterator is
+    /// alive. Those aren't seen by the iterator.
+    pub(crate) fn from_start(
+        batch: Option<&TxBatch>,
+        db: &'a DB,
+        cf: &ColumnFamily,
+        read_opts: ReadOptions,
+    ) -> Result<Self, DatabaseError> {
+        let staged = match batch {
+            Some(TxBatch::Indexed(batch)) => batch.staged_writes(cf)?,
+            _ => StagedWrites::default(),
+        };
+
+        let mut db = db.raw_iterator_cf_opt(cf, read_opts);
+        db.seek_to_first();
+        Ok(Self {
+            db,
+            writes: staged.writes.into_iter().peekable(),
+            deleted_ranges: staged.deleted_ranges,
+        })
+    }
+
+    /// Whether the database entry at `key` was deleted by a range delete of the batch
+    fn is_range_deleted(&self, key: &[u8]) -> bool {
+        self.deleted_ranges.iter().any(|(from, to)| in_range(key, from, to))
+    }
+}
+
+impl Iterator for MergedIter<'_> {
+    type Item = Result<(Vec<u8>, Vec<u8>), DatabaseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if !self.db.valid() {
+                if let Err(e) = self.db.status() {
+                    return Some(Err(DatabaseError::Other(format!(
+                        "RocksDB iterator error: {}",
+                        e
+                    ))));
+                }
+            }
+
+            let db_key = self.db.key().map(<[u8]>::to_vec);
+            let take_staged = match (&db_key, self.writes.peek()) {
+                (None, None) => return None,
+                (None, Some(_)) => true,
+                (Some(_), None) => false,
+                (Some(db_key), Some((staged_key, _))) => {
+                    if staged_key == db_key {
+                        // The staged write replaces the database entry
+                        self.db.next();
+                    }
+                    staged_key <= db_key
+                }
+            };
+
+            if take_staged {
+                let (key, value) = self.writes.next().expect("peeked");
+                match value {
+                    Some(value) => return Some(Ok((key, value))),
+                    None => continue,
+                }
+            }
+
+            let key = db_key.expect("database entry");
+            let value = self.db.value().map(<[u8]>::to_vec).unwrap_or_default();
+            self.db.next();
+            if !self.is_range_deleted(&key) {
+                return Some(Ok((key, value)));
+            }
+        }
+    }
+}
@@ -1,46 +1,140 @@
 use super::dupsort::DupSortHelper;
+use super::slow_op::SlowOpTimer;
 use crate::implementation::rocks::tx::CFPtr;
+use crate::tables::{
+    debug_assert_dupsort_prefix_len, debug_assert_key_round_trip, KeyLayout, RocksTable,
+};
+use crate::write_gate::{WriteGate, WritePermit};
 use reth_db_api::{
     cursor::{
         DbCursorRO, DbCursorRW, DbDupCursorRO, DbDupCursorRW, DupWalker, RangeWalker,
         ReverseWalker, Walker,
     },
     table::{Compress, Decode, Decompress, DupSort, Encode, Table},
-    DatabaseError,
+    DatabaseError, DatabaseWriteOperation,
 };
-use rocksdb::{Direction, IteratorMode, ReadOptions, DB};
+use reth_storage_errors::db::{DatabaseErrorInfo, DatabaseWriteError};
+use rocksdb::{Direction, IteratorMode, ReadOptions, Snapshot, DB};
 use std::ops::RangeBounds;
 use std::result::Result::Ok;
-use std::sync::{Arc, Mutex};
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc, Mutex,
+};
+use std::time::Duration;
 use std::{marker::PhantomData, ops::Bound};
 
+/// Error code MDBX reports when a key/data pair already exists
+const MDBX_KEYEXIST: i32 = -30799;
+
 /// RocksDB cursor implementation
+///
+/// A cursor owns its position: several cursors created from one transaction, even on the same
+/// table, move independently of each other.
+///
+/// Cursors are opened on [`RocksTable`]s, whose key bounds every [`Table`] meets.
 pub struct RocksCursor<T: Table, const WRITE: bool> {
+    /// Snapshot of the transaction that created the cursor, if it holds one, read by every
+    /// iterator. Declared before `db` so it is released before the DB handle is dropped.
+    snapshot: Option<Arc<Snapshot<'static>>>,
     db: Arc<DB>,
     cf: CFPtr,
     current_key_bytes: Mutex<Option<Vec<u8>>>,
     current_value_bytes: Mutex<Option<Vec<u8>>>,
     next_seek_key: Mutex<Option<Vec<u8>>>,
     read_opts: ReadOptions,
+    /// Whether blocks read by the cursor are added to the block cache
+    fill_cache: bool,
+    /// Gate passed by each write the cursor makes to the database, for write transactions
+    /// without a permit of their own
+    write_gate: Option<Arc<WriteGate>>,
+    /// RocksDB iterators created so far
+    iterators_created: AtomicU64,
+    /// Steps taken through those iterators
+    iterator_steps: AtomicU64,
     _marker: std::marker::PhantomData<T>,
 }
 
-impl<T: Table, const WRITE: bool> RocksCursor<T, WRITE>
-where
-    T::Key: Encode + Decode + Clone,
-{
-    pub(crate) fn new(db: Arc<DB>, cf: CFPtr) -> Result<Self, DatabaseError> {
+/// Work a cursor did in RocksDB, see [`RocksCursor::op_stats`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CursorOpStats {
+    /// RocksDB iterators created
+    pub iterators_created: u64,
+    /// Entries read through those iterators, forward or backward
+    pub iterator_steps: u64,
+}
+
+impl<T: RocksTable, const WRITE: bool> RocksCursor<T, WRITE> {
+    pub(crate) fn new(
+        db: Arc<DB>,
+        cf: CFPtr,
+        snapshot: Option<Arc<Snapshot<'static>>>,
+    ) -> Result<Self, DatabaseError> {
         Ok(Self {
+            snapshot,
             db,
             cf,
             next_seek_key: Mutex::new(None),
             current_key_bytes: Mutex::new(None),
             current_value_bytes: Mutex::new(None),
             read_opts: ReadOptions::default(),
+            fill_cache: true,
+            write_gate: None,
+            iterators_created: AtomicU64::new(0),
+            iterator_steps: AtomicU64::new(0),
             _marker: PhantomData,
         })
     }
 
+    /// RocksDB iterators created and steps taken through them since the cursor was created.
+    ///
+    /// Every positioning operation creates its own iterator, so walking N entries creates
+    /// about N iterators; this shows which walks pay for it.
+    pub fn op_stats(&self) -> CursorOpStats {
+        CursorOpStats {
+            iterators_created: self.iterators_created.load(Ordering::Relaxed),
+            iterator_steps: self.iterator_steps.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Set whether blocks read by the cursor are added to the block cache
+    pub(crate) fn with_fill_cache(mut self, fill_cache: bool) -> Self {
+        self.fill_cache = fill_cache;
+        self
+    }
+
+    /// Set the gate each write of the cursor passes
+    pub(crate) fn with_write_gate(mut self, gate: Option<Arc<WriteGate>>) -> Self {
+        self.write_gate = gate;
+        self
+    }
+
+    /// Wait for the cursor's write gate to be open, holding it open until the permit is dropped
+    fn enter_write_gate(&self) -> Option<WritePermit> {
+        self.write_gate.as_ref().map(|gate| gate.enter())
+    }
+
+    /// Forget the cursor's position, so it behaves as if freshly created: `current` returns
+    /// `None` and `next` starts over from the first entry
+    pub fn reset(&mut self) {
+        self.clear_position();
+        *self.next_seek_key.get_mut().unwrap_or_else(|poisoned| poisoned.into_inner()) = None;
+    }
+
+    /// Read options for a single iterator, pinned to the cursor's snapshot if it has one
+    fn read_options(&self) -> ReadOptions {
+        let mut read_opts = ReadOptions::default();
+        if let Some(snapshot) = &self.snapshot {
+            read_opts.set_snapshot(snapshot.as_ref());
+        }
+        read_opts.fill_cache(self.fill_cache);
+        if KeyLayout::of_table::<T>().prefix_bloom.is_some() {
+            // A seek in prefix mode may skip the files holding the following keys
+            read_opts.set_total_order_seek(true);
+        }
+        read_opts
+    }
+
     /// Get the column family reference safely
     #[inline]
     fn get_cf(&self) -> &rocksdb::ColumnFamily {
@@ -50,9 +144,52 @@ where
     }
 
     /// Create a single-use iterator for a specific operation
-    fn create_iterator(&self, mode: IteratorMode) -> rocksdb::DBIterator {
-        let cf = self.get_cf();
-        self.db.iterator_cf_opt(cf, ReadOptions::default(), mode)
+    fn create_iterator(
+        &self,
+        mode: IteratorMode,
+    ) -> impl Iterator<Item = Result<(Box<[u8]>, Box<[u8]>), rocksdb::Error>> + '_ {
+        self.create_iterator_opt(self.read_options(), mode)
+    }
+
+    /// Create a single-use iterator with the given read options, counted in
+    /// [`RocksCursor::op_stats`]
+    fn create_iterator_opt(
+        &self,
+        read_opts: ReadOptions,
+        mode: IteratorMode,
+    ) -> impl Iterator<Item = Result<(Box<[u8]>, Box<[u8]>), rocksdb::Error>> + '_ {
+        self.iterators_created.fetch_add(1, Ordering::Relaxed);
+        self.db.iterator_cf_opt(self.get_cf(), read_opts, mode).inspect(|_| {
+            self.iterator_steps.fetch_add(1, Ordering::Relaxed);
+        })
+    }
+
+    /// Create an iterator over all entries whose raw key starts with `prefix`
+    fn prefix_iterator<'a>(
+        &'a self,
+        prefix: &'a [u8],
+    ) -> impl Iterator<Item = Result<(Box<[u8]>, Box<[u8]>), DatabaseError>> + 'a {
+        self.prefix_iterator_from(prefix, prefix)
+    }
+
+    /// Create an iterator over the entries whose raw key starts with `prefix`, beginning at the
+    /// first key at or after `from`
+    fn prefix_iterator_from<'a>(
+        &'a self,
+        prefix: &'a [u8],
+        from: &[u8],
+    ) -> impl Iterator<Item = Result<(Box<[u8]>, Box<[u8]>), DatabaseError>> + 'a {
+        let mut read_opts = self.read_options();
+        read_opts.set_iterate_lower_bound(prefix.to_vec());
+
+        self.create_iterator_opt(read_opts, IteratorMode::From(from, Direction::Forward))
+            .map(|item| {
+                item.map_err(|e| DatabaseError::Other(format!("RocksDB iterator error: {}", e)))
+            })
+            .take_while(move |item| match item {
+                Ok((key, _)) => key.starts_with(prefix),
+                Err(_) => true,
+            })
     }
 
     /// Get the current key/value pair
@@ -131,7 +268,11 @@ where
         *value_guard = None;
     }
 
-    /// Get the first key/value pair from the database
+    /// Get the first key/value pair from the database.
+    ///
+    /// An empty table costs a single iterator seek, so there is no shortcut through
+    /// `rocksdb.estimate-num-keys`: it subtracts deletes from entries without matching them up,
+    /// and reads 0 for a table holding one key next to a delete of another.
     fn get_first(&self) -> Result<Option<(T::Key, T::Value)>, DatabaseError> {
         // Create an iterator that starts at the beginning
         let mut iter = self.create_iterator(IteratorMode::Start);
@@ -215,7 +356,9 @@ where
             }
             Some(Err(e)) => Err(DatabaseError::Other(format!("RocksDB iterator error: {}", e))),
             None => {
-                // No entries after the given key, clear the current position
+                // No entries after the given key. Like a freshly created cursor, the cleared
+                // position makes `current` return `None` and `prev` step back from the end,
+                // rather than from the entry the cursor was at before the seek.
                 self.clear_position();
                 Ok(None)
             }
@@ -223,17 +366,16 @@ where
     }
 
     fn get_seek_exact(&self, key: T::Key) -> Result<Option<(T::Key, T::Value)>, DatabaseError> {
-        let cf = self.get_cf();
-
         // Encode the key
         let encoded_key = key.encode();
 
-        // Create a new ReadOptions for this specific query
-        let read_opts = ReadOptions::default();
+        // Create a new ReadOptions for this specific query. An exact match shares the prefix of
+        // the key, so the seek may skip files by their prefix bloom.
+        let mut read_opts = self.read_options();
+        read_opts.set_total_order_seek(false);
 
         // Create an iterator that starts at the given key
-        let mut iter = self.db.iterator_cf_opt(
-            cf,
+        let mut iter = self.create_iterator_opt(
             read_opts,
             IteratorMode::From(encoded_key.as_ref(), Direction::Forward),
         );
@@ -281,15 +423,18 @@ where
             }
         };
 
-        // Create an iterator that starts right after the current position
+        // Create an iterator that starts at the current position
         let mut iter =
             self.create_iterator(IteratorMode::From(&current_key_bytes, Direction::Forward));
 
-        // Get the current item
-        let current_item = iter.next();
+        // Skip the current item, unless it was deleted after the cursor moved to it, in which
+        // case the iterator already starts at the next one
+        let mut next = iter.next();
+        if matches!(&next, Some(Ok((key_bytes, _))) if **key_bytes == *current_key_bytes) {
+            next = iter.next();
+        }
 
-        // Get the next item
-        match iter.next() {
+        match next {
             Some(Ok((key_bytes, value_bytes))) => {
                 // Update the current position
                 self.update_position(key_bytes.to_vec(), value_bytes.to_vec());
@@ -330,25 +475,18 @@ where
             }
         };
 
-        // Create an iterator that starts right before the current position
+        // Create an iterator that starts at the current position
         let mut iter =
             self.create_iterator(IteratorMode::From(&current_key_bytes, Direction::Reverse));
 
-        // Skip the current item (which is the one we're positioned at)
-        match iter.next() {
-            Some(Ok(_)) => {}
-            Some(Err(e)) => {
-                return Err(DatabaseError::Other(format!("RocksDB iterator error: {}", e)))
-            }
-            None => {
-                // No entries, clear the current position
-                self.clear_position();
-                return Ok(None);
-            }
+        // Skip the current item, unless it was deleted after the cursor moved to it, in which
+        // case the iterator already starts at the previous one
+        let mut prev = iter.next();
+        if matches!(&prev, Some(Ok((key_bytes, _))) if **key_bytes == *current_key_bytes) {
+            prev = iter.next();
         }
 
-        // Get the previous item
-        match iter.next() {
+        match prev {
             Some(Ok((key_bytes, value_bytes))) => {
                 // Update the current position
                 self.update_position(key_bytes.to_vec(), value_bytes.to_vec());
@@ -479,6 +617,9 @@ where
     T::Value: Compress + Decompress,
 {
     fn upsert(&mut self, key: T::Key, value: &T::Value) -> Result<(), DatabaseError> {
+        debug_assert_key_round_trip::<T>(&key);
+        debug_assert_dupsort_prefix_len::<T>(&key);
+
         // Clone before encoding
         let key_clone = key.clone();
 
@@ -492,7 +633,22 @@ where
         let db = self.db.clone();
         let cf = unsafe { &*self.cf };
 
-        db.put_cf(cf, key_bytes, value_bytes).map_err(|e| DatabaseError::Other(e.to_string()))
+        let permit = self.enter_write_gate();
+        db.put_cf(cf, &key_bytes, &value_bytes).map_err(|e| DatabaseError::Other(e.to_string()))?;
+        drop(permit);
+
+        // Don't let `current` return the value that was just replaced
+        let on_key = self
+            .current_key_bytes
+            .get_mut()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .as_deref()
+            == Some(key_bytes.as_ref());
+        if on_key {
+            *self.current_value_bytes.get_mut().unwrap_or_else(|poisoned| poisoned.into_inner()) =
+                Some(value_bytes);
+        }
+        Ok(())
     }
 
     fn insert(&mut self, key: T::Key, value: &T::Value) -> Result<(), DatabaseError> {
@@ -516,7 +672,9 @@ where
             let key_clone = key.clone();
             let key_bytes = key_clone.encode();
 
+            let permit = self.enter_write_gate();
             db.delete_cf(cf, key_bytes).map_err(|e| DatabaseError::Other(e.to_string()))?;
+            drop(permit);
 
             // Move to next item
             let _ = self.next()?;
@@ -531,13 +689,230 @@ pub struct RocksDupCursor<T: DupSort, const WRITE: bool> {
     current_key: Option<T::Key>,
 }
 
-impl<T: DupSort, const WRITE: bool> RocksDupCursor<T, WRITE>
+impl<T: DupSort + RocksTable, const WRITE: bool> RocksDupCursor<T, WRITE>
+where
+    T::SubKey: Encode + Decode + Clone,
+{
+    pub(crate) fn new(
+        db: Arc<DB>,
+        cf: CFPtr,
+        snapshot: Option<Arc<Snapshot<'static>>>,
+    ) -> Result<Self, DatabaseError> {
+        Ok(Self { inner: RocksCursor::new(db, cf, snapshot)?, current_key: None })
+    }
+
+    /// Set whether blocks read by the cursor are added to the block cache
+    pub(crate) fn with_fill_cache(mut self, fill_cache: bool) -> Self {
+        self.inner = self.inner.with_fill_cache(fill_cache);
+        self
+    }
+
+    /// Set the gate each write of the cursor passes
+    pub(crate) fn with_write_gate(mut self, gate: Option<Arc<WriteGate>>) -> Self {
+        self.inner = self.inner.with_write_gate(gate);
+        self
+    }
+
+    /// Forget the cursor's position, including the duplicate group it was in. See
+    /// [`RocksCursor::reset`].
+    pub fn reset(&mut self) {
+        self.inner.reset();
+        self.current_key = None;
+    }
+
+    /// RocksDB work done by the cursor. See [`RocksCursor::op_stats`].
+    pub fn op_stats(&self) -> CursorOpStats {
+        self.inner.op_stats()
+    }
+
+    /// Collect every duplicate value stored under `key`, in subkey order.
+    ///
+    /// Returns an empty vector if the key is absent.
+    pub fn all_dup_values(&mut self, key: T::Key) -> Result<Vec<T::Value>, DatabaseError>
+    where
+        T::Value: Decompress,
+    {
+        let prefix = DupSortHelper::create_prefix::<T>(&key)?;
+
+        let values = self
+            .inner
+            .prefix_iterator(&prefix)
+            .map(|item| item.and_then(|(_, value)| T::Value::decompress(&value)))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        self.current_key = (!values.is_empty()).then_some(key);
+        Ok(values)
+    }
+
+    /// Collect the duplicate values stored under `key` with subkeys in `from..to`, in subkey
+    /// order.
+    ///
+    /// Returns an empty vector if the key is absent or no subkey falls in the range.
+    pub fn dup_range(
+        &mut self,
+        key: T::Key,
+        from: T::SubKey,
+        to: T::SubKey,
+    ) -> Result<Vec<T::Value>, DatabaseError>
+    where
+        T::Value: Decompress,
+    {
+        let prefix = DupSortHelper::create_prefix::<T>(&key)?;
+        let start = DupSortHelper::create_composite_key::<T>(&key, &from)?;
+        let end = DupSortHelper::create_composite_key::<T>(&key, &to)?;
+
+        let values = self
+            .inner
+            .prefix_iterator_from(&prefix, &start)
+            .take_while(|item| item.as_ref().map_or(true, |(raw_key, _)| **raw_key < *end))
+            .map(|item| item.and_then(|(_, value)| T::Value::decompress(&value)))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        self.current_key = (!values.is_empty()).then_some(key);
+        Ok(values)
+    }
+
+    /// Whether a duplicate of `key` with the same subkey as `value_bytes` is already stored,
+    /// either under its composite key or as the plain entry of `key`
+    fn contains_dup(&self, key: &T::Key, value_bytes: &[u8]) -> Result<bool, DatabaseError> {
+        let subkey = DupSortHelper::value_subkey::<T>(value_bytes)?;
+        let composite = DupSortHelper::composite_key_for_value::<T>(key, value_bytes)?;
+        let get = |raw_key: &[u8]| {
+            self.inner
+                .db
+                .get_pinned_cf(self.inner.get_cf(), raw_key)
+                .map_err(|e| DatabaseError::Other(format!("RocksDB error: {}", e)))
+        };
+
+        if get(&composite)?.is_some() {
+            return Ok(true);
+        }
+        match get(key.clone().encode().as_ref())? {
+            Some(plain) => Ok(DupSortHelper::value_subkey::<T>(&plain).is_ok_and(|s| s == subkey)),
+            None => Ok(false),
+        }
+    }
+
+    /// Position on the first duplicate of `key`, i.e. the composite entry with the smallest
+    /// subkey
+    fn seek_first_dup(&mut self, key: &T::Key) -> Result<Option<T::Value>, DatabaseError>
+    where
+        T::Value: Decompress,
+    {
+        let prefix = DupSortHelper::create_prefix::<T>(key)?;
+        self.seek_dup_from(&prefix, &prefix)
+    }
+
+    /// Position on the first duplicate of `key` whose subkey is greater than or equal to
+    /// `subkey`
+    fn seek_dup_at_or_after(
+        &mut self,
+        key: &T::Key,
+        subkey: &T::SubKey,
+    ) -> Result<Option<T::Value>, DatabaseError>
+    where
+        T::Value: Decompress,
+    {
+        let prefix = DupSortHelper::create_prefix::<T>(key)?;
+        let from = DupSortHelper::create_composite_key::<T>(key, subkey)?;
+        self.seek_dup_from(&prefix, &from)
+    }
+
+    /// Position on the first composite entry under `prefix` at or after the raw key `from`
+    fn seek_dup_from(
+        &mut self,
+        prefix: &[u8],
+        from: &[u8],
+    ) -> Result<Option<T::Value>, DatabaseError>
+    where
+        T::Value: Decompress,
+    {
+        let Some((key_bytes, value_bytes)) =
+            self.inner.prefix_iterator_from(prefix, from).next().transpose()?
+        else {
+            return Ok(None);
+        };
+
+        let value = T::Value::decompress(&value_bytes)?;
+        self.inner.update_position(key_bytes.into_vec(), value_bytes.into_vec());
+        Ok(Some(value))
+    }
+
+    /// Advance to the next duplicate of `key` when the cursor sits on one of its composite
+    /// entries.
+    ///
+    /// Returns `None` if the cursor isn't positioned inside the group of `key`, and `Some(None)`
+    /// once the group is exhausted.
+    fn next_dup_in_group(&mut self, key: &T::Key) -> Result<Option<Option<T::Value>>, DatabaseError>
+    where
+        T::Value: Decompress,
+    {
+        let prefix = DupSortHelper::create_prefix::<T>(key)?;
+        let current = match self.inner.current_key_bytes.lock() {
+            Ok(guard) => guard.clone(),
+            Err(poisoned) => poisoned.into_inner().clone(),
+        };
+        let Some(current) = current.filter(|bytes| bytes.starts_with(&prefix)) else {
+            return Ok(None);
+        };
+
+        self.next_value_with_prefix(&current, &prefix).map(Some)
+    }
+
+    /// Move to the entry following `from` if its key starts with `prefix`, returning its value.
+    ///
+    /// Only the value is decompressed, the key is compared as raw bytes.
+    fn next_value_with_prefix(
+        &mut self,
+        from: &[u8],
+        prefix: &[u8],
+    ) -> Result<Option<T::Value>, DatabaseError>
+    where
+        T::Value: Decompress,
+    {
+        for item in self.inner.create_iterator(IteratorMode::From(from, Direction::Forward)) {
+            let (key_bytes, value_bytes) =
+                item.map_err(|e| DatabaseError::Other(format!("RocksDB iterator error: {}", e)))?;
+            if *key_bytes == *from {
+                continue;
+            }
+            if !key_bytes.starts_with(prefix) {
+                break;
+            }
+
+            let value = T::Value::decompress(&value_bytes)?;
+            self.inner.update_position(key_bytes.into_vec(), value_bytes.into_vec());
+            return Ok(Some(value));
+        }
+
+        Ok(None)
+    }
+}
+
+impl<T: DupSort> RocksDupCursor<T, true>
 where
     T::Key: Encode + Decode + Clone,
     T::SubKey: Encode + Decode + Clone,
+    T::Value: Compress,
 {
-    pub(crate) fn new(db: Arc<DB>, cf: CFPtr) -> Result<Self, DatabaseError> {
-        Ok(Self { inner: RocksCursor::new(db, cf)?, current_key: None })
+    /// Insert or replace the duplicate value stored under `key` and `subkey`
+    pub fn upsert_dup(
+        &mut self,
+        key: T::Key,
+        subkey: T::SubKey,
+        value: &T::Value,
+    ) -> Result<(), DatabaseError> {
+        let composite_key = DupSortHelper::create_composite_key::<T>(&key, &subkey)?;
+
+        let mut compressed = <T::Value as Compress>::Compressed::default();
+        value.compress_to_buf(&mut compressed);
+        let value_bytes: Vec<u8> = compressed.into();
+
+        let _permit = self.inner.enter_write_gate();
+        self.inner
+            .db
+            .put_cf(self.inner.get_cf(), composite_key, value_bytes)
+            .map_err(|e| DatabaseError::Other(e.to_string()))
     }
 }
 impl<T: DupSort, const WRITE: bool> DbCursorRO<T> for RocksDupCursor<T, WRITE>
@@ -557,6 +932,12 @@ where
     }
 
     fn seek_exact(&mut self, key: T::Key) -> Result<Option<(T::Key, T::Value)>, DatabaseError> {
+        // Land on the smallest subkey so a following `next_dup` walks the whole group
+        if let Some(value) = self.seek_first_dup(&key)? {
+            self.current_key = Some(key.clone());
+            return Ok(Some((key, value)));
+        }
+
         let key_clone = key.clone();
         let result = self.inner.seek_exact(key_clone)?;
         if result.is_some() {
@@ -686,6 +1067,12 @@ where
     T::SubKey: Encode + Decode + Clone,
 {
     fn next_dup(&mut self) -> Result<Option<(T::Key, T::Value)>, DatabaseError> {
+        if let Some(current_key) = self.current_key.clone() {
+            if let Some(next) = self.next_dup_in_group(&current_key)? {
+                return Ok(next.map(|value| (current_key, value)));
+            }
+        }
+
         if let Some(ref current_key) = self.current_key {
             let next = self.inner.next()?;
             if let Some((key, value)) = next {
@@ -711,7 +1098,17 @@ where
     }
 
     fn next_dup_val(&mut self) -> Result<Option<T::Value>, DatabaseError> {
-        self.next_dup().map(|opt| opt.map(|(_, v)| v))
+        let Some(current_key) = self.current_key.as_ref() else { return Ok(None) };
+        let prefix = DupSortHelper::create_prefix::<T>(current_key)?;
+        let current = match self.inner.current_key_bytes.lock() {
+            Ok(guard) => guard.clone(),
+            Err(poisoned) => poisoned.into_inner().clone(),
+        };
+        let Some(current) = current else { return Ok(None) };
+
+        // Whether the cursor sits on a plain entry of the key or one of its duplicates, the next
+        // duplicate is the following entry under the key's composite prefix
+        self.next_value_with_prefix(&current, &prefix)
     }
 
     fn seek_by_key_subkey(
@@ -719,6 +1116,12 @@ where
         key: T::Key,
         subkey: T::SubKey,
     ) -> Result<Option<T::Value>, DatabaseError> {
+        // Duplicates stored under composite keys: first subkey at or after the requested one
+        if let Some(value) = self.seek_dup_at_or_after(&key, &subkey)? {
+            self.current_key = Some(key);
+            return Ok(Some(value));
+        }
+
         let composite_key_vec = DupSortHelper::create_composite_key::<T>(&key, &subkey)?;
 
         // Convert the Vec<u8> to T::Key using encode_composite_key
@@ -811,21 +1214,67 @@ where
         Ok(())
     }
 
+    /// Like MDBX, appending a value whose subkey is already stored under `key` fails with a
+    /// [`DatabaseWriteOperation::CursorAppendDup`] write error instead of storing it twice.
+    /// Otherwise the value is stored under the composite key of `key` and its subkey.
     fn append_dup(&mut self, key: T::Key, value: T::Value) -> Result<(), DatabaseError> {
-        // Note: append_dup takes ownership of value, but inner.append expects a reference
-        self.inner.append(key, &value)
+        let mut compressed = <T::Value as Compress>::Compressed::default();
+        value.compress_to_buf(&mut compressed);
+
+        if self.contains_dup(&key, compressed.as_ref())? {
+            return Err(DatabaseWriteError {
+                info: DatabaseErrorInfo {
+                    message: "key/data pair already exists".into(),
+                    code: MDBX_KEYEXIST,
+                },
+                operation: DatabaseWriteOperation::CursorAppendDup,
+                table_name: T::NAME,
+                key: key.encode().into(),
+            }
+            .into());
+        }
+
+        let composite_key = DupSortHelper::composite_key_for_value::<T>(&key, compressed.as_ref())?;
+        let value_bytes: Vec<u8> = compressed.into();
+        let _permit = self.inner.enter_write_gate();
+        self.inner
+            .db
+            .put_cf(self.inner.get_cf(), composite_key, value_bytes)
+            .map_err(|e| DatabaseError::Other(e.to_string()))
     }
 }
 
 pub struct ThreadSafeRocksCursor<T: Table, const WRITE: bool> {
     cursor: Mutex<RocksCursor<T, WRITE>>,
+    /// Operations taking longer than this are logged
+    slow_op_threshold: Option<Duration>,
     // Add a phantom data to ensure proper Send/Sync implementation
     _marker: std::marker::PhantomData<*const ()>,
 }
 
 impl<T: Table, const WRITE: bool> ThreadSafeRocksCursor<T, WRITE> {
     pub fn new(cursor: RocksCursor<T, WRITE>) -> Self {
-        Self { cursor: Mutex::new(cursor), _marker: std::marker::PhantomData }
+        Self {
+            cursor: Mutex::new(cursor),
+            slow_op_threshold: None,
+            _marker: std::marker::PhantomData,
+        }
+    }
+
+    /// Log operations taking longer than `threshold`
+    pub(crate) fn with_slow_op_threshold(mut self, threshold: Option<Duration>) -> Self {
+        self.slow_op_threshold = threshold;
+        self
+    }
+
+    /// Forget the cursor's position. See [`RocksCursor::reset`].
+    pub fn reset(&mut self) {
+        self.cursor.get_mut().unwrap_or_else(|poisoned| poisoned.into_inner()).reset();
+    }
+
+    /// RocksDB work done by the cursor. See [`RocksCursor::op_stats`].
+    pub fn op_stats(&self) -> CursorOpStats {
+        self.cursor.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).op_stats()
     }
 }
 
@@ -839,6 +1288,7 @@ where
             Ok(guard) => guard,
             Err(poisoned) => poisoned.into_inner(),
         };
+        let _timer = SlowOpTimer::start(self.slow_op_threshold, T::NAME, "first");
         guard.first()
     }
 
@@ -847,6 +1297,7 @@ where
             Ok(guard) => guard,
             Err(poisoned) => poisoned.into_inner(),
         };
+        let _timer = SlowOpTimer::start(self.slow_op_threshold, T::NAME, "seek_exact");
         cursor_guard.seek_exact(key)
     }
 
@@ -856,6 +1307,7 @@ where
             Ok(guard) => guard,
             Err(poisoned) => poisoned.into_inner(),
         };
+        let _timer = SlowOpTimer::start(self.slow_op_threshold, T::NAME, "seek");
         cursor_guard.seek(key)
     }
 
@@ -865,6 +1317,7 @@ where
             Ok(guard) => guard,
             Err(poisoned) => poisoned.into_inner(),
         };
+        let _timer = SlowOpTimer::start(self.slow_op_threshold, T::NAME, "next");
         cursor_guard.next()
     }
 
@@ -874,6 +1327,7 @@ where
             Ok(guard) => guard,
             Err(poisoned) => poisoned.into_inner(),
         };
+        let _timer = SlowOpTimer::start(self.slow_op_threshold, T::NAME, "prev");
         cursor_guard.prev()
     }
 
@@ -883,6 +1337,7 @@ where
             Ok(guard) => guard,
             Err(poisoned) => poisoned.into_inner(),
         };
+        let _timer = SlowOpTimer::start(self.slow_op_threshold, T::NAME, "last");
         cursor_guard.last()
     }
 
@@ -892,6 +1347,7 @@ where
             Ok(guard) => guard,
             Err(poisoned) => poisoned.into_inner(),
         };
+        let _timer = SlowOpTimer::start(self.slow_op_threshold, T::NAME, "current");
         cursor_guard.current()
     }
 
@@ -970,25 +1426,34 @@ where
 {
     fn upsert(&mut self, key: T::Key, value: &T::Value) -> Result<(), DatabaseError> {
         let mut cursor_guard = self.cursor.lock().unwrap();
+        let _timer = SlowOpTimer::start(self.slow_op_threshold, T::NAME, "upsert");
         cursor_guard.upsert(key, value)
     }
 
     fn insert(&mut self, key: T::Key, value: &T::Value) -> Result<(), DatabaseError> {
         let mut cursor_guard = self.cursor.lock().unwrap();
+        let _timer = SlowOpTimer::start(self.slow_op_threshold, T::NAME, "insert");
         cursor_guard.insert(key, value)
     }
 
     fn append(&mut self, key: T::Key, value: &T::Value) -> Result<(), DatabaseError> {
         let mut cursor_guard = self.cursor.lock().unwrap();
+        let _timer = SlowOpTimer::start(self.slow_op_threshold, T::NAME, "append");
         cursor_guard.append(key, value)
     }
 
     fn delete_current(&mut self) -> Result<(), DatabaseError> {
         let mut cursor_guard = self.cursor.lock().unwrap();
+        let _timer = SlowOpTimer::start(self.slow_op_threshold, T::NAME, "delete_current");
         cursor_guard.delete_current()
     }
 }
 
+// SAFETY: the fields that aren't `Send` on their own are the raw column family pointer and the
+// `*const ()` marker. The pointer refers to a column family handle owned by the `DB`, which the
+// inner cursor keeps alive through its `Arc<DB>`, and RocksDB handles may be used from any
+// thread. The marker carries no data. Keys and values only move with the cursor if they are
+// `Send` themselves.
 unsafe impl<T: Table, const WRITE: bool> Send for ThreadSafeRocksCursor<T, WRITE>
 where
     T::Key: Send,
@@ -996,6 +1461,9 @@ where
 {
 }
 
+// SAFETY: every method takes the inner cursor's mutex, so threads sharing the wrapper never
+// touch the cursor position or the column family pointer concurrently. The underlying `DB` is
+// safe to read and write from several threads at once.
 unsafe impl<T: Table, const WRITE: bool> Sync for ThreadSafeRocksCursor<T, WRITE>
 where
     T::Key: Sync,
@@ -1005,13 +1473,81 @@ where
 
 pub struct ThreadSafeRocksDupCursor<T: DupSort, const WRITE: bool> {
     cursor: Mutex<RocksDupCursor<T, WRITE>>,
+    /// Operations taking longer than this are logged
+    slow_op_threshold: Option<Duration>,
     // Add a phantom data to ensure proper Send/Sync implementation
     _marker: std::marker::PhantomData<*const ()>,
 }
 
 impl<T: DupSort, const WRITE: bool> ThreadSafeRocksDupCursor<T, WRITE> {
     pub fn new(cursor: RocksDupCursor<T, WRITE>) -> Self {
-        Self { cursor: Mutex::new(cursor), _marker: std::marker::PhantomData }
+        Self {
+            cursor: Mutex::new(cursor),
+            slow_op_threshold: None,
+            _marker: std::marker::PhantomData,
+        }
+    }
+
+    /// Log operations taking longer than `threshold`
+    pub(crate) fn with_slow_op_threshold(mut self, threshold: Option<Duration>) -> Self {
+        self.slow_op_threshold = threshold;
+        self
+    }
+
+    /// Forget the cursor's position. See [`RocksDupCursor::reset`].
+    pub fn reset(&mut self) {
+        self.cursor.get_mut().unwrap_or_else(|poisoned| poisoned.into_inner()).reset();
+    }
+
+    /// RocksDB work done by the cursor. See [`RocksCursor::op_stats`].
+    pub fn op_stats(&self) -> CursorOpStats {
+        self.cursor.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).op_stats()
+    }
+}
+
+impl<T: DupSort, const WRITE: bool> ThreadSafeRocksDupCursor<T, WRITE>
+where
+    T::Key: Encode + Decode + Clone,
+    T::SubKey: Encode + Decode + Clone,
+    T::Value: Decompress,
+{
+    /// Collect every duplicate value stored under `key`, in subkey order
+    pub fn all_dup_values(&mut self, key: T::Key) -> Result<Vec<T::Value>, DatabaseError> {
+        let mut cursor_guard = self.cursor.lock().unwrap();
+        let _timer = SlowOpTimer::start(self.slow_op_threshold, T::NAME, "all_dup_values");
+        cursor_guard.all_dup_values(key)
+    }
+
+    /// Collect the duplicate values stored under `key` with subkeys in `from..to`, in subkey
+    /// order
+    pub fn dup_range(
+        &mut self,
+        key: T::Key,
+        from: T::SubKey,
+        to: T::SubKey,
+    ) -> Result<Vec<T::Value>, DatabaseError> {
+        let mut cursor_guard = self.cursor.lock().unwrap();
+        let _timer = SlowOpTimer::start(self.slow_op_threshold, T::NAME, "dup_range");
+        cursor_guard.dup_range(key, from, to)
+    }
+}
+
+impl<T: DupSort> ThreadSafeRocksDupCursor<T, true>
+where
+    T::Key: Encode + Decode + Clone,
+    T::SubKey: Encode + Decode + Clone,
+    T::Value: Compress,
+{
+    /// Insert or replace the duplicate value stored under `key` and `subkey`
+    pub fn upsert_dup(
+        &mut self,
+        key: T::Key,
+        subkey: T::SubKey,
+        value: &T::Value,
+    ) -> Result<(), DatabaseError> {
+        let mut cursor_guard = self.cursor.lock().unwrap();
+        let _timer = SlowOpTimer::start(self.slow_op_threshold, T::NAME, "upsert_dup");
+        cursor_guard.upsert_dup(key, subkey, value)
     }
 }
 
@@ -1023,36 +1559,43 @@ where
 {
     fn first(&mut self) -> Result<Option<(T::Key, T::Value)>, DatabaseError> {
         let mut cursor_guard = self.cursor.lock().unwrap();
+        let _timer = SlowOpTimer::start(self.slow_op_threshold, T::NAME, "first");
         cursor_guard.first()
     }
 
     fn seek_exact(&mut self, key: T::Key) -> Result<Option<(T::Key, T::Value)>, DatabaseError> {
         let mut cursor_guard = self.cursor.lock().unwrap();
+        let _timer = SlowOpTimer::start(self.slow_op_threshold, T::NAME, "seek_exact");
         cursor_guard.seek_exact(key)
     }
 
     fn seek(&mut self, key: T::Key) -> Result<Option<(T::Key, T::Value)>, DatabaseError> {
         let mut cursor_guard = self.cursor.lock().unwrap();
+        let _timer = SlowOpTimer::start(self.slow_op_threshold, T::NAME, "seek");
         cursor_guard.seek(key)
     }
 
     fn next(&mut self) -> Result<Option<(T::Key, T::Value)>, DatabaseError> {
         let mut cursor_guard = self.cursor.lock().unwrap();
+        let _timer = SlowOpTimer::start(self.slow_op_threshold, T::NAME, "next");
         cursor_guard.next()
     }
 
     fn prev(&mut self) -> Result<Option<(T::Key, T::Value)>, DatabaseError> {
         let mut cursor_guard = self.cursor.lock().unwrap();
+        let _timer = SlowOpTimer::start(self.slow_op_threshold, T::NAME, "prev");
         cursor_guard.prev()
     }
 
     fn last(&mut self) -> Result<Option<(T::Key, T::Value)>, DatabaseError> {
         let mut cursor_guard = self.cursor.lock().unwrap();
+        let _timer = SlowOpTimer::start(self.slow_op_threshold, T::NAME, "last");
         cursor_guard.last()
     }
 
     fn current(&mut self) -> Result<Option<(T::Key, T::Value)>, DatabaseError> {
         let mut cursor_guard = self.cursor.lock().unwrap();
+        let _timer = SlowOpTimer::start(self.slow_op_threshold, T::NAME, "current");
         cursor_guard.current()
     }
 
@@ -1132,16 +1675,19 @@ where
 {
     fn next_dup(&mut self) -> Result<Option<(T::Key, T::Value)>, DatabaseError> {
         let mut cursor_guard = self.cursor.lock().unwrap();
+        let _timer = SlowOpTimer::start(self.slow_op_threshold, T::NAME, "next_dup");
         cursor_guard.next_dup()
     }
 
     fn next_no_dup(&mut self) -> Result<Option<(T::Key, T::Value)>, DatabaseError> {
         let mut cursor_guard = self.cursor.lock().unwrap();
+        let _timer = SlowOpTimer::start(self.slow_op_threshold, T::NAME, "next_no_dup");
         cursor_guard.next_no_dup()
     }
 
     fn next_dup_val(&mut self) -> Result<Option<T::Value>, DatabaseError> {
         let mut cursor_guard = self.cursor.lock().unwrap();
+        let _timer = SlowOpTimer::start(self.slow_op_threshold, T::NAME, "next_dup_val");
         cursor_guard.next_dup_val()
     }
 
@@ -1151,6 +1697,7 @@ where
         subkey: T::SubKey,
     ) -> Result<Option<T::Value>, DatabaseError> {
         let mut cursor_guard = self.cursor.lock().unwrap();
+        let _timer = SlowOpTimer::start(self.slow_op_threshold, T::NAME, "seek_by_key_subkey");
         cursor_guard.seek_by_key_subkey(key, subkey)
     }
 
@@ -1193,11 +1740,14 @@ where
 {
     fn delete_current_duplicates(&mut self) -> Result<(), DatabaseError> {
         let mut cursor_guard = self.cursor.lock().unwrap();
+        let _timer =
+            SlowOpTimer::start(self.slow_op_threshold, T::NAME, "delete_current_duplicates");
         cursor_guard.delete_current_duplicates()
     }
 
     fn append_dup(&mut self, key: T::Key, value: T::Value) -> Result<(), DatabaseError> {
         let mut cursor_guard = self.cursor.lock().unwrap();
+        let _timer = SlowOpTimer::start(self.slow_op_threshold, T::NAME, "append_dup");
         cursor_guard.append_dup(key, value)
     }
 }
@@ -1210,25 +1760,31 @@ where
 {
     fn upsert(&mut self, key: T::Key, value: &T::Value) -> Result<(), DatabaseError> {
         let mut cursor_guard = self.cursor.lock().unwrap();
+        let _timer = SlowOpTimer::start(self.slow_op_threshold, T::NAME, "upsert");
         cursor_guard.upsert(key, value)
     }
 
     fn insert(&mut self, key: T::Key, value: &T::Value) -> Result<(), DatabaseError> {
         let mut cursor_guard = self.cursor.lock().unwrap();
+        let _timer = SlowOpTimer::start(self.slow_op_threshold, T::NAME, "insert");
         cursor_guard.insert(key, value)
     }
 
     fn append(&mut self, key: T::Key, value: &T::Value) -> Result<(), DatabaseError> {
         let mut cursor_guard = self.cursor.lock().unwrap();
+        let _timer = SlowOpTimer::start(self.slow_op_threshold, T::NAME, "append");
         cursor_guard.append(key, value)
     }
 
     fn delete_current(&mut self) -> Result<(), DatabaseError> {
         let mut cursor_guard = self.cursor.lock().unwrap();
+        let _timer = SlowOpTimer::start(self.slow_op_threshold, T::NAME, "delete_current");
         cursor_guard.delete_current()
     }
 }
 
+// SAFETY: as for `ThreadSafeRocksCursor`, the dup cursor only adds the current primary key,
+// which is `Send` whenever `T::Key` is.
 unsafe impl<T: DupSort, const WRITE: bool> Send for ThreadSafeRocksDupCursor<T, WRITE>
 where
     T::Key: Send,
@@ -1237,6 +1793,7 @@ where
 {
 }
 
+// SAFETY: as for `ThreadSafeRocksCursor`, all access goes through the inner cursor's mutex.
 unsafe impl<T: DupSort, const WRITE: bool> Sync for ThreadSafeRocksDupCursor<T, WRITE>
 where
     T::Key: Sync,
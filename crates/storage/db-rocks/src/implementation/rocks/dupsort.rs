@@ -1,14 +1,58 @@
+use crate::tables::{debug_assert_dupsort_prefix_len, trie::StorageTrieTable};
 use alloy_primitives::B256;
 use bytes::{BufMut, BytesMut};
-use reth_db_api::table::Decode;
+use reth_db::{
+    AccountChangeSets, HashedStorages, PlainStorageState, StorageChangeSets, StoragesTrie,
+};
+use reth_db_api::table::{Decode, Decompress};
 use reth_db_api::{
+    models::AccountBeforeTx,
     table::{DupSort, Encode},
     DatabaseError,
 };
+use reth_primitives::StorageEntry;
+use reth_trie_common::StorageTrieEntry;
+use std::any::TypeId;
 
 /// Delimiter used to separate key and subkey in DUPSORT tables
 const DELIMITER: u8 = 0xFF;
 
+/// Subkey of the values of a DUPSORT table, implemented by every DUPSORT table
+pub(crate) trait DupSortValue: DupSort {
+    /// Encoded subkey of the compressed `value`, the one its duplicate is stored under
+    fn value_subkey(value: &[u8]) -> Result<Vec<u8>, DatabaseError>;
+}
+
+impl DupSortValue for HashedStorages {
+    fn value_subkey(value: &[u8]) -> Result<Vec<u8>, DatabaseError> {
+        Ok(StorageEntry::decompress(value)?.key.encode().to_vec())
+    }
+}
+
+impl DupSortValue for PlainStorageState {
+    fn value_subkey(value: &[u8]) -> Result<Vec<u8>, DatabaseError> {
+        Ok(StorageEntry::decompress(value)?.key.encode().to_vec())
+    }
+}
+
+impl DupSortValue for StorageChangeSets {
+    fn value_subkey(value: &[u8]) -> Result<Vec<u8>, DatabaseError> {
+        Ok(StorageEntry::decompress(value)?.key.encode().to_vec())
+    }
+}
+
+impl DupSortValue for AccountChangeSets {
+    fn value_subkey(value: &[u8]) -> Result<Vec<u8>, DatabaseError> {
+        Ok(AccountBeforeTx::decompress(value)?.address.encode().to_vec())
+    }
+}
+
+impl DupSortValue for StoragesTrie {
+    fn value_subkey(value: &[u8]) -> Result<Vec<u8>, DatabaseError> {
+        Ok(StorageTrieEntry::decompress(value)?.nibbles.encode().to_vec())
+    }
+}
+
 /// Helper functions for DUPSORT implementation in RocksDB
 pub(crate) struct DupSortHelper;
 
@@ -18,6 +62,7 @@ impl DupSortHelper {
         key: &T::Key,
         subkey: &T::SubKey,
     ) -> Result<Vec<u8>, DatabaseError> {
+        debug_assert_dupsort_prefix_len::<T>(key);
         let mut bytes = BytesMut::new();
 
         // Encode main key
@@ -34,6 +79,15 @@ impl DupSortHelper {
         Ok(bytes.to_vec())
     }
 
+    /// Bytes a DUPSORT entry for `key` and `subkey` is stored under, for inspecting exactly
+    /// what's on disk from tests and tools
+    pub(crate) fn composite_key_bytes<T: DupSort>(
+        key: &T::Key,
+        subkey: &T::SubKey,
+    ) -> Result<Vec<u8>, DatabaseError> {
+        Self::create_composite_key::<T>(key, subkey)
+    }
+
     /// Extract key and subkey from composite key
     pub(crate) fn split_composite_key<T: DupSort>(
         composite: &[u8],
@@ -49,8 +103,66 @@ impl DupSortHelper {
         }
     }
 
+    /// Primary key of a raw entry of a DUPSORT table, stored either under the plain key or under
+    /// a composite key.
+    ///
+    /// The delimiter byte may also occur inside the encoded key, so every occurrence is tried
+    /// until the bytes before it decode as a key and the bytes after it as a subkey.
+    pub(crate) fn primary_key<T: DupSort>(raw: &[u8]) -> Result<T::Key, DatabaseError> {
+        if let Ok(key) = T::Key::decode(raw) {
+            return Ok(key);
+        }
+
+        raw.iter()
+            .enumerate()
+            .filter(|(_, byte)| **byte == DELIMITER)
+            .find_map(|(pos, _)| {
+                let key = T::Key::decode(&raw[..pos]).ok()?;
+                T::SubKey::decode(&raw[pos + 1..]).ok().map(|_| key)
+            })
+            .ok_or(DatabaseError::Decode)
+    }
+
+    /// Composite key a compressed DUPSORT value is stored under for `key`, with the subkey taken
+    /// from the value itself
+    pub(crate) fn composite_key_for_value<T: DupSort>(
+        key: &T::Key,
+        value: &[u8],
+    ) -> Result<Vec<u8>, DatabaseError> {
+        let mut composite = Self::create_prefix::<T>(key)?;
+        composite.extend_from_slice(&Self::value_subkey::<T>(value)?);
+        Ok(composite)
+    }
+
+    /// [`DupSortValue::value_subkey`] of the DUPSORT table `T`.
+    ///
+    /// Cursors only know `T` as a [`DupSort`], so its implementation is looked up by type. Fails
+    /// for tables that don't implement [`DupSortValue`].
+    pub(crate) fn value_subkey<T: DupSort>(value: &[u8]) -> Result<Vec<u8>, DatabaseError> {
+        macro_rules! dispatch {
+            ($($table:ty),* $(,)?) => {
+                $(
+                    if TypeId::of::<T>() == TypeId::of::<$table>() {
+                        return <$table as DupSortValue>::value_subkey(value);
+                    }
+                )*
+            };
+        }
+        dispatch!(
+            StorageTrieTable,
+            HashedStorages,
+            PlainStorageState,
+            StorageChangeSets,
+            AccountChangeSets,
+            StoragesTrie,
+        );
+
+        Err(DatabaseError::Other(format!("{} doesn't declare the subkey of its values", T::NAME)))
+    }
+
     /// Create prefix for scanning all subkeys of a key
     pub(crate) fn create_prefix<T: DupSort>(key: &T::Key) -> Result<Vec<u8>, DatabaseError> {
+        debug_assert_dupsort_prefix_len::<T>(key);
         let mut bytes = BytesMut::new();
         let key_bytes = key.clone().encode();
         bytes.put_slice(key_bytes.as_ref());
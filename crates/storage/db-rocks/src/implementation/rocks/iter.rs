@@ -0,0 +1,63 @@
+use crate::implementation::rocks::tx::RocksTransaction;
+use reth_db_api::{
+    table::{Decode, Decompress, Table},
+    DatabaseError,
+};
+use rocksdb::{DBIterator, IteratorMode};
+use std::marker::PhantomData;
+
+/// Iterator over a whole table that owns the transaction it reads from.
+///
+/// Unlike [`Walker`](reth_db_api::cursor::Walker), it doesn't borrow a cursor, so it can be moved
+/// to another thread or outlive the scope that opened the transaction.
+pub struct OwnedTableIter<T: Table> {
+    /// Underlying RocksDB iterator.
+    /// Declared before `tx` so it is released before the transaction (and its DB handle).
+    iter: DBIterator<'static>,
+    /// Transaction owning the DB handle and, for read transactions, the snapshot
+    tx: RocksTransaction<false>,
+    _marker: PhantomData<T>,
+}
+
+impl<T: Table> std::fmt::Debug for OwnedTableIter<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("OwnedTableIter").field("table", &T::NAME).field("tx", &self.tx).finish()
+    }
+}
+
+impl<T: Table> OwnedTableIter<T> {
+    pub(crate) fn new(tx: RocksTransaction<false>) -> Result<Self, DatabaseError> {
+        let db = tx.get_db_clone();
+        let cf = tx.get_cf_ref::<T>()?;
+        let read_opts = tx.scan_read_options();
+
+        let iter = db.iterator_cf_opt(cf, read_opts, IteratorMode::Start);
+        // Safety: the iterator borrows the DB kept alive by the `Arc` held in `tx`, which is
+        // dropped after the iterator.
+        let iter: DBIterator<'static> = unsafe { std::mem::transmute(iter) };
+
+        Ok(Self { iter, tx, _marker: PhantomData })
+    }
+}
+
+impl<T: Table> Iterator for OwnedTableIter<T>
+where
+    T::Key: Decode,
+    T::Value: Decompress,
+{
+    type Item = Result<(T::Key, T::Value), DatabaseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.iter.next()? {
+            Ok((key_bytes, value_bytes)) => Some(
+                T::Key::decode(&key_bytes)
+                    .and_then(|key| T::Value::decompress(&value_bytes).map(|value| (key, value))),
+            ),
+            Err(e) => Some(Err(DatabaseError::Other(format!("RocksDB iterator error: {}", e)))),
+        }
+    }
+}
+
+// Safety: the iterator and snapshot are only ever accessed through `&mut self`, and RocksDB
+// iterators can be used from any thread as long as they're not used concurrently.
+unsafe impl<T: Table> Send for OwnedTableIter<T> {}
@@ -1,4 +1,7 @@
+pub(crate) mod batch_index;
 pub(crate) mod cursor;
 pub(crate) mod dupsort;
+pub(crate) mod iter;
+pub(crate) mod slow_op;
 pub(crate) mod trie;
 pub(crate) mod tx;
@@ -0,0 +1,39 @@
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+/// Times a database operation, warning when it is dropped if the operation took longer than
+/// the configured [`RocksDBConfig::slow_op_threshold`](crate::RocksDBConfig::slow_op_threshold)
+pub(crate) struct SlowOpTimer {
+    /// Threshold and start time, `None` if slow operations aren't logged
+    started: Option<(Duration, Instant)>,
+    table: &'static str,
+    operation: &'static str,
+}
+
+impl SlowOpTimer {
+    /// Start timing `operation` on `table`. Doesn't read the clock if `threshold` is `None`.
+    pub(crate) fn start(
+        threshold: Option<Duration>,
+        table: &'static str,
+        operation: &'static str,
+    ) -> Self {
+        Self { started: threshold.map(|threshold| (threshold, Instant::now())), table, operation }
+    }
+}
+
+impl Drop for SlowOpTimer {
+    fn drop(&mut self) {
+        if let Some((threshold, started)) = self.started {
+            let elapsed = started.elapsed();
+            if elapsed > threshold {
+                warn!(
+                    target: "storage::db::rocks",
+                    table = self.table,
+                    operation = self.operation,
+                    ?elapsed,
+                    "Slow database operation"
+                );
+            }
+        }
+    }
+}
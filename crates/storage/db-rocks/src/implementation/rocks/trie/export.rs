@@ -0,0 +1,74 @@
+use crate::{
+    implementation::rocks::tx::RocksTransaction,
+    tables::trie::{AccountTrieTable, StorageTrieTable, TrieNibbles, TrieNodeValue},
+};
+use alloy_primitives::B256;
+use reth_db::{AccountsTrie, StoragesTrie};
+use reth_db_api::{
+    cursor::DbCursorRO,
+    table::{Compress, Decompress, Encode, Table},
+    transaction::DbTx,
+    DatabaseError,
+};
+use reth_trie_common::{StorageTrieEntry, StoredNibbles, StoredNibblesSubKey};
+use std::io::Write;
+
+impl<const WRITE: bool> RocksTransaction<WRITE> {
+    /// Export the account and storage trie tables in reth's native table encoding, so the output
+    /// can be ingested by any reth database backend.
+    ///
+    /// Each entry is written as three length-prefixed fields (big-endian `u32` lengths): the reth
+    /// table name (`AccountsTrie` or `StoragesTrie`), the encoded key and the compressed value.
+    /// Account entries are keyed by [`StoredNibbles`] with a
+    /// [`BranchNodeCompact`](reth_trie_common::BranchNodeCompact) value, storage entries by
+    /// hashed address with a [`StorageTrieEntry`] value.
+    pub fn export_trie_reth_format(&self, out: &mut impl Write) -> Result<(), DatabaseError> {
+        let mut account_cursor = self.scan_cursor::<AccountTrieTable>()?;
+        let mut entry = account_cursor.first()?;
+        while let Some((TrieNibbles(nibbles), node)) = entry {
+            write_record(
+                out,
+                AccountsTrie::NAME,
+                StoredNibbles(nibbles).encode().as_ref(),
+                node.compress().as_ref(),
+            )?;
+            entry = account_cursor.next()?;
+        }
+
+        for item in self.raw_iter::<StorageTrieTable>()? {
+            let (key, value) =
+                item.map_err(|e| DatabaseError::Other(format!("RocksDB iterator error: {}", e)))?;
+
+            // Plain and composite keys both start with the hashed address
+            let hashed_address =
+                key.get(..32).map(B256::from_slice).ok_or(DatabaseError::Decode)?;
+            let value = TrieNodeValue::decompress(&value)?;
+            let node = self.get_node_required(value.node)?.to_branch_node();
+
+            let entry = StorageTrieEntry { nibbles: StoredNibblesSubKey(value.nibbles.0), node };
+            write_record(
+                out,
+                StoragesTrie::NAME,
+                hashed_address.as_slice(),
+                entry.compress().as_ref(),
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Write a single export record
+fn write_record(
+    out: &mut impl Write,
+    table: &str,
+    key: &[u8],
+    value: &[u8],
+) -> Result<(), DatabaseError> {
+    for field in [table.as_bytes(), key, value] {
+        out.write_all(&(field.len() as u32).to_be_bytes())
+            .and_then(|_| out.write_all(field))
+            .map_err(|e| DatabaseError::Other(format!("Failed to write trie export: {}", e)))?;
+    }
+    Ok(())
+}
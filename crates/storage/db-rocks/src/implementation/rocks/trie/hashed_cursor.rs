@@ -1,4 +1,4 @@
-use crate::RocksTransaction;
+use crate::{tables::hashed::HashedStorageEntry, RocksTransaction};
 use alloy_primitives::StorageValue;
 use alloy_primitives::B256;
 use reth_db::transaction::DbTx;
@@ -37,9 +37,8 @@ impl<'tx> HashedCursorFactory for RocksHashedCursorFactory<'tx> {
         &self,
         hashed_address: B256,
     ) -> Result<Self::StorageCursor, DatabaseError> {
-        let cursor = self.tx.cursor_read::<HashedStorages>()?;
         let dup_cursor = self.tx.cursor_dup_read::<HashedStorages>()?;
-        Ok(RocksHashedStorageCursor { cursor, dup_cursor, hashed_address, _phantom: PhantomData })
+        Ok(RocksHashedStorageCursor { dup_cursor, hashed_address, _phantom: PhantomData })
     }
 }
 
@@ -90,67 +89,41 @@ impl<'tx> HashedCursor for RocksHashedAccountCursor<'tx> {
 
 /// Implementation of HashedStorageCursor
 pub struct RocksHashedStorageCursor<'tx> {
-    cursor: <RocksTransaction<false> as DbTx>::Cursor<HashedStorages>,
     dup_cursor: <RocksTransaction<false> as DbTx>::DupCursor<HashedStorages>,
     hashed_address: B256,
     _phantom: PhantomData<&'tx ()>,
 }
 
+impl<'tx> RocksHashedStorageCursor<'tx> {
+    /// Position at the first slot of the address greater than or equal to `slot`
+    pub fn seek_entry(&mut self, slot: B256) -> Result<Option<HashedStorageEntry>, DatabaseError> {
+        Ok(self
+            .dup_cursor
+            .seek_by_key_subkey(self.hashed_address, slot)?
+            .map(HashedStorageEntry::from))
+    }
+
+    /// Advance to the next slot of the address
+    pub fn next_entry(&mut self) -> Result<Option<HashedStorageEntry>, DatabaseError> {
+        Ok(self.dup_cursor.next_dup_val()?.map(HashedStorageEntry::from))
+    }
+}
+
 impl<'tx> HashedCursor for RocksHashedStorageCursor<'tx> {
     type Value = StorageValue;
 
     fn seek(&mut self, key: B256) -> Result<Option<(B256, Self::Value)>, DatabaseError> {
-        println!(
-            "HashedStorageCursor: seeking slot {:?} for address {:?}",
-            key, self.hashed_address
-        );
-
-        if let Some((found_address, _)) = self.cursor.seek_exact(self.hashed_address)? {
-            if found_address == self.hashed_address {
-                // We're using the appropriate address, now seek for the key
-                if let Some(entry) = self.dup_cursor.seek_by_key_subkey(self.hashed_address, key)? {
-                    println!("HashedStorageCursor: found slot {:?}", key);
-                    return Ok(Some((key, entry.value)));
-                }
-            }
-        }
-
-        println!("HashedStorageCursor: no matching slot found");
-        Ok(None)
+        Ok(self.seek_entry(key)?.map(|entry| (entry.slot, entry.value)))
     }
 
     fn next(&mut self) -> Result<Option<(B256, Self::Value)>, DatabaseError> {
-        println!("HashedStorageCursor: next() called for address {:?}", self.hashed_address);
-
-        // Check if we have any values for this address
-        if let Some((address, _)) = self.cursor.seek_exact(self.hashed_address)? {
-            if address == self.hashed_address {
-                // Use next_dup to get the next storage value for this address
-                if let Some((_, entry)) = self.dup_cursor.next_dup()? {
-                    // Extract the storage key and value from the entry
-                    let storage_key = entry.key;
-                    println!("HashedStorageCursor: next() found slot {:?}", storage_key);
-                    return Ok(Some((storage_key, entry.value)));
-                }
-            }
-        }
-
-        println!("HashedStorageCursor: next() found no more entries");
-        Ok(None)
+        Ok(self.next_entry()?.map(|entry| (entry.slot, entry.value)))
     }
 }
 
 impl<'tx> HashedStorageCursor for RocksHashedStorageCursor<'tx> {
     fn is_storage_empty(&mut self) -> Result<bool, DatabaseError> {
-        println!(
-            "HashedStorageCursor: checking if storage is empty for address {:?}",
-            self.hashed_address
-        );
-
         // Check if there are any entries for this address
-        let result = self.cursor.seek_exact(self.hashed_address)?.is_none();
-
-        println!("HashedStorageCursor: storage is empty: {}", result);
-        Ok(result)
+        Ok(self.dup_cursor.seek_exact(self.hashed_address)?.is_none())
     }
 }
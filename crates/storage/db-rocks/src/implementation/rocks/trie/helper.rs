@@ -1,20 +1,43 @@
 use crate::{
     implementation::rocks::tx::RocksTransaction,
-    tables::trie::{AccountTrieTable, StorageTrieTable, TrieNibbles, TrieNodeValue, TrieTable},
+    tables::trie::{
+        AccountTrieTable, StorageTrieTable, StoredTrieNode, TrieNibbles, TrieNodePaths,
+        TrieNodeValue, TrieTable,
+    },
+};
+use alloy_primitives::{keccak256, Address, B256};
+use reth_db::{HashedAccounts, HashedStorages};
+use reth_db_api::{
+    cursor::{DbCursorRO, DbDupCursorRO},
+    transaction::{DbTx, DbTxMut},
+    DatabaseError,
 };
-use alloy_primitives::{keccak256, B256};
-use reth_db_api::transaction::DbTxMut;
 use reth_execution_errors::StateRootError;
+use reth_primitives::StorageEntry;
+use reth_primitives_traits::Account;
 use reth_trie::{
-    hashed_cursor::HashedPostStateCursorFactory, updates::TrieUpdates, BranchNodeCompact,
-    HashedPostState, StateRoot, StoredNibbles,
+    hashed_cursor::HashedPostStateCursorFactory,
+    trie_cursor::{
+        noop::NoopTrieCursorFactory, InMemoryTrieCursorFactory, TrieCursor, TrieCursorFactory,
+    },
+    updates::{StorageTrieUpdates, TrieUpdates},
+    HashBuilder, HashedPostState, Nibbles, StateRoot, StateRootProgress, StoredNibbles,
+    EMPTY_ROOT_HASH,
 };
+use std::collections::HashSet;
 
 ////////////////////////////
 // STATE ROOT CALCULATION //
 ////////////////////////////
 
-/// Helper function to calculate state root directly from post state
+/// Calculate the state root after `post_state`, starting from the trie nodes stored in
+/// `AccountTrieTable`/`StorageTrieTable`.
+///
+/// The calculation is incremental: only the paths in the post state's prefix sets are walked
+/// down to the hashed tables, every other subtrie is taken as its stored branch node hash. The
+/// stored nodes must match the hashed tables as seen by `tx`, as after
+/// [`calculate_state_root_with_updates`] is committed along with the hashed state; a trie that
+/// lags behind them gives a wrong root.
 pub fn calculate_state_root(
     tx: &RocksTransaction<false>,
     post_state: HashedPostState,
@@ -31,11 +54,204 @@ pub fn calculate_state_root(
     calculator.root()
 }
 
-/// Calculate state root from post state and store all trie nodes
+impl RocksTransaction<false> {
+    /// Overlay state root of each post state, in order, without writing anything.
+    ///
+    /// Every root is computed against this transaction's view of the database, its snapshot if
+    /// it holds one, so candidates that yield the same root lead to the same state.
+    pub fn roots_for(&self, states: Vec<HashedPostState>) -> Result<Vec<B256>, DatabaseError> {
+        states.into_iter().map(|state| Ok(calculate_state_root(self, state)?)).collect()
+    }
+
+    /// Trie updates turning the trie of the `before` state into the trie of the `after` state,
+    /// both overlaid on this transaction's view of the database. Nothing is written.
+    ///
+    /// Applying the updates of `before` and then the returned ones leaves the trie tables as
+    /// applying the updates of `after` directly would. Nodes only touched by `before` are
+    /// restored from the database or removed, as the database holds them.
+    pub fn trie_diff(
+        &self,
+        before: HashedPostState,
+        after: HashedPostState,
+    ) -> Result<TrieUpdates, DatabaseError> {
+        let before = self.state_trie_updates(before)?;
+        let after = self.state_trie_updates(after)?;
+        let factory = self.trie_cursor_factory();
+        let mut diff = TrieUpdates::default();
+
+        let mut account_cursor = factory.account_trie_cursor()?;
+        let paths: HashSet<&Nibbles> = [&before, &after]
+            .into_iter()
+            .flat_map(|updates| updates.account_nodes.keys().chain(&updates.removed_nodes))
+            .collect();
+        for path in paths {
+            let mut node_in = |updates: &TrieUpdates| -> Result<_, DatabaseError> {
+                Ok(match updates.account_nodes.get(path) {
+                    Some(node) => Some(node.clone()),
+                    None if updates.removed_nodes.contains(path) => None,
+                    None => account_cursor.seek_exact(path.clone())?.map(|(_, node)| node),
+                })
+            };
+            let (old, new) = (node_in(&before)?, node_in(&after)?);
+            if old != new {
+                match new {
+                    Some(node) => {
+                        diff.account_nodes.insert(path.clone(), node);
+                    }
+                    None => {
+                        diff.removed_nodes.insert(path.clone());
+                    }
+                }
+            }
+        }
+
+        let addresses: HashSet<B256> =
+            before.storage_tries.keys().chain(after.storage_tries.keys()).copied().collect();
+        let empty = StorageTrieUpdates::default();
+        for hashed_address in addresses {
+            let old_updates = before.storage_tries.get(&hashed_address).unwrap_or(&empty);
+            let new_updates = after.storage_tries.get(&hashed_address).unwrap_or(&empty);
+
+            // A wiped trie holds exactly the new nodes, whatever was there before
+            if new_updates.is_deleted {
+                diff.storage_tries.insert(hashed_address, new_updates.clone());
+                continue;
+            }
+
+            let mut storage_cursor = factory.storage_trie_cursor(hashed_address)?;
+            let mut node_in =
+                |updates: &StorageTrieUpdates, path: &Nibbles| -> Result<_, DatabaseError> {
+                    Ok(match updates.storage_nodes.get(path) {
+                        Some(node) => Some(node.clone()),
+                        None if updates.is_deleted || updates.removed_nodes.contains(path) => None,
+                        None => storage_cursor.seek_exact(path.clone())?.map(|(_, node)| node),
+                    })
+                };
+
+            let mut storage_diff = StorageTrieUpdates::default();
+            let paths: HashSet<&Nibbles> = [old_updates, new_updates]
+                .into_iter()
+                .flat_map(|updates| updates.storage_nodes.keys().chain(&updates.removed_nodes))
+                .collect();
+            for path in paths {
+                let (old, new) = (node_in(old_updates, path)?, node_in(new_updates, path)?);
+                if old != new {
+                    match new {
+                        Some(node) => {
+                            storage_diff.storage_nodes.insert(path.clone(), node);
+                        }
+                        None => {
+                            storage_diff.removed_nodes.insert(path.clone());
+                        }
+                    }
+                }
+            }
+            // Nodes of the database wiped by `before` have to come back
+            if old_updates.is_deleted {
+                let mut entry = storage_cursor.seek(Nibbles::default())?;
+                while let Some((path, node)) = entry {
+                    if !new_updates.storage_nodes.contains_key(&path) &&
+                        !new_updates.removed_nodes.contains(&path)
+                    {
+                        storage_diff.storage_nodes.insert(path, node);
+                    }
+                    entry = storage_cursor.next()?;
+                }
+            }
+
+            if !storage_diff.is_empty() {
+                diff.storage_tries.insert(hashed_address, storage_diff);
+            }
+        }
+
+        Ok(diff)
+    }
+
+    /// Each of `addresses` with its account, `None` if it doesn't exist, and its storage root,
+    /// in the order given.
+    ///
+    /// The addresses are sorted by hashed address and looked up in a single forward walk: one
+    /// cursor over `HashedAccounts` and one over `HashedStorages` move along together, and the
+    /// slots of each present account are fed to a [`HashBuilder`] as the cursor passes them. The
+    /// storage root is thus computed from the hashed storage itself, without reading the stored
+    /// storage trie. Absent accounts report [`EMPTY_ROOT_HASH`].
+    pub fn accounts_with_storage_roots(
+        &self,
+        addresses: &[Address],
+    ) -> Result<Vec<(Address, Option<Account>, B256)>, DatabaseError> {
+        let mut hashed: Vec<(B256, usize)> = addresses
+            .iter()
+            .enumerate()
+            .map(|(index, address)| (keccak256(address), index))
+            .collect();
+        hashed.sort_unstable();
+
+        let mut results = vec![None; addresses.len()];
+        let mut account_cursor = self.cursor_read::<HashedAccounts>()?;
+        let mut storage_cursor = self.cursor_dup_read::<HashedStorages>()?;
+        // Entry the account cursor rests on, at or after the last looked up hashed address
+        let mut entry: Option<(B256, Account)> = None;
+        // Last looked up hashed address with its account and storage root, for repeats
+        let mut last: Option<(B256, Option<Account>, B256)> = None;
+        for (hashed_address, index) in hashed {
+            let (account, storage_root) = match last {
+                Some((key, account, storage_root)) if key == hashed_address => {
+                    (account, storage_root)
+                }
+                _ => {
+                    if entry.is_none_or(|(key, _)| key < hashed_address) {
+                        entry = account_cursor.seek(hashed_address)?;
+                    }
+                    let account = entry.filter(|(key, _)| *key == hashed_address).map(|(_, a)| a);
+
+                    let storage_root = if account.is_some() {
+                        let mut hash_builder = HashBuilder::default();
+                        let mut slot = storage_cursor.seek_exact(hashed_address)?.map(|(_, s)| s);
+                        while let Some(StorageEntry { key, value }) = slot {
+                            hash_builder.add_leaf(
+                                Nibbles::unpack(key),
+                                alloy_rlp::encode_fixed_size(&value).as_ref(),
+                            );
+                            slot = storage_cursor.next_dup_val()?;
+                        }
+                        hash_builder.root()
+                    } else {
+                        EMPTY_ROOT_HASH
+                    };
+                    last = Some((hashed_address, account, storage_root));
+                    (account, storage_root)
+                }
+            };
+            results[index] = Some((addresses[index], account, storage_root));
+        }
+
+        Ok(results.into_iter().flatten().collect())
+    }
+
+    /// Trie updates of `state` overlaid on this transaction's view of the database
+    fn state_trie_updates(&self, state: HashedPostState) -> Result<TrieUpdates, DatabaseError> {
+        let prefix_sets = state.construct_prefix_sets().freeze();
+        let state_sorted = state.into_sorted();
+
+        let (_, updates) = StateRoot::new(
+            self.trie_cursor_factory(),
+            HashedPostStateCursorFactory::new(self.hashed_cursor_factory(), &state_sorted),
+        )
+        .with_prefix_sets(prefix_sets)
+        .root_with_updates()?;
+        Ok(updates)
+    }
+}
+
+/// Calculate state root from post state and store all trie nodes.
+///
+/// If `expected_root` is set and the computed root differs, returns
+/// [`StateRootError::RootMismatch`] without writing any trie nodes.
 pub fn calculate_state_root_with_updates(
     read_tx: &RocksTransaction<false>,
     write_tx: &RocksTransaction<true>,
     post_state: HashedPostState,
+    expected_root: Option<B256>,
 ) -> Result<B256, StateRootError> {
     // let prefix_sets = post_state.construct_prefix_sets().freeze();
     println!("Post state account count: {}", post_state.accounts.len());
@@ -62,6 +278,13 @@ pub fn calculate_state_root_with_updates(
     println!("Updates has {} storage tries", updates.storage_tries.len());
     println!("Storage Tries {:?}", updates.storage_tries);
 
+    // Don't persist a trie that doesn't match the expected root
+    if let Some(expected) = expected_root {
+        if root != expected {
+            return Err(StateRootError::RootMismatch { got: root, expected });
+        }
+    }
+
     // Store all the trie nodes
     commit_trie_updates(write_tx, updates)?;
     println!("a4");
@@ -69,76 +292,212 @@ pub fn calculate_state_root_with_updates(
     Ok(root)
 }
 
-/// Stores all trie nodes in the database
-fn commit_trie_updates(
+/// Calculate state root from post state and store all trie nodes, like
+/// [`calculate_state_root_with_updates`], but stage the trie updates on `write_tx` every
+/// `chunk_size` hashed entries walked instead of holding all of them until the root is known.
+///
+/// `read_tx` keeps seeing the trie as it was before any chunk was staged, so every chunk is
+/// computed against the same base. Unlike [`calculate_state_root_with_updates`] there is no
+/// expected root to check against, as nodes are already staged by the time the root is known;
+/// drop `write_tx` instead of committing it to discard them.
+pub fn calculate_state_root_streaming(
+    read_tx: &RocksTransaction<false>,
+    write_tx: &RocksTransaction<true>,
+    post_state: HashedPostState,
+    chunk_size: u64,
+) -> Result<B256, StateRootError> {
+    let prefix_sets = post_state.construct_prefix_sets().freeze();
+    let state_sorted = post_state.into_sorted();
+
+    let mut intermediate_state = None;
+    loop {
+        let progress = StateRoot::new(
+            read_tx.trie_cursor_factory(),
+            HashedPostStateCursorFactory::new(read_tx.hashed_cursor_factory(), &state_sorted),
+        )
+        .with_prefix_sets(prefix_sets.clone())
+        .with_threshold(chunk_size)
+        .with_intermediate_state(intermediate_state.take())
+        .root_with_progress()?;
+
+        match progress {
+            StateRootProgress::Progress(state, _, updates) => {
+                commit_trie_updates(write_tx, updates)?;
+                intermediate_state = Some(*state);
+            }
+            StateRootProgress::Complete(root, _, updates) => {
+                commit_trie_updates(write_tx, updates)?;
+                return Ok(root);
+            }
+        }
+    }
+}
+
+/// Calculate the state root after each of `states`, applied in order on top of `read_tx`'s
+/// view of the database, and store the trie nodes of the last one.
+///
+/// The states are merged as they go, later changes taking precedence, so each root covers all
+/// the states up to it. The trie updates of the states before are overlaid on the stored trie,
+/// so each root only walks the paths its own state touches. The accumulated updates are written
+/// once, instead of a commit per state. Like [`calculate_state_root_with_updates`], the hashed
+/// state itself isn't written.
+pub fn calculate_state_root_batched(
+    read_tx: &RocksTransaction<false>,
+    write_tx: &RocksTransaction<true>,
+    states: Vec<HashedPostState>,
+) -> Result<Vec<B256>, DatabaseError> {
+    let mut merged = HashedPostState::default();
+    let mut nodes = TrieUpdates::default();
+    let mut roots = Vec::with_capacity(states.len());
+    for state in states {
+        let prefix_sets = state.construct_prefix_sets().freeze();
+        merged.extend(state);
+
+        let nodes_sorted = nodes.clone().into_sorted();
+        let state_sorted = merged.clone().into_sorted();
+        let (root, updates) = StateRoot::new(
+            InMemoryTrieCursorFactory::new(read_tx.trie_cursor_factory(), &nodes_sorted),
+            HashedPostStateCursorFactory::new(read_tx.hashed_cursor_factory(), &state_sorted),
+        )
+        .with_prefix_sets(prefix_sets)
+        .root_with_updates()?;
+
+        nodes.extend(updates);
+        roots.push(root);
+    }
+
+    if !roots.is_empty() {
+        commit_trie_updates(write_tx, nodes)?;
+    }
+
+    Ok(roots)
+}
+
+impl RocksTransaction<true> {
+    /// Rebuild the trie tables from `HashedAccounts`/`HashedStorages`.
+    ///
+    /// Existing trie nodes are discarded and the full state root is recomputed from the hashed
+    /// tables as seen by `read_tx`. The resulting nodes are written to this transaction, which
+    /// must be committed for them to persist. Returns the recomputed state root.
+    pub fn rebuild_trie_tables(
+        &self,
+        read_tx: &RocksTransaction<false>,
+    ) -> Result<B256, DatabaseError> {
+        self.clear::<AccountTrieTable>()?;
+        self.clear::<StorageTrieTable>()?;
+        self.clear::<TrieTable>()?;
+        self.clear::<TrieNodePaths>()?;
+
+        // Ignore whatever is left in the trie tables and walk the hashed tables from scratch
+        let (root, updates) =
+            StateRoot::new(NoopTrieCursorFactory, read_tx.hashed_cursor_factory())
+                .root_with_updates()?;
+
+        commit_trie_updates(self, updates)?;
+
+        Ok(root)
+    }
+}
+
+/// Stores all trie nodes in the database and deletes the removed ones.
+///
+/// Every node is stored once in its canonical [`StoredTrieNode`] encoding in [`TrieTable`], keyed
+/// by the hash of that encoding. [`AccountTrieTable`] maps each path to the node and
+/// [`TrieNodePaths`] maps the hash of each account node back to its path, dropping the entry of
+/// a node when it is replaced or removed. [`StorageTrieTable`] maps each path to the node hash.
+///
+/// With the `metrics` feature, the number of nodes written and deleted is added to the
+/// `rocksdb_trie_*_total` counters.
+pub(crate) fn commit_trie_updates(
     tx: &RocksTransaction<true>,
     updates: TrieUpdates,
 ) -> Result<(), StateRootError> {
-    let mut account_nodes_count = 0;
+    // Drop removed account trie nodes
+    let mut account_nodes_deleted = 0u64;
+    for nibbles in updates.removed_nodes {
+        unindex_account_node(tx, &nibbles).map_err(StateRootError::Database)?;
+        tx.delete::<AccountTrieTable>(TrieNibbles(nibbles), None)
+            .map_err(StateRootError::Database)?;
+        account_nodes_deleted += 1;
+    }
+
+    let mut account_nodes_count = 0u64;
     // Store all account trie nodes
-    for (hash, node) in updates.account_nodes {
-        println!("HERE");
-        tx.put::<AccountTrieTable>(TrieNibbles(hash), node.clone())
-            .map_err(|e| StateRootError::Database(e))?;
+    for (path, node) in updates.account_nodes {
+        unindex_account_node(tx, &path).map_err(StateRootError::Database)?;
+
+        let stored_node = StoredTrieNode::from(&node);
+        let node_hash = stored_node.hash();
+        tx.put::<AccountTrieTable>(TrieNibbles(path.clone()), node)
+            .map_err(StateRootError::Database)?;
+        tx.put::<TrieNodePaths>(node_hash, TrieNibbles(path)).map_err(StateRootError::Database)?;
         account_nodes_count += 1;
 
-        // Also store in TrieTable with hash -> RLP
-        let node_rlp = encode_branch_node_to_rlp(&node);
-        let node_hash = keccak256(&node_rlp);
-        tx.put::<TrieTable>(node_hash, node_rlp).map_err(|e| StateRootError::Database(e))?;
+        // Also store in TrieTable with hash -> encoded node
+        tx.put::<TrieTable>(node_hash, stored_node).map_err(StateRootError::Database)?;
     }
     println!("Stored {} account nodes", account_nodes_count);
 
     // Store all storage trie nodes
-    let mut storage_nodes_count = 0;
+    let mut storage_nodes_count = 0u64;
+    let mut storage_nodes_deleted = 0u64;
+    let mut storage_tries_deleted = 0u64;
     for (hashed_address, storage_updates) in updates.storage_tries {
         println!("Processing storage trie for address: {}", hashed_address);
+
+        // Wipe the storage trie of a destroyed account before writing any new nodes, plain
+        // and composite entries alike
+        if storage_updates.is_deleted {
+            tx.wipe_storage_trie(hashed_address).map_err(StateRootError::Database)?;
+            storage_tries_deleted += 1;
+        }
+        for path in storage_updates.removed_nodes {
+            tx.delete_storage_trie_node(hashed_address, path).map_err(StateRootError::Database)?;
+            storage_nodes_deleted += 1;
+        }
+
         for (storage_hash, node) in storage_updates.storage_nodes {
             // Create a properly formatted storage node value
-            let node_hash = keccak256(&encode_branch_node_to_rlp(&node));
+            let stored_node = StoredTrieNode::from(&node);
+            let node_hash = stored_node.hash();
             let node_value =
                 TrieNodeValue { nibbles: StoredNibbles(storage_hash), node: node_hash };
 
+            // Keep the node itself resolvable by its hash
+            tx.put::<TrieTable>(node_hash, stored_node).map_err(StateRootError::Database)?;
+
             // Store in StorageTrieTable
             tx.put::<StorageTrieTable>(hashed_address, node_value)
-                .map_err(|e| StateRootError::Database(e))?;
+                .map_err(StateRootError::Database)?;
 
             storage_nodes_count += 1;
         }
     }
     println!("Stored {} storage nodes", storage_nodes_count);
 
+    #[cfg(feature = "metrics")]
+    {
+        metrics::counter!("rocksdb_trie_account_nodes_written_total")
+            .increment(account_nodes_count);
+        metrics::counter!("rocksdb_trie_account_nodes_deleted_total")
+            .increment(account_nodes_deleted);
+        metrics::counter!("rocksdb_trie_storage_nodes_written_total")
+            .increment(storage_nodes_count);
+        metrics::counter!("rocksdb_trie_storage_nodes_deleted_total")
+            .increment(storage_nodes_deleted);
+        metrics::counter!("rocksdb_trie_storage_tries_deleted_total")
+            .increment(storage_tries_deleted);
+    }
     Ok(())
 }
 
-/// Helper function to encode a BranchNodeCompact to RLP bytes
-fn encode_branch_node_to_rlp(node: &BranchNodeCompact) -> Vec<u8> {
-    let mut result = Vec::new();
-
-    // Add state_mask (2 bytes)
-    result.extend_from_slice(&node.state_mask.get().to_be_bytes());
-
-    // Add tree_mask (2 bytes)
-    result.extend_from_slice(&node.tree_mask.get().to_be_bytes());
-
-    // Add hash_mask (2 bytes)
-    result.extend_from_slice(&node.hash_mask.get().to_be_bytes());
-
-    // Add number of hashes (1 byte)
-    result.push(node.hashes.len() as u8);
-
-    // Add each hash (32 bytes each)
-    for hash in node.hashes.iter() {
-        result.extend_from_slice(hash.as_slice());
+/// Drop the [`TrieNodePaths`] entry of the account node currently stored at `path`, if it still
+/// points there
+fn unindex_account_node(tx: &RocksTransaction<true>, path: &Nibbles) -> Result<(), DatabaseError> {
+    let Some(old) = tx.get::<AccountTrieTable>(TrieNibbles(path.clone()))? else { return Ok(()) };
+    let old_hash = StoredTrieNode::from(&old).hash();
+    if tx.get::<TrieNodePaths>(old_hash)?.is_some_and(|indexed| indexed.0 == *path) {
+        tx.delete::<TrieNodePaths>(old_hash, None)?;
     }
-
-    // Add root_hash (33 bytes - 1 byte flag + 32 bytes hash if Some)
-    if let Some(hash) = &node.root_hash {
-        result.push(1); // Indicator for Some
-        result.extend_from_slice(hash.as_slice());
-    } else {
-        result.push(0); // Indicator for None
-    }
-
-    result
+    Ok(())
 }
@@ -1,7 +1,10 @@
 mod cursor;
+mod export;
 mod hashed_cursor;
 mod helper;
+mod orphans;
 mod storage;
+mod walk;
 
 pub(crate) use cursor::*;
 pub(crate) use hashed_cursor::*;
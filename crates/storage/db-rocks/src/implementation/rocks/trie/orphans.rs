@@ -0,0 +1,75 @@
+use crate::{
+    implementation::rocks::tx::RocksTransaction,
+    tables::trie::{AccountTrieTable, TrieNibbles},
+};
+use alloy_primitives::B256;
+use reth_db_api::{cursor::DbCursorRO, transaction::DbTx, DatabaseError};
+use reth_trie::Nibbles;
+
+/// A stored node on the path from the root to the entry being visited
+struct Ancestor {
+    path: Nibbles,
+    reachable: bool,
+    /// Bits of the node's tree mask, i.e. the children expected to be stored
+    tree_mask: u16,
+    /// Child nibbles already matched to a stored node
+    claimed: u16,
+}
+
+impl RocksTransaction<false> {
+    /// Find stored [`AccountTrieTable`] nodes that can't be reached from the trie with the given
+    /// `root`.
+    ///
+    /// A node is reachable if its closest stored ancestor is reachable and has the tree mask bit
+    /// for the child containing it set, and no other node already takes that child's place. Nodes
+    /// without a stored ancestor hang off the root and are reachable. If the root node itself is
+    /// stored, its hash must match `root`.
+    pub fn find_orphaned_trie_nodes(&self, root: B256) -> Result<Vec<TrieNibbles>, DatabaseError> {
+        let mut cursor = self.cursor_read::<AccountTrieTable>()?;
+        let mut ancestors: Vec<Ancestor> = Vec::new();
+        let mut orphans = Vec::new();
+
+        // Keys are ordered by nibble path, so each node is visited after all of its ancestors
+        let mut entry = cursor.first()?;
+        while let Some((TrieNibbles(path), node)) = entry {
+            if path.is_empty() {
+                if let Some(stored_root) = node.root_hash.filter(|hash| *hash != root) {
+                    return Err(DatabaseError::Other(format!(
+                        "Stored trie root {} does not match {}",
+                        stored_root, root
+                    )));
+                }
+            }
+
+            while ancestors.last().is_some_and(|parent| !path.starts_with(&parent.path)) {
+                ancestors.pop();
+            }
+
+            let reachable = match ancestors.last_mut() {
+                None => true,
+                Some(parent) => {
+                    let bit = 1u16 << path[parent.path.len()];
+                    let reachable = parent.reachable &&
+                        parent.tree_mask & bit != 0 &&
+                        parent.claimed & bit == 0;
+                    parent.claimed |= bit;
+                    reachable
+                }
+            };
+
+            if !reachable {
+                orphans.push(TrieNibbles(path.clone()));
+            }
+            ancestors.push(Ancestor {
+                path,
+                reachable,
+                tree_mask: node.tree_mask.get(),
+                claimed: 0,
+            });
+
+            entry = cursor.next()?;
+        }
+
+        Ok(orphans)
+    }
+}
@@ -1,6 +1,10 @@
 use crate::{
+    errors::RocksDBError,
     implementation::rocks::tx::RocksTransaction,
-    tables::trie::{AccountTrieTable, StorageTrieTable, TrieNibbles, TrieNodeValue, TrieTable},
+    tables::trie::{
+        AccountTrieTable, StorageTrieTable, StoredTrieNode, TrieNibbles, TrieNodePaths,
+        TrieNodeValue, TrieTable,
+    },
 };
 use alloy_primitives::{keccak256, Address, B256};
 use eyre::Ok;
@@ -24,10 +28,21 @@ use reth_trie_db::{
 /// Implementation of trie storage operations
 impl<const WRITE: bool> RocksTransaction<WRITE> {
     /// Get a trie node by its hash
-    pub fn get_node(&self, hash: B256) -> Result<Option<Vec<u8>>, DatabaseError> {
+    pub fn get_node(&self, hash: B256) -> Result<Option<StoredTrieNode>, DatabaseError> {
         self.get::<TrieTable>(hash)
     }
 
+    /// Get a trie node that is expected to exist, failing with
+    /// [`RocksDBError::MissingNode`] if it is absent
+    pub fn get_node_required(&self, hash: B256) -> Result<StoredTrieNode, RocksDBError> {
+        self.get_node(hash)?.ok_or(RocksDBError::MissingNode(hash))
+    }
+
+    /// Get the path in the account trie of the node with the given hash
+    pub fn get_node_path(&self, hash: B256) -> Result<Option<TrieNibbles>, DatabaseError> {
+        self.get::<TrieNodePaths>(hash)
+    }
+
     /// Get an account by its hash
     pub fn get_account(
         &self,
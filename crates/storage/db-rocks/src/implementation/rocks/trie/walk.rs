@@ -0,0 +1,73 @@
+use crate::{
+    implementation::rocks::tx::RocksTransaction,
+    tables::trie::{AccountTrieTable, TrieNibbles},
+};
+use reth_db_api::{cursor::DbCursorRO, transaction::DbTx, DatabaseError};
+use reth_trie::{BranchNodeCompact, Nibbles};
+
+impl RocksTransaction<false> {
+    /// Visit the stored [`AccountTrieTable`] nodes in depth-first order from the root, each
+    /// node before its children and children by ascending nibble.
+    ///
+    /// The children of a node are the ones its tree mask says are stored: for each set bit, the
+    /// first node under the path extended with that nibble, possibly further down behind an
+    /// extension. The root node usually isn't stored, then the walk starts from the nodes
+    /// without a stored ancestor.
+    ///
+    /// Returns the paths of children a tree mask expects but that aren't stored, in the order
+    /// the walk met them; their subtries are skipped. Each child's path strictly extends its
+    /// parent's, so a corrupt trie can't make the walk loop.
+    pub fn walk_account_trie_dfs(
+        &self,
+        mut visit: impl FnMut(&Nibbles, &BranchNodeCompact),
+    ) -> Result<Vec<TrieNibbles>, DatabaseError> {
+        let mut cursor = self.cursor_read::<AccountTrieTable>()?;
+        let mut missing = Vec::new();
+        let mut stack = Vec::new();
+
+        match cursor.seek_exact(TrieNibbles(Nibbles::default()))? {
+            Some((TrieNibbles(path), root)) => stack.push((path, root)),
+            // Any nibble may lead to a top-level node, and none has to
+            None => {
+                let children = stored_children(&mut cursor, &Nibbles::default(), u16::MAX)?;
+                stack.extend(children.into_iter().rev().filter_map(Result::ok));
+            }
+        }
+
+        while let Some((path, node)) = stack.pop() {
+            visit(&path, &node);
+
+            let (children, absent): (Vec<_>, Vec<_>) =
+                stored_children(&mut cursor, &path, node.tree_mask.get())?
+                    .into_iter()
+                    .partition(Result::is_ok);
+            missing.extend(absent.into_iter().filter_map(Result::err).map(TrieNibbles));
+            // Pushed in reverse, so they're popped by ascending nibble
+            stack.extend(children.into_iter().rev().filter_map(Result::ok));
+        }
+
+        Ok(missing)
+    }
+}
+
+/// Children of the node at `path` for the nibbles set in `tree_mask`, by ascending nibble: the
+/// first stored node under each child's path, or the child's path if none is stored
+fn stored_children<C: DbCursorRO<AccountTrieTable>>(
+    cursor: &mut C,
+    path: &Nibbles,
+    tree_mask: u16,
+) -> Result<Vec<Result<(Nibbles, BranchNodeCompact), Nibbles>>, DatabaseError> {
+    let mut children = Vec::new();
+    for nibble in (0..16u8).filter(|nibble| tree_mask & (1 << nibble) != 0) {
+        let mut child_path = path.clone();
+        child_path.push(nibble);
+
+        children.push(match cursor.seek(TrieNibbles(child_path.clone()))? {
+            Some((TrieNibbles(stored), node)) if stored.starts_with(&child_path) => {
+                Ok((stored, node))
+            }
+            _ => Err(child_path),
+        });
+    }
+    Ok(children)
+}
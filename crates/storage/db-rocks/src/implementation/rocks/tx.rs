@@ -1,7 +1,20 @@
+use super::batch_index::{IndexedBatch, MergedIter, TxBatch};
 use super::cursor::{ThreadSafeRocksCursor, ThreadSafeRocksDupCursor};
+use super::dupsort::DupSortHelper;
+use super::iter::OwnedTableIter;
+use super::slow_op::SlowOpTimer;
 use super::trie::RocksHashedCursorFactory;
+use crate::bounded_writer::{WriteBudget, WriteReservation};
+use crate::config::LongReadTransactionAction;
+use crate::errors::RocksDBError;
 use crate::implementation::rocks::cursor::{RocksCursor, RocksDupCursor};
 use crate::implementation::rocks::trie::RocksTrieCursorFactory;
+use crate::tables::hashed::{AccountAtBlock, HashedAccountHistory};
+use crate::tables::trie::{BlockStateRoots, StorageTrieTable};
+use crate::tables::{debug_assert_dupsort_prefix_len, debug_assert_key_round_trip};
+use crate::write_gate::{WriteGate, WritePermit};
+use alloy_primitives::{Address, Bytes, B256, KECCAK256_EMPTY};
+use reth_db::{Bytecodes, HashedAccounts, HashedStorages};
 use reth_db_api::table::TableImporter;
 use reth_db_api::{
     cursor::{DbCursorRO, DbCursorRW, DbDupCursorRO},
@@ -9,33 +22,125 @@ use reth_db_api::{
     transaction::{DbTx, DbTxMut},
     DatabaseError,
 };
-use rocksdb::{ColumnFamily, ReadOptions, WriteBatch, WriteOptions, DB};
+use reth_primitives_traits::Account;
+use reth_trie::{proof::Proof, AccountProof, Nibbles, StoredNibbles};
+use rocksdb::{ColumnFamily, ReadOptions, Snapshot, WriteBatch, WriteOptions, DB};
+use std::collections::BTreeSet;
 use std::marker::PhantomData;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tracing::{debug, warn};
 
 pub(crate) type CFPtr = *const ColumnFamily;
 
+/// Callback run after a write transaction commits, with the operations it applied in order.
+/// Set through [`RocksDB::set_post_commit_hook`](crate::RocksDB::set_post_commit_hook).
+pub type PostCommitHook = dyn Fn(&[StagedOp]) + Send + Sync;
+
+/// A mutation staged in a write transaction, not yet committed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StagedOp {
+    /// Insert or overwrite `key`
+    Put {
+        /// Column family name
+        cf: &'static str,
+        /// Encoded key
+        key: Vec<u8>,
+    },
+    /// Delete `key`
+    Delete {
+        /// Column family name
+        cf: &'static str,
+        /// Encoded key
+        key: Vec<u8>,
+    },
+    /// Delete all keys in `[from, to)`
+    DeleteRange {
+        /// Column family name
+        cf: &'static str,
+        /// Start of the range, inclusive
+        from: Vec<u8>,
+        /// End of the range, exclusive
+        to: Vec<u8>,
+    },
+}
+
 /// Generic transaction type for RocksDB
 pub struct RocksTransaction<const WRITE: bool> {
+    /// Snapshot pinned by read transactions, shared with the cursors they create.
+    /// Declared before `db` so it is released before the DB handle is dropped.
+    snapshot: Option<Arc<Snapshot<'static>>>,
     /// Reference to DB
     db: Arc<DB>,
-    /// Write batch for mutations (only used in write transactions)
-    batch: Option<Mutex<WriteBatch>>,
+    /// Write batch for mutations (only used in write transactions). Indexed when enabled
+    /// through [`TransactionBuilder::index_writes`], so reads see the staged writes.
+    batch: Option<Mutex<TxBatch>>,
+    /// Log of the operations staged in `batch`, in order.
+    /// Kept separately since `WriteBatch` iteration doesn't report column families or range
+    /// deletes.
+    staged: Mutex<Vec<StagedOp>>,
     /// Read options
     read_opts: ReadOptions,
     /// Write options
     write_opts: WriteOptions,
+    /// Time at which the transaction was opened
+    started_at: Instant,
+    /// Maximum duration a read transaction may be held, and what to do once exceeded
+    read_limit: Option<(Duration, LongReadTransactionAction)>,
+    /// Whether the long read warning has already been emitted
+    long_read_warned: AtomicBool,
+    /// Whether full-table reads (raw iteration, exports) fill the block cache
+    scan_fill_cache: bool,
+    /// Share of a [`BoundedWriter`](crate::BoundedWriter)'s budget taken by the staged writes,
+    /// released when the transaction is dropped
+    reservation: Option<WriteReservation>,
+    /// Permit of the database's write gate, keeping consistent snapshots waiting until the
+    /// transaction is committed or dropped
+    write_permit: Option<WritePermit>,
+    /// Gate of the database passed by each write to it instead, for write transactions created
+    /// with [`RocksTransaction::from_db`], which hold no permit
+    write_gate: Option<Arc<WriteGate>>,
+    /// Run once a write transaction has committed
+    post_commit_hook: Option<Arc<PostCommitHook>>,
+    /// Operations taking longer than this are logged
+    slow_op_threshold: Option<Duration>,
     /// Marker for transaction type
     _marker: PhantomData<bool>,
 }
 
+/// Staged writes of a write transaction, written by the caller instead of [`DbTx::commit`]
+pub(crate) struct DeferredCommit {
+    batch: WriteBatch,
+    staged: Vec<StagedOp>,
+    post_commit_hook: Option<Arc<PostCommitHook>>,
+    slow_op_threshold: Option<Duration>,
+}
+
+impl DeferredCommit {
+    /// Write the staged writes to `db`, timed and followed by the post-commit hook like
+    /// [`DbTx::commit`]. The batch is written from a copy, so a failed write can be retried.
+    pub(crate) fn write(&self, db: &DB, opts: &WriteOptions) -> Result<(), DatabaseError> {
+        let _timer = SlowOpTimer::start(self.slow_op_threshold, "*", "commit");
+        db.write_opt(WriteBatch::from_data(self.batch.data()), opts)
+            .map_err(|e| DatabaseError::Other(format!("Failed to commit transaction: {}", e)))?;
+
+        if let Some(hook) = &self.post_commit_hook {
+            hook(&self.staged);
+        }
+        Ok(())
+    }
+}
+
 impl<const WRITE: bool> std::fmt::Debug for RocksTransaction<WRITE> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("RocksTransaction")
             .field("db", &self.db)
             .field("batch", &format!("<WriteOpts>"))
             .field("read_opts", &format!("<ReadOptions>"))
+            .field("started_at", &self.started_at)
+            .field("read_limit", &self.read_limit)
             .field("_marker", &self._marker)
             .finish()
     }
@@ -43,18 +148,84 @@ impl<const WRITE: bool> std::fmt::Debug for RocksTransaction<WRITE> {
 
 impl<const WRITE: bool> RocksTransaction<WRITE> {
     /// Create new transaction
+    #[deprecated(
+        note = "the transaction kind is set by `WRITE`; use `RocksTransaction::new_read` or \
+                `RocksTransaction::new_write`"
+    )]
     pub fn new(db: Arc<DB>, _write: bool) -> Self {
-        let batch = if WRITE { Some(Mutex::new(WriteBatch::default())) } else { None };
+        Self::from_db(db)
+    }
+
+    /// Create a transaction on `db` with default options, not pinned to a snapshot.
+    ///
+    /// Use [`RocksDB::transaction_builder`](crate::RocksDB::transaction_builder) to configure it.
+    ///
+    /// If `db` is wrapped by a [`RocksDB`](crate::RocksDB), a write transaction passes its write
+    /// gate for every write to the database, its commit and the writes of its cursors, so they
+    /// wait while a [`consistent_snapshot`](crate::RocksDB::consistent_snapshot) is taken.
+    pub fn from_db(db: Arc<DB>) -> Self {
+        let batch = if WRITE { Some(Mutex::new(TxBatch::default())) } else { None };
+        let write_gate = if WRITE { WriteGate::registered(&db) } else { None };
 
         Self {
+            snapshot: None,
             db,
             batch,
+            staged: Mutex::new(Vec::new()),
             read_opts: ReadOptions::default(),
             write_opts: WriteOptions::default(),
+            started_at: Instant::now(),
+            read_limit: None,
+            long_read_warned: AtomicBool::new(false),
+            scan_fill_cache: false,
+            reservation: None,
+            write_permit: None,
+            write_gate,
+            post_commit_hook: None,
+            slow_op_threshold: None,
             _marker: PhantomData,
         }
     }
 
+    /// Time elapsed since the transaction was opened
+    pub fn elapsed(&self) -> Duration {
+        self.started_at.elapsed()
+    }
+
+    /// Whether this transaction has been flagged for outliving its configured maximum duration
+    pub fn long_read_warned(&self) -> bool {
+        self.long_read_warned.load(Ordering::Relaxed)
+    }
+
+    /// Check the age of a read transaction against the configured limit
+    fn check_read_duration(&self) -> Result<(), DatabaseError> {
+        let Some((max_duration, action)) = self.read_limit else { return Ok(()) };
+
+        let elapsed = self.elapsed();
+        if elapsed <= max_duration {
+            return Ok(());
+        }
+
+        match action {
+            LongReadTransactionAction::Warn => {
+                if !self.long_read_warned.swap(true, Ordering::Relaxed) {
+                    warn!(
+                        target: "storage::db::rocks",
+                        ?elapsed,
+                        ?max_duration,
+                        "Read transaction held beyond configured maximum"
+                    );
+                    metrics::counter!("rocksdb_long_read_transactions_total").increment(1);
+                }
+                Ok(())
+            }
+            LongReadTransactionAction::Error => Err(DatabaseError::Other(format!(
+                "Read transaction held for {:?}, exceeding maximum of {:?}",
+                elapsed, max_duration
+            ))),
+        }
+    }
+
     /// Get the column family handle for a table
     fn get_cf<T: Table>(&self) -> Result<CFPtr, DatabaseError> {
         let table_name = T::NAME;
@@ -71,44 +242,630 @@ impl<const WRITE: bool> RocksTransaction<WRITE> {
         }
     }
 
-    pub fn get_db_clone(&self) -> Arc<DB> {
-        self.db.clone()
+    /// Get the column family handle for a table as a reference
+    pub(crate) fn get_cf_ref<T: Table>(&self) -> Result<&ColumnFamily, DatabaseError> {
+        // Safety: the column family lives as long as the DB, which `self` keeps alive
+        Ok(unsafe { &*self.get_cf::<T>()? })
     }
 
-    /// Create a trie cursor factory for this transaction
-    #[allow(dead_code)]
-    pub fn trie_cursor_factory(&self) -> RocksTrieCursorFactory<'_>
-    where
-        Self: Sized,
-    {
-        assert!(!WRITE, "trie_cursor_factory only works with read-only txn");
-        // We need to create a read-only version to match the expected type
-        let tx = Box::new(RocksTransaction::<false> {
-            db: self.db.clone(),
-            batch: None,
-            read_opts: ReadOptions::default(),
-            write_opts: WriteOptions::default(),
-            _marker: PhantomData,
-        });
+    /// Read options pinned to this transaction's snapshot, if it holds one
+    pub(crate) fn snapshot_read_options(&self) -> ReadOptions {
+        let mut read_opts = ReadOptions::default();
+        if let Some(snapshot) = &self.snapshot {
+            read_opts.set_snapshot(snapshot.as_ref());
+        }
+        read_opts
+    }
+
+    /// Read options of an iterator walking a whole table, pinned to the transaction's snapshot.
+    ///
+    /// Iterators of tables with a prefix extractor only stay in key order across prefixes in
+    /// total order, so walks that don't know the table's layout must always ask for it.
+    pub(crate) fn scan_read_options(&self) -> ReadOptions {
+        let mut read_opts = self.snapshot_read_options();
+        read_opts.set_total_order_seek(true);
+        read_opts
+    }
+
+    /// Value of `key` as this transaction sees it, its staged writes merged over the database
+    /// read at the transaction's snapshot. Returns `None` if writes aren't indexed, leaving the
+    /// read to the caller.
+    fn indexed_value(
+        &self,
+        cf: &ColumnFamily,
+        key: &[u8],
+    ) -> Option<Result<Option<Vec<u8>>, DatabaseError>> {
+        let batch = self.batch.as_ref()?.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        batch.get_merged(&self.db, cf, key, &self.read_opts)
+    }
+
+    /// Set whether full-table reads (raw iteration, exports) fill the block cache
+    pub(crate) fn with_scan_fill_cache(mut self, enabled: bool) -> Self {
+        self.scan_fill_cache = enabled;
+        self
+    }
+
+    /// Iterate the raw key/value bytes of `T` in order.
+    ///
+    /// Blocks read only fill the block cache if enabled through
+    /// [`RocksDBConfig::scan_fill_cache`](crate::RocksDBConfig::scan_fill_cache).
+    pub(crate) fn raw_iter<T: Table>(&self) -> Result<rocksdb::DBIterator<'_>, DatabaseError> {
+        self.check_read_duration()?;
+        let cf = self.get_cf_ref::<T>()?;
+        let mut read_opts = self.scan_read_options();
+        read_opts.fill_cache(self.scan_fill_cache);
+        Ok(self.db.iterator_cf_opt(cf, read_opts, rocksdb::IteratorMode::Start))
+    }
+
+    /// Like [`DbTx::cursor_read`], but blocks read by the cursor are not added to the block
+    /// cache, so a full-table scan doesn't evict data serving point reads
+    pub fn cursor_read_no_cache<T: Table>(
+        &self,
+    ) -> Result<ThreadSafeRocksCursor<T, WRITE>, DatabaseError> {
+        self.cursor_with_fill_cache::<T>(false)
+    }
+
+    /// Cursor for a full-table read, filling the block cache only if enabled through
+    /// [`RocksDBConfig::scan_fill_cache`](crate::RocksDBConfig::scan_fill_cache)
+    pub(crate) fn scan_cursor<T: Table>(
+        &self,
+    ) -> Result<ThreadSafeRocksCursor<T, WRITE>, DatabaseError> {
+        self.cursor_with_fill_cache::<T>(self.scan_fill_cache)
+    }
+
+    fn cursor_with_fill_cache<T: Table>(
+        &self,
+        fill_cache: bool,
+    ) -> Result<ThreadSafeRocksCursor<T, WRITE>, DatabaseError> {
+        self.check_read_duration()?;
+        let cf_ptr = self.get_cf::<T>()?;
+        let inner_cursor = RocksCursor::new(self.db.clone(), cf_ptr, self.snapshot.clone())?
+            .with_fill_cache(fill_cache);
+        Ok(ThreadSafeRocksCursor::new(inner_cursor).with_slow_op_threshold(self.slow_op_threshold))
+    }
+
+    /// Iterate the keys of `T` in order, without reading or decoding values
+    pub fn iter_keys<T: Table>(
+        &self,
+    ) -> Result<impl Iterator<Item = Result<T::Key, DatabaseError>> + '_, DatabaseError> {
+        self.check_read_duration()?;
+        let cf = self.get_cf_ref::<T>()?;
+
+        let mut iter = self.db.raw_iterator_cf_opt(cf, self.scan_read_options());
+        iter.seek_to_first();
 
-        RocksTrieCursorFactory::new(Box::leak(tx))
+        let mut done = false;
+        Ok(std::iter::from_fn(move || {
+            if done {
+                return None;
+            }
+
+            if !iter.valid() {
+                done = true;
+                // Surface the error that invalidated the iterator, if any
+                return iter
+                    .status()
+                    .err()
+                    .map(|e| Err(DatabaseError::Other(format!("RocksDB iterator error: {}", e))));
+            }
+
+            let key = iter.key().map(T::Key::decode);
+            iter.next();
+            key
+        }))
     }
 
-    pub fn hashed_cursor_factory(&self) -> RocksHashedCursorFactory<'_>
+    /// Get a value, giving up once `timeout` has elapsed.
+    ///
+    /// Sets both the read deadline and the IO timeout on a per-call `ReadOptions`. A timed out read
+    /// fails with [`RocksDBError::Timeout`]. Note that RocksDB only checks the deadline between IO
+    /// operations, so hitting it reliably requires slow IO.
+    pub fn get_with_timeout<T: Table>(
+        &self,
+        key: T::Key,
+        timeout: Duration,
+    ) -> Result<Option<T::Value>, RocksDBError>
     where
-        Self: Sized,
+        T::Value: Decompress,
     {
-        assert!(!WRITE, "hashed_cursor_factory only works with read-only txn");
-        // We need to create a read-only version to match the expected type
-        let tx = Box::new(RocksTransaction::<false> {
-            db: self.db.clone(),
-            batch: None,
-            read_opts: ReadOptions::default(),
-            write_opts: WriteOptions::default(),
-            _marker: PhantomData,
+        self.check_read_duration()?;
+        let cf = self.get_cf_ref::<T>()?;
+
+        // The deadline is an absolute time since epoch, the IO timeout is relative
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+        let mut read_opts = self.snapshot_read_options();
+        read_opts.set_deadline(now + timeout);
+        read_opts.set_io_timeout(timeout);
+
+        match self.db.get_cf_opt(cf, key.encode(), &read_opts) {
+            Ok(Some(value_bytes)) => Ok(Some(T::Value::decompress(&value_bytes)?)),
+            Ok(None) => Ok(None),
+            Err(e) if e.kind() == rocksdb::ErrorKind::TimedOut => {
+                Err(RocksDBError::Timeout(timeout))
+            }
+            Err(e) => Err(RocksDBError::RocksDB(e)),
+        }
+    }
+
+    /// Like [`DbTx::get`], but also return the exact bytes stored for the value, including a
+    /// value staged in this transaction. Hashes must be taken over these bytes, as compressing
+    /// the decoded value again isn't guaranteed to reproduce them.
+    pub fn get_with_raw<T: Table>(
+        &self,
+        key: T::Key,
+    ) -> Result<Option<(T::Value, Vec<u8>)>, DatabaseError> {
+        self.check_read_duration()?;
+        let cf = self.get_cf_ref::<T>()?;
+
+        let key_bytes = key.encode();
+        let raw = match self.indexed_value(cf, key_bytes.as_ref()) {
+            Some(value) => value?,
+            None => self
+                .db
+                .get_cf_opt(cf, key_bytes, &self.read_opts)
+                .map_err(|e| DatabaseError::Other(format!("RocksDB error: {}", e)))?,
+        };
+
+        raw.map(|raw| Ok((T::Value::decompress(&raw)?, raw))).transpose()
+    }
+
+    pub fn get_db_clone(&self) -> Arc<DB> {
+        self.db.clone()
+    }
+}
+
+/// Configures the options of a transaction before it is created.
+///
+/// Created by [`RocksDB::transaction_builder`](crate::RocksDB::transaction_builder), starting
+/// from the database's configuration.
+#[derive(Clone)]
+pub struct TransactionBuilder {
+    db: Arc<DB>,
+    read_only: bool,
+    snapshot: bool,
+    readahead_size: Option<usize>,
+    sync: bool,
+    disable_wal: bool,
+    index_writes: bool,
+    read_limit: Option<(Duration, LongReadTransactionAction)>,
+    scan_fill_cache: bool,
+    post_commit_hook: Option<Arc<PostCommitHook>>,
+    slow_op_threshold: Option<Duration>,
+    write_gate: Option<Arc<WriteGate>>,
+}
+
+impl std::fmt::Debug for TransactionBuilder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TransactionBuilder")
+            .field("db", &self.db)
+            .field("read_only", &self.read_only)
+            .field("snapshot", &self.snapshot)
+            .field("readahead_size", &self.readahead_size)
+            .field("sync", &self.sync)
+            .field("disable_wal", &self.disable_wal)
+            .field("index_writes", &self.index_writes)
+            .field("read_limit", &self.read_limit)
+            .finish()
+    }
+}
+
+impl TransactionBuilder {
+    /// Create a builder with RocksDB's default options, pinning read transactions to a snapshot
+    pub(crate) fn new(db: Arc<DB>, read_only: bool) -> Self {
+        Self {
+            db,
+            read_only,
+            snapshot: true,
+            readahead_size: None,
+            sync: false,
+            disable_wal: false,
+            index_writes: false,
+            read_limit: None,
+            scan_fill_cache: false,
+            post_commit_hook: None,
+            slow_op_threshold: None,
+            write_gate: None,
+        }
+    }
+
+    /// Pin read transactions to a snapshot taken when the transaction is created. Enabled by
+    /// default; when disabled, reads see the latest committed data.
+    pub fn snapshot(mut self, enabled: bool) -> Self {
+        self.snapshot = enabled;
+        self
+    }
+
+    /// Read ahead `size` bytes when iterating, which speeds up large scans on spinning disks
+    pub fn readahead_size(mut self, size: usize) -> Self {
+        self.readahead_size = Some(size);
+        self
+    }
+
+    /// Sync the WAL to disk on commit of write transactions
+    pub fn sync(mut self, enabled: bool) -> Self {
+        self.sync = enabled;
+        self
+    }
+
+    /// Skip the WAL for write transactions. Their writes are lost on a crash until the memtable
+    /// is flushed.
+    pub fn disable_wal(mut self, disabled: bool) -> Self {
+        self.disable_wal = disabled;
+        self
+    }
+
+    /// Stage the writes of write transactions in a `WriteBatchWithIndex`, so their reads see
+    /// what they staged before it is committed. See [`RocksTransaction::iter_merged`].
+    pub fn index_writes(mut self, enabled: bool) -> Self {
+        self.index_writes = enabled;
+        self
+    }
+
+    /// Set the maximum duration of read transactions, and what to do once exceeded
+    pub(crate) fn read_limit(
+        mut self,
+        read_limit: Option<(Duration, LongReadTransactionAction)>,
+    ) -> Self {
+        self.read_limit = read_limit;
+        self
+    }
+
+    /// Set whether full-table reads fill the block cache
+    pub(crate) fn scan_fill_cache(mut self, enabled: bool) -> Self {
+        self.scan_fill_cache = enabled;
+        self
+    }
+
+    /// Set the duration above which operations are logged as slow
+    pub(crate) fn slow_op_threshold(mut self, threshold: Option<Duration>) -> Self {
+        self.slow_op_threshold = threshold;
+        self
+    }
+
+    /// Set the hook run after write transactions commit
+    pub(crate) fn post_commit_hook(mut self, hook: Option<Arc<PostCommitHook>>) -> Self {
+        self.post_commit_hook = hook;
+        self
+    }
+
+    /// Set the gate write transactions have to pass
+    pub(crate) fn write_gate(mut self, gate: Arc<WriteGate>) -> Self {
+        self.write_gate = Some(gate);
+        self
+    }
+
+    /// Create a read transaction
+    pub fn read(self) -> RocksTransaction<false> {
+        let mut tx = if self.snapshot {
+            RocksTransaction::new_with_snapshot(self.db, self.read_limit)
+        } else {
+            let mut tx = RocksTransaction::from_db(self.db);
+            tx.read_limit = self.read_limit;
+            tx
+        };
+        if let Some(size) = self.readahead_size {
+            tx.read_opts.set_readahead_size(size);
+        }
+        tx.slow_op_threshold = self.slow_op_threshold;
+        tx.with_scan_fill_cache(self.scan_fill_cache)
+    }
+
+    /// Create a write transaction. Fails if the database was opened read-only.
+    pub fn write(self) -> Result<RocksTransaction<true>, DatabaseError> {
+        if self.read_only {
+            return Err(DatabaseError::Other(
+                "Write transactions are not supported on a read-only database".to_string(),
+            ));
+        }
+
+        // Waits while a consistent snapshot is being taken
+        let write_permit = self.write_gate.map(|gate| gate.enter());
+
+        let mut tx = RocksTransaction::from_db(self.db);
+        // Passing the gate again while holding a permit would wait for the permit itself
+        if write_permit.is_some() {
+            tx.write_gate = None;
+        }
+        tx.write_permit = write_permit;
+        if let Some(size) = self.readahead_size {
+            tx.read_opts.set_readahead_size(size);
+        }
+        tx.write_opts.set_sync(self.sync);
+        tx.write_opts.disable_wal(self.disable_wal);
+        if self.index_writes {
+            tx.batch = Some(Mutex::new(TxBatch::Indexed(IndexedBatch::default())));
+        }
+        tx.post_commit_hook = self.post_commit_hook;
+        tx.slow_op_threshold = self.slow_op_threshold;
+        Ok(tx.with_scan_fill_cache(self.scan_fill_cache))
+    }
+}
+
+impl RocksTransaction<false> {
+    /// Create a read transaction on `db` with default options, not pinned to a snapshot. Same
+    /// as [`RocksTransaction::from_db`].
+    pub fn new_read(db: Arc<DB>) -> Self {
+        Self::from_db(db)
+    }
+
+    /// Trie cursor factory reading through this transaction, and so through its snapshot if it
+    /// holds one
+    pub fn trie_cursor_factory(&self) -> RocksTrieCursorFactory<'_> {
+        RocksTrieCursorFactory::new(self)
+    }
+
+    /// Hashed state cursor factory reading through this transaction, and so through its
+    /// snapshot if it holds one
+    pub fn hashed_cursor_factory(&self) -> RocksHashedCursorFactory<'_> {
+        RocksHashedCursorFactory::new(self)
+    }
+
+    /// Create a read transaction pinned to a snapshot of the database.
+    ///
+    /// If `read_limit` is set, reads are checked against the transaction's age.
+    pub(crate) fn new_with_snapshot(
+        db: Arc<DB>,
+        read_limit: Option<(Duration, LongReadTransactionAction)>,
+    ) -> Self {
+        // Safety: the snapshot borrows the DB owned by the `Arc` stored alongside it in the
+        // transaction, and is dropped before it.
+        let snapshot: Snapshot<'static> = unsafe { std::mem::transmute(db.snapshot()) };
+
+        let mut read_opts = ReadOptions::default();
+        read_opts.set_snapshot(&snapshot);
+
+        let mut tx = Self::from_db(db);
+        tx.snapshot = Some(Arc::new(snapshot));
+        tx.read_opts = read_opts;
+        tx.read_limit = read_limit;
+        tx
+    }
+
+    /// Iterate a DUPSORT table one primary key at a time, each yielded once with all of its
+    /// duplicate values in order.
+    ///
+    /// The table is read in a single ordered pass, covering duplicates stored under composite
+    /// keys as well as plain entries.
+    pub fn iter_dup_groups<T: DupSort>(
+        &self,
+    ) -> Result<
+        impl Iterator<Item = Result<(T::Key, Vec<T::Value>), DatabaseError>> + '_,
+        DatabaseError,
+    > {
+        let mut entries = self.raw_iter::<T>()?.map(|item| {
+            let (key, value) =
+                item.map_err(|e| DatabaseError::Other(format!("RocksDB iterator error: {}", e)))?;
+            Ok((DupSortHelper::primary_key::<T>(&key)?, T::Value::decompress(&value)?))
         });
-        RocksHashedCursorFactory::new(Box::leak(tx))
+        // First entry of the next group, read while looking for the end of the current one
+        let mut next_group: Option<(T::Key, T::Value)> = None;
+
+        Ok(std::iter::from_fn(move || {
+            let (key, first) = match next_group.take() {
+                Some(entry) => entry,
+                None => match entries.next()? {
+                    Ok(entry) => entry,
+                    Err(e) => return Some(Err(e)),
+                },
+            };
+
+            let mut values = vec![first];
+            for entry in entries.by_ref() {
+                match entry {
+                    Ok((next_key, value)) if next_key == key => values.push(value),
+                    Ok(entry) => {
+                        next_group = Some(entry);
+                        break;
+                    }
+                    Err(e) => return Some(Err(e)),
+                }
+            }
+            Some(Ok((key, values)))
+        }))
+    }
+
+    /// Turn the transaction into an owned iterator over every entry of `T`.
+    ///
+    /// The iterator keeps the transaction (and its snapshot) alive, and can be sent to other
+    /// threads.
+    pub fn into_owned_iter<T: Table>(self) -> Result<OwnedTableIter<T>, DatabaseError> {
+        self.check_read_duration()?;
+        OwnedTableIter::new(self)
+    }
+
+    /// Read up to `limit` entries of `T` starting at `start`, or at the first key if `None`.
+    ///
+    /// Also returns the key to pass as `start` for the next page, `None` once the table is
+    /// exhausted. Pages read from a transaction pinned to a snapshot, as read transactions of
+    /// [`RocksDB::tx`](crate::RocksDB::tx) are, all read that snapshot, so they neither overlap
+    /// nor skip entries even if the table is written to in between.
+    pub fn page<T: Table>(
+        &self,
+        start: Option<T::Key>,
+        limit: usize,
+    ) -> Result<(Vec<(T::Key, T::Value)>, Option<T::Key>), DatabaseError> {
+        let mut cursor = self.scan_cursor::<T>()?;
+        let mut walker = cursor.walk(start)?;
+
+        let entries = walker.by_ref().take(limit).collect::<Result<Vec<_>, _>>()?;
+        // The entry after the page, if any, starts the next one
+        let next = walker.next().transpose()?.map(|(key, _)| key);
+        Ok((entries, next))
+    }
+
+    /// Entries of the non-DUPSORT table `T` with keys in `[from, to)`, in order.
+    ///
+    /// Reads through an iterator bounded to the range, so RocksDB neither reads nor returns
+    /// anything outside of it. Empty if `from` isn't below `to`.
+    pub fn range_collect<T: Table>(
+        &self,
+        from: T::Key,
+        to: T::Key,
+    ) -> Result<Vec<(T::Key, T::Value)>, DatabaseError> {
+        if T::DUPSORT {
+            return Err(DatabaseError::Other(format!(
+                "Can't collect a range of DUPSORT table {}, use iter_dup_groups",
+                T::NAME
+            )));
+        }
+        self.check_read_duration()?;
+        let cf = self.get_cf_ref::<T>()?;
+
+        // Ranges may span prefixes of tables with a prefix extractor
+        let mut read_opts = self.scan_read_options();
+        read_opts.set_iterate_lower_bound(from.encode().as_ref().to_vec());
+        read_opts.set_iterate_upper_bound(to.encode().as_ref().to_vec());
+
+        let raw = self
+            .db
+            .iterator_cf_opt(cf, read_opts, rocksdb::IteratorMode::Start)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| DatabaseError::Other(format!("RocksDB iterator error: {}", e)))?;
+
+        let mut entries = Vec::with_capacity(raw.len());
+        for (key, value) in raw {
+            entries.push((T::Key::decode(&key)?, T::Value::decompress(&value)?));
+        }
+        Ok(entries)
+    }
+
+    /// Keys of the non-DUPSORT table `T` stored more than once under different encodings, in
+    /// order and each reported once.
+    ///
+    /// RocksDB only deduplicates keys by their bytes, so a non-canonical key encoder leaves one
+    /// entry per encoding of the same logical key. Every key of the table is decoded and kept
+    /// in memory, so this is meant as a diagnostic rather than for routine use.
+    pub fn find_logical_duplicates<T: Table>(&self) -> Result<Vec<T::Key>, DatabaseError> {
+        if T::DUPSORT {
+            return Err(DatabaseError::Other(format!(
+                "Can't look for logical duplicates in DUPSORT table {}",
+                T::NAME
+            )));
+        }
+
+        let mut seen = BTreeSet::new();
+        let mut duplicates = BTreeSet::new();
+        for key in self.iter_keys::<T>()? {
+            let key = key?;
+            // Raw keys are unique, so a key decoded twice was stored under two encodings
+            if !seen.insert(key.clone()) {
+                duplicates.insert(key);
+            }
+        }
+        Ok(duplicates.into_iter().collect())
+    }
+
+    /// Bytecode of the account at `hashed_address`, looked up in [`Bytecodes`] by the account's
+    /// code hash.
+    ///
+    /// `None` if the account doesn't exist or has no code. A code hash with no bytecode stored
+    /// for it is an error, since the two tables are written together.
+    pub fn account_code(&self, hashed_address: B256) -> Result<Option<Bytes>, DatabaseError> {
+        let Some(account) = self.get::<HashedAccounts>(hashed_address)? else {
+            return Ok(None);
+        };
+        let Some(code_hash) = account.bytecode_hash.filter(|hash| *hash != KECCAK256_EMPTY) else {
+            return Ok(None);
+        };
+
+        let bytecode = self.get::<Bytecodes>(code_hash)?.ok_or_else(|| {
+            DatabaseError::Other(format!(
+                "Missing bytecode {} of account {}",
+                code_hash, hashed_address
+            ))
+        })?;
+        Ok(Some(bytecode.original_bytes()))
+    }
+
+    /// Approximate bytes on disk taken by the storage of the account at `hashed_address`: its
+    /// slots in `HashedStorages` and its nodes in [`StorageTrieTable`].
+    ///
+    /// Estimated by RocksDB from the SST index blocks, so only flushed data counts and ranges
+    /// smaller than a data block may read as 0. Compression is reflected, as the sizes are
+    /// those of the files.
+    pub fn account_storage_size(&self, hashed_address: B256) -> Result<u64, DatabaseError> {
+        self.check_read_duration()?;
+
+        // Every key of the account, plain or composite, starts with its hashed address
+        let start = hashed_address.to_vec();
+        let end = prefix_end(&start);
+        let range = [rocksdb::Range::new(&start, &end)];
+
+        let storages = self.get_cf_ref::<HashedStorages>()?;
+        let storage_trie = self.get_cf_ref::<StorageTrieTable>()?;
+        Ok([storages, storage_trie]
+            .into_iter()
+            .flat_map(|cf| self.db.get_approximate_sizes_cf(cf, &range))
+            .sum())
+    }
+
+    /// State of the account at `hashed_address` as of `block`: its latest version in
+    /// [`HashedAccountHistory`] written at or before `block`. `None` if the account has no
+    /// version that old.
+    pub fn account_at_block(
+        &self,
+        hashed_address: B256,
+        block: u64,
+    ) -> Result<Option<Account>, DatabaseError> {
+        self.check_read_duration()?;
+        let cf = self.get_cf_ref::<HashedAccountHistory>()?;
+
+        // Versions of an address are ordered by block, so the entry at or before the key is
+        // the latest one not after `block`, unless it belongs to a preceding address
+        let mut iter = self.db.raw_iterator_cf_opt(cf, self.snapshot_read_options());
+        iter.seek_for_prev(AccountAtBlock { hashed_address, block }.encode());
+        iter.status()
+            .map_err(|e| DatabaseError::Other(format!("RocksDB iterator error: {}", e)))?;
+
+        match (iter.key(), iter.value()) {
+            (Some(key), Some(value)) if key.starts_with(hashed_address.as_slice()) => {
+                Account::decompress(value).map(Some)
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// State root recorded for `block` with [`RocksTransaction::record_state_root`], `None` if
+    /// none was
+    pub fn state_root_at(&self, block: u64) -> Result<Option<B256>, DatabaseError> {
+        self.get::<BlockStateRoots>(block)
+    }
+
+    /// Generate the proof of `address` and the given storage `slots`, and check it against the
+    /// state `root`.
+    ///
+    /// A proof that doesn't verify, e.g. because the trie tables are out of date with the
+    /// hashed state or `root` isn't the root of this state, fails with
+    /// [`RocksDBError::ProofVerification`].
+    pub fn prove_and_verify(
+        &self,
+        address: Address,
+        slots: &[B256],
+        root: B256,
+    ) -> Result<AccountProof, RocksDBError> {
+        self.check_read_duration()?;
+        let proof = Proof::new(self.trie_cursor_factory(), self.hashed_cursor_factory())
+            .account_proof(address, slots)
+            .map_err(|e| DatabaseError::Other(format!("Failed to generate proof: {}", e)))?;
+
+        proof
+            .verify(root)
+            .map_err(|e| RocksDBError::ProofVerification { root, reason: e.to_string() })?;
+        Ok(proof)
+    }
+}
+
+/// Smallest key greater than every key starting with `prefix`. A prefix of only `0xFF` bytes
+/// has no such key, so it is extended with more `0xFF` bytes than any key of this crate holds.
+fn prefix_end(prefix: &[u8]) -> Vec<u8> {
+    let mut end = prefix.to_vec();
+    while let Some(last) = end.pop() {
+        if last < u8::MAX {
+            end.push(last + 1);
+            return end;
+        }
     }
+    [prefix, &[u8::MAX; 128][..]].concat()
 }
 
 // Implement read-only transaction
@@ -120,12 +877,18 @@ impl<const WRITE: bool> DbTx for RocksTransaction<WRITE> {
     where
         T::Value: Decompress,
     {
+        self.check_read_duration()?;
+        let _timer = SlowOpTimer::start(self.slow_op_threshold, T::NAME, "get");
+
         // Convert the raw pointer back to a reference safely
         // This is safe as long as the DB is alive, which it is in this context
         let cf_ptr = self.get_cf::<T>()?;
         let cf = unsafe { &*cf_ptr };
 
         let key_bytes = key.encode();
+        if let Some(value) = self.indexed_value(cf, key_bytes.as_ref()) {
+            return value?.map(|value| T::Value::decompress(&value)).transpose();
+        }
 
         match self
             .db
@@ -147,9 +910,14 @@ impl<const WRITE: bool> DbTx for RocksTransaction<WRITE> {
     where
         T::Value: Decompress,
     {
+        self.check_read_duration()?;
+
         // let cf = self.cf_to_arc_column_family(self.get_cf::<T>()?);
         let cf_ptr = self.get_cf::<T>()?;
         let cf = unsafe { &*cf_ptr };
+        if let Some(value) = self.indexed_value(cf, key.as_ref()) {
+            return value?.map(|value| T::Value::decompress(&value)).transpose();
+        }
 
         match self
             .db
@@ -164,16 +932,19 @@ impl<const WRITE: bool> DbTx for RocksTransaction<WRITE> {
         }
     }
 
+    /// Every call returns a new cursor with its own position, so moving one cursor never moves
+    /// another cursor of the same transaction
     fn cursor_read<T: Table>(&self) -> Result<Self::Cursor<T>, DatabaseError>
     where
         T::Key: Encode + Decode + Clone,
     {
+        self.check_read_duration()?;
         let cf_ptr = self.get_cf::<T>()?;
 
         // Create a regular cursor first and handle the Result
-        let inner_cursor = RocksCursor::new(self.db.clone(), cf_ptr)?;
+        let inner_cursor = RocksCursor::new(self.db.clone(), cf_ptr, self.snapshot.clone())?;
         // Now wrap the successful cursor in the thread-safe wrapper
-        Ok(ThreadSafeRocksCursor::new(inner_cursor))
+        Ok(ThreadSafeRocksCursor::new(inner_cursor).with_slow_op_threshold(self.slow_op_threshold))
     }
 
     fn cursor_dup_read<T: DupSort>(&self) -> Result<Self::DupCursor<T>, DatabaseError>
@@ -181,36 +952,50 @@ impl<const WRITE: bool> DbTx for RocksTransaction<WRITE> {
         T::Key: Encode + Decode + Clone + PartialEq,
         T::SubKey: Encode + Decode + Clone,
     {
+        self.check_read_duration()?;
         let cf_ptr = self.get_cf::<T>()?;
         // Create a regular cursor first and handle the Result
-        let inner_cursor = RocksDupCursor::new(self.db.clone(), cf_ptr)?;
+        let inner_cursor = RocksDupCursor::new(self.db.clone(), cf_ptr, self.snapshot.clone())?;
         // Now wrap the successful cursor in the thread-safe wrapper
-        Ok(ThreadSafeRocksDupCursor::new(inner_cursor))
+        Ok(ThreadSafeRocksDupCursor::new(inner_cursor)
+            .with_slow_op_threshold(self.slow_op_threshold))
     }
 
     fn commit(self) -> Result<bool, DatabaseError> {
-        if WRITE {
-            if let Some(batch) = &self.batch {
-                let mut batch_guard = match batch.lock() {
-                    Ok(guard) => guard,
-                    Err(poisoned) => poisoned.into_inner(),
-                };
+        if !WRITE {
+            // Nothing to write, but a read-only commit usually means a write tx was expected
+            debug!(target: "storage::db::rocks", "Commit called on a read-only transaction");
+            return Ok(true);
+        }
+
+        // A commit spans every table the transaction wrote to
+        let _timer = SlowOpTimer::start(self.slow_op_threshold, "*", "commit");
+        if let Some(batch) = &self.batch {
+            // A poisoned batch may hold a partially applied write, so refuse to commit it
+            let mut batch_guard = batch.lock().map_err(|_| {
+                RocksDBError::Transaction("write batch lock poisoned, refusing to commit".into())
+            })?;
+
+            // Create a new empty batch
+            let empty_batch = TxBatch::default();
 
-                // Create a new empty batch
-                let empty_batch = WriteBatch::default();
+            // Swap the empty batch with the current one to get ownership
+            let real_batch = std::mem::replace(&mut *batch_guard, empty_batch);
 
-                // Swap the empty batch with the current one to get ownership
-                let real_batch = std::mem::replace(&mut *batch_guard, empty_batch);
+            // Drop the guard before writing to avoid deadlocks
+            drop(batch_guard);
 
-                // Drop the guard before writing to avoid deadlocks
-                drop(batch_guard);
+            let permit = self.write_gate.as_ref().map(|gate| gate.enter());
+            self.db.write_opt(real_batch.into_write_batch(), &self.write_opts).map_err(|e| {
+                DatabaseError::Other(format!("Failed to commit transaction: {}", e))
+            })?;
+            drop(permit);
 
-                self.db.write_opt(real_batch, &self.write_opts).map_err(|e| {
-                    DatabaseError::Other(format!("Failed to commit transaction: {}", e))
-                })?;
+            // The log of staged operations only describes applied writes once the batch is in
+            if let Some(hook) = &self.post_commit_hook {
+                hook(&self.staged.lock().unwrap_or_else(|poisoned| poisoned.into_inner()));
             }
         }
-        // For both read-only and write transactions after committing, just drop
         Ok(true)
     }
 
@@ -223,7 +1008,8 @@ impl<const WRITE: bool> DbTx for RocksTransaction<WRITE> {
         let cf_ptr = self.get_cf::<T>()?;
         let cf = unsafe { &*cf_ptr };
         let mut count = 0;
-        let iter = self.db.iterator_cf(cf, rocksdb::IteratorMode::Start);
+        let iter =
+            self.db.iterator_cf_opt(cf, self.scan_read_options(), rocksdb::IteratorMode::Start);
         for _ in iter {
             count += 1;
         }
@@ -231,7 +1017,197 @@ impl<const WRITE: bool> DbTx for RocksTransaction<WRITE> {
     }
 
     fn disable_long_read_transaction_safety(&mut self) {
-        // No-op for RocksDB
+        self.read_limit = None;
+    }
+}
+
+impl RocksTransaction<true> {
+    /// Create a write transaction on `db` with default options. Same as
+    /// [`RocksTransaction::from_db`].
+    pub fn new_write(db: Arc<DB>) -> Self {
+        Self::from_db(db)
+    }
+
+    /// Operations staged in this transaction, in the order they will be applied on commit
+    pub fn staged_changes(&self) -> Vec<StagedOp> {
+        self.staged.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).clone()
+    }
+
+    /// Take the staged writes, to be written by the caller instead of [`DbTx::commit`]
+    pub(crate) fn into_deferred_commit(self) -> DeferredCommit {
+        let batch = self
+            .batch
+            .map(|batch| batch.into_inner().unwrap_or_else(|poisoned| poisoned.into_inner()))
+            .unwrap_or_default()
+            .into_write_batch();
+        DeferredCommit {
+            batch,
+            staged: self.staged.into_inner().unwrap_or_else(|poisoned| poisoned.into_inner()),
+            post_commit_hook: self.post_commit_hook,
+            slow_op_threshold: self.slow_op_threshold,
+        }
+    }
+
+    /// Iterate `T` as it will be once the transaction commits: the database merged with the
+    /// writes staged so far.
+    ///
+    /// Staged writes are only seen if they are indexed, see
+    /// [`TransactionBuilder::index_writes`]. Otherwise this only walks the database. Writes
+    /// staged while the iterator is alive aren't seen by it.
+    pub fn iter_merged<T: Table>(
+        &self,
+    ) -> Result<impl Iterator<Item = Result<(T::Key, T::Value), DatabaseError>> + '_, DatabaseError>
+    {
+        self.check_read_duration()?;
+        let cf = self.get_cf_ref::<T>()?;
+        let mut read_opts = self.scan_read_options();
+        read_opts.fill_cache(self.scan_fill_cache);
+        let iter = {
+            let batch = self
+                .batch
+                .as_ref()
+                .map(|batch| batch.lock().unwrap_or_else(|poisoned| poisoned.into_inner()));
+            MergedIter::from_start(batch.as_deref(), &self.db, cf, read_opts)?
+        };
+
+        Ok(iter.map(|item| {
+            let (key, value) = item?;
+            Ok((T::Key::decode(&key)?, T::Value::decompress(&value)?))
+        }))
+    }
+
+    /// Record `root` as the state root of `block`, replacing any root recorded for it before.
+    /// Read back with [`RocksTransaction::state_root_at`].
+    pub fn record_state_root(&self, block: u64, root: B256) -> Result<(), DatabaseError> {
+        self.put::<BlockStateRoots>(block, root)
+    }
+
+    /// Delete all state of the account at `hashed_address`: its row in `HashedAccounts` and
+    /// all of its entries in `HashedStorages` and [`StorageTrieTable`], plain or composite.
+    ///
+    /// Staged in the transaction's batch like any other write, so the account is removed from
+    /// all tables at once on commit.
+    pub fn prune_account(&self, hashed_address: B256) -> Result<(), DatabaseError> {
+        self.delete::<HashedAccounts>(hashed_address, None)?;
+
+        // Every key of the account, plain or composite, starts with its hashed address
+        let from = hashed_address.to_vec();
+        let to = prefix_end(&from);
+        self.stage_delete_range::<HashedStorages>(&from, &to)?;
+        self.wipe_storage_trie(hashed_address)
+    }
+
+    /// Stage the deletion of the whole storage trie of `hashed_address`, its plain entry as well
+    /// as every composite duplicate
+    pub(crate) fn wipe_storage_trie(&self, hashed_address: B256) -> Result<(), DatabaseError> {
+        let from = hashed_address.to_vec();
+        let to = prefix_end(&from);
+        self.stage_delete_range::<StorageTrieTable>(&from, &to)
+    }
+
+    /// Stage the deletion of the storage trie node of `hashed_address` at `path`, stored either
+    /// under its composite key or as the plain entry of the address
+    pub(crate) fn delete_storage_trie_node(
+        &self,
+        hashed_address: B256,
+        path: Nibbles,
+    ) -> Result<(), DatabaseError> {
+        if self
+            .get::<StorageTrieTable>(hashed_address)?
+            .is_some_and(|plain| plain.nibbles.0 == path)
+        {
+            self.delete::<StorageTrieTable>(hashed_address, None)?;
+        }
+
+        let composite = DupSortHelper::create_composite_key::<StorageTrieTable>(
+            &hashed_address,
+            &StoredNibbles(path),
+        )?;
+        self.stage_delete_raw::<StorageTrieTable>(&composite)
+    }
+
+    /// Stage the deletion of the raw key `key` of `T`, e.g. a composite DUPSORT key
+    fn stage_delete_raw<T: Table>(&self, key: &[u8]) -> Result<(), DatabaseError> {
+        let cf = self.get_cf_ref::<T>()?;
+        let Some(batch) = &self.batch else {
+            return Err(DatabaseError::Other(format!(
+                "Cannot delete from {} without a write batch",
+                T::NAME
+            )));
+        };
+        self.reserve::<T>(key.len(), key)?;
+
+        self.stage(StagedOp::Delete { cf: T::NAME, key: key.to_vec() });
+        batch.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).delete_cf(cf, key);
+        Ok(())
+    }
+
+    /// Stage the deletion of the raw keys of `T` in `[from, to)`
+    fn stage_delete_range<T: Table>(&self, from: &[u8], to: &[u8]) -> Result<(), DatabaseError> {
+        let cf = self.get_cf_ref::<T>()?;
+        let Some(batch) = &self.batch else {
+            return Err(DatabaseError::Other(format!(
+                "Cannot delete a range of {} without a write batch",
+                T::NAME
+            )));
+        };
+        self.reserve::<T>(from.len() + to.len(), from)?;
+
+        self.stage(StagedOp::DeleteRange { cf: T::NAME, from: from.to_vec(), to: to.to_vec() });
+        batch.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).delete_range_cf(cf, from, to);
+        Ok(())
+    }
+
+    /// Rewrite every entry of `T` as seen by `read_tx`, passing each value through `transform`.
+    ///
+    /// Meant for migration steps after a value codec changed: values are decoded as stored and
+    /// written back with the current encoding. Entries keep their raw keys, so DUPSORT tables
+    /// work too as long as `transform` leaves the subkey part of a value unchanged. Returns the
+    /// number of entries rewritten, which are written on commit.
+    pub fn reencode_table<T: Table>(
+        &self,
+        read_tx: &RocksTransaction<false>,
+        transform: impl Fn(T::Value) -> T::Value,
+    ) -> Result<usize, DatabaseError> {
+        let cf = self.get_cf_ref::<T>()?;
+        let Some(batch) = &self.batch else { return Ok(0) };
+
+        let mut count = 0;
+        for item in read_tx.raw_iter::<T>()? {
+            let (key, value) =
+                item.map_err(|e| DatabaseError::Other(format!("RocksDB iterator error: {}", e)))?;
+            let value: Vec<u8> = transform(T::Value::decompress(&value)?).compress().into();
+            self.reserve::<T>(key.len() + value.len(), &key)?;
+
+            self.stage(StagedOp::Put { cf: T::NAME, key: key.to_vec() });
+            batch.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).put_cf(cf, key, value);
+            count += 1;
+        }
+
+        Ok(count)
+    }
+
+    /// Count staged writes against `budget`
+    pub(crate) fn with_write_budget(mut self, budget: Arc<WriteBudget>) -> Self {
+        self.reservation = Some(WriteReservation::new(budget));
+        self
+    }
+
+    /// Take `bytes` from the write budget before staging a write of `key` to `T`, if the
+    /// transaction has one. A refused write fails with a [`DatabaseError::Write`] carrying
+    /// [`RocksDBError::WOULD_EXCEED_LIMIT_CODE`].
+    fn reserve<T: Table>(&self, bytes: usize, key: &[u8]) -> Result<(), DatabaseError> {
+        match &self.reservation {
+            Some(reservation) => {
+                reservation.reserve(bytes).map_err(|e| e.into_write_error(T::NAME, key))
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Record a staged operation
+    fn stage(&self, op: StagedOp) {
+        self.staged.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).push(op);
     }
 }
 
@@ -244,6 +1220,7 @@ impl DbTxMut for RocksTransaction<true> {
     where
         T::Value: Compress,
     {
+        let _timer = SlowOpTimer::start(self.slow_op_threshold, T::NAME, "put");
         let cf_ptr = self.get_cf::<T>()?;
         let cf = unsafe { &*cf_ptr };
 
@@ -252,8 +1229,12 @@ impl DbTxMut for RocksTransaction<true> {
                 Ok(guard) => guard,
                 Err(poisoned) => poisoned.into_inner(),
             };
+            debug_assert_key_round_trip::<T>(&key);
+            debug_assert_dupsort_prefix_len::<T>(&key);
             let key_bytes = key.encode();
             let value_bytes: Vec<u8> = value.compress().into();
+            self.reserve::<T>(key_bytes.as_ref().len() + value_bytes.len(), key_bytes.as_ref())?;
+            self.stage(StagedOp::Put { cf: T::NAME, key: key_bytes.as_ref().to_vec() });
             batch_guard.put_cf(cf, key_bytes, value_bytes);
         }
         Ok(())
@@ -264,6 +1245,7 @@ impl DbTxMut for RocksTransaction<true> {
         key: T::Key,
         _value: Option<T::Value>,
     ) -> Result<bool, DatabaseError> {
+        let _timer = SlowOpTimer::start(self.slow_op_threshold, T::NAME, "delete");
         let cf_ptr = self.get_cf::<T>()?;
         let cf = unsafe { &*cf_ptr };
 
@@ -273,6 +1255,8 @@ impl DbTxMut for RocksTransaction<true> {
                 Err(poisoned) => poisoned.into_inner(),
             };
             let key_bytes = key.encode();
+            self.reserve::<T>(key_bytes.as_ref().len(), key_bytes.as_ref())?;
+            self.stage(StagedOp::Delete { cf: T::NAME, key: key_bytes.as_ref().to_vec() });
             batch_guard.delete_cf(cf, key_bytes);
         }
         Ok(true)
@@ -290,10 +1274,17 @@ impl DbTxMut for RocksTransaction<true> {
             };
 
             // Delete all data in the column family using a range delete
-            // These are the minimum and maximum possible key values
-            let start_key = vec![0u8];
+            // These are the minimum and maximum possible key values. The start key is empty so
+            // that entries stored under an empty key (e.g. the account trie root) are removed too
+            let start_key = Vec::<u8>::new();
             let end_key = vec![255u8; 32]; // Adjust size if needed for your key format
+            self.reserve::<T>(start_key.len() + end_key.len(), &start_key)?;
 
+            self.stage(StagedOp::DeleteRange {
+                cf: T::NAME,
+                from: start_key.clone(),
+                to: end_key.clone(),
+            });
             batch_guard.delete_range_cf(cf, start_key, end_key);
             return Ok(());
         }
@@ -315,9 +1306,10 @@ impl DbTxMut for RocksTransaction<true> {
     {
         let cf_ptr = self.get_cf::<T>()?;
         // Create a regular cursor first and handle the Result
-        let inner_cursor = RocksCursor::new(self.db.clone(), cf_ptr)?;
+        let inner_cursor = RocksCursor::new(self.db.clone(), cf_ptr, None)?
+            .with_write_gate(self.write_gate.clone());
         // Now wrap the successful cursor in the thread-safe wrapper
-        Ok(ThreadSafeRocksCursor::new(inner_cursor))
+        Ok(ThreadSafeRocksCursor::new(inner_cursor).with_slow_op_threshold(self.slow_op_threshold))
     }
 
     fn cursor_dup_write<T: DupSort>(&self) -> Result<Self::DupCursorMut<T>, DatabaseError>
@@ -327,9 +1319,11 @@ impl DbTxMut for RocksTransaction<true> {
     {
         let cf_ptr = self.get_cf::<T>()?;
         // Create a regular cursor first and handle the Result
-        let inner_cursor = RocksDupCursor::new(self.db.clone(), cf_ptr)?;
+        let inner_cursor = RocksDupCursor::new(self.db.clone(), cf_ptr, None)?
+            .with_write_gate(self.write_gate.clone());
         // Now wrap the successful cursor in the thread-safe wrapper
-        Ok(ThreadSafeRocksDupCursor::new(inner_cursor))
+        Ok(ThreadSafeRocksDupCursor::new(inner_cursor)
+            .with_slow_op_threshold(self.slow_op_threshold))
     }
 }
 
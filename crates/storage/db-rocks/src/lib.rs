@@ -57,16 +57,46 @@ RETH RocksDB Implementation Structure
 #![warn(missing_copy_implementations)]
 #![warn(rust_2018_idioms)]
 
+mod append_log;
+mod archive;
+mod auto_compact;
+mod bounded_writer;
+mod config;
+mod db;
+mod diff;
 mod errors;
+mod group_commit;
 mod implementation;
+mod scan;
 mod tables;
 mod test;
+mod version;
+mod write_gate;
 
+pub use append_log::AppendLog;
+pub use archive::ExportCheckpoint;
+pub use auto_compact::{AutoCompactConfig, AutoCompactor};
+pub use bounded_writer::BoundedWriter;
+pub use config::{CfOverride, LongReadTransactionAction, Profile, RocksDBConfig};
+pub use db::{RocksDB, WriteStallStats};
+pub use diff::TableDiffSummary;
 pub use errors::RocksDBError;
-pub use implementation::rocks::trie::{calculate_state_root, calculate_state_root_with_updates};
-pub use implementation::rocks::tx::RocksTransaction;
+pub use group_commit::{GroupCommit, GroupCommitPolicy};
+pub use implementation::rocks::cursor::CursorOpStats;
+pub use implementation::rocks::iter::OwnedTableIter;
+pub use implementation::rocks::trie::{
+    calculate_state_root, calculate_state_root_batched, calculate_state_root_streaming,
+    calculate_state_root_with_updates,
+};
+pub use implementation::rocks::tx::{
+    PostCommitHook, RocksTransaction, StagedOp, TransactionBuilder,
+};
 pub use reth_primitives_traits::Account;
 pub use reth_trie::HashedPostState;
+pub use scan::{ScanReport, TableScanReport};
+pub use tables::hashed::HashedStorageEntry;
+pub use tables::keys::{account_trie_key, hashed_address, hashed_slot};
+pub use tables::{RocksTable, TableSchema};
 pub use test::utils;
 
 // /*
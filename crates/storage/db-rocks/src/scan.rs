@@ -0,0 +1,230 @@
+use crate::tables::{
+    hashed::HashedAccountHistory,
+    trie::{AccountTrieTable, BlockStateRoots, StorageTrieTable, TrieNodePaths, TrieTable},
+};
+use reth_db::{TableViewer, Tables};
+use reth_db_api::{
+    table::{Decode, Decompress, DupSort, Table},
+    DatabaseError,
+};
+use rocksdb::{IteratorMode, ReadOptions, DB};
+use std::{
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Mutex,
+    },
+    thread,
+};
+
+/// Result of scanning a single table
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TableScanReport {
+    /// Table name
+    pub table: &'static str,
+    /// Number of entries visited
+    pub entries: u64,
+    /// Raw keys of the entries that failed to decode
+    pub corrupt_keys: Vec<Vec<u8>>,
+}
+
+impl TableScanReport {
+    /// Whether every entry of the table decoded
+    pub fn is_clean(&self) -> bool {
+        self.corrupt_keys.is_empty()
+    }
+}
+
+/// Result of an integrity scan across all tables
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ScanReport {
+    /// Per-table results, sorted by table name
+    pub tables: Vec<TableScanReport>,
+    /// Whether the scan was cancelled before every table was visited
+    pub cancelled: bool,
+}
+
+impl ScanReport {
+    /// Whether every scanned table is free of corrupt entries
+    pub fn is_clean(&self) -> bool {
+        self.tables.iter().all(TableScanReport::is_clean)
+    }
+
+    /// Names of the tables containing corrupt entries
+    pub fn corrupt_tables(&self) -> Vec<&'static str> {
+        self.tables.iter().filter(|report| !report.is_clean()).map(|report| report.table).collect()
+    }
+}
+
+/// Scan every table on `threads` workers, each table read through its own snapshot.
+///
+/// Workers stop picking up tables once `cancel` is set; tables that were not fully scanned are
+/// left out of the report and [`ScanReport::cancelled`] is set. Blocks read only fill the block
+/// cache if `fill_cache` is set.
+pub(crate) fn scan_tables(
+    db: &DB,
+    threads: usize,
+    cancel: &AtomicBool,
+    fill_cache: bool,
+) -> Result<ScanReport, DatabaseError> {
+    let targets = ScanTarget::all();
+    let next = AtomicUsize::new(0);
+    let results = Mutex::new(Vec::with_capacity(targets.len()));
+
+    thread::scope(|scope| {
+        for _ in 0..threads.clamp(1, targets.len()) {
+            scope.spawn(|| {
+                while !cancel.load(Ordering::Relaxed) {
+                    let Some(target) = targets.get(next.fetch_add(1, Ordering::Relaxed)) else {
+                        break;
+                    };
+                    let result = target.scan(db, cancel, fill_cache);
+                    results.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).push(result);
+                }
+            });
+        }
+    });
+
+    let mut tables = Vec::with_capacity(targets.len());
+    for result in results.into_inner().unwrap_or_else(|poisoned| poisoned.into_inner()) {
+        if let Some(report) = result? {
+            tables.push(report);
+        }
+    }
+    tables.sort_by_key(|report| report.table);
+
+    Ok(ScanReport { tables, cancelled: cancel.load(Ordering::Relaxed) })
+}
+
+/// A table visited by the integrity scan
+#[derive(Debug, Clone, Copy)]
+enum ScanTarget {
+    /// One of reth's tables
+    Table(Tables),
+    /// [`TrieTable`]
+    Trie,
+    /// [`AccountTrieTable`]
+    AccountTrie,
+    /// [`StorageTrieTable`]
+    StorageTrie,
+    /// [`HashedAccountHistory`]
+    AccountHistory,
+    /// [`BlockStateRoots`]
+    StateRoots,
+    /// [`TrieNodePaths`]
+    NodePaths,
+}
+
+impl ScanTarget {
+    /// All tables that have a column family in the database
+    fn all() -> Vec<Self> {
+        Tables::ALL
+            .iter()
+            .copied()
+            .map(Self::Table)
+            .chain([
+                Self::Trie,
+                Self::AccountTrie,
+                Self::StorageTrie,
+                Self::AccountHistory,
+                Self::StateRoots,
+                Self::NodePaths,
+            ])
+            .collect()
+    }
+
+    /// Scan the table. Returns `None` if its column family doesn't exist or the scan was
+    /// cancelled.
+    fn scan(
+        self,
+        db: &DB,
+        cancel: &AtomicBool,
+        fill_cache: bool,
+    ) -> Result<Option<TableScanReport>, DatabaseError> {
+        match self {
+            Self::Table(table) => table.view(&TableScanner { db, cancel, fill_cache }),
+            Self::Trie => scan_table::<TrieTable>(db, cancel, fill_cache),
+            Self::AccountTrie => scan_table::<AccountTrieTable>(db, cancel, fill_cache),
+            Self::StorageTrie => scan_dup_table::<StorageTrieTable>(db, cancel, fill_cache),
+            Self::AccountHistory => scan_table::<HashedAccountHistory>(db, cancel, fill_cache),
+            Self::StateRoots => scan_table::<BlockStateRoots>(db, cancel, fill_cache),
+            Self::NodePaths => scan_table::<TrieNodePaths>(db, cancel, fill_cache),
+        }
+    }
+}
+
+/// Dispatches the scan of a [`Tables`] variant to its typed table
+struct TableScanner<'a> {
+    db: &'a DB,
+    cancel: &'a AtomicBool,
+    fill_cache: bool,
+}
+
+impl TableViewer<Option<TableScanReport>> for TableScanner<'_> {
+    type Error = DatabaseError;
+
+    fn view<T: Table>(&self) -> Result<Option<TableScanReport>, Self::Error> {
+        scan_table::<T>(self.db, self.cancel, self.fill_cache)
+    }
+
+    fn view_dupsort<T: DupSort>(&self) -> Result<Option<TableScanReport>, Self::Error> {
+        scan_dup_table::<T>(self.db, self.cancel, self.fill_cache)
+    }
+}
+
+/// Scan a table, checking that every key and value decodes
+fn scan_table<T: Table>(
+    db: &DB,
+    cancel: &AtomicBool,
+    fill_cache: bool,
+) -> Result<Option<TableScanReport>, DatabaseError> {
+    scan_cf(db, T::NAME, cancel, fill_cache, |key, value| {
+        T::Key::decode(key).is_ok() && T::Value::decompress(value).is_ok()
+    })
+}
+
+/// Scan a DUPSORT table, checking that every value decodes. Keys are skipped as duplicate entries
+/// are stored under composite keys.
+fn scan_dup_table<T: DupSort>(
+    db: &DB,
+    cancel: &AtomicBool,
+    fill_cache: bool,
+) -> Result<Option<TableScanReport>, DatabaseError> {
+    scan_cf(db, T::NAME, cancel, fill_cache, |_, value| T::Value::decompress(value).is_ok())
+}
+
+/// Iterate a column family through a fresh snapshot, recording entries rejected by `is_valid`
+fn scan_cf(
+    db: &DB,
+    name: &'static str,
+    cancel: &AtomicBool,
+    fill_cache: bool,
+    is_valid: impl Fn(&[u8], &[u8]) -> bool,
+) -> Result<Option<TableScanReport>, DatabaseError> {
+    let Some(cf) = db.cf_handle(name) else { return Ok(None) };
+
+    let snapshot = db.snapshot();
+    let mut read_opts = ReadOptions::default();
+    read_opts.fill_cache(fill_cache);
+    read_opts.set_total_order_seek(true);
+    let mut report = TableScanReport { table: name, entries: 0, corrupt_keys: Vec::new() };
+
+    for item in snapshot.iterator_cf_opt(cf, read_opts, IteratorMode::Start) {
+        if cancel.load(Ordering::Relaxed) {
+            return Ok(None);
+        }
+
+        let (key, value) =
+            item.map_err(|e| DatabaseError::Other(format!("Failed to scan {}: {}", name, e)))?;
+
+        // Some decoders index into the input without checking its length, so a corrupt entry
+        // must not take the whole scan down
+        let valid = panic::catch_unwind(AssertUnwindSafe(|| is_valid(&key, &value)));
+        if !valid.unwrap_or(false) {
+            report.corrupt_keys.push(key.into_vec());
+        }
+        report.entries += 1;
+    }
+
+    Ok(Some(report))
+}
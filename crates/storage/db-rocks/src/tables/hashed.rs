@@ -0,0 +1,81 @@
+use super::TableConfig;
+use alloy_primitives::{B256, U256};
+use reth_db_api::{
+    table::{Decode, Encode, Table},
+    DatabaseError,
+};
+use reth_primitives::StorageEntry;
+use reth_primitives_traits::Account;
+use serde::{Deserialize, Serialize};
+
+/// Table storing every version of each hashed account, keyed by the block it was written at.
+///
+/// Iterates by hashed address, then by block number, so the state of an account at a block is
+/// the last entry of its address at or before that block.
+#[derive(Debug)]
+pub(crate) struct HashedAccountHistory;
+
+impl Table for HashedAccountHistory {
+    const NAME: &'static str = "hashed_account_history";
+    const DUPSORT: bool = false;
+
+    type Key = AccountAtBlock;
+    type Value = Account;
+}
+
+impl TableConfig for HashedAccountHistory {}
+
+/// Key of [`HashedAccountHistory`]: the hashed address followed by the big-endian block
+/// number, so byte order matches the derived `Ord`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct AccountAtBlock {
+    /// Hashed address of the account
+    pub hashed_address: B256,
+    /// Block the version was written at
+    pub block: u64,
+}
+
+impl Encode for AccountAtBlock {
+    type Encoded = Vec<u8>;
+
+    fn encode(self) -> Self::Encoded {
+        let mut bytes = Vec::with_capacity(40);
+        bytes.extend_from_slice(self.hashed_address.as_slice());
+        bytes.extend_from_slice(&self.block.to_be_bytes());
+        bytes
+    }
+}
+
+impl Decode for AccountAtBlock {
+    fn decode(bytes: &[u8]) -> Result<Self, DatabaseError> {
+        if bytes.len() != 40 {
+            return Err(DatabaseError::Decode);
+        }
+        let (hashed_address, block) = bytes.split_at(32);
+        Ok(Self {
+            hashed_address: B256::from_slice(hashed_address),
+            block: u64::from_be_bytes(block.try_into().map_err(|_| DatabaseError::Decode)?),
+        })
+    }
+}
+
+/// A single storage slot of a hashed address, as read from `HashedStorages`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HashedStorageEntry {
+    /// Hashed storage slot
+    pub slot: B256,
+    /// Value stored in the slot
+    pub value: U256,
+}
+
+impl From<StorageEntry> for HashedStorageEntry {
+    fn from(entry: StorageEntry) -> Self {
+        Self { slot: entry.key, value: entry.value }
+    }
+}
+
+impl From<HashedStorageEntry> for StorageEntry {
+    fn from(entry: HashedStorageEntry) -> Self {
+        Self { key: entry.slot, value: entry.value }
+    }
+}
@@ -0,0 +1,18 @@
+use crate::tables::trie::TrieNibbles;
+use alloy_primitives::{keccak256, Address, B256};
+use reth_trie::Nibbles;
+
+/// Key of `address` in the hashed state tables
+pub fn hashed_address(address: Address) -> B256 {
+    keccak256(address)
+}
+
+/// Key of storage `slot` in the hashed storage tables
+pub fn hashed_slot(slot: B256) -> B256 {
+    keccak256(slot)
+}
+
+/// Path of `address` in the account trie, i.e. the nibbles of its hashed address
+pub fn account_trie_key(address: Address) -> TrieNibbles {
+    TrieNibbles(Nibbles::unpack(hashed_address(address)))
+}
@@ -1,37 +1,343 @@
+pub(crate) mod hashed;
+pub(crate) mod keys;
 pub(crate) mod raw;
 pub(crate) mod trie;
 
-use reth_db_api::table::Table;
+use crate::config::RocksDBConfig;
+use crate::version::RESERVED_CFS;
+use reth_db_api::table::{Decode, Encode, Table};
 use reth_db_api::DatabaseError;
-use rocksdb::{ColumnFamilyDescriptor, Options};
+use rocksdb::{BlockBasedOptions, ColumnFamilyDescriptor, Options};
+use std::cmp::Ordering;
 
-/// Trait for getting RocksDB-specific table configurations
+/// A [`Table`] the cursors of this crate can be opened on: one whose key can be encoded,
+/// decoded, cloned and compared.
+///
+/// Sealed, and implemented for every table meeting these bounds, which [`Table::Key`] already
+/// implies. A table whose key misses one is rejected where the table is defined, rather than
+/// by each cursor method it reaches.
+pub trait RocksTable: Table<Key = <Self as RocksTable>::CursorKey> + sealed::Sealed {
+    /// Key of the table, with the bounds cursors need
+    type CursorKey: Encode + Decode + Clone + PartialEq;
+}
+
+impl<T: Table> RocksTable for T
+where
+    T::Key: Encode + Decode + Clone + PartialEq,
+{
+    type CursorKey = T::Key;
+}
+
+mod sealed {
+    use reth_db_api::table::{Decode, Encode, Table};
+
+    pub trait Sealed {}
+
+    impl<T: Table> Sealed for T where T::Key: Encode + Decode + Clone + PartialEq {}
+}
+
+/// Trait for getting RocksDB-specific table configurations.
+///
+/// Tables of this crate implement it, overriding the methods describing their key layout.
+/// Reth's tables keep the default layout, see [`KeyLayout::of_table`].
 pub(crate) trait TableConfig: Table {
     /// Get column family options for this table
     fn column_family_options() -> Options {
+        KeyLayout::of::<Self>().column_family_options()
+    }
+
+    /// Custom key order of this table, `None` for byte-lexicographic order of the encoded keys
+    fn comparator() -> Option<TableComparator> {
+        None
+    }
+
+    /// Length of the key prefix this table keeps a bloom filter over, `None` for no prefix
+    /// bloom.
+    ///
+    /// Point reads of keys whose prefix is absent from an SST file skip the file. Cursors of
+    /// these tables seek in total order, as a seek in prefix mode could skip files holding the
+    /// following keys of other prefixes.
+    fn prefix_bloom() -> Option<usize> {
+        None
+    }
+
+    /// Length of the key prefix extracted by this table's column family, `None` without a
+    /// prefix extractor
+    fn prefix_len() -> Option<usize> {
+        KeyLayout::of::<Self>().prefix_len()
+    }
+
+    /// Get column family descriptor for this table
+    fn descriptor() -> ColumnFamilyDescriptor {
+        KeyLayout::of::<Self>().descriptor(Self::NAME, None)
+    }
+
+    /// Get column family descriptor for this table, starting from `base_opts`
+    fn descriptor_with(base_opts: &Options) -> ColumnFamilyDescriptor {
+        KeyLayout::of::<Self>().descriptor(Self::NAME, Some(base_opts))
+    }
+}
+
+/// Layout of a table's keys in its column family, as declared through [`TableConfig`]
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct KeyLayout {
+    /// Whether the table stores several values per key
+    pub(crate) dupsort: bool,
+    /// [`TableConfig::prefix_bloom`]
+    pub(crate) prefix_bloom: Option<usize>,
+    /// [`TableConfig::comparator`]
+    pub(crate) comparator: Option<TableComparator>,
+}
+
+impl KeyLayout {
+    /// Layout declared by the table `T`
+    pub(crate) fn of<T: TableConfig>() -> Self {
+        Self { dupsort: T::DUPSORT, prefix_bloom: T::prefix_bloom(), comparator: T::comparator() }
+    }
+
+    /// Layout of the table `T`, for code that only knows it as a [`Table`]: the declared
+    /// layout of the tables of this crate, the default one of reth's tables
+    pub(crate) fn of_table<T: Table>() -> Self {
+        Self::named(T::NAME).unwrap_or(Self { dupsort: T::DUPSORT, ..Self::default() })
+    }
+
+    /// Layout declared by the table of this crate named `name`, `None` for other names
+    pub(crate) fn named(name: &str) -> Option<Self> {
+        match name {
+            trie::TrieTable::NAME => Some(Self::of::<trie::TrieTable>()),
+            trie::AccountTrieTable::NAME => Some(Self::of::<trie::AccountTrieTable>()),
+            trie::StorageTrieTable::NAME => Some(Self::of::<trie::StorageTrieTable>()),
+            trie::BlockStateRoots::NAME => Some(Self::of::<trie::BlockStateRoots>()),
+            trie::TrieNodePaths::NAME => Some(Self::of::<trie::TrieNodePaths>()),
+            hashed::HashedAccountHistory::NAME => Some(Self::of::<hashed::HashedAccountHistory>()),
+            _ => None,
+        }
+    }
+
+    /// Length of the key prefix extracted by the column family, `None` without a prefix
+    /// extractor
+    pub(crate) fn prefix_len(self) -> Option<usize> {
+        self.prefix_bloom.or(self.dupsort.then_some(DUPSORT_PREFIX_LEN))
+    }
+
+    /// Default column family options of the table
+    fn column_family_options(self) -> Options {
         let mut opts = Options::default();
 
         // Set basic options that apply to all tables
         opts.set_compression_type(rocksdb::DBCompressionType::Lz4);
         opts.set_bottommost_compression_type(rocksdb::DBCompressionType::Zstd);
 
-        // If table is DUPSORT, we need to configure prefix extractor
-        if Self::DUPSORT {
-            // Configure prefix scanning for DUPSORT tables
-            opts.set_prefix_extractor(rocksdb::SliceTransform::create_fixed_prefix(32));
+        if self.prefix_bloom.is_some() {
+            opts.set_block_based_table_factory(&bloom_filter_options(BlockBasedOptions::default()));
         }
+        self.apply(&mut opts);
 
         opts
     }
 
-    /// Get column family descriptor for this table
-    fn descriptor() -> ColumnFamilyDescriptor {
-        ColumnFamilyDescriptor::new(Self::NAME, Self::column_family_options())
+    /// Descriptor of the column family `name`, starting from `base_opts` if set or the default
+    /// options of the table otherwise
+    fn descriptor(self, name: &str, base_opts: Option<&Options>) -> ColumnFamilyDescriptor {
+        let opts = match base_opts {
+            Some(base_opts) => {
+                let mut opts = base_opts.clone();
+                self.apply(&mut opts);
+                opts
+            }
+            None => self.column_family_options(),
+        };
+        ColumnFamilyDescriptor::new(name, opts)
+    }
+
+    /// Set the prefix extractor and comparator of the layout in `opts`
+    fn apply(self, opts: &mut Options) {
+        if let Some(len) = self.prefix_len() {
+            opts.set_prefix_extractor(rocksdb::SliceTransform::create_fixed_prefix(len));
+        }
+        if let Some(comparator) = self.comparator {
+            comparator.install(opts);
+        }
     }
 }
 
-// Implement TableConfig for all Tables
-impl<T: Table> TableConfig for T {}
+/// Key order of a column family other than RocksDB's default byte-lexicographic order.
+///
+/// RocksDB persists only the comparator's name and refuses to open a column family with a
+/// comparator of another name, but it can't tell if the ordering behind a name changed. Once a
+/// table holds data, its comparator must keep both its name and its ordering forever; changing
+/// the ordering under the same name silently corrupts lookups and iteration.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct TableComparator {
+    /// Name persisted by RocksDB and checked on every open
+    pub(crate) name: &'static str,
+    /// Ordering of two encoded keys
+    pub(crate) compare: fn(&[u8], &[u8]) -> Ordering,
+}
+
+impl TableComparator {
+    /// Install the comparator in column family options
+    fn install(self, opts: &mut Options) {
+        opts.set_comparator(self.name, Box::new(self.compare));
+    }
+}
+
+/// `block_opts` with a bloom filter, built over both whole keys and the prefixes of the
+/// column family's prefix extractor
+pub(crate) fn bloom_filter_options(mut block_opts: BlockBasedOptions) -> BlockBasedOptions {
+    block_opts.set_bloom_filter(10.0, false);
+    block_opts.set_whole_key_filtering(true);
+    block_opts
+}
+
+/// [`TableConfig::descriptor`] of the table named `name`, for every reth table and the trie
+/// tables. Returns `None` for unknown names.
+pub(crate) fn table_descriptor(name: &str) -> Option<ColumnFamilyDescriptor> {
+    descriptor_of(name, None)
+}
+
+/// [`TableConfig::descriptor_with`] of the table named `name`, starting from the options
+/// `config` sets for it, as the column family is opened by [`crate::RocksDB::open`]. Returns
+/// `None` for unknown names.
+pub(crate) fn table_descriptor_for(
+    name: &str,
+    config: &RocksDBConfig,
+) -> Option<ColumnFamilyDescriptor> {
+    descriptor_of(name, Some(&config.table_options_for(name)))
+}
+
+/// Descriptor of the table named `name`, starting from `base_opts` if set
+fn descriptor_of(name: &str, base_opts: Option<&Options>) -> Option<ColumnFamilyDescriptor> {
+    use reth_db::{TableViewer, Tables};
+
+    /// Builds the descriptor of a [`Tables`] variant
+    struct DescriptorViewer<'a>(Option<&'a Options>);
+
+    impl TableViewer<ColumnFamilyDescriptor> for DescriptorViewer<'_> {
+        type Error = std::convert::Infallible;
+
+        fn view<T: Table>(&self) -> Result<ColumnFamilyDescriptor, Self::Error> {
+            Ok(KeyLayout::of_table::<T>().descriptor(T::NAME, self.0))
+        }
+    }
+
+    match KeyLayout::named(name) {
+        Some(layout) => Some(layout.descriptor(name, base_opts)),
+        None => Tables::ALL
+            .iter()
+            .find(|table| table.name() == name)
+            .and_then(|table| table.view(&DescriptorViewer(base_opts)).ok()),
+    }
+}
+
+/// Layout of a table in RocksDB, as listed by [`crate::RocksDB::table_schema`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TableSchema {
+    /// Name of the table and its column family
+    pub name: &'static str,
+    /// Whether the table stores several values per key
+    pub dupsort: bool,
+    /// Length of the key prefix the column family extracts, `None` without a prefix extractor
+    pub prefix_len: Option<usize>,
+    /// Rust type name of the keys. Informational only, it isn't stable across compilers.
+    pub key_type: &'static str,
+    /// Rust type name of the values. Informational only, it isn't stable across compilers.
+    pub value_type: &'static str,
+}
+
+impl TableSchema {
+    /// Schema of the table `T`
+    pub(crate) fn of<T: Table>() -> Self {
+        Self {
+            name: T::NAME,
+            dupsort: T::DUPSORT,
+            prefix_len: KeyLayout::of_table::<T>().prefix_len(),
+            key_type: std::any::type_name::<T::Key>(),
+            value_type: std::any::type_name::<T::Value>(),
+        }
+    }
+}
+
+/// [`TableSchema`] of every reth table and the trie tables, in the order their column families
+/// are opened
+pub(crate) fn table_schemas() -> Vec<TableSchema> {
+    use reth_db::{TableViewer, Tables};
+
+    /// Builds the schema of a [`Tables`] variant
+    struct SchemaViewer;
+
+    impl TableViewer<TableSchema> for SchemaViewer {
+        type Error = std::convert::Infallible;
+
+        fn view<T: Table>(&self) -> Result<TableSchema, Self::Error> {
+            Ok(TableSchema::of::<T>())
+        }
+    }
+
+    Tables::ALL
+        .iter()
+        .filter_map(|table| table.view(&SchemaViewer).ok())
+        .chain([
+            TableSchema::of::<trie::TrieTable>(),
+            TableSchema::of::<trie::AccountTrieTable>(),
+            TableSchema::of::<trie::StorageTrieTable>(),
+            TableSchema::of::<trie::BlockStateRoots>(),
+            TableSchema::of::<trie::TrieNodePaths>(),
+            TableSchema::of::<hashed::HashedAccountHistory>(),
+        ])
+        .collect()
+}
+
+/// Length of the prefix DUPSORT tables extract from their keys, which must be the length of
+/// their encoded primary keys for prefix seeks to stay within one key's duplicates
+pub(crate) const DUPSORT_PREFIX_LEN: usize = 32;
+
+/// Assert that `key` of the DUPSORT table `T` encodes to [`DUPSORT_PREFIX_LEN`] bytes, catching
+/// tables whose keys don't fit the prefix extractor at write and seek time. Only checked in
+/// debug builds.
+#[inline]
+pub(crate) fn debug_assert_dupsort_prefix_len<T: Table>(key: &T::Key) {
+    if cfg!(debug_assertions) && T::DUPSORT {
+        let encoded = key.clone().encode();
+        assert!(
+            encoded.as_ref().len() == DUPSORT_PREFIX_LEN,
+            "{} key encodes to {} bytes, but the DUPSORT prefix length is {}",
+            T::NAME,
+            encoded.as_ref().len(),
+            DUPSORT_PREFIX_LEN
+        );
+    }
+}
+
+/// Assert that `key` decodes back to itself once encoded, catching asymmetric key codecs at
+/// write time. Only checked in debug builds.
+#[inline]
+pub(crate) fn debug_assert_key_round_trip<T: Table>(key: &T::Key) {
+    if cfg!(debug_assertions) {
+        let encoded = key.clone().encode();
+        let round_trips = T::Key::decode(encoded.as_ref()).is_ok_and(|decoded| &decoded == key);
+        assert!(
+            round_trips,
+            "{} key doesn't round-trip through its codec: encoded as {:?}",
+            T::NAME,
+            encoded.as_ref()
+        );
+    }
+}
+
+/// Refuse table names that are those of a column family reserved for metadata, see
+/// [`RESERVED_CFS`]
+pub(crate) fn check_table_names<'a>(
+    names: impl IntoIterator<Item = &'a str>,
+) -> Result<(), DatabaseError> {
+    match names.into_iter().find(|name| RESERVED_CFS.contains(name)) {
+        Some(name) => Err(DatabaseError::Other(format!(
+            "Table {} would be stored in a column family reserved for metadata",
+            name
+        ))),
+        None => Ok(()),
+    }
+}
 
 /// Utility functions for managing tables in RocksDB
 pub(crate) struct TableManagement;
@@ -42,6 +348,7 @@ impl TableManagement {
         db: &mut rocksdb::DB,
         tables: &[&str],
     ) -> Result<(), DatabaseError> {
+        check_table_names(tables.iter().copied())?;
         for table in tables {
             if !db.cf_handle(table).is_some() {
                 db.create_cf(table, &Options::default()).map_err(|e| {
@@ -52,18 +359,22 @@ impl TableManagement {
         Ok(())
     }
 
-    /// Get all column family descriptors for all tables
-    pub(crate) fn get_all_column_family_descriptors() -> Vec<ColumnFamilyDescriptor> {
+    /// Get all column family descriptors for all tables, with the options `config` sets for each
+    pub(crate) fn get_all_column_family_descriptors(
+        config: &RocksDBConfig,
+    ) -> Vec<ColumnFamilyDescriptor> {
         // WHAT IS TABLES/TABLE????
         use reth_db::Tables;
         Tables::ALL
             .iter()
             .map(|table| {
-                let mut opts = Options::default();
+                let mut opts = config.table_options_for(table.name());
 
                 // Configure options based on table type
                 if table.is_dupsort() {
-                    opts.set_prefix_extractor(rocksdb::SliceTransform::create_fixed_prefix(32));
+                    opts.set_prefix_extractor(rocksdb::SliceTransform::create_fixed_prefix(
+                        DUPSORT_PREFIX_LEN,
+                    ));
                 }
 
                 ColumnFamilyDescriptor::new(table.name(), opts)
@@ -1,4 +1,4 @@
-use rocksdb::{DBIterator, IteratorMode, DB};
+use rocksdb::{DBIterator, IteratorMode, ReadOptions, DB};
 use std::sync::Arc;
 
 /// Raw table access wrapper
@@ -30,6 +30,9 @@ impl<'a> RawTable<'a> {
 
     /// Create iterator over raw values
     pub(crate) fn iterator(&self, mode: IteratorMode) -> DBIterator {
-        self.db.iterator_cf(self.cf_handle, mode)
+        // Seeks must reach keys of any prefix of tables with a prefix extractor
+        let mut read_opts = ReadOptions::default();
+        read_opts.set_total_order_seek(true);
+        self.db.iterator_cf_opt(self.cf_handle, read_opts, mode)
     }
 }
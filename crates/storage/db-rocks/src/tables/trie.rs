@@ -1,11 +1,15 @@
+use super::TableConfig;
+use crate::implementation::rocks::dupsort::DupSortValue;
 use alloy_primitives::B256;
 use reth_codecs::Compact;
 use reth_db_api::table::{Decode, DupSort, Encode, Table};
-use reth_trie::{BranchNodeCompact, Nibbles}; // For encoding/decoding
+use reth_trie::{BranchNodeCompact, Nibbles, TrieMask}; // For encoding/decoding
 use reth_trie_common::StoredNibbles;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
 /// Table storing the trie nodes.
+///
+/// Iterates in byte order of the node hash.
 #[derive(Debug)]
 pub(crate) struct TrieTable;
 
@@ -14,10 +18,15 @@ impl Table for TrieTable {
     const DUPSORT: bool = false;
 
     type Key = B256; // Node hash
-    type Value = Vec<u8>; // RLP encoded node data
+    type Value = StoredTrieNode; // Encoded node data
 }
 
+impl TableConfig for TrieTable {}
+
 /// Table storing account trie nodes.
+///
+/// Keys are encoded one nibble per byte, so the table iterates in nibble path order with a path
+/// sorting before its extensions (`[]`, `[0]`, `[0, 1]`, `[1]`, ...).
 #[derive(Debug)]
 pub(crate) struct AccountTrieTable;
 
@@ -29,7 +38,18 @@ impl Table for AccountTrieTable {
     type Value = BranchNodeCompact; // Changed from Account to BranchNodeCompact
 }
 
+impl TableConfig for AccountTrieTable {
+    // Nibble paths are stored one nibble per byte, so this covers the first 8 nibbles
+    fn prefix_bloom() -> Option<usize> {
+        Some(8)
+    }
+}
+
 /// Table storing storage trie nodes.
+///
+/// Iterates by hashed address, then by nibble path within an address. Entries written through
+/// a dup cursor are stored under `address | 0xFF | path`; since addresses are fixed-size and
+/// path nibbles never reach `0xFF`, byte order of these keys is exactly that order.
 #[derive(Debug)]
 pub(crate) struct StorageTrieTable;
 
@@ -41,15 +61,105 @@ impl Table for StorageTrieTable {
     type Value = TrieNodeValue;
 }
 
+impl TableConfig for StorageTrieTable {}
+
 // Define StorageTrieEntry
 impl DupSort for StorageTrieTable {
     type SubKey = StoredNibbles;
 }
 
+impl DupSortValue for StorageTrieTable {
+    fn value_subkey(value: &[u8]) -> Result<Vec<u8>, reth_db_api::DatabaseError> {
+        // Values lead with the length of their path, so the path is read back from the value
+        Ok(<TrieNodeValue as reth_db_api::table::Decompress>::decompress(value)?
+            .nibbles
+            .encode()
+            .into())
+    }
+}
+
+/// Table storing the state root of every block.
+///
+/// Block numbers are encoded big-endian, so the table iterates by ascending block number.
+#[derive(Debug)]
+pub(crate) struct BlockStateRoots;
+
+impl Table for BlockStateRoots {
+    const NAME: &'static str = "block_state_roots";
+    const DUPSORT: bool = false;
+
+    type Key = u64;
+    type Value = B256;
+}
+
+impl TableConfig for BlockStateRoots {}
+
+/// Table mapping the hash of every account trie node to its path in [`AccountTrieTable`].
+///
+/// Kept in step with [`AccountTrieTable`], so a node found by hash in [`TrieTable`] can be
+/// located in the trie.
+#[derive(Debug)]
+pub(crate) struct TrieNodePaths;
+
+impl Table for TrieNodePaths {
+    const NAME: &'static str = "trie_node_paths";
+    const DUPSORT: bool = false;
+
+    type Key = B256;
+    type Value = TrieNibbles;
+}
+
+impl TableConfig for TrieNodePaths {}
+
 /// Wrapper type for Nibbles that implements necessary database traits
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub struct TrieNibbles(pub Nibbles);
 
+impl TrieNibbles {
+    /// Encode two nibbles per byte, about half the size of [`Encode::encode`]'s one byte per
+    /// nibble.
+    ///
+    /// Follows the hex-prefix layout of the Ethereum trie without the leaf flag: the first byte
+    /// holds the parity in its high nibble and, for an odd length, the first nibble in its low
+    /// nibble; the remaining nibbles follow two per byte.
+    ///
+    /// Packed keys do NOT sort in nibble order (packed `[2, 0]` sorts before packed `[1]`), so
+    /// they must not be used for tables that are iterated or sought in order, like the trie
+    /// tables walked by the trie cursors.
+    pub fn encode_packed(&self) -> Vec<u8> {
+        let nibbles = self.0.as_slice();
+        let (first, rest) = if nibbles.len() % 2 == 1 {
+            (0x10 | nibbles[0], &nibbles[1..])
+        } else {
+            (0x00, nibbles)
+        };
+
+        let mut packed = Vec::with_capacity(1 + rest.len() / 2);
+        packed.push(first);
+        packed.extend(rest.chunks_exact(2).map(|pair| (pair[0] << 4) | pair[1]));
+        packed
+    }
+
+    /// Decode nibbles packed by [`TrieNibbles::encode_packed`]
+    pub fn decode_packed(bytes: &[u8]) -> Result<Self, reth_db_api::DatabaseError> {
+        let (&first, rest) = bytes.split_first().ok_or(reth_db_api::DatabaseError::Decode)?;
+
+        let mut nibbles = Vec::with_capacity(1 + rest.len() * 2);
+        match first >> 4 {
+            // An even length leaves the low nibble of the prefix byte unused
+            0 if first & 0x0f == 0 => {}
+            1 => nibbles.push(first & 0x0f),
+            _ => return Err(reth_db_api::DatabaseError::Decode),
+        }
+        for byte in rest {
+            nibbles.push(byte >> 4);
+            nibbles.push(byte & 0x0f);
+        }
+
+        Ok(TrieNibbles(Nibbles::from_nibbles(&nibbles)))
+    }
+}
+
 impl Encode for TrieNibbles {
     type Encoded = Vec<u8>;
 
@@ -74,6 +184,24 @@ impl Decode for TrieNibbles {
     }
 }
 
+impl reth_db_api::table::Compress for TrieNibbles {
+    type Compressed = Vec<u8>;
+
+    fn compress(self) -> Vec<u8> {
+        self.encode()
+    }
+
+    fn compress_to_buf<B: bytes::BufMut + AsMut<[u8]>>(&self, buf: &mut B) {
+        buf.put_slice(self.0.as_slice());
+    }
+}
+
+impl reth_db_api::table::Decompress for TrieNibbles {
+    fn decompress(bytes: &[u8]) -> Result<Self, reth_db_api::DatabaseError> {
+        Self::decode(bytes)
+    }
+}
+
 // Implement serde traits which are needed for the Key trait
 impl serde::Serialize for TrieNibbles {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
@@ -116,6 +244,10 @@ impl From<TrieNibbles> for Nibbles {
     }
 }
 
+/// Value of [`StorageTrieTable`]: the node's nibble path and hash.
+///
+/// Stored as `nibble count (1) | nibbles (1 byte each) | node hash (32)`. The explicit count
+/// lets decoding check that the lengths add up rather than guess where the nibbles end.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct TrieNodeValue {
     pub nibbles: StoredNibbles,
@@ -126,26 +258,13 @@ impl Encode for TrieNodeValue {
     type Encoded = Vec<u8>;
 
     fn encode(self) -> Vec<u8> {
-        let mut bytes = Vec::new();
-        bytes.extend_from_slice(&self.nibbles.encode());
-        bytes.extend_from_slice(self.node.as_slice());
-        bytes
+        reth_db_api::table::Compress::compress(self)
     }
 }
 
 impl Decode for TrieNodeValue {
     fn decode(bytes: &[u8]) -> Result<Self, reth_db_api::DatabaseError> {
-        if bytes.len() < 32 {
-            return Err(reth_db_api::DatabaseError::Decode);
-        }
-
-        // Split bytes between nibbles part and value hash
-        let (nibbles_bytes, value_bytes) = bytes.split_at(bytes.len() - 32);
-
-        Ok(Self {
-            nibbles: StoredNibbles::decode(nibbles_bytes)?,
-            node: B256::from_slice(value_bytes),
-        })
+        reth_db_api::table::Decompress::decompress(bytes)
     }
 }
 
@@ -159,37 +278,44 @@ impl reth_db_api::table::Compress for TrieNodeValue {
     }
 
     fn compress_to_buf<B: bytes::BufMut + AsMut<[u8]>>(&self, buf: &mut B) {
-        // Then write the nibbles using Compact trait
+        let len = u8::try_from(self.nibbles.0.len()).expect("trie paths hold at most 64 nibbles");
+        buf.put_u8(len);
         self.nibbles.to_compact(buf);
-
-        // Finally encode the node hash (B256)
         buf.put_slice(self.node.as_ref());
     }
 }
 
 impl reth_db_api::table::Decompress for TrieNodeValue {
     fn decompress(bytes: &[u8]) -> Result<Self, reth_db_api::DatabaseError> {
-        if bytes.is_empty() {
+        let (&len, rest) = bytes.split_first().ok_or(reth_db_api::DatabaseError::Decode)?;
+        let len = len as usize;
+
+        // Exactly the announced nibbles and the hash, so truncated or padded values are caught
+        if rest.len() != len + B256::len_bytes() {
             return Err(reth_db_api::DatabaseError::Decode);
         }
+        let (nibbles, node) = rest.split_at(len);
+        Self::from_parts(nibbles, node)
+    }
+}
 
-        // Since we can't directly use the private reth_codecs::decode_varuint function,
-        // we'll decode bytes in a way that's compatible with our encoding above.
-
-        // Decode the nibbles using Compact's from_compact
-        // The StoredNibbles::from_compact will advance the buffer correctly
-        let (nibbles, remaining) = StoredNibbles::from_compact(bytes, bytes.len() - 32);
+impl TrieNodeValue {
+    /// Decode a value stored by schema version 1, before the nibble count was recorded: every
+    /// byte but the trailing hash is a nibble. Only used to migrate such values.
+    pub(crate) fn decompress_unprefixed(bytes: &[u8]) -> Result<Self, reth_db_api::DatabaseError> {
+        let split =
+            bytes.len().checked_sub(B256::len_bytes()).ok_or(reth_db_api::DatabaseError::Decode)?;
+        let (nibbles, node) = bytes.split_at(split);
+        Self::from_parts(nibbles, node)
+    }
 
-        // Check if we have enough bytes left for the node hash (B256 = 32 bytes)
-        if remaining.len() < 32 {
+    /// Build a value from its nibbles, one per byte, and its 32-byte hash
+    fn from_parts(nibbles: &[u8], node: &[u8]) -> Result<Self, reth_db_api::DatabaseError> {
+        if nibbles.iter().any(|&nibble| nibble > 0xf) {
             return Err(reth_db_api::DatabaseError::Decode);
         }
-
-        // Extract and convert the node hash
-        let mut node = B256::default();
-        <B256 as AsMut<[u8]>>::as_mut(&mut node).copy_from_slice(&remaining[..32]);
-
-        Ok(TrieNodeValue { nibbles, node })
+        let (nibbles, _) = StoredNibbles::from_compact(nibbles, nibbles.len());
+        Ok(Self { nibbles, node: B256::from_slice(node) })
     }
 }
 
@@ -214,3 +340,156 @@ impl<'de> Deserialize<'de> for TrieNodeValue {
         Self::decode(&bytes).map_err(serde::de::Error::custom)
     }
 }
+
+/// Encoded branch node stored in [`TrieTable`].
+///
+/// Layout: `state_mask (2) | tree_mask (2) | hash_mask (2) | hash count (1) | hashes (32 each) |
+/// root flag (1) | root hash (32, only if flag is 1)`. The layout and the mask invariants of
+/// [`BranchNodeCompact`] are validated on every decode so corrupted entries are caught as soon as
+/// they're read.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StoredTrieNode(Vec<u8>);
+
+impl StoredTrieNode {
+    /// Size of the fixed header: three masks and the hash count
+    const HEADER_LEN: usize = 7;
+
+    /// Wrap already encoded bytes, validating the layout
+    pub fn from_bytes(bytes: Vec<u8>) -> Result<Self, reth_db_api::DatabaseError> {
+        Self::validate(&bytes)?;
+        Ok(Self(bytes))
+    }
+
+    /// Raw encoded bytes
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// Hash of the encoded node, used as its key in [`TrieTable`]
+    pub fn hash(&self) -> B256 {
+        alloy_primitives::keccak256(&self.0)
+    }
+
+    /// Decode back into a [`BranchNodeCompact`]
+    pub fn to_branch_node(&self) -> BranchNodeCompact {
+        let bytes = &self.0;
+        let state_mask = TrieMask::new(u16::from_be_bytes([bytes[0], bytes[1]]));
+        let tree_mask = TrieMask::new(u16::from_be_bytes([bytes[2], bytes[3]]));
+        let hash_mask = TrieMask::new(u16::from_be_bytes([bytes[4], bytes[5]]));
+        let hash_count = bytes[6] as usize;
+
+        let hashes_end = Self::HEADER_LEN + hash_count * 32;
+        let hashes = bytes[Self::HEADER_LEN..hashes_end].chunks_exact(32).map(B256::from_slice);
+
+        let root_hash =
+            (bytes[hashes_end] == 1).then(|| B256::from_slice(&bytes[hashes_end + 1..]));
+
+        BranchNodeCompact::new(state_mask, tree_mask, hash_mask, hashes.collect(), root_hash)
+    }
+
+    /// Check that `bytes` follows the encoded node layout
+    fn validate(bytes: &[u8]) -> Result<(), reth_db_api::DatabaseError> {
+        if bytes.len() < Self::HEADER_LEN + 1 {
+            return Err(reth_db_api::DatabaseError::Decode);
+        }
+
+        let hash_count = bytes[6] as usize;
+        let hashes_end = Self::HEADER_LEN + hash_count * 32;
+
+        let expected_len = match bytes.get(hashes_end) {
+            Some(0) => hashes_end + 1,
+            Some(1) => hashes_end + 1 + 32,
+            _ => return Err(reth_db_api::DatabaseError::Decode),
+        };
+
+        if bytes.len() != expected_len {
+            return Err(reth_db_api::DatabaseError::Decode);
+        }
+
+        // Every hash belongs to a child flagged in the hash mask, and both the tree and hash masks
+        // only flag children the state mask has
+        let state_mask = u16::from_be_bytes([bytes[0], bytes[1]]);
+        let tree_mask = u16::from_be_bytes([bytes[2], bytes[3]]);
+        let hash_mask = u16::from_be_bytes([bytes[4], bytes[5]]);
+        if hash_mask.count_ones() as usize != hash_count {
+            return Err(reth_db_api::DatabaseError::Decode);
+        }
+        if tree_mask & !state_mask != 0 || hash_mask & !state_mask != 0 {
+            return Err(reth_db_api::DatabaseError::Decode);
+        }
+
+        Ok(())
+    }
+}
+
+impl From<&BranchNodeCompact> for StoredTrieNode {
+    fn from(node: &BranchNodeCompact) -> Self {
+        let mut result = Vec::with_capacity(
+            Self::HEADER_LEN + node.hashes.len() * 32 + 1 + node.root_hash.map_or(0, |_| 32),
+        );
+
+        // Masks (2 bytes each)
+        result.extend_from_slice(&node.state_mask.get().to_be_bytes());
+        result.extend_from_slice(&node.tree_mask.get().to_be_bytes());
+        result.extend_from_slice(&node.hash_mask.get().to_be_bytes());
+
+        // Number of hashes (1 byte), then each hash (32 bytes each)
+        result.push(node.hashes.len() as u8);
+        for hash in node.hashes.iter() {
+            result.extend_from_slice(hash.as_slice());
+        }
+
+        // Root hash (1 byte flag + 32 bytes hash if Some)
+        if let Some(hash) = &node.root_hash {
+            result.push(1);
+            result.extend_from_slice(hash.as_slice());
+        } else {
+            result.push(0);
+        }
+
+        Self(result)
+    }
+}
+
+impl Decode for StoredTrieNode {
+    fn decode(bytes: &[u8]) -> Result<Self, reth_db_api::DatabaseError> {
+        Self::from_bytes(bytes.to_vec())
+    }
+}
+
+impl reth_db_api::table::Compress for StoredTrieNode {
+    type Compressed = Vec<u8>;
+
+    fn compress(self) -> Vec<u8> {
+        self.0
+    }
+
+    fn compress_to_buf<B: bytes::BufMut + AsMut<[u8]>>(&self, buf: &mut B) {
+        buf.put_slice(&self.0);
+    }
+}
+
+impl reth_db_api::table::Decompress for StoredTrieNode {
+    fn decompress(bytes: &[u8]) -> Result<Self, reth_db_api::DatabaseError> {
+        Self::decode(bytes)
+    }
+}
+
+impl Serialize for StoredTrieNode {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.0.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for StoredTrieNode {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let bytes = Vec::<u8>::deserialize(deserializer)?;
+        Self::from_bytes(bytes).map_err(serde::de::Error::custom)
+    }
+}
@@ -1,5 +1,8 @@
+mod rocks_cursor_fuzz_test;
 mod rocks_cursor_test;
 mod rocks_db_ops_test;
+mod rocks_db_test;
+mod rocks_order_test;
 mod rocks_proof_test;
 mod rocks_stateroot_test;
 pub mod utils;
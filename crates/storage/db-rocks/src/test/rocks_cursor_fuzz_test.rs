@@ -0,0 +1,169 @@
+/// Randomized cursor consistency check: a sequence of writes, seeks and moves is applied to a
+/// table cursor and mirrored in a `BTreeMap`, and every result has to match the map.
+///
+/// The sequence is generated from a fixed seed, so a failure reproduces on every run. Set
+/// `ROCKS_CURSOR_FUZZ_OPS` to run a longer sequence and `ROCKS_CURSOR_FUZZ_SEED` to try other
+/// sequences; failures report the seed and step.
+#[cfg(test)]
+mod rocks_cursor_fuzz_test {
+    use crate::test::utils::create_test_db;
+    use crate::{Account, RocksTransaction};
+    use alloy_primitives::{B256, U256};
+    use reth_db::HashedAccounts;
+    use reth_db_api::{
+        cursor::{DbCursorRO, DbCursorRW},
+        transaction::{DbTx, DbTxMut},
+    };
+    use std::collections::BTreeMap;
+    use std::ops::Bound;
+
+    const DEFAULT_OPS: usize = 2_000;
+    const DEFAULT_SEED: u64 = 0x5EED_CAFE_F00D_0001;
+    /// Few enough distinct keys for seeks to hit existing entries and writes to overwrite
+    const KEY_SPACE: u64 = 48;
+
+    /// xorshift64*, enough to spread operations without an extra dependency
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 >> 12;
+            self.0 ^= self.0 << 25;
+            self.0 ^= self.0 >> 27;
+            self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+        }
+
+        fn below(&mut self, n: u64) -> u64 {
+            self.next() % n
+        }
+    }
+
+    #[derive(Debug)]
+    enum Op {
+        Upsert(B256, Account),
+        DeleteCurrent,
+        First,
+        Last,
+        Seek(B256),
+        SeekExact(B256),
+        Next,
+        Prev,
+        Current,
+    }
+
+    fn env_or<T: std::str::FromStr>(name: &str, default: T) -> T {
+        std::env::var(name).ok().and_then(|value| value.parse().ok()).unwrap_or(default)
+    }
+
+    fn random_key(rng: &mut Rng) -> B256 {
+        // Spread over both ends of the key so entries don't only differ in their last byte
+        let n = rng.below(KEY_SPACE) as u8;
+        let mut key = [0u8; 32];
+        key[0] = n / 8;
+        key[31] = n;
+        B256::from(key)
+    }
+
+    /// Next operation. Moves relative to the position are only generated while the oracle is
+    /// positioned, so the sequence never depends on where an unpositioned cursor would go.
+    fn random_op(rng: &mut Rng, positioned: bool) -> Op {
+        let choices = if positioned { 9 } else { 5 };
+        match rng.below(choices) {
+            0 => {
+                let nonce = rng.next();
+                Op::Upsert(
+                    random_key(rng),
+                    Account { nonce, balance: U256::from(nonce >> 8), bytecode_hash: None },
+                )
+            }
+            1 => Op::First,
+            2 => Op::Last,
+            3 => Op::Seek(random_key(rng)),
+            4 => Op::SeekExact(random_key(rng)),
+            5 => Op::Next,
+            6 => Op::Prev,
+            7 => Op::Current,
+            _ => Op::DeleteCurrent,
+        }
+    }
+
+    #[test]
+    fn test_cursor_matches_btreemap_oracle() {
+        let ops = env_or("ROCKS_CURSOR_FUZZ_OPS", DEFAULT_OPS);
+        let seed = env_or("ROCKS_CURSOR_FUZZ_SEED", DEFAULT_SEED);
+        let mut rng = Rng(seed.max(1));
+
+        let (db, _temp_dir) = create_test_db();
+        let tx = RocksTransaction::<true>::from_db(db.clone());
+        let mut cursor = tx.cursor_write::<HashedAccounts>().unwrap();
+
+        let mut oracle = BTreeMap::<B256, Account>::new();
+        // Key the oracle cursor is positioned at
+        let mut position: Option<B256> = None;
+
+        for step in 0..ops {
+            let op = random_op(&mut rng, position.is_some());
+            let entry = |key: Option<&B256>, oracle: &BTreeMap<B256, Account>| {
+                key.map(|key| (*key, oracle[key]))
+            };
+
+            let (actual, expected) = match &op {
+                Op::Upsert(key, account) => {
+                    cursor.upsert(*key, account).unwrap();
+                    oracle.insert(*key, *account);
+                    (None, None)
+                }
+                Op::DeleteCurrent => {
+                    cursor.delete_current().unwrap();
+                    let deleted = position.take().unwrap();
+                    oracle.remove(&deleted);
+                    // The cursor moves on to the entry after the deleted one
+                    position = oracle
+                        .range((Bound::Excluded(deleted), Bound::Unbounded))
+                        .next()
+                        .map(|(key, _)| *key);
+                    (cursor.current().unwrap(), entry(position.as_ref(), &oracle))
+                }
+                Op::First => {
+                    position = oracle.keys().next().copied();
+                    (cursor.first().unwrap(), entry(position.as_ref(), &oracle))
+                }
+                Op::Last => {
+                    position = oracle.keys().next_back().copied();
+                    (cursor.last().unwrap(), entry(position.as_ref(), &oracle))
+                }
+                Op::Seek(key) => {
+                    position = oracle.range(*key..).next().map(|(key, _)| *key);
+                    (cursor.seek(*key).unwrap(), entry(position.as_ref(), &oracle))
+                }
+                Op::SeekExact(key) => {
+                    position = oracle.contains_key(key).then_some(*key);
+                    (cursor.seek_exact(*key).unwrap(), entry(position.as_ref(), &oracle))
+                }
+                Op::Next => {
+                    let from = position.unwrap();
+                    position = oracle
+                        .range((Bound::Excluded(from), Bound::Unbounded))
+                        .next()
+                        .map(|(key, _)| *key);
+                    (cursor.next().unwrap(), entry(position.as_ref(), &oracle))
+                }
+                Op::Prev => {
+                    let from = position.unwrap();
+                    position = oracle.range(..from).next_back().map(|(key, _)| *key);
+                    (cursor.prev().unwrap(), entry(position.as_ref(), &oracle))
+                }
+                Op::Current => (cursor.current().unwrap(), entry(position.as_ref(), &oracle)),
+            };
+
+            assert_eq!(actual, expected, "step {step} of seed {seed:#x}: {op:?}");
+        }
+
+        // Whatever the sequence did, the table ends up holding exactly the oracle's entries
+        drop(cursor);
+        let read_tx = RocksTransaction::<false>::from_db(db);
+        let mut cursor = read_tx.cursor_read::<HashedAccounts>().unwrap();
+        let stored = cursor.walk(None).unwrap().collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(stored, oracle.into_iter().collect::<Vec<_>>(), "final state of seed {seed:#x}");
+    }
+}
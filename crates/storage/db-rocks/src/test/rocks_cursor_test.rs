@@ -1,22 +1,32 @@
 #[cfg(test)]
 mod rocks_cursor_test {
     use crate::test::utils::create_test_db; // Replace with the correct module path where `create_test_db` is defined
-    use crate::{implementation::rocks::trie::RocksHashedCursorFactory, Account, RocksTransaction};
+    use crate::{
+        implementation::rocks::cursor::{ThreadSafeRocksCursor, ThreadSafeRocksDupCursor},
+        implementation::rocks::trie::RocksHashedCursorFactory,
+        tables::{TableComparator, TableConfig},
+        Account, CursorOpStats, HashedStorageEntry, RocksTransaction,
+    };
     use alloy_primitives::{keccak256, Address, B256, U256};
     use reth_db::{
-        cursor::DbCursorRO,
+        cursor::{DbCursorRO, DbDupCursorRO},
         transaction::{DbTx, DbTxMut},
-        HashedAccounts,
+        HashedAccounts, HashedStorages,
     };
+    use reth_db_api::table::Table;
+    use reth_primitives::StorageEntry;
     use reth_trie::hashed_cursor::{HashedCursor, HashedCursorFactory};
+    use rocksdb::{ColumnFamilyDescriptor, Options, DB};
     use std::collections::BTreeMap;
+    use std::sync::Arc;
+    use tempfile::TempDir;
 
     #[test]
     fn test_rocks_cursor_basic() {
         let (db, _temp_dir) = create_test_db();
 
         // Create a write transaction and insert some test data
-        let write_tx = RocksTransaction::<true>::new(db.clone(), true);
+        let write_tx = RocksTransaction::<true>::from_db(db.clone());
 
         // Create test keys and values
         let key1 = B256::from([1; 32]);
@@ -42,7 +52,7 @@ mod rocks_cursor_test {
         write_tx.commit().unwrap();
 
         // Test with a read transaction
-        let read_tx = RocksTransaction::<false>::new(db.clone(), false);
+        let read_tx = RocksTransaction::<false>::from_db(db.clone());
 
         // Get a cursor directly
         let mut cursor = read_tx.cursor_read::<HashedAccounts>().unwrap();
@@ -63,7 +73,7 @@ mod rocks_cursor_test {
         let (db, _temp_dir) = create_test_db();
 
         // Create a write transaction
-        let write_tx = RocksTransaction::<true>::new(db.clone(), true);
+        let write_tx = RocksTransaction::<true>::from_db(db.clone());
 
         // Create multiple test keys and values
         let mut keys = Vec::new();
@@ -91,7 +101,7 @@ mod rocks_cursor_test {
         write_tx.commit().unwrap();
 
         // Test with a read transaction
-        let read_tx = RocksTransaction::<false>::new(db.clone(), false);
+        let read_tx = RocksTransaction::<false>::from_db(db.clone());
 
         // Get a cursor
         let mut cursor = read_tx.cursor_read::<HashedAccounts>().unwrap();
@@ -251,7 +261,7 @@ mod rocks_cursor_test {
     #[test]
     fn test_rocks_cursor_empty_db() {
         let (db, _temp_dir) = create_test_db();
-        let read_tx = RocksTransaction::<false>::new(db.clone(), false);
+        let read_tx = RocksTransaction::<false>::from_db(db.clone());
         let mut cursor = read_tx.cursor_read::<HashedAccounts>().unwrap();
 
         // Test first() on empty database
@@ -282,7 +292,7 @@ mod rocks_cursor_test {
         let (db, _temp_dir) = create_test_db();
 
         // Create a write transaction and insert one test entry
-        let write_tx = RocksTransaction::<true>::new(db.clone(), true);
+        let write_tx = RocksTransaction::<true>::from_db(db.clone());
         let key = B256::from([1u8; 32]);
         let value = Account {
             nonce: 1,
@@ -293,7 +303,7 @@ mod rocks_cursor_test {
         write_tx.commit().unwrap();
 
         // Test with a read transaction
-        let read_tx = RocksTransaction::<false>::new(db.clone(), false);
+        let read_tx = RocksTransaction::<false>::from_db(db.clone());
         let mut cursor = read_tx.cursor_read::<HashedAccounts>().unwrap();
 
         // Test first() with single entry
@@ -335,7 +345,7 @@ mod rocks_cursor_test {
         let (db, _temp_dir) = create_test_db();
 
         // Create a write transaction and insert some test accounts
-        let write_tx = RocksTransaction::<true>::new(db.clone(), true);
+        let write_tx = RocksTransaction::<true>::from_db(db.clone());
 
         // Create test accounts
         let addr1 = keccak256(Address::from([1; 20]));
@@ -373,7 +383,7 @@ mod rocks_cursor_test {
         println!("Transaction committed");
 
         // Verify accounts were stored
-        let verify_tx = RocksTransaction::<false>::new(db.clone(), false);
+        let verify_tx = RocksTransaction::<false>::from_db(db.clone());
 
         let acct1 = verify_tx.get::<HashedAccounts>(addr1).unwrap();
         let acct2 = verify_tx.get::<HashedAccounts>(addr2).unwrap();
@@ -385,7 +395,7 @@ mod rocks_cursor_test {
         );
 
         // Create a read transaction to test the cursor
-        let read_tx = RocksTransaction::<false>::new(db.clone(), false);
+        let read_tx = RocksTransaction::<false>::from_db(db.clone());
 
         // Create and test hashed account cursor
         let hashed_factory = RocksHashedCursorFactory::new(&read_tx);
@@ -438,4 +448,433 @@ mod rocks_cursor_test {
         println!("Next result: \n  -{:?}", next_result);
         assert!(next_result.is_none(), "Failed to get next account");
     }
+
+    #[test]
+    fn test_iter_keys_matches_cursor() {
+        let (db, _temp_dir) = create_test_db();
+
+        let write_tx = RocksTransaction::<true>::from_db(db.clone());
+        for i in (0..50u8).rev() {
+            let account = Account { nonce: i as u64, balance: U256::from(i), bytecode_hash: None };
+            write_tx.put::<HashedAccounts>(keccak256([i]), account).unwrap();
+        }
+        write_tx.commit().unwrap();
+
+        let read_tx = RocksTransaction::<false>::from_db(db.clone());
+
+        let mut cursor_keys = Vec::new();
+        let mut cursor = read_tx.cursor_read::<HashedAccounts>().unwrap();
+        let mut entry = cursor.first().unwrap();
+        while let Some((key, _)) = entry {
+            cursor_keys.push(key);
+            entry = cursor.next().unwrap();
+        }
+
+        let keys =
+            read_tx.iter_keys::<HashedAccounts>().unwrap().collect::<Result<Vec<_>, _>>().unwrap();
+
+        assert_eq!(keys.len(), 50);
+        assert_eq!(keys, cursor_keys);
+    }
+
+    #[test]
+    fn test_hashed_storage_cursor_iterates_slots_in_order() {
+        let (db, _temp_dir) = create_test_db();
+
+        let hashed_address = keccak256(Address::from([1; 20]));
+        let other_address = keccak256(Address::from([2; 20]));
+
+        // Insert slots out of order, plus one slot of another address
+        let slots: BTreeMap<B256, U256> = [(9u8, 90u64), (3, 30), (7, 70), (1, 10)]
+            .into_iter()
+            .map(|(slot, value)| (B256::from([slot; 32]), U256::from(value)))
+            .collect();
+
+        let write_tx = RocksTransaction::<true>::from_db(db.clone());
+        let mut cursor = write_tx.cursor_dup_write::<HashedStorages>().unwrap();
+        for (slot, value) in slots.iter().rev() {
+            cursor
+                .upsert_dup(hashed_address, *slot, &StorageEntry { key: *slot, value: *value })
+                .unwrap();
+        }
+        let other_slot = B256::from([5; 32]);
+        cursor
+            .upsert_dup(
+                other_address,
+                other_slot,
+                &StorageEntry { key: other_slot, value: U256::from(5) },
+            )
+            .unwrap();
+        drop(cursor);
+        write_tx.commit().unwrap();
+
+        let read_tx = RocksTransaction::<false>::from_db(db.clone());
+        let factory = RocksHashedCursorFactory::new(&read_tx);
+        let mut cursor = factory.hashed_storage_cursor(hashed_address).unwrap();
+
+        let mut entries = Vec::new();
+        let mut next = cursor.seek_entry(B256::ZERO).unwrap();
+        while let Some(entry) = next {
+            entries.push(entry);
+            next = cursor.next_entry().unwrap();
+        }
+
+        let expected: Vec<HashedStorageEntry> = slots
+            .iter()
+            .map(|(slot, value)| HashedStorageEntry { slot: *slot, value: *value })
+            .collect();
+        assert_eq!(entries, expected);
+
+        // Seeking lands on the first slot at or after the requested one
+        assert_eq!(
+            cursor.seek(B256::from([4; 32])).unwrap(),
+            Some((B256::from([7; 32]), U256::from(70)))
+        );
+        assert_eq!(cursor.next().unwrap(), Some((B256::from([9; 32]), U256::from(90))));
+        assert_eq!(cursor.next().unwrap(), None);
+    }
+
+    #[test]
+    fn test_next_dup_val_matches_next_dup() {
+        let (db, _temp_dir) = create_test_db();
+
+        let hashed_address = keccak256(Address::from([1; 20]));
+        let other_address = keccak256(Address::from([2; 20]));
+
+        let write_tx = RocksTransaction::<true>::from_db(db.clone());
+        let mut cursor = write_tx.cursor_dup_write::<HashedStorages>().unwrap();
+        for i in 1..=5u8 {
+            let slot = B256::from([i; 32]);
+            cursor
+                .upsert_dup(hashed_address, slot, &StorageEntry { key: slot, value: U256::from(i) })
+                .unwrap();
+        }
+        // The duplicates of the next key must not be returned
+        let other_slot = B256::from([0; 32]);
+        cursor
+            .upsert_dup(
+                other_address,
+                other_slot,
+                &StorageEntry { key: other_slot, value: U256::from(100) },
+            )
+            .unwrap();
+        drop(cursor);
+        write_tx.commit().unwrap();
+
+        let read_tx = RocksTransaction::<false>::from_db(db);
+
+        let mut cursor = read_tx.cursor_dup_read::<HashedStorages>().unwrap();
+        cursor.seek_exact(hashed_address).unwrap();
+        let mut pairs = Vec::new();
+        while let Some((_, value)) = cursor.next_dup().unwrap() {
+            pairs.push(value);
+        }
+
+        let mut cursor = read_tx.cursor_dup_read::<HashedStorages>().unwrap();
+        cursor.seek_exact(hashed_address).unwrap();
+        let mut values = Vec::new();
+        while let Some(value) = cursor.next_dup_val().unwrap() {
+            values.push(value);
+        }
+
+        assert_eq!(values.len(), 4);
+        assert_eq!(values, pairs);
+        assert_eq!(values.last().unwrap().value, U256::from(5));
+    }
+
+    #[test]
+    fn test_cursor_wrappers_are_send_and_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<ThreadSafeRocksCursor<HashedAccounts, false>>();
+        assert_send_sync::<ThreadSafeRocksCursor<HashedAccounts, true>>();
+        assert_send_sync::<ThreadSafeRocksDupCursor<HashedStorages, false>>();
+        assert_send_sync::<ThreadSafeRocksDupCursor<HashedStorages, true>>();
+    }
+
+    #[test]
+    fn test_cursor_moved_to_worker_thread() {
+        let (db, _temp_dir) = create_test_db();
+
+        let hashed_address = keccak256(Address::from([1; 20]));
+        let write_tx = RocksTransaction::<true>::from_db(db.clone());
+        for i in 1..=10u8 {
+            let account = Account { nonce: i as u64, balance: U256::from(i), bytecode_hash: None };
+            write_tx.put::<HashedAccounts>(B256::from([i; 32]), account).unwrap();
+        }
+        let mut storage_cursor = write_tx.cursor_dup_write::<HashedStorages>().unwrap();
+        for i in 1..=5u8 {
+            let slot = B256::from([i; 32]);
+            storage_cursor
+                .upsert_dup(hashed_address, slot, &StorageEntry { key: slot, value: U256::from(i) })
+                .unwrap();
+        }
+        drop(storage_cursor);
+        write_tx.commit().unwrap();
+
+        let read_tx = RocksTransaction::<false>::from_db(db);
+        let read_accounts = |mut cursor: ThreadSafeRocksCursor<HashedAccounts, false>| {
+            let mut entries = vec![cursor.seek(B256::from([4; 32])).unwrap()];
+            entries.push(cursor.next().unwrap());
+            entries.push(cursor.prev().unwrap());
+            entries.push(cursor.last().unwrap());
+            entries.extend(cursor.walk(None).unwrap().map(|entry| Some(entry.unwrap())));
+            entries
+        };
+        let read_slots = move |mut cursor: ThreadSafeRocksDupCursor<HashedStorages, false>| {
+            let mut values = vec![cursor.seek_by_key_subkey(hashed_address, B256::ZERO).unwrap()];
+            while let Some(value) = cursor.next_dup_val().unwrap() {
+                values.push(Some(value));
+            }
+            values
+        };
+
+        let accounts_here = read_accounts(read_tx.cursor_read::<HashedAccounts>().unwrap());
+        let slots_here = read_slots(read_tx.cursor_dup_read::<HashedStorages>().unwrap());
+
+        // The cursors are created here, then moved to and used only on the worker
+        let account_cursor = read_tx.cursor_read::<HashedAccounts>().unwrap();
+        let slot_cursor = read_tx.cursor_dup_read::<HashedStorages>().unwrap();
+        let (accounts_there, slots_there) =
+            std::thread::spawn(move || (read_accounts(account_cursor), read_slots(slot_cursor)))
+                .join()
+                .unwrap();
+
+        assert_eq!(accounts_there, accounts_here);
+        assert_eq!(slots_there, slots_here);
+        assert_eq!(accounts_here.len(), 14);
+        assert_eq!(slots_here.len(), 5);
+    }
+
+    #[test]
+    fn test_cursors_of_one_transaction_are_independent() {
+        let (db, _temp_dir) = create_test_db();
+
+        let key = |i: u8| B256::with_last_byte(i);
+        let account = |i: u8| Account { nonce: i as u64, balance: U256::ZERO, bytecode_hash: None };
+        let write_tx = RocksTransaction::<true>::from_db(db.clone());
+        for i in 1..=5u8 {
+            write_tx.put::<HashedAccounts>(key(i), account(i)).unwrap();
+        }
+        write_tx.commit().unwrap();
+
+        let read_tx = RocksTransaction::<false>::from_db(db);
+        let mut first = read_tx.cursor_read::<HashedAccounts>().unwrap();
+        let mut second = read_tx.cursor_read::<HashedAccounts>().unwrap();
+
+        assert_eq!(first.first().unwrap(), Some((key(1), account(1))));
+        assert_eq!(second.first().unwrap(), Some((key(1), account(1))));
+
+        // Advancing the first cursor leaves the second where it was
+        assert_eq!(first.next().unwrap(), Some((key(2), account(2))));
+        assert_eq!(first.next().unwrap(), Some((key(3), account(3))));
+        assert_eq!(second.current().unwrap(), Some((key(1), account(1))));
+        assert_eq!(second.next().unwrap(), Some((key(2), account(2))));
+
+        // And the other way around, including seeks and walking off the end
+        assert_eq!(second.seek(key(5)).unwrap(), Some((key(5), account(5))));
+        assert_eq!(second.next().unwrap(), None);
+        assert_eq!(first.current().unwrap(), Some((key(3), account(3))));
+        assert_eq!(first.next().unwrap(), Some((key(4), account(4))));
+        assert_eq!(first.prev().unwrap(), Some((key(3), account(3))));
+    }
+
+    #[test]
+    fn test_cursor_on_empty_table() {
+        let (db, _temp_dir) = create_test_db();
+
+        let read_tx = RocksTransaction::<false>::from_db(db.clone());
+        let mut cursor = read_tx.cursor_read::<HashedAccounts>().unwrap();
+        assert_eq!(cursor.first().unwrap(), None);
+        assert_eq!(cursor.last().unwrap(), None);
+        assert_eq!(cursor.current().unwrap(), None);
+        assert_eq!(cursor.next().unwrap(), None);
+        assert_eq!(cursor.prev().unwrap(), None);
+
+        // One key, next to a delete that brings the key estimate down to 0
+        let key = B256::with_last_byte(1);
+        let account = Account { nonce: 1, balance: U256::ZERO, bytecode_hash: None };
+        let write_tx = RocksTransaction::<true>::from_db(db.clone());
+        write_tx.put::<HashedAccounts>(key, account).unwrap();
+        write_tx.delete::<HashedAccounts>(B256::with_last_byte(2), None).unwrap();
+        write_tx.commit().unwrap();
+        let cf = db.cf_handle(HashedAccounts::NAME).unwrap();
+        assert_eq!(db.property_int_value_cf(cf, "rocksdb.estimate-num-keys").unwrap(), Some(0));
+
+        let read_tx = RocksTransaction::<false>::from_db(db);
+        let mut cursor = read_tx.cursor_read::<HashedAccounts>().unwrap();
+        assert_eq!(cursor.current().unwrap(), None);
+        assert_eq!(cursor.first().unwrap(), Some((key, account)));
+        assert_eq!(cursor.current().unwrap(), Some((key, account)));
+        assert_eq!(cursor.last().unwrap(), Some((key, account)));
+        assert_eq!(cursor.current().unwrap(), Some((key, account)));
+    }
+
+    #[test]
+    fn test_cursor_reset() {
+        let (db, _temp_dir) = create_test_db();
+
+        let key = |i: u8| B256::with_last_byte(i);
+        let account = |i: u8| Account { nonce: i as u64, balance: U256::ZERO, bytecode_hash: None };
+        let write_tx = RocksTransaction::<true>::from_db(db.clone());
+        for i in 1..=5u8 {
+            write_tx.put::<HashedAccounts>(key(i), account(i)).unwrap();
+        }
+        write_tx.commit().unwrap();
+
+        let read_tx = RocksTransaction::<false>::from_db(db);
+        let mut cursor = read_tx.cursor_read::<HashedAccounts>().unwrap();
+
+        // After a full walk
+        let walked = cursor.walk(None).unwrap().collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(walked.len(), 5);
+        cursor.reset();
+        assert_eq!(cursor.current().unwrap(), None);
+        assert_eq!(cursor.next().unwrap(), Some((key(1), account(1))));
+
+        // And from the middle of the table
+        assert_eq!(cursor.seek(key(3)).unwrap(), Some((key(3), account(3))));
+        cursor.reset();
+        assert_eq!(cursor.current().unwrap(), None);
+        assert_eq!(cursor.next().unwrap(), Some((key(1), account(1))));
+        assert_eq!(cursor.next().unwrap(), Some((key(2), account(2))));
+    }
+
+    #[test]
+    fn test_seek_past_end_clears_position() {
+        let (db, _temp_dir) = create_test_db();
+
+        let key = |i: u8| B256::with_last_byte(i);
+        let account = |i: u8| Account { nonce: i as u64, balance: U256::ZERO, bytecode_hash: None };
+        let write_tx = RocksTransaction::<true>::from_db(db.clone());
+        for i in 1..=5u8 {
+            write_tx.put::<HashedAccounts>(key(i), account(i)).unwrap();
+        }
+        write_tx.commit().unwrap();
+
+        let read_tx = RocksTransaction::<false>::from_db(db);
+        let mut cursor = read_tx.cursor_read::<HashedAccounts>().unwrap();
+
+        // From the middle of the table, nothing of the previous position is left behind
+        assert_eq!(cursor.seek(key(3)).unwrap(), Some((key(3), account(3))));
+        assert_eq!(cursor.seek(key(6)).unwrap(), None);
+        assert_eq!(cursor.current().unwrap(), None);
+        assert_eq!(cursor.prev().unwrap(), Some((key(5), account(5))));
+        assert_eq!(cursor.current().unwrap(), Some((key(5), account(5))));
+        assert_eq!(cursor.prev().unwrap(), Some((key(4), account(4))));
+
+        // Likewise from the last entry itself
+        assert_eq!(cursor.last().unwrap(), Some((key(5), account(5))));
+        assert_eq!(cursor.seek(B256::repeat_byte(0xff)).unwrap(), None);
+        assert_eq!(cursor.current().unwrap(), None);
+        assert_eq!(cursor.prev().unwrap(), Some((key(5), account(5))));
+    }
+
+    #[test]
+    fn test_cursor_op_stats() {
+        const ENTRIES: u64 = 50;
+        let (db, _temp_dir) = create_test_db();
+
+        let write_tx = RocksTransaction::<true>::from_db(db.clone());
+        for i in 0..ENTRIES {
+            let account = Account { nonce: i, balance: U256::ZERO, bytecode_hash: None };
+            write_tx.put::<HashedAccounts>(B256::with_last_byte(i as u8), account).unwrap();
+        }
+        write_tx.commit().unwrap();
+
+        let read_tx = RocksTransaction::<false>::from_db(db);
+        let mut cursor = read_tx.cursor_read::<HashedAccounts>().unwrap();
+        assert_eq!(cursor.op_stats(), CursorOpStats::default());
+
+        // Every move creates its own iterator: one for `first`, then one per `next` including
+        // the one finding the end. Each `next` steps over the current entry before the next one.
+        let walked = cursor.walk(None).unwrap().collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(walked.len() as u64, ENTRIES);
+        assert_eq!(
+            cursor.op_stats(),
+            CursorOpStats { iterators_created: ENTRIES + 1, iterator_steps: 2 * ENTRIES }
+        );
+
+        // A point lookup costs a single iterator and step
+        let before = cursor.op_stats();
+        assert!(cursor.seek_exact(B256::with_last_byte(7)).unwrap().is_some());
+        let after = cursor.op_stats();
+        assert_eq!(after.iterators_created - before.iterators_created, 1);
+        assert_eq!(after.iterator_steps - before.iterator_steps, 1);
+
+        // Dup cursors report the work of their inner cursor
+        let mut dup_cursor = read_tx.cursor_dup_read::<HashedStorages>().unwrap();
+        assert!(dup_cursor.first().unwrap().is_none());
+        assert_eq!(dup_cursor.op_stats().iterators_created, 1);
+    }
+
+    /// Byte-lexicographic order reversed, so the largest key comes first
+    const REVERSE_BYTES: TableComparator =
+        TableComparator { name: "reth.ReverseBytewiseComparator", compare: |a, b| b.cmp(a) };
+
+    /// Scratch table sorted by [`REVERSE_BYTES`]
+    #[derive(Debug)]
+    struct ScratchReverseTable;
+
+    impl Table for ScratchReverseTable {
+        const NAME: &'static str = "scratch_reverse";
+        const DUPSORT: bool = false;
+
+        type Key = B256;
+        type Value = Account;
+    }
+
+    impl TableConfig for ScratchReverseTable {
+        fn comparator() -> Option<TableComparator> {
+            Some(REVERSE_BYTES)
+        }
+    }
+
+    #[test]
+    fn test_custom_comparator_orders_table() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut opts = Options::default();
+        opts.create_if_missing(true);
+        opts.create_missing_column_families(true);
+
+        let db = DB::open_cf_descriptors(
+            &opts,
+            temp_dir.path(),
+            vec![ScratchReverseTable::descriptor()],
+        )
+        .unwrap();
+        let db = Arc::new(db);
+
+        let account = |nonce: u64| Account { nonce, balance: U256::ZERO, bytecode_hash: None };
+        let write_tx = RocksTransaction::<true>::from_db(db.clone());
+        for i in [3u8, 9, 1, 7, 5] {
+            write_tx
+                .put::<ScratchReverseTable>(B256::with_last_byte(i), account(i as u64))
+                .unwrap();
+        }
+        write_tx.commit().unwrap();
+
+        // The largest key comes first and iteration runs in descending order
+        let read_tx = RocksTransaction::<false>::from_db(db.clone());
+        let mut cursor = read_tx.cursor_read::<ScratchReverseTable>().unwrap();
+        let mut keys = Vec::new();
+        let mut entry = cursor.first().unwrap();
+        while let Some((key, _)) = entry {
+            keys.push(key);
+            entry = cursor.next().unwrap();
+        }
+        let expected = [9u8, 7, 5, 3, 1].map(B256::with_last_byte);
+        assert_eq!(keys, expected);
+        assert_eq!(cursor.last().unwrap().map(|(key, _)| key), Some(B256::with_last_byte(1)));
+        assert_eq!(
+            read_tx.get::<ScratchReverseTable>(B256::with_last_byte(7)).unwrap(),
+            Some(account(7))
+        );
+        drop(cursor);
+        drop(read_tx);
+        drop(db);
+
+        // The comparator is checked on open, reopening with the default one fails
+        let default_cf = ColumnFamilyDescriptor::new(ScratchReverseTable::NAME, Options::default());
+        assert!(DB::open_cf_descriptors(&opts, temp_dir.path(), vec![default_cf]).is_err());
+    }
 }
@@ -1,22 +1,36 @@
 #[cfg(test)]
 mod rocks_db_ops_test {
-    use crate::test::utils::{create_test_branch_node, create_test_db};
+    use crate::implementation::rocks::dupsort::DupSortHelper;
+    use crate::test::utils::{
+        build_test_db, create_test_branch_node, create_test_db, create_test_rocksdb_with_tables,
+    };
     use crate::{
-        calculate_state_root, calculate_state_root_with_updates,
-        tables::trie::{AccountTrieTable, StorageTrieTable, TrieNibbles, TrieNodeValue},
-        Account, HashedPostState, RocksTransaction,
+        account_trie_key, calculate_state_root, calculate_state_root_with_updates, hashed_address,
+        hashed_slot,
+        tables::hashed::{AccountAtBlock, HashedAccountHistory},
+        tables::trie::{
+            AccountTrieTable, StorageTrieTable, StoredTrieNode, TrieNibbles, TrieNodeValue,
+            TrieTable,
+        },
+        Account, HashedPostState, RocksDBConfig, RocksDBError, RocksTransaction,
     };
-    use alloy_primitives::{keccak256, Address, B256, U256};
+    use alloy_primitives::{keccak256, Address, Bytes, B256, U256};
     use reth_db::transaction::{DbTx, DbTxMut};
+    use reth_db::{Bytecodes, HashedAccounts, HashedStorages};
     use reth_db_api::cursor::{DbCursorRO, DbDupCursorRO, DbDupCursorRW};
+    use reth_db_api::table::{Compress, Decode, Decompress, DupSort, Encode, Table};
+    use reth_db_api::{DatabaseError, DatabaseWriteOperation};
+    use reth_primitives::StorageEntry;
+    use reth_primitives_traits::Bytecode;
     use reth_trie::{BranchNodeCompact, Nibbles, StoredNibbles, TrieMask};
+    use std::collections::HashMap;
 
     #[test]
     fn test_put_get_account_trie_node() {
         let (db, _temp_dir) = create_test_db();
 
         // Creating a Writable txn <WRITE: true>
-        let tx = RocksTransaction::<true>::new(db.clone(), true);
+        let tx = RocksTransaction::<true>::from_db(db.clone());
 
         // Creating dummy nibbles (key)
         let nibbles = Nibbles::from_nibbles(&[1, 2, 3, 4, 5, 6, 7, 8, 9, 10]);
@@ -32,7 +46,7 @@ mod rocks_db_ops_test {
         tx.commit().unwrap();
 
         // Creating a Read txn <WRITE: false>
-        let read_tx = RocksTransaction::<false>::new(db.clone(), false);
+        let read_tx = RocksTransaction::<false>::from_db(db.clone());
 
         // Getting the value from the db
         let stored_val = read_tx.get::<AccountTrieTable>(key.clone()).unwrap();
@@ -47,7 +61,7 @@ mod rocks_db_ops_test {
         let (db, _temp_dir) = create_test_db();
 
         // Create a writable txn
-        let tx = RocksTransaction::<true>::new(db.clone(), true);
+        let tx = RocksTransaction::<true>::from_db(db.clone());
 
         // Creating test account and hash it
         let address = Address::from([1; 20]);
@@ -73,7 +87,7 @@ mod rocks_db_ops_test {
         tx.commit().unwrap();
 
         // Create a read transaction
-        let read_tx = RocksTransaction::<false>::new(db, false);
+        let read_tx = RocksTransaction::<false>::from_db(db);
 
         // Try to get the value back
         let mut read_cursor = read_tx.cursor_dup_read::<StorageTrieTable>().unwrap();
@@ -92,7 +106,7 @@ mod rocks_db_ops_test {
         let (db, _temp_dir) = create_test_db();
 
         // Creating a Writable txn <WRITE: true>
-        let tx = RocksTransaction::<true>::new(db.clone(), true);
+        let tx = RocksTransaction::<true>::from_db(db.clone());
 
         // Insert multiple account trie nodes
         let mut keys = Vec::new();
@@ -113,7 +127,7 @@ mod rocks_db_ops_test {
         tx.commit().unwrap();
 
         // Creating a read txn
-        let read_tx = RocksTransaction::<false>::new(db.clone(), false);
+        let read_tx = RocksTransaction::<false>::from_db(db.clone());
 
         // Test cursor navigation
         let mut cursor = read_tx.cursor_read::<AccountTrieTable>().unwrap();
@@ -149,7 +163,7 @@ mod rocks_db_ops_test {
         let (db, _temp_dir) = create_test_db();
 
         // Create writable txn
-        let tx = RocksTransaction::<true>::new(db.clone(), true);
+        let tx = RocksTransaction::<true>::from_db(db.clone());
 
         // Creating test key and vals
         let nibbles = Nibbles::from_nibbles(&[1, 2, 3, 4]);
@@ -161,17 +175,17 @@ mod rocks_db_ops_test {
         tx.commit().unwrap();
 
         // Verify if it is there
-        let read_tx = RocksTransaction::<false>::new(db.clone(), false);
+        let read_tx = RocksTransaction::<false>::from_db(db.clone());
         assert!(read_tx.get::<AccountTrieTable>(key.clone()).unwrap().is_some());
         assert_eq!(read_tx.get::<AccountTrieTable>(key.clone()).unwrap().unwrap(), val);
 
         // Delete the k-v pair
-        let delete_tx = RocksTransaction::<true>::new(db.clone(), true);
+        let delete_tx = RocksTransaction::<true>::from_db(db.clone());
         delete_tx.delete::<AccountTrieTable>(key.clone(), None).unwrap();
         delete_tx.commit().unwrap();
 
         // Verify if it's gone
-        let verify_tx = RocksTransaction::<false>::new(db.clone(), false);
+        let verify_tx = RocksTransaction::<false>::from_db(db.clone());
         assert!(verify_tx.get::<AccountTrieTable>(key).unwrap().is_none());
     }
 
@@ -180,7 +194,7 @@ mod rocks_db_ops_test {
         let (db, _temp_dir) = create_test_db();
 
         // Create writable tx
-        let tx = RocksTransaction::<true>::new(db.clone(), true);
+        let tx = RocksTransaction::<true>::from_db(db.clone());
 
         // Create a key
         let nibbles = Nibbles::from_nibbles(&[1, 2, 3, 4, 5, 6]);
@@ -198,7 +212,7 @@ mod rocks_db_ops_test {
         tx.commit().unwrap();
 
         // Verify we can retrieve it
-        let read_tx = RocksTransaction::<false>::new(db.clone(), false);
+        let read_tx = RocksTransaction::<false>::from_db(db.clone());
         let result = read_tx.get::<AccountTrieTable>(key).unwrap();
         assert!(result.is_some());
         assert_eq!(result.unwrap(), empty_val);
@@ -209,7 +223,7 @@ mod rocks_db_ops_test {
         let (db, _temp_dir) = create_test_db();
 
         // Create a writable transaction
-        let tx = RocksTransaction::<true>::new(db.clone(), true);
+        let tx = RocksTransaction::<true>::from_db(db.clone());
 
         // Create test key and value
         let nibbles = Nibbles::from_nibbles(&[9, 8, 7, 6, 5]);
@@ -223,7 +237,7 @@ mod rocks_db_ops_test {
         tx.abort();
 
         // Verify the data was not persisted
-        let read_tx = RocksTransaction::<false>::new(db.clone(), false);
+        let read_tx = RocksTransaction::<false>::from_db(db.clone());
         assert!(read_tx.get::<AccountTrieTable>(key.clone()).unwrap().is_none());
     }
 
@@ -232,7 +246,7 @@ mod rocks_db_ops_test {
         let (db, _temp_dir) = create_test_db();
 
         // Create a writable transaction
-        let tx = RocksTransaction::<true>::new(db.clone(), true);
+        let tx = RocksTransaction::<true>::from_db(db.clone());
 
         // Create a large key (many nibbles)
         let mut nibble_vec = Vec::new();
@@ -266,7 +280,7 @@ mod rocks_db_ops_test {
         tx.commit().unwrap();
 
         // Verify we can retrieve it correctly
-        let read_tx = RocksTransaction::<false>::new(db.clone(), false);
+        let read_tx = RocksTransaction::<false>::from_db(db.clone());
         let result = read_tx.get::<AccountTrieTable>(large_key).unwrap();
         assert!(result.is_some());
         assert_eq!(result.unwrap(), large_node);
@@ -277,7 +291,7 @@ mod rocks_db_ops_test {
         let (db, _temp_dir) = create_test_db();
 
         // Create initial transaction
-        let tx1 = RocksTransaction::<true>::new(db.clone(), true);
+        let tx1 = RocksTransaction::<true>::from_db(db.clone());
 
         // Create test key
         let nibbles = Nibbles::from_nibbles(&[1, 3, 5, 7, 9]);
@@ -291,7 +305,7 @@ mod rocks_db_ops_test {
         tx1.commit().unwrap();
 
         // Create second transaction to update the value
-        let tx2 = RocksTransaction::<true>::new(db.clone(), true);
+        let tx2 = RocksTransaction::<true>::from_db(db.clone());
 
         // Create new value with different root hash
         let state_mask = TrieMask::new(0);
@@ -308,7 +322,7 @@ mod rocks_db_ops_test {
         tx2.commit().unwrap();
 
         // Verify the value was updated
-        let read_tx = RocksTransaction::<false>::new(db.clone(), false);
+        let read_tx = RocksTransaction::<false>::from_db(db.clone());
         let result = read_tx.get::<AccountTrieTable>(key).unwrap();
         assert!(result.is_some());
 
@@ -379,12 +393,12 @@ mod rocks_db_ops_test {
             println!("Prefix Sets: {:?}", prefix_sets);
 
             // Create transactions for reading and writing
-            let read_tx = RocksTransaction::<false>::new(db.clone(), false);
-            let write_tx = RocksTransaction::<true>::new(db.clone(), true);
+            let read_tx = RocksTransaction::<false>::from_db(db.clone());
+            let write_tx = RocksTransaction::<true>::from_db(db.clone());
 
             // Calculate state root and store nodes
             _initial_root =
-                calculate_state_root_with_updates(&read_tx, &write_tx, post_state).unwrap();
+                calculate_state_root_with_updates(&read_tx, &write_tx, post_state, None).unwrap();
 
             // Manually insert a test node to verify DB writes are working
             let test_nibbles = Nibbles::from_nibbles_unchecked(vec![0, 1, 2, 3]);
@@ -405,7 +419,7 @@ mod rocks_db_ops_test {
             println!("Running sub-test: Verify initial node storage");
 
             // Verify that nodes were stored by checking if we can retrieve them
-            let verify_tx = RocksTransaction::<false>::new(db.clone(), false);
+            let verify_tx = RocksTransaction::<false>::from_db(db.clone());
 
             // Check if we can read from AccountTrieTable
             let mut cursor = verify_tx.cursor_read::<AccountTrieTable>().unwrap();
@@ -442,14 +456,15 @@ mod rocks_db_ops_test {
             updated_post_state.storages.insert(hashed_address, updated_storage);
 
             // Create new transactions
-            let read_tx2 = RocksTransaction::<false>::new(db.clone(), false);
-            let write_tx2 = RocksTransaction::<true>::new(db.clone(), true);
+            let read_tx2 = RocksTransaction::<false>::from_db(db.clone());
+            let write_tx2 = RocksTransaction::<true>::from_db(db.clone());
 
             // Calculate new state root and store updated nodes
             _updated_root = calculate_state_root_with_updates(
                 &read_tx2,
                 &write_tx2,
                 updated_post_state.clone(),
+                None,
             )
             .unwrap();
 
@@ -465,7 +480,7 @@ mod rocks_db_ops_test {
             println!("Running sub-test: Verify updated node storage");
 
             // Verify that we can still read entries
-            let verify_tx2 = RocksTransaction::<false>::new(db.clone(), false);
+            let verify_tx2 = RocksTransaction::<false>::from_db(db.clone());
             let mut cursor2 = verify_tx2.cursor_read::<AccountTrieTable>().unwrap();
             let mut updated_entries = 0;
             let mut first_entry2 = cursor2.first().unwrap();
@@ -500,7 +515,7 @@ mod rocks_db_ops_test {
             verification_state.storages.insert(hashed_address, storage);
 
             // Calculate the root again with a fresh transaction
-            let read_tx3 = RocksTransaction::<false>::new(db.clone(), false);
+            let read_tx3 = RocksTransaction::<false>::from_db(db.clone());
             let recomputed_root = calculate_state_root(&read_tx3, verification_state).unwrap();
 
             assert_eq!(
@@ -509,4 +524,1106 @@ mod rocks_db_ops_test {
             );
         }
     }
+
+    #[test]
+    fn test_stored_trie_node_validation() {
+        let (db, _temp_dir) = create_test_db();
+
+        // A well-formed node round-trips through the typed path
+        let node = create_test_branch_node();
+        let stored = StoredTrieNode::from(&node);
+        let hash = stored.hash();
+
+        let tx = RocksTransaction::<true>::from_db(db.clone());
+        tx.put::<TrieTable>(hash, stored.clone()).unwrap();
+        tx.commit().unwrap();
+
+        let read_tx = RocksTransaction::<false>::from_db(db.clone());
+        let fetched = read_tx.get_node(hash).unwrap().unwrap();
+        assert_eq!(fetched, stored);
+        assert_eq!(fetched.to_branch_node(), node);
+
+        // A malformed node written via the raw path is rejected on typed read
+        let bad_hash = B256::from([0xab; 32]);
+        let cf = db.cf_handle(TrieTable::NAME).unwrap();
+        db.put_cf(cf, bad_hash.as_slice(), [0u8, 1, 2, 3]).unwrap();
+
+        assert!(read_tx.get_node(bad_hash).is_err());
+    }
+
+    /// Encode a node header by hand, bypassing the mask checks of [`BranchNodeCompact::new`]
+    fn raw_node(state_mask: u16, tree_mask: u16, hash_mask: u16, hash_count: u8) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&state_mask.to_be_bytes());
+        bytes.extend_from_slice(&tree_mask.to_be_bytes());
+        bytes.extend_from_slice(&hash_mask.to_be_bytes());
+        bytes.push(hash_count);
+        for i in 0..hash_count {
+            bytes.extend_from_slice(&[i; 32]);
+        }
+        bytes.push(0);
+        bytes
+    }
+
+    #[test]
+    fn test_stored_trie_node_rejects_hash_count_mismatch() {
+        // Two children flagged in the hash mask, two hashes
+        assert!(StoredTrieNode::from_bytes(raw_node(0b11, 0, 0b11, 2)).is_ok());
+
+        // Two children flagged in the hash mask, but only one hash
+        assert!(matches!(
+            StoredTrieNode::from_bytes(raw_node(0b11, 0, 0b11, 1)),
+            Err(DatabaseError::Decode)
+        ));
+        // One child flagged in the hash mask, but two hashes
+        assert!(matches!(
+            StoredTrieNode::from_bytes(raw_node(0b11, 0, 0b01, 2)),
+            Err(DatabaseError::Decode)
+        ));
+    }
+
+    #[test]
+    fn test_stored_trie_node_rejects_masks_outside_state_mask() {
+        // Tree and hash masks within the state mask
+        assert!(StoredTrieNode::from_bytes(raw_node(0b111, 0b001, 0b110, 2)).is_ok());
+
+        // Tree mask flags a child the state mask doesn't have
+        assert!(matches!(
+            StoredTrieNode::from_bytes(raw_node(0b011, 0b100, 0, 0)),
+            Err(DatabaseError::Decode)
+        ));
+        // Hash mask flags a child the state mask doesn't have
+        assert!(matches!(
+            StoredTrieNode::from_bytes(raw_node(0b011, 0, 0b100, 1)),
+            Err(DatabaseError::Decode)
+        ));
+    }
+
+    #[test]
+    fn test_get_node_required() {
+        let (db, _temp_dir) = create_test_db();
+
+        let stored = StoredTrieNode::from(&create_test_branch_node());
+        let hash = stored.hash();
+
+        let tx = RocksTransaction::<true>::from_db(db.clone());
+        tx.put::<TrieTable>(hash, stored.clone()).unwrap();
+        tx.commit().unwrap();
+
+        let read_tx = RocksTransaction::<false>::from_db(db.clone());
+        assert_eq!(read_tx.get_node_required(hash).unwrap(), stored);
+
+        // An absent node is reported with its hash
+        let missing_hash = B256::from([0xcd; 32]);
+        assert_eq!(read_tx.get_node(missing_hash).unwrap(), None);
+        assert!(matches!(
+            read_tx.get_node_required(missing_hash),
+            Err(RocksDBError::MissingNode(hash)) if hash == missing_hash
+        ));
+    }
+
+    #[test]
+    fn test_all_dup_values() {
+        let (db, _temp_dir) = create_test_db();
+
+        let address_hash = keccak256(Address::from([1; 20]));
+        let other_hash = keccak256(Address::from([2; 20]));
+
+        // Insert storage nodes out of subkey order, plus one under another account
+        let subkeys = [vec![3, 1], vec![1, 2, 3], vec![2], vec![1, 2]];
+        let tx = RocksTransaction::<true>::from_db(db.clone());
+        let mut cursor = tx.cursor_dup_write::<StorageTrieTable>().unwrap();
+        for (i, nibbles) in subkeys.iter().enumerate() {
+            let subkey = StoredNibbles(Nibbles::from_nibbles(nibbles));
+            let value = TrieNodeValue { nibbles: subkey.clone(), node: B256::from([i as u8; 32]) };
+            cursor.upsert_dup(address_hash, subkey, &value).unwrap();
+        }
+        let other_subkey = StoredNibbles(Nibbles::from_nibbles([0]));
+        let other_value = TrieNodeValue { nibbles: other_subkey.clone(), node: B256::ZERO };
+        cursor.upsert_dup(other_hash, other_subkey, &other_value).unwrap();
+        drop(cursor);
+        tx.commit().unwrap();
+
+        let read_tx = RocksTransaction::<false>::from_db(db);
+        let mut cursor = read_tx.cursor_dup_read::<StorageTrieTable>().unwrap();
+
+        let values = cursor.all_dup_values(address_hash).unwrap();
+        let nibbles: Vec<Vec<u8>> = values.iter().map(|v| v.nibbles.0.to_vec()).collect();
+        assert_eq!(nibbles, vec![vec![1, 2], vec![1, 2, 3], vec![2], vec![3, 1]]);
+
+        // Absent key yields nothing
+        assert!(cursor.all_dup_values(B256::from([9; 32])).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_dup_range() {
+        let (db, _temp_dir) = create_test_db();
+
+        let address_hash = keccak256(Address::from([1; 20]));
+        let other_hash = keccak256(Address::from([2; 20]));
+        let slot = |i: u8| B256::with_last_byte(i);
+        let entry = |i: u8| StorageEntry { key: slot(i), value: U256::from(i) };
+
+        let tx = RocksTransaction::<true>::from_db(db.clone());
+        let mut cursor = tx.cursor_dup_write::<HashedStorages>().unwrap();
+        for i in 1..10u8 {
+            cursor.upsert_dup(address_hash, slot(i), &entry(i)).unwrap();
+        }
+        // Slots in the range under another account stay out of it
+        cursor.upsert_dup(other_hash, slot(5), &entry(50)).unwrap();
+        drop(cursor);
+        tx.commit().unwrap();
+
+        let read_tx = RocksTransaction::<false>::from_db(db);
+        let mut cursor = read_tx.cursor_dup_read::<HashedStorages>().unwrap();
+
+        let values = cursor.dup_range(address_hash, slot(3), slot(7)).unwrap();
+        assert_eq!(values, (3..7).map(entry).collect::<Vec<_>>());
+
+        // Ranges running past the group end there, empty ranges yield nothing
+        let values = cursor.dup_range(address_hash, slot(8), slot(20)).unwrap();
+        assert_eq!(values, vec![entry(8), entry(9)]);
+        assert!(cursor.dup_range(address_hash, slot(7), slot(7)).unwrap().is_empty());
+        assert!(cursor.dup_range(B256::from([9; 32]), slot(0), slot(10)).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_seek_exact_then_next_dup_walks_group() {
+        let (db, _temp_dir) = create_test_db();
+
+        let address_hash = keccak256(Address::from([1; 20]));
+        let other_hash = keccak256(Address::from([2; 20]));
+
+        // Insert storage nodes out of subkey order, plus one under another account
+        let subkeys = [vec![3, 1], vec![1, 2, 3], vec![2], vec![1, 2], vec![0]];
+        let tx = RocksTransaction::<true>::from_db(db.clone());
+        let mut cursor = tx.cursor_dup_write::<StorageTrieTable>().unwrap();
+        for (i, nibbles) in subkeys.iter().enumerate() {
+            let subkey = StoredNibbles(Nibbles::from_nibbles(nibbles));
+            let value = TrieNodeValue { nibbles: subkey.clone(), node: B256::from([i as u8; 32]) };
+            cursor.upsert_dup(address_hash, subkey, &value).unwrap();
+        }
+        let other_subkey = StoredNibbles(Nibbles::from_nibbles([0]));
+        let other_value = TrieNodeValue { nibbles: other_subkey.clone(), node: B256::ZERO };
+        cursor.upsert_dup(other_hash, other_subkey, &other_value).unwrap();
+        drop(cursor);
+        tx.commit().unwrap();
+
+        let read_tx = RocksTransaction::<false>::from_db(db);
+        let mut cursor = read_tx.cursor_dup_read::<StorageTrieTable>().unwrap();
+
+        let (key, first) = cursor.seek_exact(address_hash).unwrap().unwrap();
+        assert_eq!(key, address_hash);
+
+        let mut nibbles = vec![first.nibbles.0.to_vec()];
+        while let Some((key, value)) = cursor.next_dup().unwrap() {
+            assert_eq!(key, address_hash);
+            nibbles.push(value.nibbles.0.to_vec());
+        }
+
+        assert_eq!(nibbles, vec![vec![0], vec![1, 2], vec![1, 2, 3], vec![2], vec![3, 1]]);
+    }
+
+    #[test]
+    fn test_composite_key_bytes() {
+        let (db, _temp_dir) = create_test_db();
+
+        let key = B256::from([0x11; 32]);
+        let subkey = StoredNibbles(Nibbles::from_nibbles([1, 2, 3]));
+
+        let bytes = DupSortHelper::composite_key_bytes::<StorageTrieTable>(&key, &subkey).unwrap();
+        let mut expected = vec![0x11; 32];
+        expected.push(0xFF);
+        expected.extend_from_slice(&[1, 2, 3]);
+        assert_eq!(bytes, expected);
+
+        // Composite keys sort by subkey within the same key
+        let shorter = StoredNibbles(Nibbles::from_nibbles([1, 2]));
+        let greater = StoredNibbles(Nibbles::from_nibbles([1, 3]));
+        assert!(
+            DupSortHelper::composite_key_bytes::<StorageTrieTable>(&key, &shorter).unwrap() < bytes
+        );
+        assert!(
+            bytes < DupSortHelper::composite_key_bytes::<StorageTrieTable>(&key, &greater).unwrap()
+        );
+
+        // They are exactly what gets stored
+        let value = TrieNodeValue { nibbles: subkey.clone(), node: B256::ZERO };
+        let tx = RocksTransaction::<true>::from_db(db.clone());
+        tx.cursor_dup_write::<StorageTrieTable>().unwrap().upsert_dup(key, subkey, &value).unwrap();
+        tx.commit().unwrap();
+
+        let cf = db.cf_handle(StorageTrieTable::NAME).unwrap();
+        let (stored_key, _) =
+            db.iterator_cf(cf, rocksdb::IteratorMode::Start).next().unwrap().unwrap();
+        assert_eq!(stored_key.to_vec(), expected);
+    }
+
+    /// Key whose codec is deliberately asymmetric: big-endian on encode, little-endian on decode
+    #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+    struct AsymmetricKey(u64);
+
+    impl Encode for AsymmetricKey {
+        type Encoded = [u8; 8];
+
+        fn encode(self) -> Self::Encoded {
+            self.0.to_be_bytes()
+        }
+    }
+
+    impl Decode for AsymmetricKey {
+        fn decode(value: &[u8]) -> Result<Self, DatabaseError> {
+            Ok(Self(u64::from_le_bytes(value.try_into().map_err(|_| DatabaseError::Decode)?)))
+        }
+    }
+
+    impl serde::Serialize for AsymmetricKey {
+        fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.serialize_u64(self.0)
+        }
+    }
+
+    impl<'de> serde::Deserialize<'de> for AsymmetricKey {
+        fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            <u64 as serde::Deserialize<'de>>::deserialize(deserializer).map(Self)
+        }
+    }
+
+    /// Test-only table in its own scratch column family, keyed by [`AsymmetricKey`]
+    #[derive(Debug)]
+    struct AsymmetricTable;
+
+    impl Table for AsymmetricTable {
+        const NAME: &'static str = "scratch_asymmetric";
+        const DUPSORT: bool = false;
+
+        type Key = AsymmetricKey;
+        type Value = StoredTrieNode;
+    }
+
+    #[test]
+    fn test_key_round_trip_check_accepts_symmetric_codec() {
+        let (db, _temp_dir) = create_test_db();
+
+        let stored = StoredTrieNode::from(&create_test_branch_node());
+        let tx = RocksTransaction::<true>::from_db(db.clone());
+        tx.put::<TrieTable>(stored.hash(), stored.clone()).unwrap();
+        tx.put::<AccountTrieTable>(
+            TrieNibbles(Nibbles::from_nibbles([1, 2, 3])),
+            create_test_branch_node(),
+        )
+        .unwrap();
+        tx.commit().unwrap();
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic(expected = "key doesn't round-trip through its codec")]
+    fn test_key_round_trip_check_trips_on_asymmetric_codec() {
+        let (db, _temp_dir) =
+            create_test_rocksdb_with_tables(RocksDBConfig::default(), &[AsymmetricTable::NAME]);
+
+        let stored = StoredTrieNode::from(&create_test_branch_node());
+        let tx = RocksTransaction::<true>::from_db(db.inner());
+        let _ = tx.put::<AsymmetricTable>(AsymmetricKey(1), stored);
+    }
+
+    /// Test-only DUPSORT table in its own scratch column family, keyed by 20-byte addresses
+    /// instead of the 32 bytes DUPSORT tables are prefixed by
+    #[derive(Debug)]
+    struct ShortKeyDupTable;
+
+    impl Table for ShortKeyDupTable {
+        const NAME: &'static str = "scratch_short_key_dup";
+        const DUPSORT: bool = true;
+
+        type Key = Address;
+        type Value = TrieNodeValue;
+    }
+
+    impl DupSort for ShortKeyDupTable {
+        type SubKey = StoredNibbles;
+    }
+
+    #[test]
+    fn test_dupsort_prefix_len_check_accepts_32_byte_keys() {
+        let (db, _temp_dir) = create_test_db();
+
+        let value = TrieNodeValue {
+            nibbles: StoredNibbles(Nibbles::from_nibbles([1, 2])),
+            node: B256::repeat_byte(0xaa),
+        };
+        let tx = RocksTransaction::<true>::from_db(db.clone());
+        tx.put::<StorageTrieTable>(B256::repeat_byte(1), value.clone()).unwrap();
+        let mut cursor = tx.cursor_dup_write::<StorageTrieTable>().unwrap();
+        cursor.upsert_dup(B256::repeat_byte(2), value.nibbles.clone(), &value).unwrap();
+        assert!(cursor.seek_by_key_subkey(B256::repeat_byte(2), value.nibbles.clone()).is_ok());
+        tx.commit().unwrap();
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic(expected = "key encodes to 20 bytes, but the DUPSORT prefix length is 32")]
+    fn test_dupsort_prefix_len_check_trips_on_put() {
+        let (db, _temp_dir) =
+            create_test_rocksdb_with_tables(RocksDBConfig::default(), &[ShortKeyDupTable::NAME]);
+
+        let value = TrieNodeValue {
+            nibbles: StoredNibbles(Nibbles::from_nibbles([1, 2])),
+            node: B256::repeat_byte(0xaa),
+        };
+        let tx = RocksTransaction::<true>::from_db(db.inner());
+        let _ = tx.put::<ShortKeyDupTable>(Address::repeat_byte(1), value);
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic(expected = "key encodes to 20 bytes, but the DUPSORT prefix length is 32")]
+    fn test_dupsort_prefix_len_check_trips_on_cursor() {
+        let (db, _temp_dir) =
+            create_test_rocksdb_with_tables(RocksDBConfig::default(), &[ShortKeyDupTable::NAME]);
+
+        let tx = RocksTransaction::<false>::from_db(db.inner());
+        let mut cursor = tx.cursor_dup_read::<ShortKeyDupTable>().unwrap();
+        let _ = cursor
+            .seek_by_key_subkey(Address::repeat_byte(1), StoredNibbles(Nibbles::from_nibbles([1])));
+    }
+
+    /// Key encoded big-endian over `width` bytes, so the same value has several encodings.
+    /// Equality and order only consider the value.
+    #[derive(Debug, Clone)]
+    struct PaddedKey {
+        value: u64,
+        width: usize,
+    }
+
+    impl PartialEq for PaddedKey {
+        fn eq(&self, other: &Self) -> bool {
+            self.value == other.value
+        }
+    }
+
+    impl Eq for PaddedKey {}
+
+    impl PartialOrd for PaddedKey {
+        fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+            Some(self.cmp(other))
+        }
+    }
+
+    impl Ord for PaddedKey {
+        fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+            self.value.cmp(&other.value)
+        }
+    }
+
+    impl Encode for PaddedKey {
+        type Encoded = Vec<u8>;
+
+        fn encode(self) -> Self::Encoded {
+            self.value.to_be_bytes()[8 - self.width..].to_vec()
+        }
+    }
+
+    impl Decode for PaddedKey {
+        fn decode(value: &[u8]) -> Result<Self, DatabaseError> {
+            if value.len() > 8 {
+                return Err(DatabaseError::Decode);
+            }
+            let mut bytes = [0u8; 8];
+            bytes[8 - value.len()..].copy_from_slice(value);
+            Ok(Self { value: u64::from_be_bytes(bytes), width: value.len() })
+        }
+    }
+
+    impl serde::Serialize for PaddedKey {
+        fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.serialize_u64(self.value)
+        }
+    }
+
+    impl<'de> serde::Deserialize<'de> for PaddedKey {
+        fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            <u64 as serde::Deserialize<'de>>::deserialize(deserializer)
+                .map(|value| Self { value, width: 8 })
+        }
+    }
+
+    /// Test-only table in its own scratch column family, keyed by [`PaddedKey`]
+    #[derive(Debug)]
+    struct PaddedTable;
+
+    impl Table for PaddedTable {
+        const NAME: &'static str = "scratch_padded";
+        const DUPSORT: bool = false;
+
+        type Key = PaddedKey;
+        type Value = StoredTrieNode;
+    }
+
+    #[test]
+    fn test_find_logical_duplicates() {
+        let (db, _temp_dir) =
+            create_test_rocksdb_with_tables(RocksDBConfig::default(), &[PaddedTable::NAME]);
+        let db = db.inner();
+
+        let stored = StoredTrieNode::from(&create_test_branch_node());
+        let tx = RocksTransaction::<true>::from_db(db.clone());
+        for (value, width) in [(1, 8), (1, 4), (1, 2), (2, 8), (3, 8), (3, 1)] {
+            tx.put::<PaddedTable>(PaddedKey { value, width }, stored.clone()).unwrap();
+        }
+        for i in 0..10u8 {
+            let account = Account { nonce: i as u64, ..Default::default() };
+            tx.put::<HashedAccounts>(B256::with_last_byte(i), account).unwrap();
+        }
+        tx.commit().unwrap();
+
+        let read_tx = RocksTransaction::<false>::from_db(db);
+        let duplicates = read_tx.find_logical_duplicates::<PaddedTable>().unwrap();
+        assert_eq!(duplicates.iter().map(|key| key.value).collect::<Vec<_>>(), vec![1, 3]);
+
+        // Canonically encoded tables have none
+        assert!(read_tx.find_logical_duplicates::<HashedAccounts>().unwrap().is_empty());
+
+        // Duplicates are expected in DUPSORT tables
+        assert!(read_tx.find_logical_duplicates::<HashedStorages>().is_err());
+    }
+
+    #[test]
+    fn test_trie_nibbles_packed_round_trip() {
+        let paths: [&[u8]; 6] =
+            [&[], &[0x7], &[0x1, 0x2], &[0x0, 0x0, 0x0], &[0xf, 0x0, 0xa, 0x5], &[0x3; 65]];
+        for path in paths {
+            let nibbles = TrieNibbles(Nibbles::from_nibbles(path));
+            let packed = nibbles.encode_packed();
+            assert_eq!(TrieNibbles::decode_packed(&packed).unwrap(), nibbles, "{path:?}");
+
+            // One prefix byte, which also holds the first nibble of an odd length
+            let unpacked = nibbles.clone().encode();
+            assert_eq!(unpacked.len(), path.len());
+            assert_eq!(packed.len(), path.len() / 2 + 1);
+        }
+
+        // An odd length keeps its first nibble in the prefix byte rather than padding the end,
+        // so a trailing 0 nibble isn't confused with padding
+        let odd = TrieNibbles(Nibbles::from_nibbles([0x1, 0x2, 0x0])).encode_packed();
+        let even = TrieNibbles(Nibbles::from_nibbles([0x1, 0x2])).encode_packed();
+        assert_eq!(odd, vec![0x11, 0x20]);
+        assert_eq!(even, vec![0x00, 0x12]);
+
+        // Packed keys don't sort in nibble order
+        let one = TrieNibbles(Nibbles::from_nibbles([0x1]));
+        let two_zero = TrieNibbles(Nibbles::from_nibbles([0x2, 0x0]));
+        assert!(one < two_zero);
+        assert!(one.encode_packed() > two_zero.encode_packed());
+
+        // Malformed prefix bytes are rejected
+        assert!(TrieNibbles::decode_packed(&[]).is_err());
+        assert!(TrieNibbles::decode_packed(&[0x01, 0x12]).is_err());
+        assert!(TrieNibbles::decode_packed(&[0x20]).is_err());
+    }
+
+    #[test]
+    fn test_trie_node_value_round_trip() {
+        let paths: [&[u8]; 4] = [&[], &[0x5], &[0x1, 0x2, 0x3, 0x4, 0x5, 0x6], &[0xf; 64]];
+        for path in paths {
+            let value = TrieNodeValue {
+                nibbles: StoredNibbles(Nibbles::from_nibbles(path)),
+                node: keccak256(path),
+            };
+            let compressed = value.clone().compress();
+            assert_eq!(compressed.len(), 1 + path.len() + 32);
+            assert_eq!(compressed[0] as usize, path.len());
+            assert_eq!(TrieNodeValue::decompress(&compressed).unwrap(), value);
+            assert_eq!(TrieNodeValue::decode(&value.clone().encode()).unwrap(), value);
+
+            // Values whose lengths don't add up are rejected rather than split elsewhere
+            for len in 0..compressed.len() {
+                assert!(matches!(
+                    TrieNodeValue::decompress(&compressed[..len]),
+                    Err(DatabaseError::Decode)
+                ));
+            }
+            let padded = [&compressed[..], &[0]].concat();
+            assert!(matches!(TrieNodeValue::decompress(&padded), Err(DatabaseError::Decode)));
+        }
+
+        // A nibble out of range is rejected
+        let mut invalid = TrieNodeValue {
+            nibbles: StoredNibbles(Nibbles::from_nibbles([0x1, 0x2])),
+            node: B256::ZERO,
+        }
+        .compress();
+        invalid[1] = 0x10;
+        assert!(matches!(TrieNodeValue::decompress(&invalid), Err(DatabaseError::Decode)));
+    }
+
+    #[test]
+    fn test_storage_values_stored_trimmed() {
+        let (db, _temp_dir) = create_test_db();
+        let address = B256::from([1; 32]);
+        let values =
+            [U256::ZERO, U256::from(1), U256::from(0x1234), U256::from(u64::MAX), U256::MAX];
+
+        // `HashedStorages` values are stored through reth's codec, which writes the slot followed
+        // by the value without its leading zeros
+        let stored_len = |value: U256| StorageEntry { key: B256::ZERO, value }.compress().len();
+        assert_eq!(stored_len(U256::ZERO), 32);
+        assert_eq!(stored_len(U256::from(1)), 33);
+        assert_eq!(stored_len(U256::from(0x1234)), 34);
+        assert!(stored_len(U256::from(u64::MAX)) < 64);
+        assert_eq!(stored_len(U256::MAX), 64);
+
+        let tx = RocksTransaction::<true>::from_db(db.clone());
+        let mut cursor = tx.cursor_dup_write::<HashedStorages>().unwrap();
+        for (i, value) in values.iter().enumerate() {
+            let slot = B256::with_last_byte(i as u8);
+            cursor.upsert_dup(address, slot, &StorageEntry { key: slot, value: *value }).unwrap();
+        }
+        drop(cursor);
+        tx.commit().unwrap();
+
+        // Every value reads back in full
+        let read_tx = RocksTransaction::<false>::from_db(db);
+        let mut cursor = read_tx.cursor_dup_read::<HashedStorages>().unwrap();
+        for (i, value) in values.iter().enumerate() {
+            let slot = B256::with_last_byte(i as u8);
+            let entry = cursor.seek_by_key_subkey(address, slot).unwrap().unwrap();
+            assert_eq!(entry, StorageEntry { key: slot, value: *value });
+        }
+    }
+
+    #[test]
+    fn test_append_dup_rejects_duplicate_subkey() {
+        let (db, _temp_dir) = create_test_db();
+        let tx = RocksTransaction::<true>::from_db(db.clone());
+
+        let address_hash = keccak256(Address::from([1; 20]));
+        let value = TrieNodeValue {
+            nibbles: StoredNibbles(Nibbles::from_nibbles([5, 6, 7])),
+            node: B256::from([1; 32]),
+        };
+
+        let mut cursor = tx.cursor_dup_write::<StorageTrieTable>().unwrap();
+        cursor.append_dup(address_hash, value.clone()).unwrap();
+
+        match cursor.append_dup(address_hash, value.clone()) {
+            Err(DatabaseError::Write(err)) => {
+                assert_eq!(err.operation, DatabaseWriteOperation::CursorAppendDup);
+                assert_eq!(err.table_name, StorageTrieTable::NAME);
+                assert_eq!(err.key, address_hash.to_vec());
+            }
+            other => panic!("expected a duplicate append error, got {:?}", other),
+        }
+
+        // Duplicates are told apart by subkey, not by the whole value
+        let same_path = TrieNodeValue { node: B256::from([2; 32]), ..value.clone() };
+        assert!(matches!(
+            cursor.append_dup(address_hash, same_path),
+            Err(DatabaseError::Write(err)) if err.operation == DatabaseWriteOperation::CursorAppendDup
+        ));
+        let other_path = TrieNodeValue {
+            nibbles: StoredNibbles(Nibbles::from_nibbles([5, 6, 8])),
+            ..value.clone()
+        };
+        cursor.append_dup(address_hash, other_path.clone()).unwrap();
+
+        // Hashed storage subkeys lead the value as they are
+        let slot = B256::from([3; 32]);
+        let mut storage_cursor = tx.cursor_dup_write::<HashedStorages>().unwrap();
+        storage_cursor
+            .append_dup(address_hash, StorageEntry { key: slot, value: U256::from(1) })
+            .unwrap();
+        assert!(storage_cursor
+            .append_dup(address_hash, StorageEntry { key: slot, value: U256::from(2) })
+            .is_err());
+
+        // Each value went under its composite key, none under the plain key
+        let cf = db.cf_handle(StorageTrieTable::NAME).unwrap();
+        for stored in [&value, &other_path] {
+            let composite = DupSortHelper::composite_key_bytes::<StorageTrieTable>(
+                &address_hash,
+                &stored.nibbles,
+            )
+            .unwrap();
+            assert!(db.get_cf(cf, composite).unwrap().is_some());
+        }
+        assert!(db.get_cf(cf, address_hash.encode()).unwrap().is_none());
+
+        // The first value is still stored once, followed by the other path
+        let read_tx = RocksTransaction::<false>::from_db(db);
+        let mut read_cursor = read_tx.cursor_dup_read::<StorageTrieTable>().unwrap();
+        assert_eq!(read_cursor.seek_exact(address_hash).unwrap(), Some((address_hash, value)));
+        assert_eq!(read_cursor.next_dup().unwrap(), Some((address_hash, other_path)));
+        assert!(read_cursor.next_dup().unwrap().is_none());
+
+        let mut storage_cursor = read_tx.cursor_dup_read::<HashedStorages>().unwrap();
+        assert_eq!(
+            storage_cursor.seek_by_key_subkey(address_hash, slot).unwrap(),
+            Some(StorageEntry { key: slot, value: U256::from(1) })
+        );
+    }
+
+    #[test]
+    fn test_value_subkey_is_declared_per_table() {
+        let nibbles = StoredNibbles(Nibbles::from_nibbles([5, 6, 7]));
+        let node = TrieNodeValue { nibbles: nibbles.clone(), node: B256::from([1; 32]) };
+        assert_eq!(
+            DupSortHelper::value_subkey::<StorageTrieTable>(&node.clone().compress()).unwrap(),
+            nibbles.encode()
+        );
+
+        let slot = B256::from([3; 32]);
+        let entry = StorageEntry { key: slot, value: U256::from(1) }.compress();
+        assert_eq!(DupSortHelper::value_subkey::<HashedStorages>(&entry).unwrap(), slot.to_vec());
+
+        // A table that doesn't declare its subkey is refused instead of guessed at, even when
+        // it shares a column family with one that does
+        assert!(matches!(
+            DupSortHelper::value_subkey::<ShortKeyDupTable>(&node.compress()),
+            Err(DatabaseError::Other(_))
+        ));
+    }
+
+    #[test]
+    fn test_hashed_key_helpers() {
+        use alloy_primitives::b256;
+
+        assert_eq!(
+            hashed_address(Address::ZERO),
+            b256!("5380c7b7ae81a58eb98d9c78de4a1fd7fd9535fc953ed2be602daaa41767312a")
+        );
+        assert_eq!(
+            hashed_slot(B256::ZERO),
+            b256!("290decd9548b62a8d60345a988386fc84ba6bc95484008f6362f93160ef3e563")
+        );
+
+        for address in [Address::ZERO, Address::from([1; 20]), Address::from([0xab; 20])] {
+            assert_eq!(hashed_address(address), keccak256(address));
+            assert_eq!(account_trie_key(address), TrieNibbles(Nibbles::unpack(keccak256(address))));
+        }
+        assert_eq!(account_trie_key(Address::ZERO).0.len(), 64);
+    }
+
+    #[test]
+    fn test_iter_dup_groups() {
+        let (db, _temp_dir) = create_test_db();
+
+        // The last account's key is made of delimiter bytes
+        let accounts = [
+            (B256::from([0x01; 32]), vec![vec![1], vec![2, 3], vec![4]]),
+            (B256::from([0x02; 32]), vec![vec![5]]),
+            (B256::from([0xff; 32]), vec![vec![0xf, 0xf], vec![6]]),
+        ];
+        let node_value = |nibbles: &[u8]| TrieNodeValue {
+            nibbles: StoredNibbles(Nibbles::from_nibbles(nibbles)),
+            node: keccak256(nibbles),
+        };
+
+        let tx = RocksTransaction::<true>::from_db(db.clone());
+        let mut cursor = tx.cursor_dup_write::<StorageTrieTable>().unwrap();
+        for (account, nodes) in &accounts {
+            // Insert in reverse to check values come back in subkey order
+            for nibbles in nodes.iter().rev() {
+                let value = node_value(nibbles);
+                cursor.upsert_dup(*account, value.nibbles.clone(), &value).unwrap();
+            }
+        }
+        drop(cursor);
+        tx.commit().unwrap();
+
+        let read_tx = RocksTransaction::<false>::from_db(db);
+        let groups = read_tx
+            .iter_dup_groups::<StorageTrieTable>()
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+
+        let expected = accounts
+            .iter()
+            .map(|(account, nodes)| {
+                let mut values: Vec<_> = nodes.iter().map(|nibbles| node_value(nibbles)).collect();
+                values.sort_by(|a, b| a.nibbles.cmp(&b.nibbles));
+                (*account, values)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(groups, expected);
+    }
+
+    #[test]
+    fn test_reencode_table() {
+        let (db, _temp_dir) = create_test_db();
+
+        let account =
+            |nonce: u64| Account { nonce, balance: U256::from(nonce), bytecode_hash: None };
+        let node_value = |nibbles: &[u8], version: u8| TrieNodeValue {
+            nibbles: StoredNibbles(Nibbles::from_nibbles(nibbles)),
+            node: keccak256([nibbles, &[version][..]].concat()),
+        };
+        let address = B256::from([0x01; 32]);
+        let paths: [&[u8]; 3] = [&[1], &[1, 2], &[3]];
+
+        let tx = RocksTransaction::<true>::from_db(db.clone());
+        for i in 0..50u64 {
+            tx.put::<HashedAccounts>(keccak256(i.to_be_bytes()), account(i)).unwrap();
+        }
+        let mut cursor = tx.cursor_dup_write::<StorageTrieTable>().unwrap();
+        for path in paths {
+            let value = node_value(path, 0);
+            cursor.upsert_dup(address, value.nibbles.clone(), &value).unwrap();
+        }
+        drop(cursor);
+        tx.commit().unwrap();
+
+        // Bump the nonce of every account and point every storage node at a new hash
+        let read_tx = RocksTransaction::<false>::from_db(db.clone());
+        let write_tx = RocksTransaction::<true>::from_db(db.clone());
+        let rewritten = write_tx
+            .reencode_table::<HashedAccounts>(&read_tx, |mut account| {
+                account.nonce += 1;
+                account
+            })
+            .unwrap();
+        assert_eq!(rewritten, 50);
+        let rewritten = write_tx
+            .reencode_table::<StorageTrieTable>(&read_tx, |value| TrieNodeValue {
+                node: keccak256([value.nibbles.0.as_slice(), &[1u8][..]].concat()),
+                ..value
+            })
+            .unwrap();
+        assert_eq!(rewritten, paths.len());
+        write_tx.commit().unwrap();
+
+        let tx = RocksTransaction::<false>::from_db(db);
+        assert_eq!(tx.entries::<HashedAccounts>().unwrap(), 50);
+        for i in 0..50u64 {
+            let mut expected = account(i);
+            expected.nonce += 1;
+            assert_eq!(
+                tx.get::<HashedAccounts>(keccak256(i.to_be_bytes())).unwrap(),
+                Some(expected)
+            );
+        }
+
+        let groups = tx
+            .iter_dup_groups::<StorageTrieTable>()
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let expected = paths.iter().map(|path| node_value(*path, 1)).collect::<Vec<_>>();
+        assert_eq!(groups, vec![(address, expected)]);
+    }
+
+    #[test]
+    fn test_account_code() {
+        let (db, _temp_dir) = build_test_db(&[HashedAccounts::NAME, Bytecodes::NAME]);
+
+        let code = Bytes::from_static(&[0x60, 0x80, 0x60, 0x40, 0x52, 0x00]);
+        let code_hash = keccak256(&code);
+        let contract = B256::repeat_byte(0x01);
+        let eoa = B256::repeat_byte(0x02);
+
+        let tx = RocksTransaction::<true>::from_db(db.clone());
+        tx.put::<HashedAccounts>(
+            contract,
+            Account { nonce: 1, balance: U256::ZERO, bytecode_hash: Some(code_hash) },
+        )
+        .unwrap();
+        tx.put::<HashedAccounts>(
+            eoa,
+            Account { nonce: 7, balance: U256::from(100), bytecode_hash: None },
+        )
+        .unwrap();
+        tx.put::<Bytecodes>(code_hash, Bytecode::new_raw(code.clone())).unwrap();
+        tx.commit().unwrap();
+
+        let tx = RocksTransaction::<false>::from_db(db);
+        assert_eq!(tx.account_code(contract).unwrap(), Some(code));
+        assert_eq!(tx.account_code(eoa).unwrap(), None);
+        assert_eq!(tx.account_code(B256::repeat_byte(0x03)).unwrap(), None);
+    }
+
+    #[test]
+    fn test_get_with_raw() {
+        let (db, _temp_dir) = create_test_db();
+
+        let stored = StoredTrieNode::from(&create_test_branch_node());
+        let hash = stored.hash();
+        let written: Vec<u8> = stored.clone().compress().into();
+
+        // Uncommitted writes are returned too
+        let tx = RocksTransaction::<true>::from_db(db.clone());
+        tx.put::<TrieTable>(hash, stored.clone()).unwrap();
+        assert_eq!(tx.get_with_raw::<TrieTable>(hash).unwrap(), Some((stored.clone(), written)));
+        tx.commit().unwrap();
+
+        // The raw bytes are exactly those on disk
+        let cf = db.cf_handle(TrieTable::NAME).unwrap();
+        let on_disk = db.get_cf(cf, hash).unwrap().unwrap();
+        let tx = RocksTransaction::<false>::from_db(db.clone());
+        let (value, raw) = tx.get_with_raw::<TrieTable>(hash).unwrap().unwrap();
+        assert_eq!(raw, on_disk);
+        assert_eq!(value, stored);
+        assert_eq!(StoredTrieNode::decompress(&raw).unwrap(), value);
+
+        assert_eq!(tx.get_with_raw::<TrieTable>(B256::repeat_byte(0x01)).unwrap(), None);
+    }
+
+    #[test]
+    fn test_account_at_block() {
+        let (db, _temp_dir) = build_test_db(&[HashedAccountHistory::NAME]);
+
+        let address = B256::repeat_byte(0x22);
+        let version =
+            |nonce: u64| Account { nonce, balance: U256::from(nonce * 10), bytecode_hash: None };
+
+        let tx = RocksTransaction::<true>::from_db(db.clone());
+        for block in [1u64, 5, 10] {
+            tx.put::<HashedAccountHistory>(
+                AccountAtBlock { hashed_address: address, block },
+                version(block),
+            )
+            .unwrap();
+        }
+        // Neighbouring addresses must not leak into the lookup
+        for (neighbour, block) in [(B256::repeat_byte(0x21), 0u64), (B256::repeat_byte(0x23), 2)] {
+            tx.put::<HashedAccountHistory>(
+                AccountAtBlock { hashed_address: neighbour, block },
+                version(100),
+            )
+            .unwrap();
+        }
+        tx.commit().unwrap();
+
+        let tx = RocksTransaction::<false>::from_db(db);
+        assert_eq!(tx.account_at_block(address, 0).unwrap(), None);
+        assert_eq!(tx.account_at_block(address, 1).unwrap(), Some(version(1)));
+        assert_eq!(tx.account_at_block(address, 3).unwrap(), Some(version(1)));
+        assert_eq!(tx.account_at_block(address, 7).unwrap(), Some(version(5)));
+        assert_eq!(tx.account_at_block(address, 10).unwrap(), Some(version(10)));
+        assert_eq!(tx.account_at_block(address, 12).unwrap(), Some(version(10)));
+        assert_eq!(tx.account_at_block(B256::repeat_byte(0x24), 12).unwrap(), None);
+    }
+
+    #[test]
+    fn test_account_storage_size() {
+        let (db, _temp_dir) = create_test_db();
+
+        // Write slots and storage trie nodes of two accounts straight to their column families
+        let write_storage = |address: B256, slots: u64| {
+            let storages = db.cf_handle(HashedStorages::NAME).unwrap();
+            let storage_trie = db.cf_handle(StorageTrieTable::NAME).unwrap();
+            for i in 0..slots {
+                let slot = keccak256(i.to_be_bytes());
+                let entry = StorageEntry { key: slot, value: U256::from(u64::MAX - i) };
+                let key =
+                    DupSortHelper::composite_key_bytes::<HashedStorages>(&address, &slot).unwrap();
+                db.put_cf(storages, key, entry.compress()).unwrap();
+
+                let nibbles = StoredNibbles(Nibbles::unpack(slot));
+                let node = TrieNodeValue { nibbles: nibbles.clone(), node: slot };
+                let key =
+                    DupSortHelper::composite_key_bytes::<StorageTrieTable>(&address, &nibbles)
+                        .unwrap();
+                db.put_cf(storage_trie, key, node.compress()).unwrap();
+            }
+        };
+        let large = B256::repeat_byte(0x10);
+        let small = B256::repeat_byte(0x20);
+        write_storage(large, 5_000);
+        write_storage(small, 5);
+        for table in [HashedStorages::NAME, StorageTrieTable::NAME] {
+            db.flush_cf(db.cf_handle(table).unwrap()).unwrap();
+        }
+
+        let tx = RocksTransaction::<false>::from_db(db);
+        let large_size = tx.account_storage_size(large).unwrap();
+        let small_size = tx.account_storage_size(small).unwrap();
+        assert!(large_size > small_size, "{large_size} <= {small_size}");
+        assert!(large_size > 5_000 * 32);
+        assert_eq!(tx.account_storage_size(B256::repeat_byte(0x30)).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_typed_transaction_constructors() {
+        let (db, _temp_dir) = create_test_db();
+        let key = B256::repeat_byte(0x01);
+        let account = Account { nonce: 3, balance: U256::from(30), bytecode_hash: None };
+
+        let write_tx = RocksTransaction::new_write(db.clone());
+        write_tx.put::<HashedAccounts>(key, account).unwrap();
+        assert_eq!(write_tx.staged_changes().len(), 1);
+        write_tx.commit().unwrap();
+
+        let read_tx = RocksTransaction::new_read(db.clone());
+        assert_eq!(read_tx.get::<HashedAccounts>(key).unwrap(), Some(account));
+
+        // The deprecated constructor keeps working, whatever the flag says
+        #[allow(deprecated)]
+        let (write_tx, read_tx) = (
+            RocksTransaction::<true>::new(db.clone(), true),
+            RocksTransaction::<false>::new(db, true),
+        );
+        write_tx.delete::<HashedAccounts>(key, None).unwrap();
+        write_tx.commit().unwrap();
+        assert_eq!(read_tx.get::<HashedAccounts>(key).unwrap(), None);
+    }
+
+    #[test]
+    fn test_page() {
+        let (db, _temp_dir) = create_test_db();
+        let account =
+            |n: u8| Account { nonce: n as u64, balance: U256::from(n), bytecode_hash: None };
+
+        let tx = RocksTransaction::<true>::from_db(db.clone());
+        for n in 1..=25u8 {
+            tx.put::<HashedAccounts>(B256::with_last_byte(n), account(n)).unwrap();
+        }
+        tx.commit().unwrap();
+
+        let tx = RocksTransaction::<false>::from_db(db);
+        let mut pages = Vec::new();
+        let mut start = None;
+        loop {
+            let (entries, next) = tx.page::<HashedAccounts>(start, 10).unwrap();
+            pages.push((entries, next));
+            start = next;
+            if start.is_none() {
+                break;
+            }
+        }
+
+        assert_eq!(pages.iter().map(|(entries, _)| entries.len()).collect::<Vec<_>>(), [10, 10, 5]);
+        assert_eq!(pages[0].1, Some(B256::with_last_byte(11)));
+        assert_eq!(pages[1].1, Some(B256::with_last_byte(21)));
+        assert_eq!(pages[2].1, None);
+
+        // Together the pages hold every entry exactly once, in order
+        let all: Vec<_> = pages.into_iter().flat_map(|(entries, _)| entries).collect();
+        let expected: Vec<_> = (1..=25u8).map(|n| (B256::with_last_byte(n), account(n))).collect();
+        assert_eq!(all, expected);
+
+        let (entries, next) =
+            tx.page::<HashedAccounts>(Some(B256::with_last_byte(26)), 10).unwrap();
+        assert!(entries.is_empty());
+        assert_eq!(next, None);
+    }
+
+    #[test]
+    fn test_pages_read_one_snapshot() {
+        let (db, _temp_dir) = create_test_db();
+        let account =
+            |n: u8| Account { nonce: n as u64, balance: U256::from(n), bytecode_hash: None };
+
+        let tx = RocksTransaction::<true>::from_db(db.clone());
+        for n in (2..=40u8).step_by(2) {
+            tx.put::<HashedAccounts>(B256::with_last_byte(n), account(n)).unwrap();
+        }
+        tx.commit().unwrap();
+
+        let read_tx = RocksTransaction::<false>::new_with_snapshot(db.clone(), None);
+        let (first, next) = read_tx.page::<HashedAccounts>(None, 10).unwrap();
+        assert_eq!(next, Some(B256::with_last_byte(22)));
+
+        // Entries added on both sides of the page boundary, and one of the next page deleted
+        let tx = RocksTransaction::<true>::from_db(db);
+        for n in [1, 21, 23] {
+            tx.put::<HashedAccounts>(B256::with_last_byte(n), account(n)).unwrap();
+        }
+        tx.delete::<HashedAccounts>(B256::with_last_byte(22), None).unwrap();
+        tx.commit().unwrap();
+
+        let (second, next) = read_tx.page::<HashedAccounts>(next, 10).unwrap();
+        assert_eq!(next, None);
+        let all: Vec<_> = first.into_iter().chain(second).collect();
+        let expected: Vec<_> =
+            (2..=40u8).step_by(2).map(|n| (B256::with_last_byte(n), account(n))).collect();
+        assert_eq!(all, expected);
+    }
+
+    #[test]
+    fn test_prune_account() {
+        let (db, _temp_dir) = create_test_db();
+        let pruned = B256::repeat_byte(0x55);
+        // Neighbors on both sides of the pruned account's key prefix
+        let neighbors = [B256::repeat_byte(0x54), B256::repeat_byte(0x56)];
+
+        let tx = RocksTransaction::<true>::from_db(db.clone());
+        for address in neighbors.iter().chain([&pruned]) {
+            tx.put::<HashedAccounts>(*address, Account { nonce: 1, ..Default::default() }).unwrap();
+            let mut storages = tx.cursor_dup_write::<HashedStorages>().unwrap();
+            let mut storage_trie = tx.cursor_dup_write::<StorageTrieTable>().unwrap();
+            for i in 0..5u8 {
+                let slot = B256::with_last_byte(i);
+                storages
+                    .upsert_dup(*address, slot, &StorageEntry { key: slot, value: U256::from(i) })
+                    .unwrap();
+                let nibbles = StoredNibbles(Nibbles::from_nibbles([i]));
+                let value = TrieNodeValue { nibbles: nibbles.clone(), node: slot };
+                storage_trie.upsert_dup(*address, nibbles, &value).unwrap();
+            }
+        }
+        tx.commit().unwrap();
+
+        let tx = RocksTransaction::<true>::from_db(db.clone());
+        tx.prune_account(pruned).unwrap();
+        tx.commit().unwrap();
+
+        let tx = RocksTransaction::<false>::from_db(db);
+        // Number of entries of each address
+        fn dup_counts<T: DupSort<Key = B256>>(
+            tx: &RocksTransaction<false>,
+        ) -> HashMap<B256, usize> {
+            tx.iter_dup_groups::<T>()
+                .unwrap()
+                .map(|group| group.map(|(key, values)| (key, values.len())))
+                .collect::<Result<_, _>>()
+                .unwrap()
+        }
+        let storages = dup_counts::<HashedStorages>(&tx);
+        let storage_trie = dup_counts::<StorageTrieTable>(&tx);
+        let storage_count = |address: B256| storages.get(&address).copied().unwrap_or(0);
+        let storage_trie_count = |address: B256| storage_trie.get(&address).copied().unwrap_or(0);
+        assert_eq!(tx.get::<HashedAccounts>(pruned).unwrap(), None);
+        assert_eq!(storage_count(pruned), 0);
+        assert_eq!(storage_trie_count(pruned), 0);
+        for address in neighbors {
+            assert!(tx.get::<HashedAccounts>(address).unwrap().is_some());
+            assert_eq!(storage_count(address), 5);
+            assert_eq!(storage_trie_count(address), 5);
+        }
+    }
+
+    #[test]
+    fn test_range_collect() {
+        let (db, _temp_dir) = create_test_db();
+        let account =
+            |n: u8| Account { nonce: n as u64, balance: U256::from(n), bytecode_hash: None };
+        let key = B256::with_last_byte;
+
+        let tx = RocksTransaction::<true>::from_db(db.clone());
+        for n in 0..100u8 {
+            tx.put::<HashedAccounts>(key(n), account(n)).unwrap();
+        }
+        tx.commit().unwrap();
+
+        let tx = RocksTransaction::<false>::from_db(db);
+        let entries = tx.range_collect::<HashedAccounts>(key(30), key(70)).unwrap();
+        assert_eq!(entries.len(), 40);
+        assert_eq!(entries, (30..70u8).map(|n| (key(n), account(n))).collect::<Vec<_>>());
+
+        // Empty and reversed ranges
+        assert!(tx.range_collect::<HashedAccounts>(key(50), key(50)).unwrap().is_empty());
+        assert!(tx.range_collect::<HashedAccounts>(key(70), key(30)).unwrap().is_empty());
+
+        // Ranges reaching past the stored keys
+        assert_eq!(tx.range_collect::<HashedAccounts>(key(90), key(200)).unwrap().len(), 10);
+        assert!(tx.range_collect::<HashedAccounts>(key(150), key(200)).unwrap().is_empty());
+        assert_eq!(
+            tx.range_collect::<HashedAccounts>(B256::ZERO, B256::repeat_byte(0xff)).unwrap().len(),
+            100
+        );
+
+        assert!(tx.range_collect::<HashedStorages>(key(0), key(10)).is_err());
+    }
 }
@@ -0,0 +1,2548 @@
+#[cfg(test)]
+mod rocks_db_test {
+    use crate::implementation::rocks::dupsort::DupSortHelper;
+    use crate::tables::trie::{
+        AccountTrieTable, BlockStateRoots, StorageTrieTable, StoredTrieNode, TrieNibbles,
+        TrieNodeValue, TrieTable,
+    };
+    use crate::tables::TableConfig;
+    use crate::test::utils::{
+        build_test_db, cf_prefix_extractor, create_test_branch_node, create_test_db,
+        create_test_rocksdb, create_test_rocksdb_with_tables, TEST_TABLES,
+    };
+    use crate::version::{write_version, VersionManager, CURRENT_VERSION, META_CF};
+    use crate::write_gate::WriteGate;
+    use crate::{
+        Account, AppendLog, AutoCompactConfig, CfOverride, GroupCommitPolicy,
+        LongReadTransactionAction, Profile, RocksDB, RocksDBConfig, RocksDBError, RocksTransaction,
+        StagedOp, TableDiffSummary, WriteStallStats,
+    };
+    use alloy_primitives::{Address, B256, U256};
+    use reth_db::{
+        cursor::{DbCursorRO, DbCursorRW, DbDupCursorRO},
+        transaction::{DbTx, DbTxMut},
+        AccountsTrie, CanonicalHeaders, HashedAccounts, HashedStorages, PlainAccountState,
+        StoragesTrie,
+    };
+    use reth_db_api::{
+        database::Database,
+        table::{Compress, Decompress, Encode, Table},
+        DatabaseError,
+    };
+    use reth_primitives::StorageEntry;
+    use reth_trie::{BranchNodeCompact, Nibbles, TrieMask};
+    use reth_trie_common::{StorageTrieEntry, StoredNibbles, StoredNibblesSubKey};
+    use rocksdb::{
+        DBCompactionStyle, DBCompressionType, DBRecoveryMode, IteratorMode, Options, WriteOptions,
+        DB,
+    };
+    use std::io::Cursor;
+    use std::sync::{atomic::AtomicBool, Arc};
+    use std::time::{Duration, Instant};
+    use tempfile::TempDir;
+
+    fn test_account(nonce: u64) -> Account {
+        Account { nonce, balance: U256::from(nonce * 1000), bytecode_hash: None }
+    }
+
+    #[test]
+    fn test_oldest_snapshot_age_tracks_read_tx() {
+        let (db, _temp_dir) = create_test_rocksdb(RocksDBConfig::default());
+
+        // No snapshot held yet
+        assert!(db.oldest_snapshot_age().unwrap().is_none());
+
+        let read_tx = db.tx().unwrap();
+        std::thread::sleep(Duration::from_millis(1100));
+
+        let age = db.oldest_snapshot_age().unwrap();
+        assert!(age.is_some());
+        assert!(age.unwrap() >= Duration::from_secs(1));
+
+        drop(read_tx);
+        assert!(db.oldest_snapshot_age().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_cursors_read_transaction_snapshot() {
+        let (db, _temp_dir) = create_test_rocksdb(RocksDBConfig::default());
+        let slot = B256::with_last_byte(1);
+        let storage = |value: u64| StorageEntry { key: slot, value: U256::from(value) };
+
+        let write_tx = db.tx_mut().unwrap();
+        write_tx.put::<HashedAccounts>(B256::with_last_byte(1), test_account(1)).unwrap();
+        write_tx.put::<HashedStorages>(B256::with_last_byte(1), storage(1)).unwrap();
+        write_tx.commit().unwrap();
+
+        let read_tx = db.tx().unwrap();
+        let mut cursor = read_tx.cursor_read::<HashedAccounts>().unwrap();
+        let mut dup_cursor = read_tx.cursor_dup_read::<HashedStorages>().unwrap();
+
+        // Changed and added after the transaction was opened
+        let write_tx = db.tx_mut().unwrap();
+        write_tx.put::<HashedAccounts>(B256::with_last_byte(1), test_account(10)).unwrap();
+        write_tx.put::<HashedAccounts>(B256::with_last_byte(2), test_account(2)).unwrap();
+        write_tx.put::<HashedStorages>(B256::with_last_byte(2), storage(2)).unwrap();
+        write_tx.commit().unwrap();
+
+        // Cursors, including ones created afterwards, agree with `get`
+        let expected = Some((B256::with_last_byte(1), test_account(1)));
+        assert_eq!(
+            read_tx.get::<HashedAccounts>(B256::with_last_byte(1)).unwrap(),
+            Some(test_account(1))
+        );
+        assert_eq!(cursor.first().unwrap(), expected);
+        assert_eq!(cursor.next().unwrap(), None);
+        assert_eq!(read_tx.cursor_read::<HashedAccounts>().unwrap().last().unwrap(), expected);
+        assert_eq!(dup_cursor.last().unwrap(), Some((B256::with_last_byte(1), storage(1))));
+
+        // A cursor keeps the snapshot alive past its transaction
+        drop(read_tx);
+        assert!(db.oldest_snapshot_age().unwrap().is_some());
+        assert_eq!(cursor.seek(B256::ZERO).unwrap(), expected);
+        drop((cursor, dup_cursor));
+        assert!(db.oldest_snapshot_age().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_long_read_tx_warns() {
+        let config = RocksDBConfig::default()
+            .with_max_read_transaction_duration(Duration::from_millis(50))
+            .with_long_read_transaction_action(LongReadTransactionAction::Warn);
+        let (db, _temp_dir) = create_test_rocksdb(config);
+
+        let write_tx = db.tx_mut().unwrap();
+        write_tx.put::<HashedAccounts>(B256::from([1; 32]), test_account(1)).unwrap();
+        write_tx.commit().unwrap();
+
+        let read_tx = db.tx().unwrap();
+        assert!(!read_tx.long_read_warned());
+
+        std::thread::sleep(Duration::from_millis(100));
+
+        // Reads still succeed, but the transaction is flagged
+        assert!(read_tx.get::<HashedAccounts>(B256::from([1; 32])).unwrap().is_some());
+        assert!(read_tx.long_read_warned());
+    }
+
+    #[test]
+    fn test_long_read_tx_errors() {
+        let config = RocksDBConfig::default()
+            .with_max_read_transaction_duration(Duration::from_millis(50))
+            .with_long_read_transaction_action(LongReadTransactionAction::Error);
+        let (db, _temp_dir) = create_test_rocksdb(config);
+
+        let read_tx = db.tx().unwrap();
+        std::thread::sleep(Duration::from_millis(100));
+        assert!(read_tx.get::<HashedAccounts>(B256::from([1; 32])).is_err());
+
+        // Disabling the safety check lifts the limit
+        let mut read_tx = db.tx().unwrap();
+        read_tx.disable_long_read_transaction_safety();
+        std::thread::sleep(Duration::from_millis(100));
+        assert!(read_tx.get::<HashedAccounts>(B256::from([1; 32])).is_ok());
+    }
+
+    #[test]
+    fn test_secondary_sees_writes_after_flush() {
+        let (primary, primary_dir) = create_test_rocksdb(RocksDBConfig::default());
+        let secondary_dir = TempDir::new().unwrap();
+        let secondary = RocksDB::open_secondary(
+            primary_dir.path(),
+            secondary_dir.path(),
+            RocksDBConfig::default(),
+        )
+        .unwrap();
+
+        // Write to the primary without the WAL so the entry only lives in the memtable
+        let key = B256::from([7; 32]);
+        let inner = primary.inner();
+        let cf = inner.cf_handle(HashedAccounts::NAME).unwrap();
+        let mut write_opts = WriteOptions::default();
+        write_opts.disable_wal(true);
+        let value: Vec<u8> = test_account(7).compress().into();
+        inner.put_cf_opt(cf, key.encode(), value, &write_opts).unwrap();
+
+        // The secondary catches up but can't see memtable-only data
+        secondary.try_catch_up_with_primary().unwrap();
+        assert!(secondary.tx().unwrap().get::<HashedAccounts>(key).unwrap().is_none());
+
+        // After the primary flushes, the secondary sees the entry
+        primary.flush_for_secondary().unwrap();
+        secondary.try_catch_up_with_primary().unwrap();
+        assert_eq!(
+            secondary.tx().unwrap().get::<HashedAccounts>(key).unwrap(),
+            Some(test_account(7))
+        );
+    }
+
+    #[test]
+    fn test_secondary_reads_duplicates() {
+        let (primary, primary_dir) = create_test_rocksdb(RocksDBConfig::default());
+        let address = B256::from([3; 32]);
+        let entries: Vec<_> = (1..=3u8)
+            .map(|i| StorageEntry { key: B256::with_last_byte(i), value: U256::from(i) })
+            .collect();
+
+        let tx = primary.tx_mut().unwrap();
+        let mut cursor = tx.cursor_dup_write::<HashedStorages>().unwrap();
+        for entry in &entries {
+            cursor.upsert_dup(address, entry.key, entry).unwrap();
+        }
+        drop(cursor);
+        tx.commit().unwrap();
+        primary.flush_for_secondary().unwrap();
+
+        // The secondary opens the column families with the primary's prefix extractors
+        let secondary_dir = TempDir::new().unwrap();
+        let secondary = RocksDB::open_secondary(
+            primary_dir.path(),
+            secondary_dir.path(),
+            RocksDBConfig::default(),
+        )
+        .unwrap();
+        let read_tx = secondary.tx().unwrap();
+        let mut dup_cursor = read_tx.cursor_dup_read::<HashedStorages>().unwrap();
+        assert_eq!(
+            dup_cursor.seek_by_key_subkey(address, B256::with_last_byte(2)).unwrap(),
+            Some(entries[1])
+        );
+        let walked = dup_cursor
+            .walk_dup(Some(address), None)
+            .unwrap()
+            .map(|item| item.map(|(_, entry)| entry))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(walked, entries);
+    }
+
+    #[test]
+    fn test_owned_iter_across_threads() {
+        let (db, _temp_dir) = create_test_rocksdb(RocksDBConfig::default());
+
+        let write_tx = db.tx_mut().unwrap();
+        for i in 0..10u8 {
+            write_tx.put::<HashedAccounts>(B256::from([i; 32]), test_account(i as u64)).unwrap();
+        }
+        write_tx.commit().unwrap();
+
+        let iter = db.tx().unwrap().into_owned_iter::<HashedAccounts>().unwrap();
+        let entries = std::thread::spawn(move || iter.collect::<Result<Vec<_>, _>>().unwrap())
+            .join()
+            .unwrap();
+
+        assert_eq!(entries.len(), 10);
+        for (i, (key, account)) in entries.into_iter().enumerate() {
+            assert_eq!(key, B256::from([i as u8; 32]));
+            assert_eq!(account, test_account(i as u64));
+        }
+    }
+
+    #[test]
+    fn test_compact_after_delete() {
+        let (db, _temp_dir) = create_test_rocksdb(RocksDBConfig::default());
+
+        let write_tx = db.tx_mut().unwrap();
+        for i in 0..10_000u32 {
+            let mut key = [0u8; 32];
+            key[..4].copy_from_slice(&i.to_be_bytes());
+            write_tx.put::<HashedAccounts>(B256::from(key), test_account(i as u64)).unwrap();
+        }
+        write_tx.commit().unwrap();
+        db.flush_for_secondary().unwrap();
+
+        let live_size = |db: &RocksDB| {
+            db.property_int_value_cf::<HashedAccounts>("rocksdb.estimate-live-data-size")
+                .unwrap()
+                .unwrap_or_default()
+        };
+        let before = live_size(&db);
+        assert!(before > 0);
+
+        db.compact_after_delete::<HashedAccounts>(B256::ZERO, B256::repeat_byte(0xff)).unwrap();
+
+        let after = live_size(&db);
+        assert!(after < before / 10, "live data size should drop: {} -> {}", before, after);
+
+        let mut cursor = db.tx().unwrap().cursor_read::<HashedAccounts>().unwrap();
+        assert!(cursor.first().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_migrate_unprefixed_trie_node_values() {
+        let temp_dir = TempDir::new().unwrap();
+        let hashed_address = B256::with_last_byte(7);
+        let paths: [&[u8]; 3] = [&[], &[0x1, 0x2, 0x3], &[0xa; 64]];
+
+        // Simulate a version 1 database, storing values without their nibble count
+        {
+            let db = RocksDB::open(temp_dir.path(), RocksDBConfig::default()).unwrap();
+            let inner = db.inner();
+            let cf = inner.cf_handle(StorageTrieTable::NAME).unwrap();
+            for (i, path) in paths.iter().enumerate() {
+                let subkey = StoredNibblesSubKey(Nibbles::from_nibbles(path));
+                let key = DupSortHelper::composite_key_bytes::<StorageTrieTable>(
+                    &hashed_address,
+                    &subkey,
+                )
+                .unwrap();
+                let legacy = [*path, &[i as u8; 32][..]].concat();
+                inner.put_cf(cf, key, legacy).unwrap();
+            }
+            write_version(&inner, 1).unwrap();
+        }
+
+        let db = RocksDB::open(temp_dir.path(), RocksDBConfig::default()).unwrap();
+        assert_eq!(VersionManager::new(&db.inner()).unwrap().current_version(), 2);
+
+        let tx = db.tx().unwrap();
+        let mut cursor = tx.cursor_dup_read::<StorageTrieTable>().unwrap();
+        let mut values = Vec::new();
+        let mut entry = cursor.seek_exact(hashed_address).unwrap();
+        while let Some((_, value)) = entry {
+            values.push(value);
+            entry = cursor.next_dup().unwrap();
+        }
+        assert_eq!(
+            values,
+            paths
+                .iter()
+                .enumerate()
+                .map(|(i, path)| TrieNodeValue {
+                    nibbles: StoredNibbles(Nibbles::from_nibbles(path)),
+                    node: B256::from([i as u8; 32]),
+                })
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_interrupted_migration_resumes_without_rewriting_values() {
+        let (db, _temp_dir) = create_test_rocksdb(RocksDBConfig::default());
+        let inner = db.inner();
+        let cf = inner.cf_handle(StorageTrieTable::NAME).unwrap();
+        let hashed_address = B256::with_last_byte(7);
+        let key = |i: u8| {
+            let subkey = StoredNibblesSubKey(Nibbles::from_nibbles([i >> 4, i & 0xf]));
+            DupSortHelper::composite_key_bytes::<StorageTrieTable>(&hashed_address, &subkey)
+                .unwrap()
+        };
+        // Short paths, whose prefixed values also decode as unprefixed ones
+        let legacy = |i: u8| [&[i >> 4, i & 0xf][..], &[i; 32][..]].concat();
+
+        for i in 0..10 {
+            inner.put_cf(cf, key(i), legacy(i)).unwrap();
+        }
+        // A value the migration fails on, after committing the chunks before it
+        inner.put_cf(cf, key(7), [0xff; 4]).unwrap();
+        write_version(&inner, 1).unwrap();
+
+        assert!(VersionManager::prefix_trie_node_values(&inner, 2).is_err());
+        assert_eq!(VersionManager::new(&inner).unwrap().current_version(), 1);
+
+        // Resuming rewrites the remaining values only, then records the version
+        inner.put_cf(cf, key(7), legacy(7)).unwrap();
+        VersionManager::prefix_trie_node_values(&inner, 2).unwrap();
+        assert_eq!(VersionManager::new(&inner).unwrap().current_version(), 2);
+
+        for i in 0..10 {
+            let value = TrieNodeValue::decompress(&inner.get_cf(cf, key(i)).unwrap().unwrap());
+            assert_eq!(
+                value.unwrap(),
+                TrieNodeValue {
+                    nibbles: StoredNibbles(Nibbles::from_nibbles([i >> 4, i & 0xf])),
+                    node: B256::from([i; 32]),
+                }
+            );
+        }
+        let meta = inner.cf_handle(META_CF).unwrap();
+        assert!(inner.get_cf(meta, b"migration_progress").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_version_stored_in_meta_cf() {
+        let temp_dir = TempDir::new().unwrap();
+
+        // Simulate a database written by an older release, with the version in the default CF
+        {
+            let mut opts = Options::default();
+            opts.create_if_missing(true);
+            let legacy = DB::open(&opts, temp_dir.path()).unwrap();
+            legacy.put(b"db_version", 1u32.to_be_bytes()).unwrap();
+        }
+
+        let db = RocksDB::open(temp_dir.path(), RocksDBConfig::default()).unwrap();
+        let inner = db.inner();
+
+        // The version was moved out of the default CF into the meta CF, then migrated
+        assert!(inner.get(b"db_version").unwrap().is_none());
+        let meta = inner.cf_handle(META_CF).unwrap();
+        assert_eq!(inner.get_cf(meta, b"db_version").unwrap(), Some(2u32.to_be_bytes().to_vec()));
+        assert_eq!(VersionManager::new(&inner).unwrap().current_version(), 2);
+
+        // The meta CF is never exposed as a table, and no table contains the version key
+        let tables = db.table_names().unwrap();
+        assert!(!tables.iter().any(|name| name == META_CF));
+        for name in tables {
+            let cf = inner.cf_handle(&name).unwrap();
+            for entry in inner.iterator_cf(cf, IteratorMode::Start) {
+                let (key, _) = entry.unwrap();
+                assert_ne!(key.as_ref(), b"db_version");
+            }
+        }
+    }
+
+    #[test]
+    fn test_block_based_options() {
+        let write_and_read = |db: &RocksDB| {
+            let write_tx = db.tx_mut().unwrap();
+            for i in 0..1_000u32 {
+                let mut key = [0u8; 32];
+                key[..4].copy_from_slice(&i.to_be_bytes());
+                write_tx.put::<HashedAccounts>(B256::from(key), test_account(i as u64)).unwrap();
+            }
+            write_tx.commit().unwrap();
+            db.flush_for_secondary().unwrap();
+
+            let read_tx = db.tx().unwrap();
+            for i in 0..1_000u32 {
+                let mut key = [0u8; 32];
+                key[..4].copy_from_slice(&i.to_be_bytes());
+                assert_eq!(
+                    read_tx.get::<HashedAccounts>(B256::from(key)).unwrap(),
+                    Some(test_account(i as u64))
+                );
+            }
+        };
+        let table_readers_mem = |db: &RocksDB| {
+            db.property_int_value_cf::<HashedAccounts>("rocksdb.estimate-table-readers-mem")
+                .unwrap()
+                .unwrap_or_default()
+        };
+
+        // Small blocks still read and write correctly
+        let (small_blocks, _dir) =
+            create_test_rocksdb(RocksDBConfig::default().with_block_size(512));
+        write_and_read(&small_blocks);
+
+        // With the flag set, index and filter blocks move from table readers to the block cache
+        let (uncached, _dir) = create_test_rocksdb(
+            RocksDBConfig::default().with_block_size(512).with_cache_index_and_filter_blocks(false),
+        );
+        write_and_read(&uncached);
+        let (cached, _dir) = create_test_rocksdb(
+            RocksDBConfig::default().with_block_size(512).with_cache_index_and_filter_blocks(true),
+        );
+        write_and_read(&cached);
+
+        assert!(table_readers_mem(&cached) < table_readers_mem(&uncached));
+        assert!(
+            cached
+                .property_int_value_cf::<HashedAccounts>("rocksdb.block-cache-usage")
+                .unwrap()
+                .unwrap_or_default()
+                > 0
+        );
+    }
+
+    #[test]
+    fn test_max_compaction_bytes() {
+        // Small memtables and compactions, so the writes span many files and compactions
+        let config = RocksDBConfig {
+            write_buffer_size: Some(64 * 1024),
+            ..RocksDBConfig::default().with_max_compaction_bytes(64 * 1024)
+        };
+        let (db, _temp_dir) = create_test_rocksdb(config);
+
+        for round in 0..10u64 {
+            let write_tx = db.tx_mut().unwrap();
+            for i in 0..1_000u64 {
+                let key = B256::from(U256::from(i * 10 + round));
+                write_tx.put::<HashedAccounts>(key, test_account(round)).unwrap();
+            }
+            write_tx.commit().unwrap();
+            db.flush_table::<HashedAccounts>().unwrap();
+        }
+
+        let inner = db.inner();
+        let cf = inner.cf_handle(HashedAccounts::NAME).unwrap();
+        inner.compact_range_cf(cf, None::<&[u8]>, None::<&[u8]>);
+        assert_eq!(
+            db.property_int_value_cf::<HashedAccounts>("rocksdb.num-files-at-level0").unwrap(),
+            Some(0)
+        );
+
+        let read_tx = db.tx().unwrap();
+        assert_eq!(read_tx.entries::<HashedAccounts>().unwrap(), 10_000);
+        for i in (0..10_000u64).step_by(7) {
+            assert_eq!(
+                read_tx.get::<HashedAccounts>(B256::from(U256::from(i))).unwrap(),
+                Some(test_account(i % 10))
+            );
+        }
+    }
+
+    #[test]
+    fn test_level0_file_num_compaction_trigger() {
+        let config = RocksDBConfig {
+            write_buffer_size: Some(64 * 1024),
+            ..RocksDBConfig::default().with_level0_file_num_compaction_trigger(2)
+        };
+        let (db, _temp_dir) = create_test_rocksdb(config);
+
+        // One level-0 file per round, well past the trigger
+        for round in 0..8u64 {
+            let write_tx = db.tx_mut().unwrap();
+            for i in 0..500u64 {
+                let key = B256::from(U256::from(i * 8 + round));
+                write_tx.put::<HashedAccounts>(key, test_account(round)).unwrap();
+            }
+            write_tx.commit().unwrap();
+            db.flush_table::<HashedAccounts>().unwrap();
+        }
+
+        // Let the background compactions settle
+        let property = |name: &str| db.property_int_value_cf::<HashedAccounts>(name).unwrap();
+        for _ in 0..500 {
+            if property("rocksdb.compaction-pending") == Some(0) &&
+                property("rocksdb.num-running-compactions") == Some(0)
+            {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        // Fewer level-0 files are left than the default trigger of 4 would allow
+        let level0_files = property("rocksdb.num-files-at-level0").unwrap();
+        assert!(level0_files < 2, "{level0_files} level-0 files left");
+
+        let read_tx = db.tx().unwrap();
+        assert_eq!(read_tx.entries::<HashedAccounts>().unwrap(), 4_000);
+        for i in (0..4_000u64).step_by(7) {
+            assert_eq!(
+                read_tx.get::<HashedAccounts>(B256::from(U256::from(i))).unwrap(),
+                Some(test_account(i % 8))
+            );
+        }
+    }
+
+    #[test]
+    fn test_block_state_roots() {
+        let (db, _temp_dir) = create_test_rocksdb(RocksDBConfig::default());
+        let root = |block: u64| B256::from(U256::from(block * 1_000_003));
+
+        // Recorded out of order, and past 255 so only big-endian keys iterate in block order
+        let write_tx = db.tx_mut().unwrap();
+        for block in (1..=10u64).rev().chain([300]) {
+            write_tx.record_state_root(block, root(block)).unwrap();
+        }
+        write_tx.commit().unwrap();
+
+        let read_tx = db.tx().unwrap();
+        for block in [1, 5, 10, 300] {
+            assert_eq!(read_tx.state_root_at(block).unwrap(), Some(root(block)));
+        }
+        assert_eq!(read_tx.state_root_at(0).unwrap(), None);
+        assert_eq!(read_tx.state_root_at(11).unwrap(), None);
+
+        let walked: Vec<_> = read_tx
+            .cursor_read::<BlockStateRoots>()
+            .unwrap()
+            .walk(None)
+            .unwrap()
+            .map(Result::unwrap)
+            .collect();
+        let expected: Vec<_> = (1..=10).chain([300]).map(|block| (block, root(block))).collect();
+        assert_eq!(walked, expected);
+    }
+
+    #[test]
+    fn test_reserved_column_families() {
+        // No table may be stored in the default or meta column families
+        for reserved in [rocksdb::DEFAULT_COLUMN_FAMILY_NAME, META_CF] {
+            let temp_dir = TempDir::new().unwrap();
+            let err =
+                RocksDB::open_with_tables(temp_dir.path(), RocksDBConfig::default(), &[reserved])
+                    .unwrap_err();
+            assert!(err.to_string().contains("reserved for metadata"), "{err}");
+        }
+
+        let temp_dir = TempDir::new().unwrap();
+        let db = RocksDB::open_with_tables(
+            temp_dir.path(),
+            RocksDBConfig::default(),
+            &["scratch_extra"],
+        )
+        .unwrap();
+        assert!(db.table_names().unwrap().contains(&"scratch_extra".to_string()));
+
+        // Metadata in the default column family isn't seen as table data
+        let inner = db.inner();
+        let default_cf = inner.cf_handle(rocksdb::DEFAULT_COLUMN_FAMILY_NAME).unwrap();
+        inner.put_cf(default_cf, b"some_metadata", b"value").unwrap();
+        drop(inner);
+
+        let table_names = db.table_names().unwrap();
+        assert!(!table_names.iter().any(|name| name == rocksdb::DEFAULT_COLUMN_FAMILY_NAME));
+        assert!(!table_names.iter().any(|name| name == META_CF));
+        let report = db.scan_all().unwrap();
+        assert!(report.tables.iter().all(|table| table.entries == 0), "{report:?}");
+    }
+
+    #[test]
+    fn test_table_schema() {
+        let (db, _temp_dir) = create_test_rocksdb(RocksDBConfig::default());
+        let schema = db.table_schema();
+        let table = |name: &str| *schema.iter().find(|table| table.name == name).unwrap();
+
+        let storage_trie = table(StorageTrieTable::NAME);
+        assert!(storage_trie.dupsort);
+        assert_eq!(storage_trie.prefix_len, Some(32));
+        assert!(storage_trie.value_type.ends_with("TrieNodeValue"));
+
+        let account_trie = table(AccountTrieTable::NAME);
+        assert!(!account_trie.dupsort);
+        assert_eq!(account_trie.prefix_len, Some(8));
+        assert!(account_trie.key_type.ends_with("TrieNibbles"));
+
+        let trie = table(TrieTable::NAME);
+        assert!(!trie.dupsort);
+        assert_eq!(trie.prefix_len, None);
+
+        assert!(table(HashedStorages::NAME).dupsort);
+
+        // Every table has a column family, and every column family but the reserved ones a table
+        let mut names: Vec<_> = schema.iter().map(|table| table.name.to_string()).collect();
+        let mut table_names = db.table_names().unwrap();
+        names.sort();
+        table_names.sort();
+        assert_eq!(names, table_names);
+    }
+
+    #[test]
+    fn test_schema_version() {
+        let (db, _temp_dir) = create_test_rocksdb(RocksDBConfig::default());
+        assert_eq!(db.schema_version().unwrap(), CURRENT_VERSION);
+        assert!(db.is_up_to_date().unwrap());
+
+        // Reading the version doesn't migrate a database stamped with an older one
+        write_version(&db.inner(), 1).unwrap();
+        assert_eq!(db.schema_version().unwrap(), 1);
+        assert!(!db.is_up_to_date().unwrap());
+        assert_eq!(db.schema_version().unwrap(), 1);
+    }
+
+    #[test]
+    fn test_open_with_retry() {
+        let temp_dir = TempDir::new().unwrap();
+        let db = RocksDB::open(temp_dir.path(), RocksDBConfig::default()).unwrap();
+
+        // Gives up once the attempts run out while the lock is held
+        let err = RocksDB::open_with_retry(
+            temp_dir.path(),
+            RocksDBConfig::default(),
+            3,
+            Duration::from_millis(1),
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("lock"), "{err}");
+
+        // Succeeds once the first handle lets go of the lock
+        std::thread::scope(|s| {
+            let retry = s.spawn(|| {
+                RocksDB::open_with_retry(
+                    temp_dir.path(),
+                    RocksDBConfig::default(),
+                    10,
+                    Duration::from_millis(10),
+                )
+            });
+            std::thread::sleep(Duration::from_millis(50));
+            drop(db);
+            assert!(retry.join().unwrap().is_ok());
+        });
+
+        // Other errors aren't retried
+        let missing = temp_dir.path().join("missing");
+        let start = std::time::Instant::now();
+        let config = RocksDBConfig { create_if_missing: false, ..RocksDBConfig::default() };
+        assert!(RocksDB::open_with_retry(&missing, config, 10, Duration::from_secs(1)).is_err());
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_append_log() {
+        let (db, _temp_dir) = create_test_rocksdb(RocksDBConfig::default());
+        let values: Vec<_> = (0..20u8).map(|i| B256::repeat_byte(0xff - i)).collect();
+
+        let write_tx = db.tx_mut().unwrap();
+        let log = AppendLog::<CanonicalHeaders>::new(&write_tx).unwrap();
+        let keys: Vec<_> = values[..10].iter().map(|value| log.append(*value).unwrap()).collect();
+        write_tx.commit().unwrap();
+        assert_eq!(keys, (0..10).collect::<Vec<_>>());
+
+        // A later transaction continues after the last committed entry, past 255 so the keys
+        // only sort in order if encoded big-endian
+        let write_tx = db.tx_mut().unwrap();
+        write_tx.put::<CanonicalHeaders>(300, B256::ZERO).unwrap();
+        write_tx.commit().unwrap();
+        let write_tx = db.tx_mut().unwrap();
+        let log = AppendLog::<CanonicalHeaders>::new(&write_tx).unwrap();
+        assert_eq!(log.next_key(), 301);
+        let later: Vec<_> = values[10..].iter().map(|value| log.append(*value).unwrap()).collect();
+        write_tx.commit().unwrap();
+        assert!(later.windows(2).all(|pair| pair[0] < pair[1]));
+        assert_eq!(later[0], 301);
+
+        let read_tx = db.tx().unwrap();
+        let walked: Vec<_> = read_tx
+            .cursor_read::<CanonicalHeaders>()
+            .unwrap()
+            .walk(None)
+            .unwrap()
+            .map(Result::unwrap)
+            .collect();
+        let expected: Vec<_> = keys
+            .into_iter()
+            .zip(values[..10].iter().copied())
+            .chain([(300, B256::ZERO)])
+            .chain(later.into_iter().zip(values[10..].iter().copied()))
+            .collect();
+        assert_eq!(walked, expected);
+    }
+
+    #[test]
+    fn test_write_stall_stats() {
+        let (db, _temp_dir) = create_test_rocksdb(RocksDBConfig::default());
+        assert_eq!(db.write_stall_stats().unwrap(), WriteStallStats::default());
+
+        for round in 0..5u64 {
+            let write_tx = db.tx_mut().unwrap();
+            for i in 0..1_000u64 {
+                write_tx
+                    .put::<HashedAccounts>(B256::from(U256::from(i * 5 + round)), test_account(i))
+                    .unwrap();
+            }
+            write_tx.commit().unwrap();
+            db.flush_table::<HashedAccounts>().unwrap();
+        }
+
+        // Five L0 files are far from the stop trigger
+        let stats = db.write_stall_stats().unwrap();
+        assert_eq!(stats.stopped_count, 0);
+    }
+
+    #[test]
+    fn test_write_stall_stats_parsing() {
+        use crate::db::{cumulative_stall_micros, write_stall_count};
+
+        // `rocksdb.dbstats` of a database that stalled for a bit over an hour
+        let db_stats = "\n** DB Stats **\n\
+            Uptime(secs): 4000.1 total, 600.0 interval\n\
+            Cumulative writes: 5 writes, 5000 keys, 5 commit groups, 1.0 writes per commit group, \
+            ingest: 0.19 MB, 0.00 MB/s\n\
+            Cumulative WAL: 5 writes, 0 syncs, 5.00 writes per sync, written: 0.19 MB, 0.00 MB/s\n\
+            Cumulative stall: 01:02:03.456 H:M:S, 93.1 percent\n\
+            Interval writes: 0 writes, 0 keys, 0 commit groups, 0.0 writes per commit group, \
+            ingest: 0.00 MB, 0.00 MB/s\n\
+            Interval stall: 00:00:0.000 H:M:S, 0.0 percent\n";
+        assert_eq!(cumulative_stall_micros(db_stats), (3600 + 2 * 60 + 3) * 1_000_000 + 456_000);
+        assert_eq!(cumulative_stall_micros("Uptime(secs): 0.0 total, 0.0 interval"), 0);
+
+        // `rocksdb.cfstats-no-file-histogram` of a table that was slowed down and stopped
+        let cf_stats = "\n** Compaction Stats [default] **\n\
+            Level    Files   Size     Score Read(GB)\n\
+            Write Stall (count): cf-l0-file-count-limit-delays-with-ongoing-compaction: 0, \
+            cf-l0-file-count-limit-stops-with-ongoing-compaction: 0, \
+            l0-file-count-limit-delays: 3, l0-file-count-limit-stops: 1, \
+            memtable-limit-delays: 2, memtable-limit-stops: 0, \
+            pending-compaction-bytes-delays: 0, pending-compaction-bytes-stops: 0, \
+            total-delays: 5, total-stops: 1, interval-total-count: 6\n";
+        assert_eq!(write_stall_count(cf_stats, "total-delays"), 5);
+        assert_eq!(write_stall_count(cf_stats, "total-stops"), 1);
+        assert_eq!(write_stall_count(cf_stats, "l0-file-count-limit-delays"), 3);
+        assert_eq!(write_stall_count(cf_stats, "no-such-count"), 0);
+        assert_eq!(write_stall_count("", "total-delays"), 0);
+    }
+
+    #[cfg(feature = "metrics")]
+    #[test]
+    fn test_write_stall_stats_publishes_metrics() {
+        use metrics_util::debugging::{DebugValue, DebuggingRecorder};
+
+        let (db, _temp_dir) = create_test_rocksdb(RocksDBConfig::default());
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+        let stats = metrics::with_local_recorder(&recorder, || db.write_stall_stats()).unwrap();
+
+        let snapshot = snapshotter.snapshot().into_vec();
+        let counter = |name: &str| {
+            snapshot.iter().find(|(key, _, _, _)| key.key().name() == name).map(
+                |(_, _, _, value)| match value {
+                    DebugValue::Counter(value) => *value,
+                    other => panic!("{name} is not a counter: {other:?}"),
+                },
+            )
+        };
+        assert_eq!(counter("rocksdb_write_stall_micros_total"), Some(stats.total_stall_micros));
+        assert_eq!(counter("rocksdb_write_stall_stops_total"), Some(stats.stopped_count));
+        assert_eq!(counter("rocksdb_write_stall_slowdowns_total"), Some(stats.slowdown_count));
+    }
+
+    #[test]
+    fn test_set_cf_options() {
+        let (db, temp_dir) = create_test_rocksdb(RocksDBConfig::default());
+
+        db.set_cf_options::<HashedAccounts>(&[("write_buffer_size", "134217728")]).unwrap();
+
+        // The new size is persisted in the latest options file, under the table's section
+        let latest_options = std::fs::read_dir(temp_dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .filter_map(|name| name.strip_prefix("OPTIONS-")?.parse::<u64>().ok())
+            .max()
+            .unwrap();
+        let options =
+            std::fs::read_to_string(temp_dir.path().join(format!("OPTIONS-{:06}", latest_options)))
+                .unwrap();
+        let section = options
+            .split("[CFOptions \"")
+            .find(|section| section.starts_with(&format!("{}\"", HashedAccounts::NAME)))
+            .unwrap();
+        assert!(section.lines().any(|line| line.trim() == "write_buffer_size=134217728"));
+
+        // Static options can't change on an open database, and nothing is applied
+        let err = db
+            .set_cf_options::<HashedAccounts>(&[
+                ("write_buffer_size", "1048576"),
+                ("num_levels", "4"),
+            ])
+            .unwrap_err();
+        let DatabaseError::Other(message) = err else { panic!("unexpected error: {err:?}") };
+        assert!(message.contains("num_levels"), "{message}");
+        assert!(message.contains(HashedAccounts::NAME), "{message}");
+        assert!(message.contains("only dynamically mutable options"), "{message}");
+
+        let write_tx = db.tx_mut().unwrap();
+        write_tx.put::<HashedAccounts>(B256::with_last_byte(1), test_account(1)).unwrap();
+        write_tx.commit().unwrap();
+        assert_eq!(
+            db.tx().unwrap().get::<HashedAccounts>(B256::with_last_byte(1)).unwrap(),
+            Some(test_account(1))
+        );
+    }
+
+    #[test]
+    fn test_restart_intervals() {
+        // Keys sharing a long prefix, like the nibble paths of a trie
+        let key = |i: u32| {
+            let mut key = [0xAB; 32];
+            key[28..].copy_from_slice(&i.to_be_bytes());
+            B256::from(key)
+        };
+        let write_and_read = |db: &RocksDB| {
+            let write_tx = db.tx_mut().unwrap();
+            for i in 0..1_000u32 {
+                write_tx.put::<HashedAccounts>(key(i), test_account(i as u64)).unwrap();
+            }
+            write_tx.commit().unwrap();
+            db.flush_for_secondary().unwrap();
+
+            let read_tx = db.tx().unwrap();
+            for i in 0..1_000u32 {
+                assert_eq!(
+                    read_tx.get::<HashedAccounts>(key(i)).unwrap(),
+                    Some(test_account(i as u64))
+                );
+            }
+            let mut cursor = read_tx.cursor_read::<HashedAccounts>().unwrap();
+            assert_eq!(cursor.seek(key(500)).unwrap(), Some((key(500), test_account(500))));
+        };
+        let table_readers_mem = |db: &RocksDB| {
+            db.property_int_value_cf::<HashedAccounts>("rocksdb.estimate-table-readers-mem")
+                .unwrap()
+                .unwrap_or_default()
+        };
+
+        // Small blocks make the index large enough for its encoding to show
+        let (default_intervals, _dir) =
+            create_test_rocksdb(RocksDBConfig::default().with_block_size(512));
+        write_and_read(&default_intervals);
+        let (tuned, _dir) = create_test_rocksdb(
+            RocksDBConfig::default()
+                .with_block_size(512)
+                .with_block_restart_interval(32)
+                .with_index_block_restart_interval(16),
+        );
+        write_and_read(&tuned);
+
+        // Delta encoding the shared prefixes shrinks the index held by the table readers
+        assert!(table_readers_mem(&tuned) < table_readers_mem(&default_intervals));
+    }
+
+    #[test]
+    fn test_get_with_timeout() {
+        let (db, _temp_dir) = create_test_rocksdb(RocksDBConfig::default());
+
+        let key = B256::from([3; 32]);
+        let write_tx = db.tx_mut().unwrap();
+        write_tx.put::<HashedAccounts>(key, test_account(3)).unwrap();
+        write_tx.commit().unwrap();
+
+        // Forcing a timeout reliably requires a slow IO environment, so only the functional
+        // path is covered here
+        let read_tx = db.tx().unwrap();
+        let timeout = Duration::from_secs(5);
+        assert_eq!(
+            read_tx.get_with_timeout::<HashedAccounts>(key, timeout).unwrap(),
+            Some(test_account(3))
+        );
+        assert!(read_tx
+            .get_with_timeout::<HashedAccounts>(B256::from([4; 32]), timeout)
+            .unwrap()
+            .is_none());
+
+        // Timeouts stay distinguishable once converted for the database interface
+        let err: DatabaseError = RocksDBError::Timeout(timeout).into();
+        assert_eq!(RocksDBError::code_of(&err), Some(RocksDBError::TIMEOUT_CODE));
+        let err: DatabaseError = RocksDBError::Config("invalid".into()).into();
+        assert_eq!(RocksDBError::code_of(&err), None);
+    }
+
+    #[test]
+    fn test_flush_single_table() {
+        let (db, _temp_dir) = create_test_rocksdb(RocksDBConfig::default());
+
+        let write_tx = db.tx_mut().unwrap();
+        write_tx.put::<HashedAccounts>(B256::from([1; 32]), test_account(1)).unwrap();
+        write_tx.put::<PlainAccountState>(Address::from([1; 20]), test_account(1)).unwrap();
+        write_tx.commit().unwrap();
+
+        db.flush_table::<HashedAccounts>().unwrap();
+
+        let sst_files = db.sst_files().unwrap();
+        assert!(sst_files.iter().any(|file| file.column_family_name == HashedAccounts::NAME));
+        assert!(!sst_files.iter().any(|file| file.column_family_name == PlainAccountState::NAME));
+
+        // The un-flushed table is still readable from its memtable
+        let read_tx = db.tx().unwrap();
+        assert!(read_tx.get::<PlainAccountState>(Address::from([1; 20])).unwrap().is_some());
+    }
+
+    #[test]
+    fn test_staged_changes() {
+        let (db, _temp_dir) = create_test_rocksdb(RocksDBConfig::default());
+
+        let put_key = B256::from([1; 32]);
+        let delete_key = B256::from([2; 32]);
+
+        let write_tx = db.tx_mut().unwrap();
+        write_tx.put::<HashedAccounts>(put_key, test_account(1)).unwrap();
+        write_tx.delete::<HashedAccounts>(delete_key, None).unwrap();
+        write_tx.clear::<PlainAccountState>().unwrap();
+
+        assert_eq!(
+            write_tx.staged_changes(),
+            vec![
+                StagedOp::Put { cf: HashedAccounts::NAME, key: put_key.to_vec() },
+                StagedOp::Delete { cf: HashedAccounts::NAME, key: delete_key.to_vec() },
+                StagedOp::DeleteRange {
+                    cf: PlainAccountState::NAME,
+                    from: Vec::new(),
+                    to: vec![255u8; 32],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_scan_all_parallel_flags_corrupt_table() {
+        let (db, _temp_dir) = create_test_rocksdb(RocksDBConfig::default());
+
+        let write_tx = db.tx_mut().unwrap();
+        for i in 1..=10u8 {
+            write_tx.put::<HashedAccounts>(B256::from([i; 32]), test_account(i as u64)).unwrap();
+            write_tx
+                .put::<PlainAccountState>(Address::from([i; 20]), test_account(i as u64))
+                .unwrap();
+        }
+        write_tx.commit().unwrap();
+
+        // Inject a value that isn't a valid trie node
+        let bad_key = B256::from([0xab; 32]);
+        let raw = db.inner();
+        raw.put_cf(raw.cf_handle(TrieTable::NAME).unwrap(), bad_key, [0u8, 1, 2, 3]).unwrap();
+
+        let sequential = db.scan_all().unwrap();
+        let parallel = db.scan_all_parallel(4, &AtomicBool::new(false)).unwrap();
+
+        assert_eq!(parallel, sequential);
+        assert!(!parallel.cancelled);
+        assert_eq!(parallel.corrupt_tables(), vec![TrieTable::NAME]);
+
+        let trie = parallel.tables.iter().find(|report| report.table == TrieTable::NAME).unwrap();
+        assert_eq!(trie.corrupt_keys, vec![bad_key.to_vec()]);
+        let accounts =
+            parallel.tables.iter().find(|report| report.table == HashedAccounts::NAME).unwrap();
+        assert_eq!(accounts.entries, 10);
+        assert!(accounts.is_clean());
+
+        // A cancelled scan reports nothing it didn't finish
+        let cancelled = db.scan_all_parallel(4, &AtomicBool::new(true)).unwrap();
+        assert!(cancelled.cancelled);
+        assert!(cancelled.tables.is_empty());
+    }
+
+    #[test]
+    fn test_shutdown_leaves_nothing_to_replay() {
+        let (db, temp_dir) = create_test_rocksdb(RocksDBConfig::default());
+
+        let write_tx = db.tx_mut().unwrap();
+        for i in 1..=10u8 {
+            write_tx.put::<HashedAccounts>(B256::from([i; 32]), test_account(i as u64)).unwrap();
+        }
+        write_tx.commit().unwrap();
+
+        db.shutdown().unwrap();
+
+        // Everything is in SST files, so the database opens fine without its WAL
+        for entry in std::fs::read_dir(temp_dir.path()).unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().is_some_and(|ext| ext == "log") {
+                std::fs::remove_file(path).unwrap();
+            }
+        }
+
+        let db = RocksDB::open(temp_dir.path(), RocksDBConfig::default()).unwrap();
+        let read_tx = db.tx().unwrap();
+        for i in 1..=10u8 {
+            assert_eq!(
+                read_tx.get::<HashedAccounts>(B256::from([i; 32])).unwrap(),
+                Some(test_account(i as u64))
+            );
+        }
+    }
+
+    #[test]
+    fn test_config_profiles() {
+        for profile in [Profile::FastSync, Profile::Archive, Profile::Dev] {
+            let (db, _temp_dir) = create_test_rocksdb(RocksDBConfig::profile(profile));
+
+            let write_tx = db.tx_mut().unwrap();
+            write_tx.put::<HashedAccounts>(B256::from([1; 32]), test_account(1)).unwrap();
+            write_tx.commit().unwrap();
+
+            let read_tx = db.tx().unwrap();
+            assert_eq!(
+                read_tx.get::<HashedAccounts>(B256::from([1; 32])).unwrap(),
+                Some(test_account(1)),
+                "{profile:?}"
+            );
+        }
+
+        let fast_sync = RocksDBConfig::profile(Profile::FastSync);
+        let archive = RocksDBConfig::profile(Profile::Archive);
+        let dev = RocksDBConfig::profile(Profile::Dev);
+
+        assert_eq!(fast_sync.compaction_style, Some(DBCompactionStyle::Universal));
+        assert_eq!(archive.compaction_style, Some(DBCompactionStyle::Level));
+        assert_eq!(archive.compression, Some(DBCompressionType::Zstd));
+        assert!(archive.paranoid_file_checks && !fast_sync.paranoid_file_checks);
+        assert!(fast_sync.write_buffer_size.unwrap() > dev.write_buffer_size.unwrap());
+
+        // Profiles only set what differs from RocksDB's defaults
+        for profile in [fast_sync, archive, dev] {
+            assert!(!profile.manual_wal_flush);
+            assert_eq!(profile.paranoid_checks, None);
+        }
+
+        // Individual fields can still be tweaked afterwards
+        let tweaked = RocksDBConfig::profile(Profile::Archive).with_block_size(64 * 1024);
+        assert_eq!(tweaked.block_size, Some(64 * 1024));
+        assert_eq!(tweaked.compression, Some(DBCompressionType::Zstd));
+    }
+
+    #[test]
+    fn test_paranoid_file_checks_enabled_on_open() {
+        // RocksDB records the options in effect in an OPTIONS file, rewritten on every change
+        let latest_options = |dir: &TempDir| {
+            let mut files: Vec<_> = std::fs::read_dir(dir.path())
+                .unwrap()
+                .map(|entry| entry.unwrap().path())
+                .filter(|path| path.file_name().unwrap().to_str().unwrap().starts_with("OPTIONS-"))
+                .collect();
+            files.sort_by_key(|path| {
+                path.file_name().unwrap().to_str().unwrap()[8..].parse::<u64>().unwrap()
+            });
+            std::fs::read_to_string(files.last().unwrap()).unwrap()
+        };
+
+        let (_db, temp_dir) = create_test_rocksdb(RocksDBConfig::profile(Profile::Archive));
+        let options = latest_options(&temp_dir);
+        assert!(!options.contains("paranoid_file_checks=false"));
+        assert!(options.contains("paranoid_file_checks=true"));
+
+        let (_db, temp_dir) = create_test_rocksdb(RocksDBConfig::default());
+        assert!(!latest_options(&temp_dir).contains("paranoid_file_checks=true"));
+    }
+
+    #[test]
+    fn test_merged_write_buffers_survive_reopen() {
+        let config = RocksDBConfig {
+            write_buffer_size: Some(64 * 1024),
+            max_write_buffer_number: Some(4),
+            min_write_buffer_number_to_merge: Some(2),
+            ..RocksDBConfig::default()
+        };
+        let (db, temp_dir) = create_test_rocksdb(config.clone());
+
+        // Enough data to fill several 64KB memtables
+        let key = |i: u64| B256::from(U256::from(i));
+        for batch in 0..10u64 {
+            let write_tx = db.tx_mut().unwrap();
+            for i in batch * 1_000..(batch + 1) * 1_000 {
+                write_tx.put::<HashedAccounts>(key(i), test_account(i)).unwrap();
+            }
+            write_tx.commit().unwrap();
+        }
+        drop(db);
+
+        let db = RocksDB::open(temp_dir.path(), config).unwrap();
+        let read_tx = db.tx().unwrap();
+        assert_eq!(read_tx.entries::<HashedAccounts>().unwrap(), 10_000);
+        for i in (0..10_000u64).step_by(97) {
+            assert_eq!(read_tx.get::<HashedAccounts>(key(i)).unwrap(), Some(test_account(i)));
+        }
+    }
+
+    #[test]
+    fn test_export_trie_reth_format() {
+        let (db, _temp_dir) = create_test_rocksdb(RocksDBConfig::default());
+
+        let node = create_test_branch_node();
+        let account_path = Nibbles::from_nibbles([1, 2, 3]);
+        let storage_path = Nibbles::from_nibbles([4, 5]);
+        let hashed_address = B256::from([7; 32]);
+
+        let stored = StoredTrieNode::from(&node);
+        let write_tx = db.tx_mut().unwrap();
+        write_tx.put::<AccountTrieTable>(TrieNibbles(account_path.clone()), node.clone()).unwrap();
+        write_tx.put::<TrieTable>(stored.hash(), stored.clone()).unwrap();
+        write_tx
+            .put::<StorageTrieTable>(
+                hashed_address,
+                TrieNodeValue { nibbles: StoredNibbles(storage_path.clone()), node: stored.hash() },
+            )
+            .unwrap();
+        write_tx.commit().unwrap();
+
+        let mut out = Vec::new();
+        db.export_trie_reth_format(&mut out).unwrap();
+
+        // Split the export into its (table, key, value) records
+        let mut fields = Vec::new();
+        let mut rest = out.as_slice();
+        while !rest.is_empty() {
+            let (len, tail) = rest.split_at(4);
+            let len = u32::from_be_bytes(len.try_into().unwrap()) as usize;
+            let (field, tail) = tail.split_at(len);
+            fields.push(field.to_vec());
+            rest = tail;
+        }
+        let records: Vec<_> = fields.chunks(3).map(|record| record.to_vec()).collect();
+
+        // Identical to how reth itself stores the entries
+        let expected_storage =
+            StorageTrieEntry { nibbles: StoredNibblesSubKey(storage_path), node: node.clone() };
+        assert_eq!(
+            records,
+            vec![
+                vec![
+                    AccountsTrie::NAME.as_bytes().to_vec(),
+                    StoredNibbles(account_path).encode(),
+                    node.compress(),
+                ],
+                vec![
+                    StoragesTrie::NAME.as_bytes().to_vec(),
+                    hashed_address.to_vec(),
+                    expected_storage.compress(),
+                ],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_open_read_only_while_open_elsewhere() {
+        let (db, temp_dir) = create_test_rocksdb(RocksDBConfig::default());
+
+        let write_tx = db.tx_mut().unwrap();
+        write_tx.put::<HashedAccounts>(B256::from([1; 32]), test_account(1)).unwrap();
+        write_tx.commit().unwrap();
+
+        // A second read-write open is refused because of the LOCK file
+        assert!(RocksDB::open(temp_dir.path(), RocksDBConfig::default()).is_err());
+
+        // The read-only open doesn't take the lock and sees the committed data
+        let read_only =
+            RocksDB::open_read_only(temp_dir.path(), RocksDBConfig::default(), false).unwrap();
+        assert!(read_only.is_read_only());
+        assert_eq!(
+            read_only.tx().unwrap().get::<HashedAccounts>(B256::from([1; 32])).unwrap(),
+            Some(test_account(1))
+        );
+        assert!(read_only.tx_mut().is_err());
+    }
+
+    #[test]
+    fn test_post_commit_hook() {
+        let (db, _temp_dir) = create_test_rocksdb(RocksDBConfig::default());
+        let calls = Arc::new(std::sync::Mutex::new(Vec::<Vec<StagedOp>>::new()));
+        {
+            let calls = calls.clone();
+            db.set_post_commit_hook(Box::new(move |ops| {
+                calls.lock().unwrap().push(ops.to_vec());
+            }));
+        }
+
+        let key = |i: u8| B256::with_last_byte(i);
+        let encoded = |i: u8| key(i).encode().to_vec();
+
+        let write_tx = db.tx_mut().unwrap();
+        write_tx.put::<HashedAccounts>(key(1), test_account(1)).unwrap();
+        write_tx.put::<HashedAccounts>(key(2), test_account(2)).unwrap();
+        write_tx.delete::<HashedAccounts>(key(1), None).unwrap();
+        write_tx.commit().unwrap();
+        assert_eq!(
+            *calls.lock().unwrap(),
+            vec![vec![
+                StagedOp::Put { cf: HashedAccounts::NAME, key: encoded(1) },
+                StagedOp::Put { cf: HashedAccounts::NAME, key: encoded(2) },
+                StagedOp::Delete { cf: HashedAccounts::NAME, key: encoded(1) },
+            ]]
+        );
+
+        // Aborted, dropped and read-only transactions don't run it
+        let write_tx = db.tx_mut().unwrap();
+        write_tx.put::<HashedAccounts>(key(3), test_account(3)).unwrap();
+        write_tx.abort();
+        let write_tx = db.tx_mut().unwrap();
+        write_tx.put::<HashedAccounts>(key(4), test_account(4)).unwrap();
+        drop(write_tx);
+        let read_tx = db.tx().unwrap();
+        assert!(read_tx.get::<HashedAccounts>(key(2)).unwrap().is_some());
+        read_tx.commit().unwrap();
+        assert_eq!(calls.lock().unwrap().len(), 1);
+
+        // Neither do commits that fail to write: sync writes need the WAL
+        let write_tx = db.transaction_builder().sync(true).disable_wal(true).write().unwrap();
+        write_tx.put::<HashedAccounts>(key(6), test_account(6)).unwrap();
+        assert!(write_tx.commit().is_err());
+        assert_eq!(calls.lock().unwrap().len(), 1);
+        assert!(db.tx().unwrap().get::<HashedAccounts>(key(6)).unwrap().is_none());
+
+        // Nor transactions created without the database, which don't know about the hook
+        let write_tx = RocksTransaction::<true>::from_db(db.inner());
+        write_tx.put::<HashedAccounts>(key(7), test_account(7)).unwrap();
+        write_tx.commit().unwrap();
+        assert_eq!(calls.lock().unwrap().len(), 1);
+
+        // Each successful commit runs it once
+        let write_tx = db.tx_mut().unwrap();
+        write_tx.put::<HashedAccounts>(key(5), test_account(5)).unwrap();
+        write_tx.commit().unwrap();
+        let calls = calls.lock().unwrap();
+        assert_eq!(calls.len(), 2);
+        assert_eq!(calls[1], vec![StagedOp::Put { cf: HashedAccounts::NAME, key: encoded(5) }]);
+    }
+
+    #[test]
+    fn test_bounded_writer_applies_backpressure() {
+        let (db, _temp_dir) = create_test_rocksdb(RocksDBConfig::default());
+        let writer = db.bounded_writer(2_000).unwrap();
+
+        // Stage until the writer pushes back
+        let first = writer.tx_mut();
+        let mut staged = 0u64;
+        let err = loop {
+            match first.put::<HashedAccounts>(B256::from(U256::from(staged)), test_account(staged))
+            {
+                Ok(()) => staged += 1,
+                Err(err) => break err,
+            }
+        };
+        assert!(staged > 0);
+        match &err {
+            DatabaseError::Write(err) => {
+                assert_eq!(err.info.code, RocksDBError::WOULD_EXCEED_LIMIT_CODE);
+                assert_eq!(err.table_name, HashedAccounts::NAME);
+                assert_eq!(err.key, B256::from(U256::from(staged)).to_vec());
+            }
+            other => panic!("expected a refused write, got {other:?}"),
+        }
+        assert_eq!(RocksDBError::code_of(&err), Some(RocksDBError::WOULD_EXCEED_LIMIT_CODE));
+        assert!(writer.pending_bytes() <= writer.max_pending_bytes());
+        assert!(writer.pending_bytes() > 0);
+
+        // The budget is shared, so another transaction can't stage either
+        let second = writer.tx_mut();
+        assert!(second.put::<HashedAccounts>(B256::repeat_byte(0xff), test_account(0)).is_err());
+
+        // Committing frees the budget
+        first.commit().unwrap();
+        assert_eq!(writer.pending_bytes(), 0);
+        second.put::<HashedAccounts>(B256::repeat_byte(0xff), test_account(0)).unwrap();
+        assert!(writer.pending_bytes() > 0);
+        drop(second);
+        assert_eq!(writer.pending_bytes(), 0);
+
+        // Everything staged before the refused write was committed, and nothing after it
+        let read_tx = db.tx().unwrap();
+        assert_eq!(read_tx.entries::<HashedAccounts>().unwrap(), staged as usize);
+        assert_eq!(
+            read_tx.get::<HashedAccounts>(B256::from(U256::from(0u64))).unwrap(),
+            Some(test_account(0))
+        );
+    }
+
+    #[test]
+    fn test_group_commit_syncs_once_per_group() {
+        let (db, temp_dir) = create_test_rocksdb(RocksDBConfig::default());
+        let committer =
+            db.group_committer(GroupCommitPolicy::new(10, Duration::from_secs(3600))).unwrap();
+
+        for i in 0..100u64 {
+            let tx = db.tx_mut().unwrap();
+            tx.put::<HashedAccounts>(B256::with_last_byte(i as u8), test_account(i)).unwrap();
+            committer.commit(tx).unwrap();
+        }
+        assert_eq!(committer.pending(), 0);
+        assert_eq!(committer.synced_writes(), 10);
+
+        // A partial group is only written once flushed
+        let tx = db.tx_mut().unwrap();
+        tx.put::<HashedAccounts>(B256::with_last_byte(100), test_account(100)).unwrap();
+        committer.commit(tx).unwrap();
+        assert_eq!(committer.pending(), 1);
+        assert!(db
+            .tx()
+            .unwrap()
+            .get::<HashedAccounts>(B256::with_last_byte(100))
+            .unwrap()
+            .is_none());
+        committer.flush().unwrap();
+        assert_eq!(committer.synced_writes(), 11);
+
+        drop(committer);
+        drop(db);
+
+        let db = RocksDB::open(temp_dir.path(), RocksDBConfig::default()).unwrap();
+        let tx = db.tx().unwrap();
+        for i in 0..=100u64 {
+            assert_eq!(
+                tx.get::<HashedAccounts>(B256::with_last_byte(i as u8)).unwrap(),
+                Some(test_account(i))
+            );
+        }
+    }
+
+    #[test]
+    fn test_group_commit_syncs_after_delay() {
+        let (db, _temp_dir) = create_test_rocksdb(RocksDBConfig::default());
+        let committer =
+            db.group_committer(GroupCommitPolicy::new(1000, Duration::from_millis(50))).unwrap();
+
+        let tx = db.tx_mut().unwrap();
+        tx.put::<HashedAccounts>(B256::with_last_byte(1), test_account(1)).unwrap();
+        committer.commit(tx).unwrap();
+        assert_eq!(committer.synced_writes(), 0);
+
+        std::thread::sleep(Duration::from_millis(60));
+        let tx = db.tx_mut().unwrap();
+        tx.put::<HashedAccounts>(B256::with_last_byte(2), test_account(2)).unwrap();
+        committer.commit(tx).unwrap();
+        assert_eq!(committer.synced_writes(), 1);
+        assert_eq!(committer.pending(), 0);
+    }
+
+    #[test]
+    fn test_group_commit_syncs_idle_group_after_delay() {
+        let (db, _temp_dir) = create_test_rocksdb(RocksDBConfig::default());
+        let committer =
+            db.group_committer(GroupCommitPolicy::new(1000, Duration::from_millis(50))).unwrap();
+
+        let tx = db.tx_mut().unwrap();
+        tx.put::<HashedAccounts>(B256::with_last_byte(1), test_account(1)).unwrap();
+        committer.commit(tx).unwrap();
+        assert_eq!(committer.pending(), 1);
+
+        // No further commit comes along, the group is written once its delay runs out
+        let started = Instant::now();
+        while committer.pending() > 0 {
+            assert!(started.elapsed() < Duration::from_secs(5), "idle group was never written");
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(committer.synced_writes(), 1);
+        assert_eq!(
+            db.tx().unwrap().get::<HashedAccounts>(B256::with_last_byte(1)).unwrap(),
+            Some(test_account(1))
+        );
+    }
+
+    #[test]
+    fn test_group_commit_runs_post_commit_hook_once_written() {
+        let (db, _temp_dir) = create_test_rocksdb(RocksDBConfig::default());
+        let calls = Arc::new(std::sync::Mutex::new(Vec::<Vec<StagedOp>>::new()));
+        {
+            let calls = calls.clone();
+            db.set_post_commit_hook(Box::new(move |ops| {
+                calls.lock().unwrap().push(ops.to_vec());
+            }));
+        }
+        let committer =
+            db.group_committer(GroupCommitPolicy::new(10, Duration::from_secs(3600))).unwrap();
+
+        let key = B256::with_last_byte(1);
+        let tx = db.tx_mut().unwrap();
+        tx.put::<HashedAccounts>(key, test_account(1)).unwrap();
+        committer.commit(tx).unwrap();
+
+        // Held back with its group, so not committed yet
+        assert!(calls.lock().unwrap().is_empty());
+
+        committer.flush().unwrap();
+        assert_eq!(
+            *calls.lock().unwrap(),
+            vec![vec![StagedOp::Put { cf: HashedAccounts::NAME, key: key.encode().to_vec() }]]
+        );
+    }
+
+    #[test]
+    fn test_scan_without_cache_fill_leaves_block_cache_alone() {
+        let (db, _temp_dir) = create_test_rocksdb(RocksDBConfig::default());
+
+        let write_tx = db.tx_mut().unwrap();
+        for i in 0..20_000u64 {
+            write_tx
+                .put::<HashedAccounts>(B256::left_padding_from(&i.to_be_bytes()), test_account(i))
+                .unwrap();
+        }
+        write_tx.commit().unwrap();
+        // Move the data out of the memtable so scans read SST blocks
+        db.flush_table::<HashedAccounts>().unwrap();
+
+        let cache_usage = || {
+            db.property_int_value_cf::<HashedAccounts>("rocksdb.block-cache-usage")
+                .unwrap()
+                .unwrap()
+        };
+        fn scan(mut cursor: impl DbCursorRO<HashedAccounts>) -> usize {
+            let mut entries = 0;
+            let mut entry = cursor.first().unwrap();
+            while entry.is_some() {
+                entries += 1;
+                entry = cursor.next().unwrap();
+            }
+            entries
+        }
+
+        let before = cache_usage();
+        let tx = db.tx().unwrap();
+        assert_eq!(scan(tx.cursor_read_no_cache::<HashedAccounts>().unwrap()), 20_000);
+        let after_uncached_scan = cache_usage();
+        assert!(after_uncached_scan < before + 64 * 1024);
+
+        assert_eq!(scan(tx.cursor_read::<HashedAccounts>().unwrap()), 20_000);
+        assert!(cache_usage() > after_uncached_scan + 256 * 1024);
+    }
+
+    #[test]
+    fn test_transaction_builder() {
+        let (db, temp_dir) = create_test_rocksdb(RocksDBConfig::default());
+
+        let write_tx = db.transaction_builder().sync(true).write().unwrap();
+        write_tx.put::<HashedAccounts>(B256::with_last_byte(1), test_account(1)).unwrap();
+        write_tx.commit().unwrap();
+
+        let pinned = db.transaction_builder().snapshot(true).readahead_size(2 * 1024 * 1024).read();
+        let unpinned = db.transaction_builder().snapshot(false).read();
+
+        let write_tx = db.transaction_builder().sync(true).write().unwrap();
+        write_tx.put::<HashedAccounts>(B256::with_last_byte(2), test_account(2)).unwrap();
+        write_tx.commit().unwrap();
+
+        // Only the snapshot-less transaction sees the later commit
+        assert_eq!(
+            pinned.get::<HashedAccounts>(B256::with_last_byte(1)).unwrap(),
+            Some(test_account(1))
+        );
+        assert!(pinned.get::<HashedAccounts>(B256::with_last_byte(2)).unwrap().is_none());
+        assert_eq!(
+            unpinned.get::<HashedAccounts>(B256::with_last_byte(2)).unwrap(),
+            Some(test_account(2))
+        );
+        drop((pinned, unpinned));
+
+        // Synced commits survive reopening
+        drop(db);
+        let db = RocksDB::open(temp_dir.path(), RocksDBConfig::default()).unwrap();
+        let tx = db.tx().unwrap();
+        for i in 1..=2 {
+            assert_eq!(
+                tx.get::<HashedAccounts>(B256::with_last_byte(i)).unwrap(),
+                Some(test_account(i as u64))
+            );
+        }
+    }
+
+    #[test]
+    fn test_open_file_count_with_low_max_open_files() {
+        // RocksDB raises anything lower to 20
+        let config = RocksDBConfig::default().with_max_open_files(20);
+        let (db, _temp_dir) = create_test_rocksdb(config);
+
+        // Flush every batch into its own SST file, more than may be kept open
+        for batch in 0..30u64 {
+            let write_tx = db.tx_mut().unwrap();
+            for i in 0..10 {
+                let key = batch * 10 + i;
+                write_tx
+                    .put::<HashedAccounts>(
+                        B256::left_padding_from(&key.to_be_bytes()),
+                        test_account(key),
+                    )
+                    .unwrap();
+            }
+            write_tx.commit().unwrap();
+            db.flush_table::<HashedAccounts>().unwrap();
+        }
+
+        let count = db.open_file_count().unwrap();
+        assert!(count >= 1);
+
+        // Files are reopened as needed to serve reads
+        let tx = db.tx().unwrap();
+        for key in 0..300u64 {
+            assert_eq!(
+                tx.get::<HashedAccounts>(B256::left_padding_from(&key.to_be_bytes())).unwrap(),
+                Some(test_account(key))
+            );
+        }
+    }
+
+    #[test]
+    fn test_indexed_writes_are_read_before_commit() {
+        let (db, _temp_dir) = create_test_rocksdb(RocksDBConfig::default());
+        let key = B256::with_last_byte;
+
+        let write_tx = db.tx_mut().unwrap();
+        for i in 1..=3 {
+            write_tx.put::<HashedAccounts>(key(i), test_account(i as u64)).unwrap();
+        }
+        write_tx.commit().unwrap();
+
+        let tx = db.transaction_builder().index_writes(true).write().unwrap();
+        tx.put::<HashedAccounts>(key(2), test_account(20)).unwrap();
+        tx.delete::<HashedAccounts>(key(3), None).unwrap();
+        tx.put::<HashedAccounts>(key(4), test_account(4)).unwrap();
+        tx.put::<HashedAccounts>(key(5), test_account(5)).unwrap();
+        tx.put::<HashedAccounts>(key(5), test_account(50)).unwrap();
+
+        // Point reads see the staged writes over the database
+        assert_eq!(tx.get::<HashedAccounts>(key(1)).unwrap(), Some(test_account(1)));
+        assert_eq!(tx.get::<HashedAccounts>(key(2)).unwrap(), Some(test_account(20)));
+        assert_eq!(tx.get::<HashedAccounts>(key(3)).unwrap(), None);
+        assert_eq!(tx.get::<HashedAccounts>(key(4)).unwrap(), Some(test_account(4)));
+        assert_eq!(tx.get::<HashedAccounts>(key(5)).unwrap(), Some(test_account(50)));
+
+        // So does a walk over the table
+        let expected = vec![
+            (key(1), test_account(1)),
+            (key(2), test_account(20)),
+            (key(4), test_account(4)),
+            (key(5), test_account(50)),
+        ];
+        let merged =
+            tx.iter_merged::<HashedAccounts>().unwrap().collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(merged, expected);
+
+        // Nothing is visible to other transactions until commit, after which the database
+        // matches the merged view
+        assert_eq!(db.tx().unwrap().get::<HashedAccounts>(key(4)).unwrap(), None);
+        tx.commit().unwrap();
+        let read_tx = db.tx().unwrap();
+        let mut cursor = read_tx.cursor_read::<HashedAccounts>().unwrap();
+        let committed = cursor.walk(None).unwrap().collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(committed, expected);
+    }
+
+    #[test]
+    fn test_indexed_writes_staged_while_iterating() {
+        let (db, _temp_dir) = create_test_rocksdb(RocksDBConfig::default());
+        let key = B256::with_last_byte;
+
+        let tx = db.transaction_builder().index_writes(true).write().unwrap();
+        tx.put::<HashedAccounts>(key(1), test_account(1)).unwrap();
+        tx.put::<HashedAccounts>(key(2), test_account(2)).unwrap();
+
+        // Writes can be staged while the iterator is alive, which keeps seeing the writes
+        // staged before it was created
+        let mut iter = tx.iter_merged::<HashedAccounts>().unwrap();
+        assert_eq!(iter.next().unwrap().unwrap(), (key(1), test_account(1)));
+        tx.put::<HashedAccounts>(key(2), test_account(20)).unwrap();
+        tx.put::<HashedAccounts>(key(3), test_account(3)).unwrap();
+        assert_eq!(iter.next().unwrap().unwrap(), (key(2), test_account(2)));
+        assert!(iter.next().is_none());
+        drop(iter);
+
+        assert_eq!(tx.get::<HashedAccounts>(key(2)).unwrap(), Some(test_account(20)));
+        let merged =
+            tx.iter_merged::<HashedAccounts>().unwrap().collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(
+            merged,
+            vec![(key(1), test_account(1)), (key(2), test_account(20)), (key(3), test_account(3))]
+        );
+    }
+
+    #[test]
+    fn test_indexed_writes_after_clear() {
+        let (db, _temp_dir) = create_test_rocksdb(RocksDBConfig::default());
+        let key = B256::with_last_byte;
+
+        let write_tx = db.tx_mut().unwrap();
+        write_tx.put::<HashedAccounts>(key(1), test_account(1)).unwrap();
+        write_tx.commit().unwrap();
+
+        let tx = db.transaction_builder().index_writes(true).write().unwrap();
+        tx.put::<HashedAccounts>(key(3), test_account(3)).unwrap();
+        tx.clear::<HashedAccounts>().unwrap();
+        tx.put::<HashedAccounts>(key(2), test_account(2)).unwrap();
+
+        // Only writes staged after the clear survive it
+        assert_eq!(tx.get::<HashedAccounts>(key(1)).unwrap(), None);
+        assert_eq!(tx.get::<HashedAccounts>(key(3)).unwrap(), None);
+        assert_eq!(tx.get::<HashedAccounts>(key(2)).unwrap(), Some(test_account(2)));
+        let merged =
+            tx.iter_merged::<HashedAccounts>().unwrap().collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(merged, vec![(key(2), test_account(2))]);
+
+        // The clear is committed as a range delete, ahead of the writes staged after it
+        tx.commit().unwrap();
+        let read_tx = db.tx().unwrap();
+        let mut cursor = read_tx.cursor_read::<HashedAccounts>().unwrap();
+        let committed = cursor.walk(None).unwrap().collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(committed, vec![(key(2), test_account(2))]);
+
+        // Without the index, reads only see committed data
+        let unindexed = db.tx_mut().unwrap();
+        unindexed.put::<HashedAccounts>(key(3), test_account(3)).unwrap();
+        assert_eq!(unindexed.get::<HashedAccounts>(key(3)).unwrap(), None);
+    }
+
+    #[test]
+    fn test_read_only_commit_is_noop() {
+        let (db, _temp_dir) = create_test_rocksdb(RocksDBConfig::default());
+
+        let write_tx = db.tx_mut().unwrap();
+        write_tx.put::<HashedAccounts>(B256::from([1; 32]), test_account(1)).unwrap();
+        write_tx.commit().unwrap();
+
+        let read_tx = db.tx().unwrap();
+        assert_eq!(
+            read_tx.get::<HashedAccounts>(B256::from([1; 32])).unwrap(),
+            Some(test_account(1))
+        );
+        assert!(read_tx.commit().unwrap());
+
+        assert_eq!(db.tx().unwrap().entries::<HashedAccounts>().unwrap(), 1);
+    }
+
+    #[test]
+    fn test_write_commit_reports_write_failure() {
+        let (db, temp_dir) = create_test_rocksdb(RocksDBConfig::default());
+        drop(db);
+
+        // Bypass the read-only check on `tx_mut` so the failure comes from the write itself
+        let read_only =
+            RocksDB::open_read_only(temp_dir.path(), RocksDBConfig::default(), false).unwrap();
+        let tx = RocksTransaction::<true>::from_db(read_only.inner());
+        tx.put::<HashedAccounts>(B256::from([1; 32]), test_account(1)).unwrap();
+
+        assert!(tx.commit().is_err());
+    }
+
+    /// Value whose compression panics, used to poison a transaction's batch lock
+    #[derive(Debug, serde::Serialize, serde::Deserialize)]
+    struct PanicOnCompress;
+
+    impl Compress for PanicOnCompress {
+        type Compressed = Vec<u8>;
+
+        fn compress_to_buf<B: bytes::BufMut + AsMut<[u8]>>(&self, _buf: &mut B) {
+            panic!("compression failed");
+        }
+    }
+
+    impl Decompress for PanicOnCompress {
+        fn decompress(_value: &[u8]) -> Result<Self, DatabaseError> {
+            Ok(Self)
+        }
+    }
+
+    /// Test-only table in its own scratch column family
+    #[derive(Debug)]
+    struct PanicTable;
+
+    impl Table for PanicTable {
+        const NAME: &'static str = "scratch_panic";
+        const DUPSORT: bool = false;
+
+        type Key = B256;
+        type Value = PanicOnCompress;
+    }
+
+    #[test]
+    fn test_write_commit_rejects_poisoned_batch() {
+        let (db, _temp_dir) =
+            create_test_rocksdb_with_tables(RocksDBConfig::default(), &[PanicTable::NAME]);
+
+        let tx = db.tx_mut().unwrap();
+        tx.put::<HashedAccounts>(B256::from([1; 32]), test_account(1)).unwrap();
+
+        // Panicking while the batch is locked leaves it poisoned
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            tx.put::<PanicTable>(B256::from([2; 32]), PanicOnCompress)
+        }));
+        assert!(result.is_err());
+
+        let err = tx.commit().unwrap_err();
+        assert!(err.to_string().contains("write batch lock poisoned"), "{err}");
+
+        // Nothing from the poisoned transaction reached the database
+        assert_eq!(db.tx().unwrap().get::<HashedAccounts>(B256::from([1; 32])).unwrap(), None);
+    }
+
+    #[test]
+    fn test_archive_round_trip() {
+        let (db, _temp_dir) = create_test_rocksdb(RocksDBConfig::default());
+
+        let write_tx = db.tx_mut().unwrap();
+        for i in 0..100u64 {
+            write_tx.put::<HashedAccounts>(B256::with_last_byte(i as u8), test_account(i)).unwrap();
+            write_tx
+                .put::<PlainAccountState>(Address::with_last_byte(i as u8), test_account(i))
+                .unwrap();
+        }
+        let node = create_test_branch_node();
+        write_tx
+            .put::<AccountTrieTable>(TrieNibbles(Nibbles::from_nibbles([1, 2, 3])), node.clone())
+            .unwrap();
+        let stored = StoredTrieNode::from(&node);
+        write_tx.put::<TrieTable>(stored.hash(), stored).unwrap();
+        write_tx.commit().unwrap();
+
+        let mut archive = Vec::new();
+        db.export_archive(&mut archive).unwrap();
+
+        let import_dir = TempDir::new().unwrap();
+        let imported = RocksDB::import_archive(
+            archive.as_slice(),
+            RocksDBConfig::default(),
+            import_dir.path(),
+        )
+        .unwrap();
+
+        // Every table matches byte for byte
+        let dump = |db: &RocksDB| {
+            let inner = db.inner();
+            db.table_names()
+                .unwrap()
+                .into_iter()
+                .map(|name| {
+                    let cf = inner.cf_handle(&name).unwrap();
+                    let entries = inner
+                        .iterator_cf(cf, IteratorMode::Start)
+                        .collect::<Result<Vec<_>, _>>()
+                        .unwrap();
+                    (name, entries)
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(dump(&imported), dump(&db));
+        assert_eq!(imported.tx().unwrap().entries::<HashedAccounts>().unwrap(), 100);
+
+        let version = |db: &RocksDB| VersionManager::new(&db.inner()).unwrap().current_version();
+        assert_eq!(version(&imported), version(&db));
+
+        // Importing over an existing database is refused
+        assert!(RocksDB::import_archive(
+            archive.as_slice(),
+            RocksDBConfig::default(),
+            import_dir.path()
+        )
+        .is_err());
+        drop(imported);
+
+        // A corrupted archive fails the checksum
+        let middle = archive.len() / 2;
+        archive[middle] ^= 0xFF;
+        let corrupt_dir = TempDir::new().unwrap();
+        assert!(RocksDB::import_archive(
+            archive.as_slice(),
+            RocksDBConfig::default(),
+            corrupt_dir.path()
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_export_resumable_across_prefixes() {
+        let (db, _temp_dir) = create_test_rocksdb(RocksDBConfig::default());
+        let inner = db.inner();
+        let node = create_test_branch_node();
+
+        // Each prefix of the account trie's bloom in its own SST file, so a resumed seek in
+        // prefix mode would skip the files of the following prefixes
+        for first in 0..8u8 {
+            let write_tx = db.tx_mut().unwrap();
+            for last in 0..4u8 {
+                let path = TrieNibbles(Nibbles::from_nibbles([first, 1, 2, 3, 4, 5, 6, 7, last]));
+                write_tx.put::<AccountTrieTable>(path, node.clone()).unwrap();
+            }
+            write_tx.commit().unwrap();
+            inner.flush_cf(inner.cf_handle(AccountTrieTable::NAME).unwrap()).unwrap();
+        }
+
+        let mut full = Vec::new();
+        db.export_archive(&mut full).unwrap();
+
+        let mut chunked = Cursor::new(Vec::new());
+        let mut checkpoint = db.export_resumable(&mut chunked, None, Some(64)).unwrap();
+        while !checkpoint.is_complete() {
+            checkpoint = db.export_resumable(&mut chunked, Some(checkpoint), Some(64)).unwrap();
+        }
+        chunked.get_mut().truncate(checkpoint.offset() as usize);
+        assert_eq!(chunked.get_ref(), &full);
+    }
+
+    #[test]
+    fn test_export_resumable() {
+        let (db, _temp_dir) = create_test_rocksdb(RocksDBConfig::default());
+
+        let write_tx = db.tx_mut().unwrap();
+        for i in 0..100u64 {
+            write_tx.put::<HashedAccounts>(B256::with_last_byte(i as u8), test_account(i)).unwrap();
+            write_tx
+                .put::<PlainAccountState>(Address::with_last_byte(i as u8), test_account(i))
+                .unwrap();
+        }
+        write_tx.commit().unwrap();
+
+        // Without a budget, a single call exports everything
+        let mut full = Cursor::new(Vec::new());
+        let checkpoint = db.export_resumable(&mut full, None, None).unwrap();
+        assert!(checkpoint.is_complete());
+        assert_eq!(checkpoint.table(), None);
+        let mut archive = Vec::new();
+        db.export_archive(&mut archive).unwrap();
+        assert_eq!(full.get_ref(), &archive);
+
+        // A tiny budget takes many calls, all reading through the snapshot of the first one
+        let mut chunked = Cursor::new(Vec::new());
+        let mut checkpoint = db.export_resumable(&mut chunked, None, Some(64)).unwrap();
+        let first_offset = checkpoint.offset();
+        assert!(!checkpoint.is_complete());
+
+        let write_tx = db.tx_mut().unwrap();
+        write_tx.put::<HashedAccounts>(B256::with_last_byte(200), test_account(200)).unwrap();
+        write_tx.commit().unwrap();
+
+        let mut calls = 1;
+        while !checkpoint.is_complete() {
+            // Garbage left at the end by an interrupted call is overwritten on resume
+            chunked.get_mut().extend_from_slice(&[0xFF; 16]);
+            checkpoint = db.export_resumable(&mut chunked, Some(checkpoint), Some(64)).unwrap();
+            calls += 1;
+        }
+        assert!(calls > 10, "{calls} calls");
+        assert!(first_offset < checkpoint.offset());
+        chunked.get_mut().truncate(checkpoint.offset() as usize);
+        assert_eq!(chunked.get_ref(), full.get_ref());
+
+        // Resuming a complete export writes nothing more
+        let offset = checkpoint.offset();
+        let checkpoint = db.export_resumable(&mut chunked, Some(checkpoint), Some(64)).unwrap();
+        assert_eq!(checkpoint.offset(), offset);
+
+        // A checkpoint only resumes on the database that created it
+        let (other, _other_dir) = create_test_rocksdb(RocksDBConfig::default());
+        assert!(other.export_resumable(&mut chunked, Some(checkpoint), None).is_err());
+
+        let import_dir = TempDir::new().unwrap();
+        let imported = RocksDB::import_archive(
+            full.get_ref().as_slice(),
+            RocksDBConfig::default(),
+            import_dir.path(),
+        )
+        .unwrap();
+        assert_eq!(imported.tx().unwrap().entries::<HashedAccounts>().unwrap(), 100);
+    }
+
+    #[test]
+    fn test_consistent_snapshot() {
+        let (db, _temp_dir) = create_test_rocksdb(RocksDBConfig::default());
+        let write_round = |tx: &RocksTransaction<true>, round: u64| {
+            for i in 0..10u8 {
+                tx.put::<HashedAccounts>(B256::with_last_byte(i), test_account(round)).unwrap();
+                tx.put::<PlainAccountState>(Address::with_last_byte(i), test_account(round))
+                    .unwrap();
+            }
+        };
+
+        let tx = db.tx_mut().unwrap();
+        write_round(&tx, 0);
+        tx.commit().unwrap();
+
+        let snapshot_dir = TempDir::new().unwrap();
+        let checkpoint = snapshot_dir.path().join("checkpoint");
+        std::thread::scope(|scope| {
+            // An open write transaction holds the snapshot back until it commits
+            let open_tx = db.tx_mut().unwrap();
+            write_round(&open_tx, 1);
+
+            let snapshot = scope.spawn(|| db.consistent_snapshot(&checkpoint).unwrap());
+            std::thread::sleep(Duration::from_millis(100));
+
+            // Meanwhile new write transactions wait for the snapshot to be taken
+            let blocked = scope.spawn(|| {
+                let tx = db.tx_mut().unwrap();
+                assert!(checkpoint.join("CURRENT").exists());
+                write_round(&tx, 2);
+                tx.commit().unwrap();
+            });
+            std::thread::sleep(Duration::from_millis(100));
+            assert!(!snapshot.is_finished());
+            assert!(!blocked.is_finished());
+
+            open_tx.commit().unwrap();
+            snapshot.join().unwrap();
+            blocked.join().unwrap();
+        });
+
+        // The snapshot holds round 1 in both tables, and none of round 2
+        let snapshot = RocksDB::open(&checkpoint, RocksDBConfig::default()).unwrap();
+        let read_tx = snapshot.tx().unwrap();
+        for i in 0..10u8 {
+            assert_eq!(
+                read_tx.get::<HashedAccounts>(B256::with_last_byte(i)).unwrap(),
+                Some(test_account(1))
+            );
+            assert_eq!(
+                read_tx.get::<PlainAccountState>(Address::with_last_byte(i)).unwrap(),
+                Some(test_account(1))
+            );
+        }
+        assert_eq!(
+            db.tx().unwrap().get::<HashedAccounts>(B256::with_last_byte(0)).unwrap(),
+            Some(test_account(2))
+        );
+
+        // The checkpoint directory must be new
+        assert!(db.consistent_snapshot(&checkpoint).is_err());
+    }
+
+    #[test]
+    fn test_write_gate_holds_back_from_db_writes() {
+        let (db, _temp_dir) = create_test_rocksdb(RocksDBConfig::default());
+        let gate = WriteGate::registered(&db.inner()).unwrap();
+
+        // Transactions on the inner database hold no permit, so closing the gate doesn't wait
+        // for them, but their cursor writes and commits wait for it to reopen
+        let closed = gate.close();
+        std::thread::scope(|scope| {
+            let cursor_write = scope.spawn(|| {
+                let tx = RocksTransaction::<true>::from_db(db.inner());
+                let mut cursor = tx.cursor_write::<HashedAccounts>().unwrap();
+                cursor.upsert(B256::with_last_byte(1), &test_account(1)).unwrap();
+            });
+            let commit = scope.spawn(|| {
+                let tx = RocksTransaction::<true>::from_db(db.inner());
+                tx.put::<HashedAccounts>(B256::with_last_byte(2), test_account(2)).unwrap();
+                tx.commit().unwrap();
+            });
+            std::thread::sleep(Duration::from_millis(100));
+            assert!(!cursor_write.is_finished());
+            assert!(!commit.is_finished());
+            assert_eq!(db.tx().unwrap().entries::<HashedAccounts>().unwrap(), 0);
+
+            drop(closed);
+            cursor_write.join().unwrap();
+            commit.join().unwrap();
+        });
+        assert_eq!(db.tx().unwrap().entries::<HashedAccounts>().unwrap(), 2);
+
+        // A database that isn't wrapped has no gate
+        let (inner, _inner_dir) = create_test_db();
+        assert!(WriteGate::registered(&inner).is_none());
+    }
+
+    #[test]
+    fn test_separate_wal_dir() {
+        let data_dir = TempDir::new().unwrap();
+        let wal_dir = TempDir::new().unwrap();
+        let config = RocksDBConfig::default().with_wal_dir(wal_dir.path().to_str().unwrap());
+
+        let db = RocksDB::open(data_dir.path(), config.clone()).unwrap();
+        let write_tx = db.tx_mut().unwrap();
+        for i in 0..100u64 {
+            write_tx.put::<HashedAccounts>(B256::with_last_byte(i as u8), test_account(i)).unwrap();
+        }
+        write_tx.commit().unwrap();
+        drop(db);
+
+        // The log lives in the WAL directory, not next to the data
+        let has_log = |dir: &TempDir| {
+            std::fs::read_dir(dir.path())
+                .unwrap()
+                .any(|entry| entry.unwrap().path().extension().is_some_and(|ext| ext == "log"))
+        };
+        assert!(has_log(&wal_dir));
+        assert!(!has_log(&data_dir));
+
+        // Opening without the WAL directory, or with another one, is refused
+        assert!(RocksDB::open(data_dir.path(), RocksDBConfig::default()).is_err());
+        let other_wal_dir = TempDir::new().unwrap();
+        let other_config =
+            RocksDBConfig::default().with_wal_dir(other_wal_dir.path().to_str().unwrap());
+        assert!(RocksDB::open(data_dir.path(), other_config).is_err());
+
+        let db = RocksDB::open(data_dir.path(), config).unwrap();
+        let read_tx = db.tx().unwrap();
+        assert_eq!(read_tx.entries::<HashedAccounts>().unwrap(), 100);
+        for i in 0..100u64 {
+            assert_eq!(
+                read_tx.get::<HashedAccounts>(B256::with_last_byte(i as u8)).unwrap(),
+                Some(test_account(i))
+            );
+        }
+    }
+
+    #[test]
+    fn test_dupsort_cfs_have_prefix_extractor() {
+        const FIXED_PREFIX: &str = "rocksdb.FixedPrefix.32";
+        const NIBBLE_PREFIX: &str = "rocksdb.FixedPrefix.8";
+
+        // The test database goes through the same table configuration as production
+        let (db, _temp_dir) = build_test_db(TEST_TABLES);
+        for name in TEST_TABLES {
+            let dupsort = [HashedStorages::NAME, StorageTrieTable::NAME].contains(name);
+            let expected = if *name == AccountTrieTable::NAME {
+                Some(NIBBLE_PREFIX)
+            } else {
+                dupsort.then_some(FIXED_PREFIX)
+            };
+            assert_eq!(cf_prefix_extractor(db.path(), name).as_deref(), expected, "{name}");
+        }
+
+        // Production DUPSORT tables carry it too, including the trie tables
+        let (db, temp_dir) = create_test_rocksdb(RocksDBConfig::default());
+        drop(db);
+        for name in [HashedStorages::NAME, StorageTrieTable::NAME] {
+            assert_eq!(
+                cf_prefix_extractor(temp_dir.path(), name).as_deref(),
+                Some(FIXED_PREFIX),
+                "{name}"
+            );
+        }
+        assert_eq!(
+            cf_prefix_extractor(temp_dir.path(), AccountTrieTable::NAME).as_deref(),
+            Some(NIBBLE_PREFIX)
+        );
+    }
+
+    #[test]
+    fn test_account_trie_prefix_bloom() {
+        // Open the account trie alone, with statistics, to count what its bloom filter saves
+        let temp_dir = TempDir::new().unwrap();
+        let config = RocksDBConfig::default();
+        let mut opts = config.db_options();
+        opts.create_if_missing(true);
+        opts.create_missing_column_families(true);
+        opts.enable_statistics();
+        let cf =
+            AccountTrieTable::descriptor_with(&config.table_options_for(AccountTrieTable::NAME));
+        let db = Arc::new(DB::open_cf_descriptors(&opts, temp_dir.path(), vec![cf]).unwrap());
+
+        let path = |first: u8, last: u8| {
+            TrieNibbles(Nibbles::from_nibbles([first, 2, 3, 4, 5, 6, 7, 8, last]))
+        };
+        let node = create_test_branch_node();
+
+        let tx = RocksTransaction::<true>::from_db(db.clone());
+        for last in 0..16 {
+            tx.put::<AccountTrieTable>(path(1, last), node.clone()).unwrap();
+            tx.put::<AccountTrieTable>(path(3, last), node.clone()).unwrap();
+        }
+        tx.commit().unwrap();
+        db.flush_cf(db.cf_handle(AccountTrieTable::NAME).unwrap()).unwrap();
+
+        let bloom_useful = || {
+            opts.get_statistics()
+                .unwrap()
+                .lines()
+                .find_map(|line| line.strip_prefix("rocksdb.bloom.filter.useful COUNT : "))
+                .map(|count| count.trim().parse::<u64>().unwrap())
+                .unwrap()
+        };
+
+        let tx = RocksTransaction::<false>::from_db(db.clone());
+        for last in 0..16 {
+            assert_eq!(tx.get::<AccountTrieTable>(path(1, last)).unwrap(), Some(node.clone()));
+        }
+
+        // Paths under a missing prefix are absent, and the filter answers for the SST file
+        let before = bloom_useful();
+        for last in 0..16 {
+            assert_eq!(tx.get::<AccountTrieTable>(path(2, last)).unwrap(), None);
+        }
+        assert!(bloom_useful() >= before + 16);
+
+        // Cursors still seek in total order past the missing prefix
+        let mut cursor = tx.cursor_read::<AccountTrieTable>().unwrap();
+        assert_eq!(cursor.seek_exact(path(2, 0)).unwrap(), None);
+        assert_eq!(cursor.seek(path(2, 0)).unwrap().map(|(key, _)| key), Some(path(3, 0)));
+        assert_eq!(cursor.seek_exact(path(3, 5)).unwrap().map(|(key, _)| key), Some(path(3, 5)));
+    }
+
+    #[test]
+    fn test_compression_ratio() {
+        let config =
+            RocksDBConfig { compression: Some(DBCompressionType::Lz4), ..RocksDBConfig::default() };
+        let (db, _temp_dir) = create_test_rocksdb(config);
+
+        assert!(db.compression_ratio("no_such_table").is_err());
+        assert_eq!(db.compression_ratio(PlainAccountState::NAME).unwrap(), 1.0);
+
+        // Long runs of zeros compress very well
+        let inner = db.inner();
+        let cf = inner.cf_handle(PlainAccountState::NAME).unwrap();
+        for i in 0..2_000u64 {
+            inner.put_cf(cf, B256::from(U256::from(i)), vec![0u8; 1024]).unwrap();
+        }
+        db.flush_table::<PlainAccountState>().unwrap();
+
+        let ratio = db.compression_ratio(PlainAccountState::NAME).unwrap();
+        assert!(ratio > 0.0 && ratio < 0.5, "ratio {ratio}");
+    }
+
+    #[test]
+    fn test_verify_sst_on_open_accepts_healthy_db() {
+        let config = RocksDBConfig::default().with_verify_sst_on_open(true);
+        let (db, temp_dir) = create_test_rocksdb(config.clone());
+
+        let write_tx = db.tx_mut().unwrap();
+        for i in 0..100u64 {
+            write_tx.put::<HashedAccounts>(B256::with_last_byte(i as u8), test_account(i)).unwrap();
+        }
+        write_tx.commit().unwrap();
+        db.flush_table::<HashedAccounts>().unwrap();
+        assert!(!db.sst_files().unwrap().is_empty());
+        drop(db);
+
+        // Every SST file is checked against the MANIFEST on both kinds of open
+        let db = RocksDB::open(temp_dir.path(), config.clone()).unwrap();
+        assert_eq!(db.tx().unwrap().entries::<HashedAccounts>().unwrap(), 100);
+        drop(db);
+        let db = RocksDB::open_read_only(temp_dir.path(), config, false).unwrap();
+        assert_eq!(
+            db.tx().unwrap().get::<HashedAccounts>(B256::with_last_byte(7)).unwrap(),
+            Some(test_account(7))
+        );
+    }
+
+    #[test]
+    fn test_wal_recovery_mode_opens_healthy_db() {
+        let config = RocksDBConfig::default().with_wal_recovery_mode(DBRecoveryMode::PointInTime);
+        let (db, temp_dir) = create_test_rocksdb(config.clone());
+
+        // Left in the WAL only, so reopening has to replay it
+        let write_tx = db.tx_mut().unwrap();
+        for i in 0..10u64 {
+            write_tx.put::<HashedAccounts>(B256::with_last_byte(i as u8), test_account(i)).unwrap();
+        }
+        write_tx.commit().unwrap();
+        drop(db);
+
+        let db = RocksDB::open(temp_dir.path(), config.clone()).unwrap();
+        assert_eq!(db.tx().unwrap().entries::<HashedAccounts>().unwrap(), 10);
+        drop(db);
+        let db = RocksDB::open_read_only(temp_dir.path(), config, false).unwrap();
+        assert_eq!(
+            db.tx().unwrap().get::<HashedAccounts>(B256::with_last_byte(3)).unwrap(),
+            Some(test_account(3))
+        );
+    }
+
+    #[test]
+    fn test_cf_override_disables_compression() {
+        let config = RocksDBConfig {
+            compression: Some(DBCompressionType::Zstd),
+            ..RocksDBConfig::default()
+        }
+        .with_cf_override(
+            HashedAccounts::NAME,
+            CfOverride { compression: Some(DBCompressionType::None), ..CfOverride::default() },
+        );
+        let (db, temp_dir) = create_test_rocksdb(config.clone());
+
+        let tx = db.tx_mut().unwrap();
+        for i in 0..100u64 {
+            tx.put::<HashedAccounts>(B256::from(U256::from(i)), test_account(i)).unwrap();
+        }
+        tx.commit().unwrap();
+        db.flush_table::<HashedAccounts>().unwrap();
+        let tx = db.tx().unwrap();
+        for i in 0..100u64 {
+            assert_eq!(
+                tx.get::<HashedAccounts>(B256::from(U256::from(i))).unwrap(),
+                Some(test_account(i))
+            );
+        }
+        drop(tx);
+
+        // Long runs of zeros shrink a lot when compressed and not at all otherwise
+        let inner = db.inner();
+        for table in [HashedAccounts::NAME, PlainAccountState::NAME] {
+            let cf = inner.cf_handle(table).unwrap();
+            for i in 0..2_000u64 {
+                inner.put_cf(cf, B256::from(U256::from(i + 1_000)), vec![0u8; 1024]).unwrap();
+            }
+            inner.flush_cf(cf).unwrap();
+        }
+
+        let uncompressed = db.compression_ratio(HashedAccounts::NAME).unwrap();
+        let compressed = db.compression_ratio(PlainAccountState::NAME).unwrap();
+        assert!(uncompressed > 0.9, "ratio {uncompressed}");
+        assert!(compressed < 0.5, "ratio {compressed}");
+
+        // The override applies again on reopen
+        drop(inner);
+        drop(db);
+        let db = RocksDB::open(temp_dir.path(), config).unwrap();
+        assert_eq!(
+            db.tx().unwrap().get::<HashedAccounts>(B256::from(U256::from(7u64))).unwrap(),
+            Some(test_account(7))
+        );
+    }
+
+    #[test]
+    fn test_cf_override_blob_files() {
+        let config = RocksDBConfig::default().with_cf_override(
+            TrieTable::NAME,
+            CfOverride { min_blob_size: Some(256), ..CfOverride::default() },
+        );
+        let (db, temp_dir) = create_test_rocksdb(config.clone());
+
+        // Nodes with all 16 child hashes take over 500 bytes, nodes without any a few bytes
+        let node = |i: u8, hash_mask: u16| {
+            let hashes =
+                (0..hash_mask.count_ones() as u8).map(|j| B256::from([i ^ j; 32])).collect();
+            StoredTrieNode::from(&BranchNodeCompact::new(
+                TrieMask::new(0xffff),
+                TrieMask::new(0),
+                TrieMask::new(hash_mask),
+                hashes,
+                None,
+            ))
+        };
+        let nodes: Vec<_> = (0..50u8).flat_map(|i| [node(i, 0xffff), node(i, 0)]).collect();
+        let tx = db.tx_mut().unwrap();
+        for node in &nodes {
+            tx.put::<TrieTable>(node.hash(), node.clone()).unwrap();
+            tx.put::<HashedAccounts>(node.hash(), test_account(1)).unwrap();
+        }
+        tx.commit().unwrap();
+        db.flush_table::<TrieTable>().unwrap();
+        db.flush_table::<HashedAccounts>().unwrap();
+
+        let blob_files = |db: &RocksDB, table: &str| {
+            let inner = db.inner();
+            inner.property_int_value_cf(inner.cf_handle(table).unwrap(), "rocksdb.num-blob-files")
+        };
+        assert!(blob_files(&db, TrieTable::NAME).unwrap().unwrap() > 0);
+        assert_eq!(blob_files(&db, HashedAccounts::NAME).unwrap(), Some(0));
+
+        let tx = db.tx().unwrap();
+        for node in &nodes {
+            assert_eq!(tx.get::<TrieTable>(node.hash()).unwrap().as_ref(), Some(node));
+        }
+        drop(tx);
+
+        // Values in blob files stay readable after reopening
+        drop(db);
+        let db = RocksDB::open(temp_dir.path(), config).unwrap();
+        let tx = db.tx().unwrap();
+        for node in &nodes {
+            assert_eq!(tx.get::<TrieTable>(node.hash()).unwrap().as_ref(), Some(node));
+        }
+    }
+
+    #[test]
+    fn test_auto_compactor_clears_pending_compaction() {
+        let (inner, _temp_dir) = build_test_db(TEST_TABLES);
+        let cf = inner.cf_handle(HashedAccounts::NAME).unwrap();
+        // Keep RocksDB from compacting the tombstones by itself
+        inner.set_options_cf(cf, &[("disable_auto_compactions", "true")]).unwrap();
+        let db = RocksDB::from_db(inner.clone(), RocksDBConfig::default());
+
+        let pending_bytes = || {
+            inner
+                .property_int_value_cf(cf, "rocksdb.estimate-pending-compaction-bytes")
+                .unwrap()
+                .unwrap()
+        };
+
+        for i in 0..1_000u64 {
+            inner.put_cf(cf, B256::from(U256::from(i)), vec![1u8; 256]).unwrap();
+        }
+        db.flush_table::<HashedAccounts>().unwrap();
+
+        // Each flush of deletes adds an L0 file of tombstones, enough of them trigger compaction
+        for chunk in (0..1_000u64).collect::<Vec<_>>().chunks(200) {
+            for i in chunk {
+                inner.delete_cf(cf, B256::from(U256::from(*i))).unwrap();
+            }
+            db.flush_table::<HashedAccounts>().unwrap();
+        }
+        let before = pending_bytes();
+        assert!(before > 0);
+
+        let config = AutoCompactConfig {
+            interval: Duration::from_secs(3600),
+            pending_compaction_bytes_threshold: 0,
+            ..AutoCompactConfig::default()
+        };
+        let compactor = db.spawn_auto_compactor(config).unwrap();
+        let compacted = compactor.tick().unwrap();
+
+        assert!(compacted.iter().any(|name| name == HashedAccounts::NAME));
+        assert!(pending_bytes() < before);
+        assert!(inner.iterator_cf(cf, IteratorMode::Start).next().is_none());
+
+        // Dropping stops the thread without waiting out the interval
+        drop(compactor);
+    }
+
+    #[test]
+    fn test_auto_compactor_backs_off_while_throttled() {
+        use crate::auto_compact::AutoCompactor;
+        use std::sync::atomic::Ordering;
+
+        static THROTTLED: AtomicBool = AtomicBool::new(true);
+
+        let (inner, _temp_dir) = build_test_db(TEST_TABLES);
+        let config = AutoCompactConfig {
+            interval: Duration::from_secs(100),
+            pending_compaction_bytes_threshold: 0,
+            max_backoff: Duration::from_secs(500),
+        };
+        let compactor =
+            AutoCompactor::spawn_with(inner, config, |_| THROTTLED.load(Ordering::SeqCst));
+        assert_eq!(compactor.delay(), config.interval);
+
+        // Every skipped check doubles the wait, up to the maximum
+        for expected in [200, 400, 500, 500] {
+            assert!(compactor.tick().unwrap().is_empty());
+            assert_eq!(compactor.delay(), Duration::from_secs(expected));
+        }
+
+        // A check that runs resets it
+        THROTTLED.store(false, Ordering::SeqCst);
+        compactor.tick().unwrap();
+        assert_eq!(compactor.delay(), config.interval);
+    }
+
+    #[test]
+    fn test_swap_cf_contents() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut opts = Options::default();
+        opts.create_if_missing(true);
+        opts.create_missing_column_families(true);
+        let inner =
+            Arc::new(DB::open_cf(&opts, temp_dir.path(), ["scratch_a", "scratch_b"]).unwrap());
+        let db = RocksDB::from_db(inner.clone(), RocksDBConfig::default());
+
+        // Overlapping keys with different values, plus keys only one side has
+        let entries_a = (0..50u8).map(|i| (vec![i], vec![0xa, i])).collect::<Vec<_>>();
+        let entries_b = (25..100u8).map(|i| (vec![i], vec![0xb, i])).collect::<Vec<_>>();
+        let cf_a = inner.cf_handle("scratch_a").unwrap();
+        let cf_b = inner.cf_handle("scratch_b").unwrap();
+        for (key, value) in &entries_a {
+            inner.put_cf(cf_a, key, value).unwrap();
+        }
+        for (key, value) in &entries_b {
+            inner.put_cf(cf_b, key, value).unwrap();
+        }
+
+        // The swap waits for the open write transaction
+        let write_tx = db.tx_mut().unwrap();
+        std::thread::scope(|s| {
+            let swapper = s.spawn(|| db.swap_cf_contents("scratch_a", "scratch_b"));
+            std::thread::sleep(Duration::from_millis(50));
+            assert!(!swapper.is_finished());
+            drop(write_tx);
+            swapper.join().unwrap().unwrap();
+        });
+
+        let contents = |cf| {
+            inner
+                .iterator_cf(cf, IteratorMode::Start)
+                .map(|item| item.map(|(key, value)| (key.to_vec(), value.to_vec())).unwrap())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(contents(cf_a), entries_b);
+        assert_eq!(contents(cf_b), entries_a);
+
+        // Swapping back in chunks smaller than either side restores the original contents
+        db.swap_cf_contents_in_chunks("scratch_a", "scratch_b", 7).unwrap();
+        assert_eq!(contents(cf_a), entries_a);
+        assert_eq!(contents(cf_b), entries_b);
+
+        assert!(db.swap_cf_contents("scratch_a", "no_such_table").is_err());
+        assert_eq!(contents(cf_a), entries_b);
+    }
+
+    /// Subscriber recording the fields of every event as `name=value` pairs
+    struct CapturingSubscriber(Arc<std::sync::Mutex<Vec<String>>>);
+
+    struct FieldRecorder(String);
+
+    impl tracing::field::Visit for FieldRecorder {
+        fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+            self.0.push_str(&format!("{}={:?} ", field.name(), value));
+        }
+
+        fn record_str(&mut self, field: &tracing::field::Field, value: &str) {
+            self.0.push_str(&format!("{}={} ", field.name(), value));
+        }
+    }
+
+    impl tracing::Subscriber for CapturingSubscriber {
+        fn enabled(&self, _metadata: &tracing::Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, _span: &tracing::span::Attributes<'_>) -> tracing::span::Id {
+            tracing::span::Id::from_u64(1)
+        }
+
+        fn record(&self, _span: &tracing::span::Id, _values: &tracing::span::Record<'_>) {}
+
+        fn record_follows_from(&self, _span: &tracing::span::Id, _follows: &tracing::span::Id) {}
+
+        fn event(&self, event: &tracing::Event<'_>) {
+            let mut recorder = FieldRecorder(String::new());
+            event.record(&mut recorder);
+            self.0.lock().unwrap().push(recorder.0);
+        }
+
+        fn enter(&self, _span: &tracing::span::Id) {}
+
+        fn exit(&self, _span: &tracing::span::Id) {}
+    }
+
+    #[test]
+    fn test_slow_op_threshold_logs_operations() {
+        let config = RocksDBConfig::default().with_slow_op_threshold(Duration::from_nanos(1));
+        let (db, _temp_dir) = create_test_rocksdb(config);
+
+        let events = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let subscriber = CapturingSubscriber(events.clone());
+        tracing::subscriber::with_default(subscriber, || {
+            let tx = db.tx_mut().unwrap();
+            tx.put::<HashedAccounts>(B256::with_last_byte(1), test_account(1)).unwrap();
+            tx.commit().unwrap();
+
+            let tx = db.tx().unwrap();
+            assert_eq!(
+                tx.get::<HashedAccounts>(B256::with_last_byte(1)).unwrap(),
+                Some(test_account(1))
+            );
+            let mut cursor = tx.cursor_read::<HashedAccounts>().unwrap();
+            assert!(cursor.first().unwrap().is_some());
+        });
+
+        let events = events.lock().unwrap();
+        let slow_op = |table: &str, operation: &str| {
+            events.iter().any(|event| {
+                event.contains("Slow database operation") &&
+                    event.contains(&format!("table={} ", table)) &&
+                    event.contains(&format!("operation={} ", operation))
+            })
+        };
+        assert!(slow_op("HashedAccounts", "put"));
+        assert!(slow_op("*", "commit"));
+        assert!(slow_op("HashedAccounts", "get"));
+        assert!(slow_op("HashedAccounts", "first"));
+    }
+
+    #[test]
+    fn test_slow_op_threshold_disabled_by_default() {
+        let (db, _temp_dir) = create_test_rocksdb(RocksDBConfig::default());
+
+        let events = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let subscriber = CapturingSubscriber(events.clone());
+        tracing::subscriber::with_default(subscriber, || {
+            let tx = db.tx_mut().unwrap();
+            tx.put::<HashedAccounts>(B256::with_last_byte(1), test_account(1)).unwrap();
+            tx.commit().unwrap();
+        });
+
+        assert!(!events
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|event| event.contains("Slow database operation")));
+    }
+
+    #[test]
+    fn test_diff_against() {
+        let (db, _temp_dir) = create_test_rocksdb(RocksDBConfig::default());
+        let tx = db.tx_mut().unwrap();
+        for i in 0..10u8 {
+            tx.put::<HashedAccounts>(B256::with_last_byte(i), test_account(i as u64)).unwrap();
+            tx.put::<PlainAccountState>(Address::with_last_byte(i), test_account(i as u64))
+                .unwrap();
+        }
+        tx.commit().unwrap();
+
+        let clone_dir = TempDir::new().unwrap();
+        let clone_path = clone_dir.path().join("clone");
+        db.consistent_snapshot(&clone_path).unwrap();
+        let clone = RocksDB::open(&clone_path, RocksDBConfig::default()).unwrap();
+        assert!(db.diff_against(&clone).unwrap().is_empty());
+
+        let tx = db.tx_mut().unwrap();
+        tx.put::<HashedAccounts>(B256::with_last_byte(0xff), test_account(42)).unwrap();
+        tx.commit().unwrap();
+
+        let expected = TableDiffSummary {
+            table: HashedAccounts::NAME,
+            only_in_self: 1,
+            only_in_other: 0,
+            value_mismatches: 0,
+        };
+        assert_eq!(db.diff_against(&clone).unwrap(), vec![expected]);
+        assert_eq!(
+            clone.diff_against(&db).unwrap(),
+            vec![TableDiffSummary { only_in_self: 0, only_in_other: 1, ..expected }]
+        );
+
+        // Changing a shared key counts as a mismatch rather than a missing key
+        let tx = clone.tx_mut().unwrap();
+        tx.put::<PlainAccountState>(Address::with_last_byte(0), test_account(42)).unwrap();
+        tx.commit().unwrap();
+        let diffs = db.diff_against(&clone).unwrap();
+        assert_eq!(diffs.len(), 2);
+        let plain = diffs.iter().find(|diff| diff.table == PlainAccountState::NAME).unwrap();
+        assert_eq!((plain.only_in_self, plain.only_in_other, plain.value_mismatches), (0, 0, 1));
+    }
+}
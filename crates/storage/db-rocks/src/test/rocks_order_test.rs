@@ -0,0 +1,163 @@
+/// Iteration order contract of each table: cursors walk entries in byte-lexicographic order of
+/// `T::Key::encode`. State root calculation, proofs and range scans rely on this, so a codec
+/// change that reorders a table must fail here. `HashedAccounts` is defined by reth and keyed by
+/// the raw 32-byte hashed address.
+#[cfg(test)]
+mod rocks_order_test {
+    use crate::implementation::rocks::dupsort::DupSortHelper;
+    use crate::tables::trie::{
+        AccountTrieTable, StorageTrieTable, StoredTrieNode, TrieNibbles, TrieNodeValue, TrieTable,
+    };
+    use crate::test::utils::{create_test_branch_node, create_test_db};
+    use crate::{Account, RocksTransaction};
+    use alloy_primitives::{keccak256, B256, U256};
+    use reth_db::HashedAccounts;
+    use reth_db_api::{
+        cursor::{DbCursorRO, DbDupCursorRO, DbDupCursorRW},
+        table::{Encode, Table},
+        transaction::{DbTx, DbTxMut},
+    };
+    use reth_trie::Nibbles;
+    use reth_trie_common::StoredNibbles;
+    use rocksdb::DB;
+    use std::sync::Arc;
+
+    /// Write `entries` in reverse, then check `first`/`next` return them sorted by encoded key
+    fn assert_iterates_in_encoded_order<T: Table>(db: Arc<DB>, entries: Vec<(T::Key, T::Value)>)
+    where
+        T::Value: Clone + PartialEq,
+    {
+        let write_tx = RocksTransaction::<true>::from_db(db.clone());
+        for (key, value) in entries.iter().rev() {
+            write_tx.put::<T>(key.clone(), value.clone()).unwrap();
+        }
+        write_tx.commit().unwrap();
+
+        let mut expected = entries;
+        expected.sort_by_key(|(key, _)| key.clone().encode().as_ref().to_vec());
+
+        let read_tx = RocksTransaction::<false>::from_db(db);
+        let mut cursor = read_tx.cursor_read::<T>().unwrap();
+        let mut walked = Vec::new();
+        let mut entry = cursor.first().unwrap();
+        while let Some(item) = entry {
+            walked.push(item);
+            entry = cursor.next().unwrap();
+        }
+
+        assert_eq!(walked, expected, "{} iterated out of encoded key order", T::NAME);
+    }
+
+    #[test]
+    fn test_hashed_accounts_order() {
+        let (db, _temp_dir) = create_test_db();
+
+        let entries = (0..20u64)
+            .map(|i| {
+                let account = Account { nonce: i, balance: U256::from(i), bytecode_hash: None };
+                (keccak256(i.to_be_bytes()), account)
+            })
+            .chain([
+                (B256::ZERO, Account::default()),
+                (B256::repeat_byte(0xff), Account::default()),
+            ])
+            .collect();
+        assert_iterates_in_encoded_order::<HashedAccounts>(db, entries);
+    }
+
+    #[test]
+    fn test_trie_table_order() {
+        let (db, _temp_dir) = create_test_db();
+
+        let mut node = create_test_branch_node();
+        let entries = (0..20u8)
+            .map(|i| {
+                node.root_hash = Some(keccak256([i]));
+                let stored = StoredTrieNode::from(&node);
+                (stored.hash(), stored)
+            })
+            .collect();
+        assert_iterates_in_encoded_order::<TrieTable>(db, entries);
+    }
+
+    #[test]
+    fn test_account_trie_table_order() {
+        let (db, _temp_dir) = create_test_db();
+
+        // A path sorts before its extensions and by nibble value otherwise
+        let paths: [&[u8]; 8] = [&[], &[0], &[0, 1], &[0, 1, 0xf], &[1], &[1, 0, 2], &[2], &[0xf]];
+        let node = create_test_branch_node();
+        let entries = paths
+            .iter()
+            .map(|path| (TrieNibbles(Nibbles::from_nibbles(path)), node.clone()))
+            .collect::<Vec<_>>();
+        assert_iterates_in_encoded_order::<AccountTrieTable>(db, entries.clone());
+
+        // The encoded order is also nibble path order
+        let mut by_path = entries.into_iter().map(|(key, _)| key).collect::<Vec<_>>();
+        by_path.sort();
+        assert_eq!(
+            by_path,
+            paths.iter().map(|path| TrieNibbles(Nibbles::from_nibbles(path))).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_storage_trie_table_order() {
+        let (db, _temp_dir) = create_test_db();
+
+        let accounts = [B256::repeat_byte(0x01), B256::repeat_byte(0x02), B256::repeat_byte(0x10)];
+        let paths: [&[u8]; 5] = [&[], &[0], &[0, 1], &[1], &[0xf, 0xf]];
+        let node_value = |path: &[u8]| TrieNodeValue {
+            nibbles: StoredNibbles(Nibbles::from_nibbles(path)),
+            node: keccak256(path),
+        };
+
+        // Insert accounts and paths in reverse
+        let write_tx = RocksTransaction::<true>::from_db(db.clone());
+        let mut cursor = write_tx.cursor_dup_write::<StorageTrieTable>().unwrap();
+        for account in accounts.iter().rev() {
+            for path in paths.iter().rev() {
+                let value = node_value(*path);
+                cursor.upsert_dup(*account, value.nibbles.clone(), &value).unwrap();
+            }
+        }
+        drop(cursor);
+        write_tx.commit().unwrap();
+
+        // On disk, keys are sorted by account, then by path
+        let expected = accounts
+            .iter()
+            .flat_map(|account| paths.iter().map(move |path| (*account, node_value(*path))))
+            .collect::<Vec<_>>();
+        let read_tx = RocksTransaction::<false>::from_db(db);
+        let raw_keys = read_tx
+            .raw_iter::<StorageTrieTable>()
+            .unwrap()
+            .map(|item| item.unwrap().0.into_vec())
+            .collect::<Vec<_>>();
+        let expected_keys = expected
+            .iter()
+            .map(|(account, value)| {
+                DupSortHelper::composite_key_bytes::<StorageTrieTable>(account, &value.nibbles)
+                    .unwrap()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(raw_keys, expected_keys);
+        let mut sorted_keys = raw_keys.clone();
+        sorted_keys.sort();
+        assert_eq!(raw_keys, sorted_keys);
+
+        // A dup cursor walks each account's paths in the same order
+        let mut cursor = read_tx.cursor_dup_read::<StorageTrieTable>().unwrap();
+        let mut walked = Vec::new();
+        for account in accounts {
+            let mut entry = cursor.seek_exact(account).unwrap();
+            while let Some(item) = entry {
+                walked.push(item);
+                entry = cursor.next_dup().unwrap();
+            }
+        }
+        assert_eq!(walked, expected);
+    }
+}
@@ -4,20 +4,25 @@ mod rocsk_proof_test {
     use crate::test::utils::{create_test_db, setup_test_state};
     use crate::{
         calculate_state_root_with_updates,
-        tables::trie::{AccountTrieTable, TrieNibbles},
-        Account, HashedPostState, RocksTransaction,
+        tables::trie::{AccountTrieTable, StorageTrieTable, TrieNibbles},
+        Account, HashedPostState, RocksDBError, RocksTransaction,
     };
     use alloy_primitives::{keccak256, Address, B256, U256};
-    use reth_db::transaction::{DbTx, DbTxMut};
-    use reth_trie::{proof::Proof, BranchNodeCompact, Nibbles, TrieMask};
+    use reth_db::{
+        cursor::DbCursorRO,
+        transaction::{DbTx, DbTxMut},
+        HashedAccounts, HashedStorages,
+    };
+    use reth_primitives::StorageEntry;
+    use reth_trie::{proof::Proof, BranchNodeCompact, Nibbles, StateRoot, TrieMask};
 
     #[test]
     fn test_account_proof_generation() {
         let (db, _temp_dir) = create_test_db();
 
         // Setup initial state
-        let read_tx = RocksTransaction::<false>::new(db.clone(), false);
-        let write_tx = RocksTransaction::<true>::new(db.clone(), true);
+        let read_tx = RocksTransaction::<false>::from_db(db.clone());
+        let write_tx = RocksTransaction::<true>::from_db(db.clone());
 
         // Create test accounts
         let account1 = Account {
@@ -42,7 +47,7 @@ mod rocsk_proof_test {
 
         // Calculate state root and get updates
         let state_root =
-            calculate_state_root_with_updates(&read_tx, &write_tx, post_state).unwrap();
+            calculate_state_root_with_updates(&read_tx, &write_tx, post_state, None).unwrap();
         println!("State root calculated: {}", state_root);
 
         // Manually insert a node for the account
@@ -65,12 +70,12 @@ mod rocsk_proof_test {
         write_tx.commit().unwrap();
 
         // Verify that we can retrieve the account node
-        let verify_tx = RocksTransaction::<false>::new(db.clone(), false);
+        let verify_tx = RocksTransaction::<false>::from_db(db.clone());
         let retrieved_node = verify_tx.get_account(TrieNibbles(account_nibbles)).unwrap();
         println!("Retrieved account node: {:?}", retrieved_node);
 
         // Generate proof
-        let proof_tx = RocksTransaction::<false>::new(db.clone(), false);
+        let proof_tx = RocksTransaction::<false>::from_db(db.clone());
         let proof_generator =
             Proof::new(proof_tx.trie_cursor_factory(), proof_tx.hashed_cursor_factory());
 
@@ -99,8 +104,8 @@ mod rocsk_proof_test {
         let (db, _temp_dir) = create_test_db();
 
         // Setup initial state
-        let read_tx = RocksTransaction::<false>::new(db.clone(), false);
-        let write_tx = RocksTransaction::<true>::new(db.clone(), true);
+        let read_tx = RocksTransaction::<false>::from_db(db.clone());
+        let write_tx = RocksTransaction::<true>::from_db(db.clone());
         let (state_root, address1, _, _) = setup_test_state(&read_tx, &write_tx);
 
         println!("State root: {}", state_root);
@@ -116,7 +121,7 @@ mod rocsk_proof_test {
         write_tx.commit().unwrap();
 
         // Generate a proof for account1
-        let proof_tx = RocksTransaction::<false>::new(db.clone(), false);
+        let proof_tx = RocksTransaction::<false>::from_db(db.clone());
 
         // Create a proof generator using RETH's Proof struct
         let proof_generator =
@@ -147,4 +152,215 @@ mod rocsk_proof_test {
             "Account proof verification should succeed with some root"
         );
     }
+
+    #[test]
+    fn test_rebuild_trie_tables_from_hashed_state() {
+        let (db, _temp_dir) = create_test_db();
+
+        // Populate the hashed accounts table
+        let addresses: Vec<Address> = (1..=5u8).map(|i| Address::from([i; 20])).collect();
+        let mut post_state = HashedPostState::default();
+        let write_tx = RocksTransaction::<true>::from_db(db.clone());
+        for (i, address) in addresses.iter().enumerate() {
+            let account = Account {
+                nonce: i as u64,
+                balance: U256::from(1000 * (i + 1)),
+                bytecode_hash: None,
+            };
+            write_tx.put::<HashedAccounts>(keccak256(address), account).unwrap();
+            post_state.accounts.insert(keccak256(address), Some(account));
+        }
+        write_tx.commit().unwrap();
+
+        // Compute the original root and trie nodes
+        let read_tx = RocksTransaction::<false>::from_db(db.clone());
+        let write_tx = RocksTransaction::<true>::from_db(db.clone());
+        let original_root =
+            calculate_state_root_with_updates(&read_tx, &write_tx, post_state, None).unwrap();
+        write_tx.commit().unwrap();
+
+        let proof_tx = RocksTransaction::<false>::from_db(db.clone());
+        let original_proof =
+            Proof::new(proof_tx.trie_cursor_factory(), proof_tx.hashed_cursor_factory())
+                .account_proof(addresses[0], &[])
+                .unwrap();
+
+        // Drop the trie tables
+        let write_tx = RocksTransaction::<true>::from_db(db.clone());
+        write_tx.clear::<AccountTrieTable>().unwrap();
+        write_tx.clear::<StorageTrieTable>().unwrap();
+        write_tx.commit().unwrap();
+
+        let check_tx = RocksTransaction::<false>::from_db(db.clone());
+        assert!(check_tx.cursor_read::<AccountTrieTable>().unwrap().first().unwrap().is_none());
+
+        // Rebuild from the hashed tables
+        let read_tx = RocksTransaction::<false>::from_db(db.clone());
+        let write_tx = RocksTransaction::<true>::from_db(db.clone());
+        let rebuilt_root = write_tx.rebuild_trie_tables(&read_tx).unwrap();
+        write_tx.commit().unwrap();
+
+        assert_eq!(rebuilt_root, original_root);
+
+        // Proofs are served from the rebuilt tables exactly as before
+        let proof_tx = RocksTransaction::<false>::from_db(db.clone());
+        let rebuilt_proof =
+            Proof::new(proof_tx.trie_cursor_factory(), proof_tx.hashed_cursor_factory())
+                .account_proof(addresses[0], &[])
+                .unwrap();
+
+        assert_eq!(rebuilt_proof.proof, original_proof.proof);
+        assert!(original_proof.verify(original_root).is_ok());
+        assert!(rebuilt_proof.verify(rebuilt_root).is_ok());
+    }
+
+    #[test]
+    fn test_find_orphaned_trie_nodes() {
+        let (db, _temp_dir) = create_test_db();
+        let root = B256::from([7; 32]);
+        let branch = |state_mask: u16, tree_mask: u16, root_hash: Option<B256>| {
+            BranchNodeCompact::new(
+                TrieMask::new(state_mask),
+                TrieMask::new(tree_mask),
+                TrieMask::new(0),
+                Vec::new(),
+                root_hash,
+            )
+        };
+
+        let write_tx = RocksTransaction::<true>::from_db(db.clone());
+        let put = |path: &[u8], node: BranchNodeCompact| {
+            write_tx
+                .put::<AccountTrieTable>(TrieNibbles(Nibbles::from_nibbles(path)), node)
+                .unwrap();
+        };
+        // Root references the subtries under nibbles 1 and 2
+        put(&[], branch(0b10110, 0b00110, Some(root)));
+        put(&[1], branch(0b11, 0b10, None));
+        put(&[1, 1], branch(0b11, 0, None));
+        // Reached through an extension below the root's child 2
+        put(&[2, 3, 4], branch(0b11, 0, None));
+        // Unreferenced: the root's tree mask doesn't include 4, node [1] doesn't include 5
+        put(&[4], branch(0b11, 0, None));
+        put(&[1, 5], branch(0b11, 0, None));
+        write_tx.commit().unwrap();
+
+        let read_tx = RocksTransaction::<false>::from_db(db.clone());
+        let orphans = read_tx.find_orphaned_trie_nodes(root).unwrap();
+        assert_eq!(
+            orphans,
+            vec![
+                TrieNibbles(Nibbles::from_nibbles([1, 5])),
+                TrieNibbles(Nibbles::from_nibbles([4])),
+            ]
+        );
+
+        // The walk refuses to start from a root that isn't the stored one
+        assert!(read_tx.find_orphaned_trie_nodes(B256::from([8; 32])).is_err());
+    }
+
+    #[test]
+    fn test_walk_account_trie_dfs() {
+        let (db, _temp_dir) = create_test_db();
+        let branch = |tree_mask: u16| {
+            BranchNodeCompact::new(
+                TrieMask::new(0xffff),
+                TrieMask::new(tree_mask),
+                TrieMask::new(0),
+                Vec::new(),
+                None,
+            )
+        };
+        let nibbles = |path: &[u8]| Nibbles::from_nibbles(path);
+
+        let write_tx = RocksTransaction::<true>::from_db(db.clone());
+        let put = |path: &[u8], node: BranchNodeCompact| {
+            write_tx.put::<AccountTrieTable>(TrieNibbles(nibbles(path)), node).unwrap();
+        };
+        // Root expects children under nibbles 1, 2 and 5, the one under 5 isn't stored
+        put(&[], branch(0b100110));
+        put(&[1], branch(0b11));
+        put(&[1, 0], branch(0));
+        // Expects a child under nibble 3 that isn't stored
+        put(&[1, 1], branch(0b1000));
+        // Reached through an extension below the root's child 2
+        put(&[2, 3, 4], branch(0));
+        // Not referenced by the root
+        put(&[4], branch(0));
+        write_tx.commit().unwrap();
+
+        let read_tx = RocksTransaction::<false>::from_db(db.clone());
+        let mut visited = Vec::new();
+        let missing = read_tx.walk_account_trie_dfs(|path, _| visited.push(path.clone())).unwrap();
+        assert_eq!(
+            visited,
+            vec![
+                nibbles(&[]),
+                nibbles(&[1]),
+                nibbles(&[1, 0]),
+                nibbles(&[1, 1]),
+                nibbles(&[2, 3, 4])
+            ]
+        );
+        assert_eq!(missing, vec![TrieNibbles(nibbles(&[5])), TrieNibbles(nibbles(&[1, 1, 3]))]);
+
+        // Without a stored root, the walk starts from every node without a stored ancestor
+        let write_tx = RocksTransaction::<true>::from_db(db.clone());
+        write_tx.delete::<AccountTrieTable>(TrieNibbles(nibbles(&[])), None).unwrap();
+        write_tx.commit().unwrap();
+
+        let read_tx = RocksTransaction::<false>::from_db(db);
+        let mut visited = Vec::new();
+        let missing = read_tx.walk_account_trie_dfs(|path, _| visited.push(path.clone())).unwrap();
+        assert_eq!(
+            visited,
+            vec![
+                nibbles(&[1]),
+                nibbles(&[1, 0]),
+                nibbles(&[1, 1]),
+                nibbles(&[2, 3, 4]),
+                nibbles(&[4])
+            ]
+        );
+        assert_eq!(missing, vec![TrieNibbles(nibbles(&[1, 1, 3]))]);
+    }
+
+    #[test]
+    fn test_prove_and_verify() {
+        let (db, _temp_dir) = create_test_db();
+
+        let address = Address::from([1; 20]);
+        let other_address = Address::from([2; 20]);
+        let slot = B256::from([3; 32]);
+        let account =
+            |nonce: u64| Account { nonce, balance: U256::from(1000), bytecode_hash: None };
+
+        let write_tx = RocksTransaction::<true>::from_db(db.clone());
+        write_tx.put::<HashedAccounts>(keccak256(address), account(1)).unwrap();
+        write_tx.put::<HashedAccounts>(keccak256(other_address), account(2)).unwrap();
+        write_tx
+            .put::<HashedStorages>(
+                keccak256(address),
+                StorageEntry { key: keccak256(slot), value: U256::from(42) },
+            )
+            .unwrap();
+        write_tx.commit().unwrap();
+
+        // Root of the state as stored, computed from the same tables the proof is read from
+        let tx = RocksTransaction::<false>::from_db(db.clone());
+        let root =
+            StateRoot::new(tx.trie_cursor_factory(), tx.hashed_cursor_factory()).root().unwrap();
+
+        let proof = tx.prove_and_verify(address, &[slot], root).unwrap();
+        assert_eq!(proof.address, address);
+        assert_eq!(proof.info, Some(account(1)));
+        assert_eq!(proof.storage_proofs.len(), 1);
+        assert_eq!(proof.storage_proofs[0].value, U256::from(42));
+
+        let wrong_root = B256::from([0xAB; 32]);
+        match tx.prove_and_verify(address, &[slot], wrong_root) {
+            Err(RocksDBError::ProofVerification { root, .. }) => assert_eq!(root, wrong_root),
+            other => panic!("expected a failed verification, got {other:?}"),
+        }
+    }
 }
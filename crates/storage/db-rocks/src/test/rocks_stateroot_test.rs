@@ -3,14 +3,22 @@ mod rocks_proof_test {
     // use crate::test::rocks_db_ops_test::create_test_db;
     use crate::test::utils::create_test_db;
     use crate::{
+        calculate_state_root, calculate_state_root_batched, calculate_state_root_streaming,
         calculate_state_root_with_updates,
-        tables::trie::{AccountTrieTable, StorageTrieTable},
+        tables::trie::{AccountTrieTable, StorageTrieTable, TrieNodePaths},
         Account, HashedPostState, RocksTransaction,
     };
     use alloy_primitives::map::B256Map;
     use alloy_primitives::{keccak256, Address, B256, U256};
-    use reth_db::{cursor::DbCursorRO, transaction::DbTx};
-    use reth_trie::HashedStorage;
+    use reth_db::{
+        cursor::{DbCursorRO, DbDupCursorRW},
+        transaction::{DbTx, DbTxMut},
+        HashedAccounts, HashedStorages,
+    };
+    use reth_execution_errors::StateRootError;
+    use reth_primitives::StorageEntry;
+    use reth_trie::{test_utils::storage_root_prehashed, HashedStorage, EMPTY_ROOT_HASH};
+    use std::collections::HashSet;
 
     // Helper function to create a test account
     fn create_test_account(nonce: u64, balance: u64, code_hash: Option<B256>) -> Account {
@@ -118,11 +126,12 @@ mod rocks_proof_test {
             HashedPostState { accounts: B256Map::default(), storages: B256Map::default() };
 
         // Create read and write transactions
-        let read_tx = RocksTransaction::<false>::new(db.clone(), false);
-        let write_tx = RocksTransaction::<true>::new(db.clone(), true);
+        let read_tx = RocksTransaction::<false>::from_db(db.clone());
+        let write_tx = RocksTransaction::<true>::from_db(db.clone());
 
         // Calculate state root with updates
-        let root = calculate_state_root_with_updates(&read_tx, &write_tx, post_state).unwrap();
+        let root =
+            calculate_state_root_with_updates(&read_tx, &write_tx, post_state, None).unwrap();
 
         // Commit the transaction
         write_tx.commit().unwrap();
@@ -131,10 +140,638 @@ mod rocks_proof_test {
         assert_eq!(root, get_empty_state_root(), "Empty state should produce the empty trie root");
 
         // Verify no trie nodes were stored (empty trie)
-        let verify_tx = RocksTransaction::<false>::new(db.clone(), false);
+        let verify_tx = RocksTransaction::<false>::from_db(db.clone());
         assert!(
             verify_account_trie_nodes(&verify_tx, 0),
             "No account trie nodes should be stored for empty state"
         );
     }
+
+    #[test]
+    fn test_expected_root_mismatch_writes_nothing() {
+        let (db, _temp_dir) = create_test_db();
+
+        let accounts = (1..=3u8)
+            .map(|i| (Address::from([i; 20]), create_test_account(i as u64, 1000, None)))
+            .collect();
+        let post_state = create_simple_post_state(accounts);
+
+        let read_tx = RocksTransaction::<false>::from_db(db.clone());
+        let write_tx = RocksTransaction::<true>::from_db(db.clone());
+        let wrong_root = B256::from([0xde; 32]);
+
+        let result =
+            calculate_state_root_with_updates(&read_tx, &write_tx, post_state, Some(wrong_root));
+        assert!(matches!(
+            result,
+            Err(StateRootError::RootMismatch { expected, .. }) if expected == wrong_root
+        ));
+
+        // Nothing was staged, so committing leaves the trie tables empty
+        write_tx.commit().unwrap();
+        let verify_tx = RocksTransaction::<false>::from_db(db.clone());
+        assert!(verify_tx.cursor_read::<AccountTrieTable>().unwrap().first().unwrap().is_none());
+        assert!(verify_tx.cursor_read::<StorageTrieTable>().unwrap().first().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_roots_for_shares_one_base() {
+        let (db, _temp_dir) = create_test_db();
+
+        let write_tx = RocksTransaction::<true>::from_db(db.clone());
+        for i in 1..=3u8 {
+            write_tx
+                .put::<HashedAccounts>(
+                    keccak256(Address::from([i; 20])),
+                    create_test_account(i as u64, 1000, None),
+                )
+                .unwrap();
+        }
+        write_tx.commit().unwrap();
+
+        let candidate = |balance| {
+            create_simple_post_state(vec![
+                (Address::from([2; 20]), create_test_account(2, balance, None)),
+                (Address::from([4; 20]), create_test_account(4, 1000, None)),
+            ])
+        };
+        let empty = HashedPostState { accounts: B256Map::default(), storages: B256Map::default() };
+
+        let read_tx = RocksTransaction::<false>::from_db(db.clone());
+        let roots = read_tx
+            .roots_for(vec![candidate(5000), candidate(5000), candidate(6000), empty.clone()])
+            .unwrap();
+
+        assert_eq!(roots.len(), 4);
+        assert_eq!(roots[0], roots[1], "identical post states must yield the same root");
+        assert_ne!(roots[0], roots[2], "differing post states must yield different roots");
+        assert_ne!(roots[0], roots[3]);
+
+        // Each root matches computing it on its own, and the base was left untouched
+        assert_eq!(roots[2], calculate_state_root(&read_tx, candidate(6000)).unwrap());
+        let fresh_tx = RocksTransaction::<false>::from_db(db.clone());
+        assert_eq!(calculate_state_root(&fresh_tx, empty).unwrap(), roots[3]);
+        assert_eq!(fresh_tx.entries::<HashedAccounts>().unwrap(), 3);
+
+        // A transaction pinned to a snapshot keeps computing against it while the database
+        // changes underneath
+        let snapshot_tx = RocksTransaction::<false>::new_with_snapshot(db.clone(), None);
+        let before = snapshot_tx.roots_for(vec![candidate(5000)]).unwrap();
+        let write_tx = RocksTransaction::<true>::from_db(db.clone());
+        write_tx
+            .put::<HashedAccounts>(
+                keccak256(Address::from([9; 20])),
+                create_test_account(9, 1000, None),
+            )
+            .unwrap();
+        write_tx.commit().unwrap();
+        assert_eq!(snapshot_tx.roots_for(vec![candidate(5000)]).unwrap(), before);
+        assert_eq!(before[0], roots[0]);
+        let live_tx = RocksTransaction::<false>::from_db(db);
+        assert_ne!(live_tx.roots_for(vec![candidate(5000)]).unwrap(), before);
+    }
+
+    #[test]
+    fn test_trie_diff_turns_before_into_after() {
+        use crate::implementation::rocks::trie::commit_trie_updates;
+
+        // Two databases with the same accounts and trie
+        let seeded_db = || {
+            let (db, temp_dir) = create_test_db();
+            let write_tx = RocksTransaction::<true>::from_db(db.clone());
+            for i in 1..=64u8 {
+                write_tx
+                    .put::<HashedAccounts>(
+                        keccak256(Address::from([i; 20])),
+                        create_test_account(i as u64, 1000, None),
+                    )
+                    .unwrap();
+            }
+            write_tx.commit().unwrap();
+
+            let read_tx = RocksTransaction::<false>::from_db(db.clone());
+            let write_tx = RocksTransaction::<true>::from_db(db.clone());
+            write_tx.rebuild_trie_tables(&read_tx).unwrap();
+            write_tx.commit().unwrap();
+            (db, temp_dir)
+        };
+        let state = |accounts: std::ops::RangeInclusive<u8>, balance| {
+            create_simple_post_state(
+                accounts
+                    .map(|i| (Address::from([i; 20]), create_test_account(i as u64, balance, None)))
+                    .collect(),
+            )
+        };
+        // Overlapping changes, with new accounts in `after`
+        let before = state(1..=20, 2000);
+        let after = state(10..=80, 3000);
+        let trie_nodes = |db| {
+            let tx = RocksTransaction::<false>::from_db(db);
+            let mut cursor = tx.cursor_read::<AccountTrieTable>().unwrap();
+            cursor.walk(None).unwrap().collect::<Result<Vec<_>, _>>().unwrap()
+        };
+
+        // Apply `before`, then the diff
+        let (diffed_db, _diffed_dir) = seeded_db();
+        let read_tx = RocksTransaction::<false>::from_db(diffed_db.clone());
+        let diff = read_tx.trie_diff(before.clone(), after.clone()).unwrap();
+        assert!(!diff.is_empty());
+
+        let write_tx = RocksTransaction::<true>::from_db(diffed_db.clone());
+        calculate_state_root_with_updates(&read_tx, &write_tx, before, None).unwrap();
+        write_tx.commit().unwrap();
+        let write_tx = RocksTransaction::<true>::from_db(diffed_db.clone());
+        commit_trie_updates(&write_tx, diff).unwrap();
+        write_tx.commit().unwrap();
+
+        // Apply `after` directly
+        let (direct_db, _direct_dir) = seeded_db();
+        let read_tx = RocksTransaction::<false>::from_db(direct_db.clone());
+        let write_tx = RocksTransaction::<true>::from_db(direct_db.clone());
+        let after_root =
+            calculate_state_root_with_updates(&read_tx, &write_tx, after.clone(), None).unwrap();
+        write_tx.commit().unwrap();
+
+        assert_eq!(trie_nodes(diffed_db.clone()), trie_nodes(direct_db));
+        let diffed_tx = RocksTransaction::<false>::from_db(diffed_db);
+        assert_eq!(calculate_state_root(&diffed_tx, after).unwrap(), after_root);
+    }
+
+    #[test]
+    fn test_calculate_state_root_batched_matches_sequential() {
+        let slots = |address: u8, values: std::ops::Range<u64>| -> (Address, Vec<(B256, U256)>) {
+            let slots =
+                values.map(|i| (keccak256([address, i as u8]), U256::from(i + 1))).collect();
+            (Address::from([address; 20]), slots)
+        };
+        let account = |address: u8, nonce: u64, balance: u64| {
+            (Address::from([address; 20]), create_test_account(nonce, balance, None))
+        };
+        // Later states update accounts and slots of earlier ones and add new ones
+        let states = vec![
+            create_post_state_with_storage(
+                vec![account(1, 1, 1000), account(2, 1, 1000), account(3, 1, 1000)],
+                vec![slots(1, 0..20)],
+            ),
+            create_post_state_with_storage(
+                vec![account(2, 2, 2000), account(4, 1, 1000)],
+                vec![slots(1, 10..30), slots(4, 0..5)],
+            ),
+            create_post_state_with_storage(
+                vec![account(1, 2, 500), account(5, 1, 1000)],
+                vec![slots(4, 3..8)],
+            ),
+        ];
+        let write_hashed_state = |tx: &RocksTransaction<true>, state: &HashedPostState| {
+            for (hashed_address, account) in &state.accounts {
+                tx.put::<HashedAccounts>(*hashed_address, account.unwrap()).unwrap();
+            }
+            let mut cursor = tx.cursor_dup_write::<HashedStorages>().unwrap();
+            for (hashed_address, storage) in &state.storages {
+                for (slot, value) in &storage.storage {
+                    cursor
+                        .upsert_dup(
+                            *hashed_address,
+                            *slot,
+                            &StorageEntry { key: *slot, value: *value },
+                        )
+                        .unwrap();
+                }
+            }
+        };
+        let root_from_tables = |db| {
+            let tx = RocksTransaction::<false>::from_db(db);
+            calculate_state_root(&tx, HashedPostState::default()).unwrap()
+        };
+
+        // One trie commit per state, each state's hashed changes written as it is applied
+        let (sequential_db, _sequential_dir) = create_test_db();
+        let mut sequential_roots = Vec::new();
+        for state in &states {
+            let read_tx = RocksTransaction::<false>::from_db(sequential_db.clone());
+            let write_tx = RocksTransaction::<true>::from_db(sequential_db.clone());
+            sequential_roots.push(
+                calculate_state_root_with_updates(&read_tx, &write_tx, state.clone(), None)
+                    .unwrap(),
+            );
+            write_hashed_state(&write_tx, state);
+            write_tx.commit().unwrap();
+        }
+
+        // A single trie commit for all of them
+        let (batched_db, _batched_dir) = create_test_db();
+        let read_tx = RocksTransaction::<false>::from_db(batched_db.clone());
+        let write_tx = RocksTransaction::<true>::from_db(batched_db.clone());
+        let batched_roots =
+            calculate_state_root_batched(&read_tx, &write_tx, states.clone()).unwrap();
+        for state in &states {
+            write_hashed_state(&write_tx, state);
+        }
+        write_tx.commit().unwrap();
+
+        assert_eq!(batched_roots, sequential_roots);
+        assert_eq!(batched_roots.iter().collect::<HashSet<_>>().len(), 3);
+
+        // The stored tries both resolve to the final root
+        assert_eq!(root_from_tables(sequential_db), sequential_roots[2]);
+        assert_eq!(root_from_tables(batched_db), batched_roots[2]);
+
+        let (empty_db, _empty_dir) = create_test_db();
+        let read_tx = RocksTransaction::<false>::from_db(empty_db.clone());
+        let write_tx = RocksTransaction::<true>::from_db(empty_db);
+        assert!(calculate_state_root_batched(&read_tx, &write_tx, Vec::new()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_state_root_reuses_stored_nodes() {
+        let base = create_post_state_with_storage(
+            (1..=50u8)
+                .map(|i| (Address::from([i; 20]), create_test_account(i as u64, 1000, None)))
+                .collect(),
+            vec![(
+                Address::from([7; 20]),
+                (0..10u8).map(|i| (keccak256([i]), U256::from(i + 1))).collect(),
+            )],
+        );
+        let changed = keccak256(Address::from([7; 20]));
+        let change = create_simple_post_state(vec![(
+            Address::from([7; 20]),
+            create_test_account(100, 5, None),
+        )]);
+
+        // Commit the base state's trie nodes along with its hashed state
+        let (db, _temp_dir) = create_test_db();
+        let read_tx = RocksTransaction::<false>::from_db(db.clone());
+        let write_tx = RocksTransaction::<true>::from_db(db.clone());
+        calculate_state_root_with_updates(&read_tx, &write_tx, base.clone(), None).unwrap();
+        for (hashed_address, account) in &base.accounts {
+            write_tx.put::<HashedAccounts>(*hashed_address, account.unwrap()).unwrap();
+        }
+        let mut cursor = write_tx.cursor_dup_write::<HashedStorages>().unwrap();
+        for (hashed_address, storage) in &base.storages {
+            for (slot, value) in &storage.storage {
+                cursor
+                    .upsert_dup(*hashed_address, *slot, &StorageEntry { key: *slot, value: *value })
+                    .unwrap();
+            }
+        }
+        drop(cursor);
+
+        // Change an account in a subtrie the change doesn't touch, behind the trie's back: an
+        // account sharing its first nibble with another one, so the root node holds the hash of
+        // their branch, and not sharing it with the changed account
+        let first_nibble = |hash: &B256| hash[0] >> 4;
+        let hashes: Vec<B256> = base.accounts.keys().copied().collect();
+        let unrelated = *hashes
+            .iter()
+            .find(|hash| {
+                first_nibble(hash) != first_nibble(&changed) &&
+                    hashes
+                        .iter()
+                        .filter(|other| first_nibble(other) == first_nibble(hash))
+                        .count()
+                        > 1
+            })
+            .unwrap();
+        let stale = create_test_account(999, 999, None);
+        write_tx.put::<HashedAccounts>(unrelated, stale).unwrap();
+        write_tx.commit().unwrap();
+
+        let read_tx = RocksTransaction::<false>::from_db(db);
+        let root = calculate_state_root(&read_tx, change.clone()).unwrap();
+
+        // The unrelated subtrie was taken from its stored hash, not walked down to the hashed
+        // table: the root is that of the state the trie was built for
+        let mut full = base;
+        full.extend(change);
+        let (empty_db, _empty_dir) = create_test_db();
+        let empty_tx = RocksTransaction::<false>::from_db(empty_db);
+        assert_eq!(root, calculate_state_root(&empty_tx, full.clone()).unwrap());
+
+        full.accounts.insert(unrelated, Some(stale));
+        assert_ne!(root, calculate_state_root(&empty_tx, full).unwrap());
+    }
+
+    #[test]
+    fn test_accounts_with_storage_roots() {
+        let (db, _temp_dir) = create_test_db();
+
+        let slots = |address: u8, count: u8| -> Vec<(B256, U256)> {
+            (1..=count)
+                .map(|i| (keccak256([address, i]), U256::from(address as u64 * 100 + i as u64)))
+                .collect()
+        };
+        // One account with enough slots for stored storage trie branches, one with a single
+        // slot and one without storage
+        let with_storage = [(1u8, slots(1, 20)), (2, slots(2, 1)), (3, Vec::new())];
+
+        let write_tx = RocksTransaction::<true>::from_db(db.clone());
+        let mut storage_cursor = write_tx.cursor_dup_write::<HashedStorages>().unwrap();
+        for (i, storage) in &with_storage {
+            let hashed_address = keccak256(Address::from([*i; 20]));
+            write_tx
+                .put::<HashedAccounts>(hashed_address, create_test_account(*i as u64, 1000, None))
+                .unwrap();
+            for (slot, value) in storage {
+                storage_cursor
+                    .upsert_dup(hashed_address, *slot, &StorageEntry { key: *slot, value: *value })
+                    .unwrap();
+            }
+        }
+        drop(storage_cursor);
+        write_tx.commit().unwrap();
+
+        let read_tx = RocksTransaction::<false>::from_db(db.clone());
+        let write_tx = RocksTransaction::<true>::from_db(db.clone());
+        write_tx.rebuild_trie_tables(&read_tx).unwrap();
+        write_tx.commit().unwrap();
+
+        // Present and absent addresses, out of hashed order, with a repeat
+        let addresses = [
+            Address::from([3; 20]),
+            Address::from([9; 20]),
+            Address::from([1; 20]),
+            Address::from([2; 20]),
+            Address::from([8; 20]),
+            Address::from([1; 20]),
+        ];
+        let tx = RocksTransaction::<false>::from_db(db);
+        let results = tx.accounts_with_storage_roots(&addresses).unwrap();
+
+        let expected = |i: u8| {
+            let storage = with_storage.iter().find(|(n, _)| *n == i).map(|(_, s)| s.clone());
+            let account = storage.as_ref().map(|_| create_test_account(i as u64, 1000, None));
+            let storage_root = storage.map_or(EMPTY_ROOT_HASH, storage_root_prehashed);
+            (Address::from([i; 20]), account, storage_root)
+        };
+        assert_eq!(
+            results,
+            vec![expected(3), expected(9), expected(1), expected(2), expected(8), expected(1)]
+        );
+        assert_ne!(results[2].2, EMPTY_ROOT_HASH);
+        assert_eq!(results[0].2, EMPTY_ROOT_HASH);
+    }
+
+    #[test]
+    fn test_calculate_state_root_streaming_matches_buffered() {
+        let accounts: Vec<_> = (0..64u8)
+            .map(|i| (Address::from([i; 20]), create_test_account(i as u64, 1000 + i as u64, None)))
+            .collect();
+        let storages: Vec<_> = (0..4u8)
+            .map(|i| {
+                let slots: Vec<_> = (0..32u8)
+                    .map(|slot| (keccak256([i, slot]), U256::from(slot as u64 + 1)))
+                    .collect();
+                (Address::from([i; 20]), slots)
+            })
+            .collect();
+        let post_state = create_post_state_with_storage(accounts, storages);
+
+        let stored_nodes = |db| {
+            let tx = RocksTransaction::<false>::from_db(db);
+            let mut account_nodes = Vec::new();
+            let mut cursor = tx.cursor_read::<AccountTrieTable>().unwrap();
+            let mut entry = cursor.first().unwrap();
+            while let Some(node) = entry {
+                account_nodes.push(node);
+                entry = cursor.next().unwrap();
+            }
+            let storage_nodes: Vec<_> =
+                tx.iter_dup_groups::<StorageTrieTable>().unwrap().map(Result::unwrap).collect();
+            (account_nodes, storage_nodes)
+        };
+
+        let (buffered_db, _buffered_dir) = create_test_db();
+        let read_tx = RocksTransaction::<false>::from_db(buffered_db.clone());
+        let write_tx = RocksTransaction::<true>::from_db(buffered_db.clone());
+        let buffered_root =
+            calculate_state_root_with_updates(&read_tx, &write_tx, post_state.clone(), None)
+                .unwrap();
+        write_tx.commit().unwrap();
+
+        // A chunk size well below the number of entries, so the root takes several chunks
+        let (streaming_db, _streaming_dir) = create_test_db();
+        let read_tx = RocksTransaction::<false>::from_db(streaming_db.clone());
+        let write_tx = RocksTransaction::<true>::from_db(streaming_db.clone());
+        let streaming_root =
+            calculate_state_root_streaming(&read_tx, &write_tx, post_state, 10).unwrap();
+        write_tx.commit().unwrap();
+
+        assert_eq!(streaming_root, buffered_root);
+        let (account_nodes, storage_nodes) = stored_nodes(streaming_db);
+        assert!(!account_nodes.is_empty());
+        assert_eq!(storage_nodes.len(), 4);
+        assert_eq!((account_nodes, storage_nodes), stored_nodes(buffered_db));
+    }
+
+    #[test]
+    fn test_trie_nodes_agree_by_path_and_hash() {
+        use reth_trie::BranchNodeCompact;
+
+        // Hash of a node's stored layout, built here rather than through `StoredTrieNode`
+        fn expected_hash(node: &BranchNodeCompact) -> B256 {
+            let mut bytes = Vec::new();
+            bytes.extend_from_slice(&node.state_mask.get().to_be_bytes());
+            bytes.extend_from_slice(&node.tree_mask.get().to_be_bytes());
+            bytes.extend_from_slice(&node.hash_mask.get().to_be_bytes());
+            bytes.push(node.hashes.len() as u8);
+            for hash in node.hashes.iter() {
+                bytes.extend_from_slice(hash.as_slice());
+            }
+            match node.root_hash {
+                Some(root) => {
+                    bytes.push(1);
+                    bytes.extend_from_slice(root.as_slice());
+                }
+                None => bytes.push(0),
+            }
+            keccak256(bytes)
+        }
+
+        // Every account node is found by its path, by its hash, and the hash leads back to the
+        // path. Returns the number of nodes.
+        fn check_account_nodes(tx: &RocksTransaction<false>) -> usize {
+            let mut account_nodes = 0;
+            let mut cursor = tx.cursor_read::<AccountTrieTable>().unwrap();
+            let mut entry = cursor.first().unwrap();
+            while let Some((path, node)) = entry {
+                let hash = expected_hash(&node);
+                assert_eq!(tx.get_node_path(hash).unwrap(), Some(path.clone()));
+                assert_eq!(tx.get_node_required(hash).unwrap().to_branch_node(), node);
+                account_nodes += 1;
+                entry = cursor.next().unwrap();
+            }
+
+            // No entry of the index outlives the node it points to
+            let mut cursor = tx.cursor_read::<TrieNodePaths>().unwrap();
+            let mut indexed = 0;
+            let mut entry = cursor.first().unwrap();
+            while let Some((hash, path)) = entry {
+                let node = tx.get_account(path).unwrap().expect("indexed path has no node");
+                assert_eq!(expected_hash(&node), hash, "index entry is stale");
+                indexed += 1;
+                entry = cursor.next().unwrap();
+            }
+            assert_eq!(indexed, account_nodes);
+
+            account_nodes
+        }
+
+        let (db, _temp_dir) = create_test_db();
+
+        let accounts = (0..64u8)
+            .map(|i| (Address::from([i; 20]), create_test_account(i as u64, 1000 + i as u64, None)))
+            .collect();
+        let storages = (0..4u8)
+            .map(|i| {
+                let slots: Vec<_> = (0..32u8)
+                    .map(|slot| (keccak256([i, slot]), U256::from(slot as u64 + 1)))
+                    .collect();
+                (Address::from([i; 20]), slots)
+            })
+            .collect();
+        let post_state = create_post_state_with_storage(accounts, storages);
+
+        let read_tx = RocksTransaction::<false>::from_db(db.clone());
+        let write_tx = RocksTransaction::<true>::from_db(db.clone());
+        calculate_state_root_with_updates(&read_tx, &write_tx, post_state, None).unwrap();
+        write_tx.commit().unwrap();
+
+        let tx = RocksTransaction::<false>::from_db(db.clone());
+        assert!(check_account_nodes(&tx) > 0);
+
+        // Every storage node's hash resolves to a stored node
+        let mut storage_nodes = 0;
+        for group in tx.iter_dup_groups::<StorageTrieTable>().unwrap() {
+            let (address, values) = group.unwrap();
+            for value in values {
+                let node = tx.get_node_required(value.node).unwrap();
+                assert_eq!(node.hash(), value.node, "storage node of {address} has a stale hash");
+                storage_nodes += 1;
+            }
+        }
+        assert!(storage_nodes > 0);
+
+        // Changing accounts rewrites nodes in place; the index follows them
+        let accounts = (0..64u8)
+            .step_by(3)
+            .map(|i| (Address::from([i; 20]), create_test_account(i as u64 + 1, 7, None)))
+            .collect();
+        let post_state = create_post_state_with_storage(accounts, Vec::new());
+        let read_tx = RocksTransaction::<false>::from_db(db.clone());
+        let write_tx = RocksTransaction::<true>::from_db(db.clone());
+        calculate_state_root_with_updates(&read_tx, &write_tx, post_state, None).unwrap();
+        write_tx.commit().unwrap();
+
+        assert!(check_account_nodes(&RocksTransaction::<false>::from_db(db)) > 0);
+    }
+
+    #[test]
+    fn test_commit_trie_updates_deletes_storage_nodes() {
+        use crate::implementation::rocks::trie::commit_trie_updates;
+        use crate::tables::trie::TrieNodeValue;
+        use reth_trie::{
+            updates::{StorageTrieUpdates, TrieUpdates},
+            Nibbles, StoredNibbles,
+        };
+
+        let (db, _temp_dir) = create_test_db();
+        let (wiped, pruned) = (B256::from([1; 32]), B256::from([2; 32]));
+        let path = |nibbles: &[u8]| Nibbles::from_nibbles(nibbles);
+        let value = |nibbles: &[u8]| TrieNodeValue {
+            nibbles: StoredNibbles(path(nibbles)),
+            node: B256::from([nibbles.len() as u8; 32]),
+        };
+
+        // Both tries hold composite duplicates and a plain entry
+        let write_tx = RocksTransaction::<true>::from_db(db.clone());
+        let mut cursor = write_tx.cursor_dup_write::<StorageTrieTable>().unwrap();
+        for address in [wiped, pruned] {
+            for nibbles in [&[1][..], &[1, 2]] {
+                cursor.upsert_dup(address, StoredNibbles(path(nibbles)), &value(nibbles)).unwrap();
+            }
+        }
+        drop(cursor);
+        for address in [wiped, pruned] {
+            write_tx.put::<StorageTrieTable>(address, value(&[3])).unwrap();
+        }
+        write_tx.commit().unwrap();
+
+        let mut updates = TrieUpdates::default();
+        updates
+            .storage_tries
+            .insert(wiped, StorageTrieUpdates { is_deleted: true, ..Default::default() });
+        let mut pruned_updates = StorageTrieUpdates::default();
+        pruned_updates.removed_nodes.extend([path(&[1]), path(&[3])]);
+        updates.storage_tries.insert(pruned, pruned_updates);
+
+        let write_tx = RocksTransaction::<true>::from_db(db.clone());
+        commit_trie_updates(&write_tx, updates).unwrap();
+        write_tx.commit().unwrap();
+
+        // The wiped trie is gone entirely, the pruned one keeps the node below the removed path
+        let read_tx = RocksTransaction::<false>::from_db(db);
+        let groups: Vec<_> =
+            read_tx.iter_dup_groups::<StorageTrieTable>().unwrap().map(Result::unwrap).collect();
+        assert_eq!(groups, vec![(pruned, vec![value(&[1, 2])])]);
+    }
+
+    #[cfg(feature = "metrics")]
+    #[test]
+    fn test_commit_trie_updates_records_node_counts() {
+        use crate::implementation::rocks::trie::commit_trie_updates;
+        use metrics_util::debugging::{DebugValue, DebuggingRecorder};
+        use reth_trie::{
+            updates::{StorageTrieUpdates, TrieUpdates},
+            BranchNodeCompact, Nibbles, TrieMask,
+        };
+
+        let (db, _temp_dir) = create_test_db();
+
+        let node = BranchNodeCompact::new(
+            TrieMask::new(0b11),
+            TrieMask::new(0),
+            TrieMask::new(0),
+            Vec::new(),
+            None,
+        );
+
+        // 3 account nodes written, 2 removed, 4 storage nodes across 2 tries, 1 trie wiped
+        let mut updates = TrieUpdates::default();
+        for i in 0..3u8 {
+            updates.account_nodes.insert(Nibbles::from_nibbles([i]), node.clone());
+        }
+        for i in 3..5u8 {
+            updates.removed_nodes.insert(Nibbles::from_nibbles([i]));
+        }
+        for (address, count, is_deleted) in [(1u8, 1u8, true), (2, 3, false)] {
+            let mut storage = StorageTrieUpdates { is_deleted, ..Default::default() };
+            for i in 0..count {
+                storage.storage_nodes.insert(Nibbles::from_nibbles([i]), node.clone());
+            }
+            updates.storage_tries.insert(B256::from([address; 32]), storage);
+        }
+
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+        let write_tx = RocksTransaction::<true>::from_db(db.clone());
+        metrics::with_local_recorder(&recorder, || commit_trie_updates(&write_tx, updates))
+            .unwrap();
+        write_tx.commit().unwrap();
+
+        let snapshot = snapshotter.snapshot().into_vec();
+        let counter = |name: &str| {
+            snapshot.iter().find(|(key, _, _, _)| key.key().name() == name).map(
+                |(_, _, _, value)| match value {
+                    DebugValue::Counter(value) => *value,
+                    other => panic!("{name} is not a counter: {other:?}"),
+                },
+            )
+        };
+
+        assert_eq!(counter("rocksdb_trie_account_nodes_written_total"), Some(3));
+        assert_eq!(counter("rocksdb_trie_account_nodes_deleted_total"), Some(2));
+        assert_eq!(counter("rocksdb_trie_storage_nodes_written_total"), Some(4));
+        assert_eq!(counter("rocksdb_trie_storage_tries_deleted_total"), Some(1));
+    }
 }
@@ -85,7 +85,8 @@ fn setup_test_state(
     post_state.storages.insert(hashed_address1, storage);
 
     // Calculate state root and commit trie
-    let state_root = calculate_state_root_with_updates(read_tx, write_tx, post_state).unwrap();
+    let state_root =
+        calculate_state_root_with_updates(read_tx, write_tx, post_state, None).unwrap();
 
     (state_root, address1, address2, storage_key)
 }
@@ -113,7 +114,7 @@ fn test_put_get_account_trie_node() {
     let (db, _temp_dir) = create_test_db();
 
     // Creating a Writable txn <WRITE: true>
-    let tx = RocksTransaction::<true>::new(db.clone(), true);
+    let tx = RocksTransaction::<true>::from_db(db.clone());
 
     // Creating dummy nibbles (key)
     let nibbles = Nibbles::from_nibbles(&[1, 2, 3, 4, 5, 6, 7, 8, 9, 10]);
@@ -129,7 +130,7 @@ fn test_put_get_account_trie_node() {
     tx.commit().unwrap();
 
     // Creating a Read txn <WRITE: false>
-    let read_tx = RocksTransaction::<false>::new(db.clone(), false);
+    let read_tx = RocksTransaction::<false>::from_db(db.clone());
 
     // Getting the value from the db
     let stored_val = read_tx.get::<AccountTrieTable>(key.clone()).unwrap();
@@ -144,7 +145,7 @@ fn test_put_get_storage_trie_node() {
     let (db, _temp_dir) = create_test_db();
 
     // Create a writable txn
-    let tx = RocksTransaction::<true>::new(db.clone(), true);
+    let tx = RocksTransaction::<true>::from_db(db.clone());
 
     // Creating test account and hash it
     let address = Address::from([1; 20]);
@@ -170,7 +171,7 @@ fn test_put_get_storage_trie_node() {
     tx.commit().unwrap();
 
     // Create a read transaction
-    let read_tx = RocksTransaction::<false>::new(db, false);
+    let read_tx = RocksTransaction::<false>::from_db(db);
 
     // Try to get the value back
     let mut read_cursor = read_tx.cursor_dup_read::<StorageTrieTable>().unwrap();
@@ -189,7 +190,7 @@ fn test_cursor_navigation() {
     let (db, _temp_dir) = create_test_db();
 
     // Creating a Writable txn <WRITE: true>
-    let tx = RocksTransaction::<true>::new(db.clone(), true);
+    let tx = RocksTransaction::<true>::from_db(db.clone());
 
     // Insert multiple account trie nodes
     let mut keys = Vec::new();
@@ -210,7 +211,7 @@ fn test_cursor_navigation() {
     tx.commit().unwrap();
 
     // Creating a read txn
-    let read_tx = RocksTransaction::<false>::new(db.clone(), false);
+    let read_tx = RocksTransaction::<false>::from_db(db.clone());
 
     // Test cursor navigation
     let mut cursor = read_tx.cursor_read::<AccountTrieTable>().unwrap();
@@ -246,7 +247,7 @@ fn test_delete_account_trie_node() {
     let (db, _temp_dir) = create_test_db();
 
     // Create writable txn
-    let tx = RocksTransaction::<true>::new(db.clone(), true);
+    let tx = RocksTransaction::<true>::from_db(db.clone());
 
     // Creating test key and vals
     let nibbles = Nibbles::from_nibbles(&[1, 2, 3, 4]);
@@ -258,17 +259,17 @@ fn test_delete_account_trie_node() {
     tx.commit().unwrap();
 
     // Verify if it is there
-    let read_tx = RocksTransaction::<false>::new(db.clone(), false);
+    let read_tx = RocksTransaction::<false>::from_db(db.clone());
     assert!(read_tx.get::<AccountTrieTable>(key.clone()).unwrap().is_some());
     assert_eq!(read_tx.get::<AccountTrieTable>(key.clone()).unwrap().unwrap(), val);
 
     // Delete the k-v pair
-    let delete_tx = RocksTransaction::<true>::new(db.clone(), true);
+    let delete_tx = RocksTransaction::<true>::from_db(db.clone());
     delete_tx.delete::<AccountTrieTable>(key.clone(), None).unwrap();
     delete_tx.commit().unwrap();
 
     // Verify if it's gone
-    let verify_tx = RocksTransaction::<false>::new(db.clone(), false);
+    let verify_tx = RocksTransaction::<false>::from_db(db.clone());
     assert!(verify_tx.get::<AccountTrieTable>(key).unwrap().is_none());
 }
 
@@ -277,7 +278,7 @@ fn test_empty_values() {
     let (db, _temp_dir) = create_test_db();
 
     // Create writable tx
-    let tx = RocksTransaction::<true>::new(db.clone(), true);
+    let tx = RocksTransaction::<true>::from_db(db.clone());
 
     // Create a key
     let nibbles = Nibbles::from_nibbles(&[1, 2, 3, 4, 5, 6]);
@@ -295,7 +296,7 @@ fn test_empty_values() {
     tx.commit().unwrap();
 
     // Verify we can retrieve it
-    let read_tx = RocksTransaction::<false>::new(db.clone(), false);
+    let read_tx = RocksTransaction::<false>::from_db(db.clone());
     let result = read_tx.get::<AccountTrieTable>(key).unwrap();
     assert!(result.is_some());
     assert_eq!(result.unwrap(), empty_val);
@@ -306,7 +307,7 @@ fn test_transaction_abort() {
     let (db, _temp_dir) = create_test_db();
 
     // Create a writable transaction
-    let tx = RocksTransaction::<true>::new(db.clone(), true);
+    let tx = RocksTransaction::<true>::from_db(db.clone());
 
     // Create test key and value
     let nibbles = Nibbles::from_nibbles(&[9, 8, 7, 6, 5]);
@@ -320,7 +321,7 @@ fn test_transaction_abort() {
     tx.abort();
 
     // Verify the data was not persisted
-    let read_tx = RocksTransaction::<false>::new(db.clone(), false);
+    let read_tx = RocksTransaction::<false>::from_db(db.clone());
     assert!(read_tx.get::<AccountTrieTable>(key.clone()).unwrap().is_none());
 }
 
@@ -329,7 +330,7 @@ fn test_large_keys_and_values() {
     let (db, _temp_dir) = create_test_db();
 
     // Create a writable transaction
-    let tx = RocksTransaction::<true>::new(db.clone(), true);
+    let tx = RocksTransaction::<true>::from_db(db.clone());
 
     // Create a large key (many nibbles)
     let mut nibble_vec = Vec::new();
@@ -363,7 +364,7 @@ fn test_large_keys_and_values() {
     tx.commit().unwrap();
 
     // Verify we can retrieve it correctly
-    let read_tx = RocksTransaction::<false>::new(db.clone(), false);
+    let read_tx = RocksTransaction::<false>::from_db(db.clone());
     let result = read_tx.get::<AccountTrieTable>(large_key).unwrap();
     assert!(result.is_some());
     assert_eq!(result.unwrap(), large_node);
@@ -374,7 +375,7 @@ fn test_update_existing_key() {
     let (db, _temp_dir) = create_test_db();
 
     // Create initial transaction
-    let tx1 = RocksTransaction::<true>::new(db.clone(), true);
+    let tx1 = RocksTransaction::<true>::from_db(db.clone());
 
     // Create test key
     let nibbles = Nibbles::from_nibbles(&[1, 3, 5, 7, 9]);
@@ -388,7 +389,7 @@ fn test_update_existing_key() {
     tx1.commit().unwrap();
 
     // Create second transaction to update the value
-    let tx2 = RocksTransaction::<true>::new(db.clone(), true);
+    let tx2 = RocksTransaction::<true>::from_db(db.clone());
 
     // Create new value with different root hash
     let state_mask = TrieMask::new(0);
@@ -405,7 +406,7 @@ fn test_update_existing_key() {
     tx2.commit().unwrap();
 
     // Verify the value was updated
-    let read_tx = RocksTransaction::<false>::new(db.clone(), false);
+    let read_tx = RocksTransaction::<false>::from_db(db.clone());
     let result = read_tx.get::<AccountTrieTable>(key).unwrap();
     assert!(result.is_some());
 
@@ -476,11 +477,12 @@ fn test_calculate_state_root_with_updates() {
         println!("Prefix Sets: {:?}", prefix_sets);
 
         // Create transactions for reading and writing
-        let read_tx = RocksTransaction::<false>::new(db.clone(), false);
-        let write_tx = RocksTransaction::<true>::new(db.clone(), true);
+        let read_tx = RocksTransaction::<false>::from_db(db.clone());
+        let write_tx = RocksTransaction::<true>::from_db(db.clone());
 
         // Calculate state root and store nodes
-        initial_root = calculate_state_root_with_updates(&read_tx, &write_tx, post_state).unwrap();
+        initial_root =
+            calculate_state_root_with_updates(&read_tx, &write_tx, post_state, None).unwrap();
 
         // Manually insert a test node to verify DB writes are working
         let test_nibbles = Nibbles::from_nibbles_unchecked(vec![0, 1, 2, 3]);
@@ -501,7 +503,7 @@ fn test_calculate_state_root_with_updates() {
         println!("Running sub-test: Verify initial node storage");
 
         // Verify that nodes were stored by checking if we can retrieve them
-        let verify_tx = RocksTransaction::<false>::new(db.clone(), false);
+        let verify_tx = RocksTransaction::<false>::from_db(db.clone());
 
         // Check if we can read from AccountTrieTable
         let mut cursor = verify_tx.cursor_read::<AccountTrieTable>().unwrap();
@@ -538,13 +540,17 @@ fn test_calculate_state_root_with_updates() {
         updated_post_state.storages.insert(hashed_address, updated_storage);
 
         // Create new transactions
-        let read_tx2 = RocksTransaction::<false>::new(db.clone(), false);
-        let write_tx2 = RocksTransaction::<true>::new(db.clone(), true);
+        let read_tx2 = RocksTransaction::<false>::from_db(db.clone());
+        let write_tx2 = RocksTransaction::<true>::from_db(db.clone());
 
         // Calculate new state root and store updated nodes
-        updated_root =
-            calculate_state_root_with_updates(&read_tx2, &write_tx2, updated_post_state.clone())
-                .unwrap();
+        updated_root = calculate_state_root_with_updates(
+            &read_tx2,
+            &write_tx2,
+            updated_post_state.clone(),
+            None,
+        )
+        .unwrap();
 
         // Commit changes
         write_tx2.commit().unwrap();
@@ -558,7 +564,7 @@ fn test_calculate_state_root_with_updates() {
         println!("Running sub-test: Verify updated node storage");
 
         // Verify that we can still read entries
-        let verify_tx2 = RocksTransaction::<false>::new(db.clone(), false);
+        let verify_tx2 = RocksTransaction::<false>::from_db(db.clone());
         let mut cursor2 = verify_tx2.cursor_read::<AccountTrieTable>().unwrap();
         let mut updated_entries = 0;
         let mut first_entry2 = cursor2.first().unwrap();
@@ -590,7 +596,7 @@ fn test_calculate_state_root_with_updates() {
         verification_state.storages.insert(hashed_address, storage);
 
         // Calculate the root again with a fresh transaction
-        let read_tx3 = RocksTransaction::<false>::new(db.clone(), false);
+        let read_tx3 = RocksTransaction::<false>::from_db(db.clone());
         let recomputed_root = calculate_state_root(&read_tx3, verification_state).unwrap();
 
         assert_eq!(
@@ -605,8 +611,8 @@ fn test_account_proof_generation() {
     let (db, _temp_dir) = create_test_db();
 
     // Setup initial state
-    let read_tx = RocksTransaction::<false>::new(db.clone(), false);
-    let write_tx = RocksTransaction::<true>::new(db.clone(), true);
+    let read_tx = RocksTransaction::<false>::from_db(db.clone());
+    let write_tx = RocksTransaction::<true>::from_db(db.clone());
 
     // Create test accounts
     let account1 =
@@ -627,7 +633,8 @@ fn test_account_proof_generation() {
     post_state.storages.insert(hashed_address1, storage1);
 
     // Calculate state root and get updates
-    let state_root = calculate_state_root_with_updates(&read_tx, &write_tx, post_state).unwrap();
+    let state_root =
+        calculate_state_root_with_updates(&read_tx, &write_tx, post_state, None).unwrap();
     println!("State root calculated: {}", state_root);
 
     // Manually insert a node for the account
@@ -649,12 +656,12 @@ fn test_account_proof_generation() {
     write_tx.commit().unwrap();
 
     // Verify that we can retrieve the account node
-    let verify_tx = RocksTransaction::<false>::new(db.clone(), false);
+    let verify_tx = RocksTransaction::<false>::from_db(db.clone());
     let retrieved_node = verify_tx.get_account(TrieNibbles(account_nibbles)).unwrap();
     println!("Retrieved account node: {:?}", retrieved_node);
 
     // Generate proof
-    let proof_tx = RocksTransaction::<false>::new(db.clone(), false);
+    let proof_tx = RocksTransaction::<false>::from_db(db.clone());
     let proof_generator =
         Proof::new(proof_tx.trie_cursor_factory(), proof_tx.hashed_cursor_factory());
 
@@ -683,7 +690,7 @@ fn test_account_proof_generation() {
 //     let (db, _temp_dir) = create_test_db();
 
 //     // Create a write transaction and insert some test accounts
-//     let write_tx = RocksTransaction::<true>::new(db.clone(), true);
+//     let write_tx = RocksTransaction::<true>::from_db(db.clone());
 
 //     // Create test accounts
 //     let addr1 = keccak256(Address::from([1; 20]));
@@ -713,14 +720,14 @@ fn test_account_proof_generation() {
 //     println!("Transaction committed");
 
 //     // Verify accounts were stored
-//     let verify_tx = RocksTransaction::<false>::new(db.clone(), false);
+//     let verify_tx = RocksTransaction::<false>::from_db(db.clone());
 //     let acct1 = verify_tx.get::<HashedAccounts>(addr1).unwrap();
 //     let acct2 = verify_tx.get::<HashedAccounts>(addr2).unwrap();
 //     let acct2 = verify_tx.get::<HashedAccounts>(addr3).unwrap();
 //     println!("Verification: Account1: {:?}, Account2: {:?}", acct1, acct2);
 
 //     // Create a read transaction to test the cursor
-//     let read_tx = RocksTransaction::<false>::new(db.clone(), false);
+//     let read_tx = RocksTransaction::<false>::from_db(db.clone());
 
 //     // Create and test hashed account cursor
 //     let hashed_factory = RocksHashedCursorFactory::new(&read_tx);
@@ -754,7 +761,7 @@ fn test_rocks_cursor_basic() {
     let (db, _temp_dir) = create_test_db();
 
     // Create a write transaction and insert some test data
-    let write_tx = RocksTransaction::<true>::new(db.clone(), true);
+    let write_tx = RocksTransaction::<true>::from_db(db.clone());
 
     // Create test keys and values
     let key1 = B256::from([1; 32]);
@@ -774,7 +781,7 @@ fn test_rocks_cursor_basic() {
     write_tx.commit().unwrap();
 
     // Test with a read transaction
-    let read_tx = RocksTransaction::<false>::new(db.clone(), false);
+    let read_tx = RocksTransaction::<false>::from_db(db.clone());
 
     // Get a cursor directly
     let mut cursor = read_tx.cursor_read::<HashedAccounts>().unwrap();
@@ -795,8 +802,8 @@ fn test_account_proof_generation1() {
     let (db, _temp_dir) = create_test_db();
 
     // Setup initial state
-    let read_tx = RocksTransaction::<false>::new(db.clone(), false);
-    let write_tx = RocksTransaction::<true>::new(db.clone(), true);
+    let read_tx = RocksTransaction::<false>::from_db(db.clone());
+    let write_tx = RocksTransaction::<true>::from_db(db.clone());
     let (state_root, address1, _, _) = setup_test_state(&read_tx, &write_tx);
 
     println!("State root: {}", state_root);
@@ -812,7 +819,7 @@ fn test_account_proof_generation1() {
     write_tx.commit().unwrap();
 
     // Generate a proof for account1
-    let proof_tx = RocksTransaction::<false>::new(db.clone(), false);
+    let proof_tx = RocksTransaction::<false>::from_db(db.clone());
 
     // Create a proof generator using RETH's Proof struct
     let proof_generator =
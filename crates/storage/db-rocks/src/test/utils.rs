@@ -1,46 +1,100 @@
 use crate::{
     calculate_state_root_with_updates,
-    tables::trie::{AccountTrieTable, StorageTrieTable, TrieNodeValue, TrieTable},
-    Account, HashedPostState, RocksTransaction,
+    tables::{
+        table_descriptor,
+        trie::{AccountTrieTable, StorageTrieTable, TrieNodeValue, TrieTable},
+    },
+    Account, HashedPostState, RocksDB, RocksDBConfig, RocksTransaction,
 };
 use alloy_primitives::{keccak256, Address, B256, U256};
 use reth_db::{HashedAccounts, HashedStorages};
 use reth_db_api::table::Table;
 use reth_trie::{BranchNodeCompact, Nibbles, StoredNibbles, TrieMask};
 use rocksdb::{Options, DB};
+use std::path::Path;
 use std::sync::Arc;
 use tempfile::TempDir;
 
+/// Tables of the database built by [`create_test_db`]
+pub const TEST_TABLES: &[&str] = &[
+    TrieTable::NAME,
+    AccountTrieTable::NAME,
+    StorageTrieTable::NAME,
+    HashedAccounts::NAME,
+    HashedStorages::NAME,
+];
+
 pub fn create_test_db() -> (Arc<DB>, TempDir) {
+    build_test_db(TEST_TABLES)
+}
+
+/// Open a database in a fresh temporary directory with a column family for each of `tables`,
+/// configured through [`TableConfig::descriptor`](crate::tables::TableConfig::descriptor) like
+/// in production.
+///
+/// Panics if a name isn't a known table.
+pub fn build_test_db(tables: &[&str]) -> (Arc<DB>, TempDir) {
     let temp_dir = TempDir::new().unwrap();
-    let path = temp_dir.path().to_str().unwrap();
 
-    // create options
     let mut opts = Options::default();
     opts.create_if_missing(true);
     opts.create_missing_column_families(true);
 
-    // Define column families
-    let cf_names = vec![
-        TrieTable::NAME,
-        AccountTrieTable::NAME,
-        StorageTrieTable::NAME,
-        HashedAccounts::NAME,
-        HashedStorages::NAME,
-    ];
-
-    // create column family descriptor
-    let cf_descriptors = cf_names
+    let cf_descriptors = tables
         .iter()
-        .map(|name| rocksdb::ColumnFamilyDescriptor::new(*name, Options::default()))
+        .map(|name| table_descriptor(name).unwrap_or_else(|| panic!("Unknown table {name}")))
         .collect::<Vec<_>>();
-
-    // Open the Database with column families
-    let db = DB::open_cf_descriptors(&opts, path, cf_descriptors).unwrap();
+    let db = DB::open_cf_descriptors(&opts, temp_dir.path(), cf_descriptors).unwrap();
 
     (Arc::new(db), temp_dir)
 }
 
+/// Prefix extractor of column family `cf` as recorded in the latest `OPTIONS` file of the
+/// database at `path`, `None` if it has none
+pub fn cf_prefix_extractor(path: &Path, cf: &str) -> Option<String> {
+    let options_file = std::fs::read_dir(path)
+        .unwrap()
+        .filter_map(|entry| {
+            let name = entry.unwrap().file_name().into_string().ok()?;
+            let number = name.strip_prefix("OPTIONS-")?.parse::<u64>().ok()?;
+            Some((number, name))
+        })
+        .max()
+        .map(|(_, name)| path.join(name))
+        .expect("database has no OPTIONS file");
+
+    let section = format!("[CFOptions \"{cf}\"]");
+    std::fs::read_to_string(options_file)
+        .unwrap()
+        .lines()
+        .map(str::trim)
+        .skip_while(|line| *line != section)
+        .skip(1)
+        .take_while(|line| !line.starts_with('['))
+        .find_map(|line| line.strip_prefix("prefix_extractor="))
+        .filter(|extractor| *extractor != "nullptr")
+        .map(str::to_string)
+}
+
+pub fn create_test_rocksdb(config: RocksDBConfig) -> (RocksDB, TempDir) {
+    let temp_dir = TempDir::new().unwrap();
+    let db = RocksDB::open(temp_dir.path(), config).unwrap();
+
+    (db, temp_dir)
+}
+
+/// [`create_test_rocksdb`], with a column family for each of the test-only `scratch_tables` on
+/// top of the registered tables, so test tables never share the column family of a real one
+pub fn create_test_rocksdb_with_tables(
+    config: RocksDBConfig,
+    scratch_tables: &[&str],
+) -> (RocksDB, TempDir) {
+    let temp_dir = TempDir::new().unwrap();
+    let db = RocksDB::open_with_tables(temp_dir.path(), config, scratch_tables).unwrap();
+
+    (db, temp_dir)
+}
+
 pub fn setup_test_state(
     read_tx: &RocksTransaction<false>,
     write_tx: &RocksTransaction<true>,
@@ -75,7 +129,8 @@ pub fn setup_test_state(
     post_state.storages.insert(hashed_address1, storage);
 
     // Calculate state root and commit trie
-    let state_root = calculate_state_root_with_updates(read_tx, write_tx, post_state).unwrap();
+    let state_root =
+        calculate_state_root_with_updates(read_tx, write_tx, post_state, None).unwrap();
 
     (state_root, address1, address2, storage_key)
 }
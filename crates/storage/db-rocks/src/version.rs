@@ -1,13 +1,27 @@
-use reth_db_api::{table::Table, DatabaseError};
-use rocksdb::{Options, DB};
+use crate::tables::trie::{StorageTrieTable, TrieNodeValue};
+use reth_db_api::{
+    table::{Compress, Table},
+    DatabaseError,
+};
+use rocksdb::{ColumnFamily, DB};
 use std::sync::atomic::{AtomicU32, Ordering};
 
 /// Current database schema version
-const CURRENT_VERSION: u32 = 1;
+pub(crate) const CURRENT_VERSION: u32 = 2;
 /// Version key used in RocksDB
 const VERSION_KEY: &[u8] = b"db_version";
-/// Default column family name
+/// Key of the progress of an interrupted migration: the version it migrates to (4 bytes,
+/// big-endian) followed by the last key it rewrote
+const MIGRATION_PROGRESS_KEY: &[u8] = b"migration_progress";
+/// Entries rewritten per write batch by migrations
+const MIGRATION_CHUNK_SIZE: usize = 10_000;
+/// Default column family name, where versions were stored by older releases
 const DEFAULT_CF: &str = "default";
+/// Reserved column family for database metadata. Never used as a table.
+pub(crate) const META_CF: &str = "__meta__";
+/// Column families holding metadata only: the meta column family and RocksDB's default one,
+/// where older releases kept the version. No table may be stored in them.
+pub(crate) const RESERVED_CFS: &[&str] = &[DEFAULT_CF, META_CF];
 
 /// Database version management
 #[derive(Debug)]
@@ -16,81 +30,191 @@ pub struct VersionManager {
     version: AtomicU32,
 }
 
-// impl VersionManager {
-//     /// Create new version manager
-//     pub fn new(db: &DB) -> Result<Self, DatabaseError> {
-//         // Try to read existing version
-//         let version = match db
-//             .get_cf(&*db.cf_handle(DEFAULT_CF).expect("Default CF always exists"), VERSION_KEY)?
-//         {
-//             Some(bytes) => {
-//                 let ver = u32::from_be_bytes(
-//                     bytes
-//                         .try_into()
-//                         .map_err(|_| DatabaseError::Other("Invalid version format".to_string()))?,
-//                 );
-//                 ver
-//             }
-//             None => {
-//                 // No version found, initialize with current version
-//                 let version = CURRENT_VERSION;
-//                 db.put_cf(
-//                     &*db.cf_handle(DEFAULT_CF).expect("Default CF always exists"),
-//                     VERSION_KEY,
-//                     &version.to_be_bytes(),
-//                 )?;
-//                 version
-//             }
-//         };
-
-//         Ok(Self { version: AtomicU32::new(version) })
-//     }
+impl VersionManager {
+    /// Create new version manager
+    pub fn new(db: &DB) -> Result<Self, DatabaseError> {
+        Self::migrate_legacy_version_key(db)?;
 
-//     /// Get current database version
-//     pub fn current_version(&self) -> u32 {
-//         self.version.load(Ordering::Relaxed)
-//     }
+        // Try to read existing version
+        let version = match read_version(db)? {
+            Some(version) => version,
+            None => {
+                // No version found, initialize with current version
+                let version = CURRENT_VERSION;
+                write_version(db, version)?;
+                version
+            }
+        };
 
-//     /// Check if database needs migration
-//     pub fn needs_migration(&self) -> bool {
-//         self.current_version() < CURRENT_VERSION
-//     }
+        Ok(Self { version: AtomicU32::new(version) })
+    }
 
-//     /// Run necessary migrations
-//     pub fn migrate(&self, db: &DB) -> Result<(), DatabaseError> {
-//         let current = self.current_version();
-//         if current >= CURRENT_VERSION {
-//             return Ok(());
-//         }
+    /// Get current database version
+    pub fn current_version(&self) -> u32 {
+        self.version.load(Ordering::Relaxed)
+    }
 
-//         // Run migrations in sequence
-//         for version in current + 1..=CURRENT_VERSION {
-//             self.run_migration(version, db)?;
-
-//             // Update version after successful migration
-//             db.put_cf(
-//                 &*db.cf_handle(DEFAULT_CF).expect("Default CF always exists"),
-//                 VERSION_KEY,
-//                 &version.to_be_bytes(),
-//             )?;
-//             self.version.store(version, Ordering::Relaxed);
-//         }
+    /// Check if database needs migration
+    pub fn needs_migration(&self) -> bool {
+        self.current_version() < CURRENT_VERSION
+    }
 
-//         Ok(())
-//     }
+    /// Run necessary migrations
+    pub fn migrate(&self, db: &DB) -> Result<(), DatabaseError> {
+        let current = self.current_version();
+        if current >= CURRENT_VERSION {
+            return Ok(());
+        }
 
-//     /// Run specific version migration
-//     fn run_migration(&self, version: u32, db: &DB) -> Result<(), DatabaseError> {
-//         match version {
-//             1 => {
-//                 // Initial version - no migration needed
-//                 Ok(())
-//             }
-//             // Add more version migrations here
-//             _ => Err(DatabaseError::Other(format!("Unknown version: {}", version))),
-//         }
-//     }
-// }
+        // Run migrations in sequence, each recording its version along with its last writes
+        for version in current + 1..=CURRENT_VERSION {
+            self.run_migration(version, db)?;
+            self.version.store(version, Ordering::Relaxed);
+        }
+
+        Ok(())
+    }
+
+    /// Run specific version migration
+    fn run_migration(&self, version: u32, db: &DB) -> Result<(), DatabaseError> {
+        match version {
+            1 => {
+                // Initial version - no migration needed
+                write_version(db, version)
+            }
+            2 => Self::prefix_trie_node_values(db, MIGRATION_CHUNK_SIZE),
+            // Add more version migrations here
+            _ => Err(DatabaseError::Other(format!("Unknown version: {}", version))),
+        }
+    }
+
+    /// Rewrite the values of [`StorageTrieTable`] with the nibble count prefixed, as introduced
+    /// by version 2, `chunk_size` entries per write batch
+    pub(crate) fn prefix_trie_node_values(db: &DB, chunk_size: usize) -> Result<(), DatabaseError> {
+        rewrite_values(db, StorageTrieTable::NAME, 2, chunk_size, |value| {
+            Ok(TrieNodeValue::decompress_unprefixed(value)?.compress())
+        })
+    }
+
+    /// Move a version key written to the default column family by older releases into the
+    /// meta column family
+    fn migrate_legacy_version_key(db: &DB) -> Result<(), DatabaseError> {
+        let Some(default_cf) = db.cf_handle(DEFAULT_CF) else { return Ok(()) };
+
+        let legacy = db
+            .get_cf(default_cf, VERSION_KEY)
+            .map_err(|e| DatabaseError::Other(format!("Failed to read version: {}", e)))?;
+
+        if let Some(bytes) = legacy {
+            let mut batch = rocksdb::WriteBatch::default();
+            batch.put_cf(meta_cf(db)?, VERSION_KEY, bytes);
+            batch.delete_cf(default_cf, VERSION_KEY);
+            db.write(batch)
+                .map_err(|e| DatabaseError::Other(format!("Failed to migrate version: {}", e)))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Rewrite every value of the column family `name` with `rewrite`, then record `version`.
+///
+/// Values are rewritten `chunk_size` entries per write batch. Each batch records the last key
+/// it rewrote under [`MIGRATION_PROGRESS_KEY`], so a migration interrupted between batches
+/// resumes after that key rather than rewriting values twice, which `rewrite` can't be expected
+/// to detect. The last batch records `version` and drops the progress, so the database is only
+/// stamped with `version` once all values are rewritten.
+fn rewrite_values(
+    db: &DB,
+    name: &str,
+    version: u32,
+    chunk_size: usize,
+    rewrite: impl Fn(&[u8]) -> Result<Vec<u8>, DatabaseError>,
+) -> Result<(), DatabaseError> {
+    let meta = meta_cf(db)?;
+    let write = |batch| {
+        db.write(batch).map_err(|e| {
+            DatabaseError::Other(format!(
+                "Failed to migrate {} to version {}: {}",
+                name, version, e
+            ))
+        })
+    };
+
+    // Progress left by an interrupted run of this migration
+    let resume_after = db
+        .get_cf(meta, MIGRATION_PROGRESS_KEY)
+        .map_err(|e| DatabaseError::Other(format!("Failed to read migration progress: {}", e)))?
+        .and_then(|progress| {
+            let (progress_version, key) = progress.split_at_checked(4)?;
+            (progress_version == version.to_be_bytes()).then(|| key.to_vec())
+        });
+
+    let mut batch = rocksdb::WriteBatch::default();
+    if let Some(cf) = db.cf_handle(name) {
+        // Values of all keys, across the prefixes of the table's extractor
+        let mut read_opts = rocksdb::ReadOptions::default();
+        read_opts.set_total_order_seek(true);
+        let mode = match &resume_after {
+            Some(key) => rocksdb::IteratorMode::From(key, rocksdb::Direction::Forward),
+            None => rocksdb::IteratorMode::Start,
+        };
+
+        // The iterator reads the implicit snapshot taken when it was created, so it doesn't see
+        // the values rewritten as it goes
+        let mut staged = 0;
+        for item in db.iterator_cf_opt(cf, read_opts, mode) {
+            let (key, value) =
+                item.map_err(|e| DatabaseError::Other(format!("RocksDB iterator error: {}", e)))?;
+            if resume_after.as_deref() == Some(&key) {
+                continue;
+            }
+
+            batch.put_cf(cf, &key, rewrite(&value)?);
+            staged += 1;
+            if staged == chunk_size {
+                batch.put_cf(
+                    meta,
+                    MIGRATION_PROGRESS_KEY,
+                    [&version.to_be_bytes()[..], &key].concat(),
+                );
+                write(std::mem::take(&mut batch))?;
+                staged = 0;
+            }
+        }
+    }
+
+    batch.put_cf(meta, VERSION_KEY, version.to_be_bytes());
+    batch.delete_cf(meta, MIGRATION_PROGRESS_KEY);
+    write(batch)
+}
+
+/// Get the meta column family handle
+fn meta_cf(db: &DB) -> Result<&ColumnFamily, DatabaseError> {
+    db.cf_handle(META_CF)
+        .ok_or_else(|| DatabaseError::Other(format!("Column family not found: {}", META_CF)))
+}
+
+/// Read the version stored in the meta column family, if any
+pub(crate) fn read_version(db: &DB) -> Result<Option<u32>, DatabaseError> {
+    db.get_cf(meta_cf(db)?, VERSION_KEY)
+        .map_err(|e| DatabaseError::Other(format!("Failed to read version: {}", e)))?
+        .map(|bytes| decode_version(&bytes))
+        .transpose()
+}
+
+/// Persist the version in the meta column family
+pub(crate) fn write_version(db: &DB, version: u32) -> Result<(), DatabaseError> {
+    db.put_cf(meta_cf(db)?, VERSION_KEY, version.to_be_bytes())
+        .map_err(|e| DatabaseError::Other(format!("Failed to write version: {}", e)))
+}
+
+/// Decode a stored version
+fn decode_version(bytes: &[u8]) -> Result<u32, DatabaseError> {
+    let bytes: [u8; 4] =
+        bytes.try_into().map_err(|_| DatabaseError::Other("Invalid version format".to_string()))?;
+    Ok(u32::from_be_bytes(bytes))
+}
 
 // /// Migration utilities
 // pub(crate) struct MigrationUtils;
@@ -0,0 +1,111 @@
+use rocksdb::DB;
+use std::sync::{Arc, Condvar, Mutex, Weak};
+
+/// Gates of the databases wrapped by a [`crate::RocksDB`], by their inner [`DB`], so
+/// transactions created from a bare [`DB`] find the gate of its wrapper
+static GATES: Mutex<Vec<(Weak<DB>, Weak<WriteGate>)>> = Mutex::new(Vec::new());
+
+/// Keeps write transactions out while the database files are being snapshotted.
+///
+/// Write transactions hold a [`WritePermit`] from creation until they are committed or dropped.
+/// Closing the gate stops new permits from being handed out, then waits for the outstanding
+/// ones to be released, so no write is in flight until the gate reopens.
+#[derive(Debug, Default)]
+pub(crate) struct WriteGate {
+    state: Mutex<GateState>,
+    /// Notified whenever the gate reopens or a permit is released
+    changed: Condvar,
+}
+
+/// Whether the gate is closed, and how many permits are outstanding
+#[derive(Debug, Default)]
+struct GateState {
+    closed: bool,
+    permits: usize,
+}
+
+impl WriteGate {
+    /// Gate of `db`, shared by every wrapper of the same [`DB`]. Created on first use.
+    pub(crate) fn of(db: &Arc<DB>) -> Arc<Self> {
+        let mut gates = GATES.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        gates.retain(|(_, gate)| gate.strong_count() > 0);
+        if let Some(gate) = Self::find(&gates, db) {
+            return gate;
+        }
+        let gate = Arc::new(Self::default());
+        gates.push((Arc::downgrade(db), Arc::downgrade(&gate)));
+        gate
+    }
+
+    /// Gate of `db` if a wrapper of it is alive, `None` for a [`DB`] never wrapped
+    pub(crate) fn registered(db: &Arc<DB>) -> Option<Arc<Self>> {
+        Self::find(&GATES.lock().unwrap_or_else(|poisoned| poisoned.into_inner()), db)
+    }
+
+    /// Live gate of `db` among `gates`
+    fn find(gates: &[(Weak<DB>, Weak<Self>)], db: &Arc<DB>) -> Option<Arc<Self>> {
+        let db = Arc::downgrade(db);
+        gates.iter().find(|(gated, _)| gated.ptr_eq(&db)).and_then(|(_, gate)| gate.upgrade())
+    }
+
+    /// Wait for the gate to be open and take a permit, released when it is dropped
+    pub(crate) fn enter(self: &Arc<Self>) -> WritePermit {
+        let state = self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let mut state = self
+            .changed
+            .wait_while(state, |state| state.closed)
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        state.permits += 1;
+        WritePermit { gate: self.clone() }
+    }
+
+    /// Close the gate and wait for every outstanding permit to be released. The gate reopens
+    /// when the returned guard is dropped.
+    ///
+    /// Waits forever if the calling thread itself holds a permit.
+    pub(crate) fn close(&self) -> ClosedGate<'_> {
+        let state = self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        // Another closer goes first
+        let mut state = self
+            .changed
+            .wait_while(state, |state| state.closed)
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        state.closed = true;
+        drop(
+            self.changed
+                .wait_while(state, |state| state.permits > 0)
+                .unwrap_or_else(|poisoned| poisoned.into_inner()),
+        );
+        ClosedGate { gate: self }
+    }
+
+    /// Change the state and wake up every waiter
+    fn update(&self, f: impl FnOnce(&mut GateState)) {
+        f(&mut self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner()));
+        self.changed.notify_all();
+    }
+}
+
+/// Permit of a write transaction to write through a [`WriteGate`]
+#[derive(Debug)]
+pub(crate) struct WritePermit {
+    gate: Arc<WriteGate>,
+}
+
+impl Drop for WritePermit {
+    fn drop(&mut self) {
+        self.gate.update(|state| state.permits -= 1);
+    }
+}
+
+/// A closed [`WriteGate`], reopened when dropped
+#[derive(Debug)]
+pub(crate) struct ClosedGate<'a> {
+    gate: &'a WriteGate,
+}
+
+impl Drop for ClosedGate<'_> {
+    fn drop(&mut self) {
+        self.gate.update(|state| state.closed = false);
+    }
+}
@@ -0,0 +1,7 @@
+//! Tables that cursors can't be opened on are rejected at compile time
+
+#[test]
+fn ui() {
+    let cases = trybuild::TestCases::new();
+    cases.compile_fail("tests/ui/*.rs");
+}
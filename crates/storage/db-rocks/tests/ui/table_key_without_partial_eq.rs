@@ -0,0 +1,40 @@
+use reth_db_api::{
+    table::{Decode, Encode, Table},
+    DatabaseError,
+};
+use reth_db_rocks::{Account, RocksTable};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct UnorderedKey(u64);
+
+impl Encode for UnorderedKey {
+    type Encoded = [u8; 8];
+
+    fn encode(self) -> Self::Encoded {
+        self.0.to_be_bytes()
+    }
+}
+
+impl Decode for UnorderedKey {
+    fn decode(value: &[u8]) -> Result<Self, DatabaseError> {
+        Ok(Self(u64::from_be_bytes(value.try_into().map_err(|_| DatabaseError::Decode)?)))
+    }
+}
+
+#[derive(Debug)]
+struct UnorderedTable;
+
+impl Table for UnorderedTable {
+    const NAME: &'static str = "unordered";
+    const DUPSORT: bool = false;
+
+    type Key = UnorderedKey;
+    type Value = Account;
+}
+
+fn open_cursor<T: RocksTable>() {}
+
+fn main() {
+    open_cursor::<UnorderedTable>();
+}